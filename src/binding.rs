@@ -0,0 +1,265 @@
+use wgpu::*;
+
+/// describes the entries of one bind group, binding indices are assigned in call order.
+/// the same description creates the layout and is checked against the shaders using it,
+/// so a layout and its wgsl declarations can't silently drift apart.
+#[derive(Clone)]
+pub struct BindGroupLayoutBuilder {
+    label: &'static str,
+    entries: Vec<BindGroupLayoutEntry>,
+}
+
+/// a created layout which remembers its entries so bind groups can be created from resources alone
+pub struct BindingLayout {
+    pub layout: BindGroupLayout,
+    label: &'static str,
+    entries: Vec<BindGroupLayoutEntry>,
+}
+
+impl BindGroupLayoutBuilder {
+    pub fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            entries: vec![],
+        }
+    }
+
+    fn push(mut self, visibility: ShaderStages, ty: BindingType) -> Self {
+        self.entries.push(BindGroupLayoutEntry {
+            binding: self.entries.len() as u32,
+            visibility,
+            ty,
+            count: None,
+        });
+        self
+    }
+
+    pub fn uniform(self, visibility: ShaderStages) -> Self {
+        self.push(visibility, BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        })
+    }
+
+    /// uniform bound with a dynamic offset per `set_bind_group`
+    pub fn uniform_dynamic(self, visibility: ShaderStages) -> Self {
+        self.push(visibility, BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: true,
+            min_binding_size: None,
+        })
+    }
+
+    pub fn storage(self, visibility: ShaderStages, read_only: bool) -> Self {
+        self.push(visibility, BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        })
+    }
+
+    /// non multisampled 2d texture
+    pub fn texture(self, visibility: ShaderStages, sample_type: TextureSampleType) -> Self {
+        self.push(visibility, BindingType::Texture {
+            sample_type,
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        })
+    }
+
+    /// non multisampled 2d array texture
+    pub fn texture_array(self, visibility: ShaderStages, sample_type: TextureSampleType) -> Self {
+        self.push(visibility, BindingType::Texture {
+            sample_type,
+            view_dimension: TextureViewDimension::D2Array,
+            multisampled: false,
+        })
+    }
+
+    pub fn texture_3d(self, visibility: ShaderStages, sample_type: TextureSampleType) -> Self {
+        self.push(visibility, BindingType::Texture {
+            sample_type,
+            view_dimension: TextureViewDimension::D3,
+            multisampled: false,
+        })
+    }
+
+    /// write only 2d storage texture
+    pub fn storage_texture(self, visibility: ShaderStages, format: TextureFormat) -> Self {
+        self.push(visibility, BindingType::StorageTexture {
+            access: StorageTextureAccess::WriteOnly,
+            format,
+            view_dimension: TextureViewDimension::D2,
+        })
+    }
+
+    pub fn sampler(self, visibility: ShaderStages, ty: SamplerBindingType) -> Self {
+        self.push(visibility, BindingType::Sampler(ty))
+    }
+
+    pub fn build(&self, device: &Device) -> BindingLayout {
+        BindingLayout {
+            layout: device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some(self.label),
+                entries: &self.entries,
+            }),
+            label: self.label,
+            entries: self.entries.clone(),
+        }
+    }
+
+    /// checks that every resource `shader` declares in `@group(group)`
+    /// has an entry of a compatible type in this layout.
+    pub fn validate(&self, group: u32, shader: &naga::Module) -> Result<(), String> {
+        for (_, global) in shader.global_variables.iter() {
+            let Some(binding) = &global.binding else {
+                continue;
+            };
+            if binding.group != group {
+                continue;
+            }
+
+            let name = global.name.as_deref().unwrap_or("<unnamed>");
+            let Some(entry) = self.entries.iter().find(|e| e.binding == binding.binding) else {
+                return Err(format!(
+                    "`{}` at @group({}) @binding({}) has no entry in layout `{}`",
+                    name, group, binding.binding, self.label,
+                ));
+            };
+
+            if !is_compatible(shader, global, &entry.ty) {
+                return Err(format!(
+                    "`{}` at @group({}) @binding({}) doesn't match {:?} in layout `{}`",
+                    name, group, binding.binding, entry.ty, self.label,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BindingLayout {
+    /// `resources` are given in binding order, one per entry
+    pub fn create_bind_group(
+        &self,
+        device: &Device,
+        label: &str,
+        resources: &[BindingResource],
+    ) -> BindGroup {
+        assert_eq!(
+            resources.len(),
+            self.entries.len(),
+            "bind group `{}` needs {} resources for layout `{}`",
+            label,
+            self.entries.len(),
+            self.label,
+        );
+
+        let entries: Vec<_> = self.entries
+            .iter()
+            .zip(resources.iter())
+            .map(|(entry, resource)| BindGroupEntry {
+                binding: entry.binding,
+                resource: resource.clone(),
+            })
+            .collect();
+
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some(label),
+            layout: &self.layout,
+            entries: &entries,
+        })
+    }
+}
+
+/// parses and validates wgsl, erring with the annotated source location of the first problem.
+/// every capability is allowed, what the device lacks is left to wgpu
+pub fn validate_wgsl(file_name: &str, source: &str) -> Result<naga::Module, String> {
+    use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| e.emit_to_string_with_path(source, file_name))?;
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| e.emit_to_string_with_path(source, file_name))?;
+    Ok(module)
+}
+
+/// parses and validates wgsl for reflection, panicking with the annotated source location on error
+pub fn reflect(file_name: &str, source: &str) -> naga::Module {
+    validate_wgsl(file_name, source).unwrap_or_else(|e| panic!("{}", e))
+}
+
+fn is_compatible(shader: &naga::Module, global: &naga::GlobalVariable, ty: &BindingType) -> bool {
+    use naga::{AddressSpace, ImageClass, ScalarKind, StorageAccess, TypeInner};
+
+    let mut inner = &shader.types[global.ty].inner;
+    if let TypeInner::BindingArray { base, .. } = inner {
+        inner = &shader.types[*base].inner;
+    }
+
+    match (global.space, inner, ty) {
+        (AddressSpace::Uniform, _, BindingType::Buffer { ty: BufferBindingType::Uniform, .. }) => true,
+        (
+            AddressSpace::Storage { access },
+            _,
+            BindingType::Buffer { ty: BufferBindingType::Storage { read_only }, .. },
+        ) => *read_only != access.contains(StorageAccess::STORE),
+        (AddressSpace::Handle, TypeInner::Sampler { comparison }, BindingType::Sampler(sampler)) => {
+            *comparison == (*sampler == SamplerBindingType::Comparison)
+        }
+        (AddressSpace::Handle, TypeInner::Image { class, .. }, BindingType::Texture { sample_type, .. }) => {
+            matches!(
+                (class, sample_type),
+                (ImageClass::Depth { .. }, TextureSampleType::Depth)
+                | (ImageClass::Sampled { kind: ScalarKind::Float, .. }, TextureSampleType::Float { .. })
+                | (ImageClass::Sampled { kind: ScalarKind::Uint, .. }, TextureSampleType::Uint)
+                | (ImageClass::Sampled { kind: ScalarKind::Sint, .. }, TextureSampleType::Sint)
+            )
+        }
+        (
+            AddressSpace::Handle,
+            TypeInner::Image { class: ImageClass::Storage { .. }, .. },
+            BindingType::StorageTexture { .. },
+        ) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_layout_matches_shader() {
+        let source = crate::shader_preprocessor(crate::draw_params::DrawParamsMode::Uniform { group: 1 })
+            .process("light.wgsl", &crate::preprocess::ShaderVariant::all().defines())
+            .unwrap();
+        let shader = reflect("light.wgsl", &source);
+        let layout = BindGroupLayoutBuilder::new("light")
+            .uniform_dynamic(ShaderStages::VERTEX | ShaderStages::FRAGMENT)
+            .uniform(ShaderStages::VERTEX | ShaderStages::FRAGMENT)
+            .texture(ShaderStages::FRAGMENT, TextureSampleType::Depth)
+            .sampler(ShaderStages::FRAGMENT, SamplerBindingType::Filtering)
+            .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: true })
+            .sampler(ShaderStages::FRAGMENT, SamplerBindingType::Filtering)
+            .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: false })
+            .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: true })
+            .sampler(ShaderStages::FRAGMENT, SamplerBindingType::Filtering)
+            .uniform(ShaderStages::FRAGMENT)
+            .texture_array(ShaderStages::FRAGMENT, TextureSampleType::Depth);
+        assert!(layout.validate(0, &shader).is_ok());
+
+        let swapped = BindGroupLayoutBuilder::new("swapped")
+            .uniform(ShaderStages::VERTEX | ShaderStages::FRAGMENT)
+            .uniform(ShaderStages::VERTEX | ShaderStages::FRAGMENT)
+            .sampler(ShaderStages::FRAGMENT, SamplerBindingType::Filtering)
+            .texture(ShaderStages::FRAGMENT, TextureSampleType::Depth);
+        assert!(swapped.validate(0, &shader).is_err());
+
+        let missing = BindGroupLayoutBuilder::new("missing")
+            .uniform(ShaderStages::VERTEX | ShaderStages::FRAGMENT);
+        assert!(missing.validate(0, &shader).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use std::{marker::PhantomData, mem::size_of};
+
+use bytemuck::Pod;
+use wgpu::*;
+
+/// buffer holding `capacity` elements of `T`, of which the first `len` were last written.
+/// growing recreates the underlying buffer, so anything bound to the old one must be rebuilt,
+/// which is signalled by `reserve` returning true.
+pub struct GpuBuffer<T: Pod> {
+    buffer: Buffer,
+    label: &'static str,
+    usage: BufferUsages,
+    capacity: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> GpuBuffer<T> {
+    /// `COPY_DST` is always added to `usage` so the buffer can be written from the queue
+    pub fn new(device: &Device, label: &'static str, usage: BufferUsages, capacity: usize) -> Self {
+        let usage = usage | BufferUsages::COPY_DST;
+        let capacity = capacity.max(1);
+        Self {
+            buffer: create_buffer::<T>(device, label, usage, capacity),
+            label,
+            usage,
+            capacity,
+            len: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// holds a single `T`
+    pub fn new_uniform(device: &Device, label: &'static str) -> Self {
+        Self::new(device, label, BufferUsages::UNIFORM, 1)
+    }
+
+    pub fn new_storage(device: &Device, label: &'static str, capacity: usize) -> Self {
+        Self::new(device, label, BufferUsages::STORAGE, capacity)
+    }
+
+    pub fn new_vertex(device: &Device, label: &'static str, capacity: usize) -> Self {
+        Self::new(device, label, BufferUsages::VERTEX, capacity)
+    }
+
+    /// makes room for at least `len` elements, at least doubling the capacity when growing.
+    /// returns true if the buffer was recreated, in which case its contents are lost
+    /// and bind groups referencing it have to be recreated.
+    pub fn reserve(&mut self, device: &Device, len: usize) -> bool {
+        if len <= self.capacity {
+            return false;
+        }
+
+        self.capacity = len.max(2 * self.capacity);
+        self.buffer = create_buffer::<T>(device, self.label, self.usage, self.capacity);
+        self.len = 0;
+        true
+    }
+
+    /// panics if `data` doesn't fit, use `reserve` beforehand when the length can change
+    pub fn write(&mut self, queue: &Queue, data: &[T]) {
+        assert!(
+            data.len() <= self.capacity,
+            "{} elements don't fit in `{}` with capacity {}",
+            data.len(),
+            self.label,
+            self.capacity,
+        );
+        if !data.is_empty() {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(data));
+        }
+        self.len = data.len();
+    }
+
+    /// writes `data` starting at element `offset`, without changing `len`
+    pub fn write_at(&self, queue: &Queue, offset: usize, data: &[T]) {
+        assert!(offset + data.len() <= self.capacity);
+        queue.write_buffer(
+            &self.buffer,
+            (offset * size_of::<T>()) as BufferAddress,
+            bytemuck::cast_slice(data),
+        );
+    }
+
+    /// for buffers filled on the gpu, marks the first `len` elements as written
+    pub fn set_len(&mut self, len: usize) {
+        assert!(len <= self.capacity);
+        self.len = len;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[allow(dead_code)]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// the written part of the buffer
+    pub fn slice(&self) -> BufferSlice<'_> {
+        self.buffer.slice(..(self.len.max(1) * size_of::<T>()) as BufferAddress)
+    }
+
+    pub fn as_entire_binding(&self) -> BindingResource<'_> {
+        self.buffer.as_entire_binding()
+    }
+}
+
+/// one `T` per slot of a dynamically offset uniform buffer, e.g. a camera per view.
+/// values are pushed while preparing a frame and uploaded together by `flush`,
+/// so each pass binds its own slot instead of seeing whatever was written last.
+pub struct UniformRing<T: Pod> {
+    buffer: Buffer,
+    label: &'static str,
+    /// bytes between slots, a multiple of the uniform offset alignment
+    stride: usize,
+    capacity: usize,
+    values: Vec<T>,
+}
+
+impl<T: Pod> UniformRing<T> {
+    pub fn new(device: &Device, label: &'static str, capacity: usize) -> Self {
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as usize;
+        let stride = size_of::<T>().div_ceil(alignment) * alignment;
+        Self {
+            buffer: device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size: (stride * capacity) as BufferAddress,
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            label,
+            stride,
+            capacity,
+            values: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// forgets the previous frame's values
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// returns the dynamic offset to bind the value's slot with
+    pub fn push(&mut self, value: T) -> u32 {
+        assert!(self.values.len() < self.capacity, "`{}` is out of slots", self.label);
+        self.values.push(value);
+        ((self.values.len() - 1) * self.stride) as u32
+    }
+
+    /// uploads the pushed values, call before submitting the passes using them
+    pub fn flush(&self, queue: &Queue) {
+        let mut data = vec![0u8; self.values.len() * self.stride];
+        for (value, chunk) in self.values.iter().zip(data.chunks_mut(self.stride)) {
+            chunk[..size_of::<T>()].copy_from_slice(bytemuck::bytes_of(value));
+        }
+        if !data.is_empty() {
+            queue.write_buffer(&self.buffer, 0, &data);
+        }
+    }
+
+    /// a single slot, for layouts with `has_dynamic_offset`
+    pub fn binding(&self) -> BindingResource<'_> {
+        BindingResource::Buffer(BufferBinding {
+            buffer: &self.buffer,
+            offset: 0,
+            size: BufferSize::new(size_of::<T>() as u64),
+        })
+    }
+}
+
+fn create_buffer<T>(device: &Device, label: &str, usage: BufferUsages, capacity: usize) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size: (capacity * size_of::<T>()) as BufferAddress,
+        usage,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,238 @@
+use crate::math::{Aabb3, Vector3};
+
+/// triangles in a leaf at most, fewer leaves trade box tests for triangle tests
+const LEAF_SIZE: usize = 4;
+
+/// a node of the flattened tree, as read by `traced_shadow.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BvhNode {
+    pub min: [f32; 3],
+    /// a leaf's first triangle, or an inner node's first child, the second following it
+    pub first: u32,
+    pub max: [f32; 3],
+    /// triangles in a leaf, 0 for inner nodes
+    pub count: u32,
+}
+
+/// world space corners, padded like wgsl's vec3s
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TriangleRaw {
+    pub a: [f32; 3],
+    pub _padding0: u32,
+    pub b: [f32; 3],
+    pub _padding1: u32,
+    pub c: [f32; 3],
+    pub _padding2: u32,
+}
+
+impl TriangleRaw {
+    fn new([a, b, c]: [Vector3; 3]) -> Self {
+        Self {
+            a: [a.x, a.y, a.z],
+            b: [b.x, b.y, b.z],
+            c: [c.x, c.y, c.z],
+            ..Default::default()
+        }
+    }
+
+    fn corners(&self) -> [Vector3; 3] {
+        [self.a, self.b, self.c].map(|[x, y, z]| Vector3::new(x, y, z))
+    }
+}
+
+/// bounding volume hierarchy over triangles, rooted at node 0 unless it's empty.
+/// each node is split at the median of its triangles' centroids along their widest axis, which
+/// builds fast enough to redo every frame for moving triangles
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    /// reordered so each leaf's are contiguous
+    pub triangles: Vec<TriangleRaw>,
+}
+
+impl Bvh {
+    pub fn build(triangles: &[[Vector3; 3]]) -> Self {
+        let centroids: Vec<Vector3> = triangles.iter().map(|&[a, b, c]| (a + b + c) / 3.0).collect();
+        let mut order: Vec<u32> = (0..triangles.len() as u32).collect();
+        let mut nodes = vec![];
+        if !triangles.is_empty() {
+            nodes.push(BvhNode::default());
+            split(&mut nodes, 0, triangles, &centroids, &mut order, 0);
+        }
+        Self {
+            nodes,
+            triangles: order.iter().map(|&i| TriangleRaw::new(triangles[i as usize])).collect(),
+        }
+    }
+
+    /// the nodes for buffers where this tree's nodes and triangles start at the given offsets
+    pub fn rebased_nodes(&self, node_offset: u32, triangle_offset: u32) -> Vec<BvhNode> {
+        self.nodes
+            .iter()
+            .map(|node| BvhNode {
+                first: node.first + if node.count > 0 { triangle_offset } else { node_offset },
+                ..*node
+            })
+            .collect()
+    }
+
+    /// whether a triangle crosses the segment from `origin` to `origin + direction`, either face.
+    /// traverses like the shader does
+    pub fn occluded(&self, origin: Vector3, direction: Vector3) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i as usize];
+            if !hits_box(node, origin, direction, 1.0) {
+                continue;
+            }
+            if node.count > 0 {
+                let leaf = &self.triangles[node.first as usize..(node.first + node.count) as usize];
+                if leaf.iter().any(|triangle| hits_triangle(triangle.corners(), origin, direction).is_some()) {
+                    return true;
+                }
+            } else {
+                stack.extend([node.first, node.first + 1]);
+            }
+        }
+        false
+    }
+
+    /// the first triangle crossing the segment from `origin` to `origin + direction` and how far
+    /// along it, either face
+    pub fn closest_hit(&self, origin: Vector3, direction: Vector3) -> Option<(f32, [Vector3; 3])> {
+        let mut closest: Option<(f32, [Vector3; 3])> = None;
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i as usize];
+            if !hits_box(node, origin, direction, closest.map_or(1.0, |(t, _)| t)) {
+                continue;
+            }
+            if node.count > 0 {
+                for triangle in &self.triangles[node.first as usize..(node.first + node.count) as usize] {
+                    let corners = triangle.corners();
+                    match hits_triangle(corners, origin, direction) {
+                        Some(t) if closest.is_none_or(|(closest, _)| t < closest) => closest = Some((t, corners)),
+                        _ => {}
+                    }
+                }
+            } else {
+                stack.extend([node.first, node.first + 1]);
+            }
+        }
+        closest
+    }
+}
+
+fn split(
+    nodes: &mut Vec<BvhNode>,
+    node: usize,
+    triangles: &[[Vector3; 3]],
+    centroids: &[Vector3],
+    order: &mut [u32],
+    first: usize,
+) {
+    let bounds = Aabb3::from_points(order.iter().flat_map(|&i| triangles[i as usize]));
+    nodes[node] = BvhNode {
+        min: [bounds.min.x, bounds.min.y, bounds.min.z],
+        first: first as u32,
+        max: [bounds.max.x, bounds.max.y, bounds.max.z],
+        count: order.len() as u32,
+    };
+    if order.len() <= LEAF_SIZE {
+        return;
+    }
+
+    let spread = Aabb3::from_points(order.iter().map(|&i| centroids[i as usize]));
+    let extent = spread.max - spread.min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+    let middle = order.len() / 2;
+    order.select_nth_unstable_by(middle, |&a, &b| {
+        centroids[a as usize][axis].total_cmp(&centroids[b as usize][axis])
+    });
+
+    let left = nodes.len();
+    nodes.extend([BvhNode::default(); 2]);
+    nodes[node].first = left as u32;
+    nodes[node].count = 0;
+    let (left_order, right_order) = order.split_at_mut(middle);
+    split(nodes, left, triangles, centroids, left_order, first);
+    split(nodes, left + 1, triangles, centroids, right_order, first + middle);
+}
+
+/// slab test of the segment up to `far` against the node's box
+fn hits_box(node: &BvhNode, origin: Vector3, direction: Vector3, mut far: f32) -> bool {
+    let mut near = 0.0f32;
+    for axis in 0..3 {
+        let inverse = 1.0 / direction[axis];
+        let t0 = (node.min[axis] - origin[axis]) * inverse;
+        let t1 = (node.max[axis] - origin[axis]) * inverse;
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    near <= far
+}
+
+/// möller-trumbore, how far along the segment it's hit strictly inside it
+fn hits_triangle([a, b, c]: [Vector3; 3], origin: Vector3, direction: Vector3) -> Option<f32> {
+    let (ab, ac) = (b - a, c - a);
+    let p = direction.cross(&ac);
+    let determinant = ab.dot(&p);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = origin - a;
+    let u = s.dot(&p) * inverse;
+    let q = s.cross(&ab);
+    let v = direction.dot(&q) * inverse;
+    let t = ac.dot(&q) * inverse;
+    ((0.0..=1.0).contains(&u) && v >= 0.0 && u + v <= 1.0 && 1e-4 < t && t < 1.0).then_some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traversal_finds_what_testing_every_triangle_does() {
+        let random = |i: u32| crate::math::noise::hash(i) as f32 / u32::MAX as f32;
+        let point = |i: u32| Vector3::new(random(3 * i), random(3 * i + 1), random(3 * i + 2)) * 10.0;
+        let triangles: Vec<[Vector3; 3]> = (0..300)
+            .map(|i| {
+                let corner = point(i);
+                [corner, corner + point(1000 + i) * 0.1, corner + point(2000 + i) * 0.1]
+            })
+            .collect();
+        let bvh = Bvh::build(&triangles);
+        assert_eq!(bvh.triangles.len(), triangles.len());
+        assert!(bvh.nodes.iter().all(|node| node.count as usize <= LEAF_SIZE));
+
+        let mut hits = 0;
+        for i in 0..500 {
+            let (origin, direction) = (point(5000 + i), point(6000 + i) - point(5000 + i));
+            let closest = triangles
+                .iter()
+                .filter_map(|&triangle| hits_triangle(triangle, origin, direction))
+                .min_by(f32::total_cmp);
+            assert_eq!(bvh.occluded(origin, direction), closest.is_some());
+            assert_eq!(bvh.closest_hit(origin, direction).map(|(t, _)| t), closest);
+            hits += closest.is_some() as u32;
+        }
+        // both outcomes are covered
+        assert!(0 < hits && hits < 500);
+
+        assert!(!Bvh::build(&[]).occluded(Vector3::IDENTITY, Vector3::new(1.0, 0.0, 0.0)));
+        let rebased = bvh.rebased_nodes(10, 20);
+        assert_eq!(rebased[0].first, bvh.nodes[0].first + 10);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::math::curves::{ArcLength, Basis, Spline};
+use crate::math::{Rotor, Vector3};
+
+/// a flight through recorded camera placements, along splines at a constant speed
+pub struct CameraPath {
+    translations: Spline<Vector3>,
+    rotations: Spline<Rotor>,
+    /// of `translations`, rebuilt when a placement is added
+    arc_length: Option<ArcLength>,
+    /// distance travelled while playing
+    playing: Option<f32>,
+    /// units per second
+    pub speed: f32,
+}
+
+impl CameraPath {
+    const SAMPLES_PER_SEGMENT: usize = 32;
+
+    pub fn new(speed: f32) -> Self {
+        Self {
+            translations: Spline::new(Basis::CatmullRom),
+            rotations: Spline::new(Basis::CatmullRom),
+            arc_length: None,
+            playing: None,
+            speed,
+        }
+    }
+
+    /// catmull rom by default, passing through every placement
+    pub fn set_basis(&mut self, basis: Basis) {
+        self.translations.basis = basis;
+        self.rotations.basis = basis;
+        if !self.translations.points.is_empty() {
+            self.arc_length = Some(ArcLength::new(&self.translations, Self::SAMPLES_PER_SEGMENT));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.translations.points.len()
+    }
+
+    pub fn push(&mut self, translation: Vector3, rotation: Rotor) {
+        self.translations.points.push(translation);
+        self.rotations.points.push(rotation);
+        self.arc_length = Some(ArcLength::new(&self.translations, Self::SAMPLES_PER_SEGMENT));
+    }
+
+    /// moves every placement by `by`, e.g. when the floating origin is rebased
+    pub fn translate(&mut self, by: Vector3) {
+        for translation in &mut self.translations.points {
+            *translation += by;
+        }
+        if !self.translations.points.is_empty() {
+            self.arc_length = Some(ArcLength::new(&self.translations, Self::SAMPLES_PER_SEGMENT));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.translations.points.clear();
+        self.rotations.points.clear();
+        self.arc_length = None;
+        self.playing = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+
+    /// starts from the beginning, or stops. a path needs 2 placements to be played
+    pub fn toggle_playing(&mut self) -> bool {
+        self.playing = match self.playing {
+            None if self.len() >= 2 => Some(0.0),
+            _ => None,
+        };
+        self.is_playing()
+    }
+
+    /// the placement `dt` seconds further along while playing, stopping at the end
+    pub fn advance(&mut self, dt: f32) -> Option<(Vector3, Rotor)> {
+        let distance = self.playing.as_mut()?;
+        let arc_length = self.arc_length.as_ref()?;
+        *distance += self.speed * dt;
+        let u = arc_length.parameter(*distance);
+        if *distance >= arc_length.length() {
+            self.playing = None;
+        }
+        Some((self.translations.evaluate(u), self.rotations.evaluate(u)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_plays_to_its_end() {
+        let mut path = CameraPath::new(2.0);
+        path.push(Vector3::new(0.0, 0.0, 0.0), Rotor::IDENTITY);
+        assert!(!path.toggle_playing());
+        path.push(Vector3::new(4.0, 0.0, 0.0), Rotor::IDENTITY);
+        assert!(path.toggle_playing());
+
+        let (translation, _) = path.advance(1.0).unwrap();
+        assert!((translation.x - 2.0).abs() < 1e-3);
+        let (translation, _) = path.advance(1.5).unwrap();
+        assert!((translation.x - 4.0).abs() < 1e-3);
+        assert!(!path.is_playing());
+        assert!(path.advance(1.0).is_none());
+    }
+}
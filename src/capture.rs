@@ -0,0 +1,51 @@
+/// single frame captures through the renderdoc in-application api,
+/// available when built with the `renderdoc` feature and launched from renderdoc
+pub struct FrameCapture {
+    #[cfg(feature = "renderdoc")]
+    renderdoc: Option<renderdoc::RenderDoc<renderdoc::V110>>,
+    /// captures written so far, to report the new ones
+    #[allow(dead_code)]
+    captures: u32,
+}
+
+impl FrameCapture {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "renderdoc")]
+            renderdoc: renderdoc::RenderDoc::new().ok(),
+            captures: 0,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_available(&self) -> bool {
+        #[cfg(feature = "renderdoc")]
+        return self.renderdoc.is_some();
+        #[cfg(not(feature = "renderdoc"))]
+        false
+    }
+
+    /// captures the next frame presented, returning false when renderdoc isn't available
+    pub fn capture_next_frame(&mut self) -> bool {
+        #[cfg(feature = "renderdoc")]
+        if let Some(renderdoc) = &mut self.renderdoc {
+            renderdoc.trigger_capture();
+            return true;
+        }
+        false
+    }
+
+    /// the file of each capture written since the last call
+    pub fn new_captures(&mut self) -> Vec<std::path::PathBuf> {
+        #[cfg(feature = "renderdoc")]
+        if let Some(renderdoc) = &self.renderdoc {
+            let captures = renderdoc.get_num_captures();
+            let new = (self.captures..captures)
+                .filter_map(|i| renderdoc.get_capture(i).map(|(path, _)| path))
+                .collect();
+            self.captures = captures;
+            return new;
+        }
+        vec![]
+    }
+}
@@ -0,0 +1,49 @@
+// scene::CASTS_SHADOW, scene::RECEIVES_SHADOW and scene::LIGHTMAPPED
+const CASTS_SHADOW: u32 = 1u;
+const RECEIVES_SHADOW: u32 = 2u;
+const LIGHTMAPPED: u32 = 4u;
+
+fn apply_affine(
+    _0: vec4<f32>,
+    _1: vec4<f32>,
+    _2: vec4<f32>,
+    pos: vec3<f32>
+) -> vec3<f32> {
+    return vec3<f32>(
+        dot(_0.xyz, pos) + _0.w,
+        dot(_1.xyz, pos) + _1.w,
+        dot(_2.xyz, pos) + _2.w,
+    );
+}
+
+// math::Vector3::apply_normal, the unit normal after the affine's inverse transpose.
+// the cofactors are the inverse transpose times the determinant, whose sign keeps mirrored
+// instances' normals on the side they faced
+fn apply_normal(
+    _0: vec4<f32>,
+    _1: vec4<f32>,
+    _2: vec4<f32>,
+    normal: vec3<f32>
+) -> vec3<f32> {
+    let cofactors = mat3x3<f32>(
+        cross(_1.xyz, _2.xyz),
+        cross(_2.xyz, _0.xyz),
+        cross(_0.xyz, _1.xyz),
+    );
+    let det = dot(_0.xyz, cofactors[0]);
+    // the columns of the matrix are the rows of the cofactors
+    return normalize(normal * cofactors) * sign(det);
+}
+
+// grass on flat ground, rock on steep slopes, snow up high
+fn terrain_albedo(height: f32, normal: vec3<f32>) -> vec3<f32> {
+    let grass = vec3<f32>(0.16, 0.32, 0.08);
+    let rock = vec3<f32>(0.35, 0.32, 0.3);
+    let snow = vec3<f32>(0.9, 0.9, 0.95);
+
+    let steepness = smoothstep(0.75, 0.55, normal.y);
+    var albedo = mix(grass, rock, steepness);
+    // the terrain sits around y = -6
+    let snow_line = smoothstep(-5.0, -4.0, height) * smoothstep(0.6, 0.8, normal.y);
+    return mix(albedo, snow, snow_line);
+}
@@ -0,0 +1,31 @@
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+
+/// lines typed into the terminal, read on a thread of their own so the event loop never blocks on them
+pub struct Console {
+    lines: Receiver<String>,
+}
+
+impl Console {
+    /// `wake` is called after each line is sent, so a waiting event loop gets to read it
+    pub fn new(wake: impl Fn() + Send + 'static) -> Self {
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    return;
+                };
+                if sender.send(line).is_err() {
+                    return;
+                }
+                wake();
+            }
+        });
+        Self { lines }
+    }
+
+    /// the lines entered since the last call, without blank ones
+    pub fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.lines.try_iter().filter(|line| !line.trim().is_empty())
+    }
+}
@@ -0,0 +1,4 @@
+@fragment
+fn fs_main(in: FullscreenOut) -> @location(0) vec4<f32> {
+    return textureSampleLevel(input, input_sampler, in.uv, 0.0);
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use wgpu::*;
+
+use crate::binding::{self, BindGroupLayoutBuilder};
+use crate::buffer::GpuBuffer;
+use crate::math::{Affine3, Color, Vector3};
+use crate::pipeline_cache::{DepthKey, PipelineCache, PipelineKey};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+const DEBUG_VERTEX_LAYOUT: VertexBufferLayout = crate::layout::vertex_layout!(DebugVertex, Vertex, 0, [
+    position => Float32x3,
+    color => Float32x3,
+]);
+
+/// world space lines, collected every frame and drawn over the scene color,
+/// depth tested against the scene but not writing depth
+pub struct DebugDraw {
+    #[allow(dead_code)]
+    key: PipelineKey,
+    pipeline: Arc<RenderPipeline>,
+    bind_group: BindGroup,
+    lines: Vec<DebugVertex>,
+    vertices: GpuBuffer<DebugVertex>,
+}
+
+impl DebugDraw {
+    /// `camera` is the camera uniform buffer of the light pass
+    pub fn new(
+        device: &Device,
+        cache: &mut PipelineCache,
+        camera: BindingResource,
+        color_format: TextureFormat,
+        depth_format: TextureFormat,
+    ) -> Self {
+        let source = include_str!("debug_draw.wgsl");
+        let layout = BindGroupLayoutBuilder::new("debug draw bind group layout")
+            .uniform(ShaderStages::VERTEX);
+        let module = binding::reflect("debug_draw.wgsl", source);
+        layout.validate(0, &module).unwrap();
+        crate::layout::validate_vertex_input(&module, "vs_main", &[DEBUG_VERTEX_LAYOUT]).unwrap();
+        let binding = layout.build(device);
+        let bind_group = binding.create_bind_group(device, "debug draw bind group", &[camera]);
+
+        cache.register(device, "debug_draw.wgsl", source, &[&binding.layout], &[DEBUG_VERTEX_LAYOUT]);
+        let key = PipelineKey {
+            topology: PrimitiveTopology::LineList,
+            depth: Some(DepthKey {
+                format: depth_format,
+                write: false,
+                compare: CompareFunction::GreaterEqual,
+                stencil: None,
+            }),
+            ..PipelineKey::new("debug_draw.wgsl", Some(color_format))
+        };
+        let pipeline = cache.get(device, key);
+
+        Self {
+            key,
+            pipeline,
+            bind_group,
+            lines: Vec::new(),
+            vertices: GpuBuffer::new_vertex(device, "Debug draw vertex buffer", 256),
+        }
+    }
+
+    /// switches to the pipeline for other target formats
+    #[allow(dead_code)]
+    pub fn set_formats(
+        &mut self,
+        device: &Device,
+        cache: &mut PipelineCache,
+        color_format: TextureFormat,
+        depth_format: TextureFormat,
+    ) {
+        self.key.color_format = Some(color_format);
+        self.key.depth.as_mut().unwrap().format = depth_format;
+        self.pipeline = cache.get(device, self.key);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn line(&mut self, a: Vector3, b: Vector3, color: Color) {
+        let color = color.to_rgb_array();
+        self.lines.push(DebugVertex { position: [a.x, a.y, a.z], color });
+        self.lines.push(DebugVertex { position: [b.x, b.y, b.z], color });
+    }
+
+    /// outline of the quad `a b c d`, plus its diagonals so it reads as a surface
+    pub fn quad(&mut self, [a, b, c, d]: [Vector3; 4], color: Color) {
+        self.line(a, b, color);
+        self.line(b, c, color);
+        self.line(c, d, color);
+        self.line(d, a, color);
+        self.line(a, c, color);
+        self.line(b, d, color);
+    }
+
+    /// from `from` to `to`, with a head of two lines in the plane containing `side`
+    pub fn arrow(&mut self, from: Vector3, to: Vector3, side: Vector3, color: Color) {
+        let head = (from - to) * 0.2;
+        let side = side * (head.length() * 0.5 / side.length());
+        self.line(from, to, color);
+        self.line(to, to + head + side, color);
+        self.line(to, to + head - side, color);
+    }
+
+    /// gizmo of a light rendering with `view` as uploaded in the light buffer:
+    /// the frustum up to `far_z`, the near plane quad and an arrow along the light direction
+    pub fn light(&mut self, view: &Affine3, near_z: f32, far_z: f32) {
+        let to_world = view.inverse();
+        // light view space corners at z, projected to -1..1 by dividing by z
+        let corners = |z: f32| [(-z, -z), (z, -z), (z, z), (-z, z)]
+            .map(|(x, y)| Vector3::new(x, y, z).apply(&to_world));
+
+        let origin = Vector3::new(0.0, 0.0, 0.0).apply(&to_world);
+        let near = corners(near_z);
+        let far = corners(far_z);
+        let frustum_color = Color::rgb(0.6, 0.5, 0.2);
+        for i in 0..4 {
+            self.line(origin, far[i], frustum_color);
+            self.line(far[i], far[(i + 1) % 4], frustum_color);
+        }
+        self.quad(near, Color::rgb(1.0, 0.8, 0.2));
+
+        let forward = Vector3::new(0.0, 0.0, near_z * 3.0).apply(&to_world);
+        self.arrow(origin, forward, near[1] - near[0], Color::rgb(1.0, 0.9, 0.4));
+    }
+
+    /// uploads the lines of this frame
+    pub fn upload(&mut self, device: &Device, queue: &Queue) {
+        self.vertices.reserve(device, self.lines.len());
+        self.vertices.write(queue, &self.lines);
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if self.lines.is_empty() {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertices.slice());
+        pass.draw(0..self.lines.len() as u32, 0..1);
+    }
+}
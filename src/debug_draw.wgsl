@@ -0,0 +1,37 @@
+struct Camera {
+    view_0: vec4<f32>,
+    view_1: vec4<f32>,
+    view_2: vec4<f32>,
+    near_z: f32,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(vertex: VertexIn) -> VertexOut {
+    var out: VertexOut;
+    let view = vec3<f32>(
+        dot(camera.view_0.xyz, vertex.position) + camera.view_0.w,
+        dot(camera.view_1.xyz, vertex.position) + camera.view_1.w,
+        dot(camera.view_2.xyz, vertex.position) + camera.view_2.w,
+    );
+    // infinite reversed z, as in the light pass
+    out.clip_position = vec4<f32>(view.xy, camera.near_z, view.z);
+    out.color = vertex.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
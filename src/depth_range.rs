@@ -0,0 +1,153 @@
+use wgpu::*;
+
+use crate::binding::{self, BindGroupLayoutBuilder, BindingLayout};
+use crate::buffer::GpuBuffer;
+use crate::readback::Readback;
+
+const WORKGROUP_SIZE: u32 = 16;
+
+/// the nearest and furthest view z of anything the camera sees, reduced from its depth buffer
+/// on the gpu and read back asynchronously, so the shadow fit can cut the camera's frustum to
+/// where there's something to shadow instead of spreading the shadow map out to its far plane.
+/// the range trails the camera by the frames the readback takes, which `margin` makes up for
+pub struct DepthRange {
+    pub enabled: bool,
+    /// of the range's ends' z, added beyond them
+    pub margin: f32,
+    pipeline: ComputePipeline,
+    binding: BindingLayout,
+    /// the min then max non background depth's bits
+    range: GpuBuffer<u32>,
+    recorded: Option<Readback<u32>>,
+    pending: Option<Readback<u32>>,
+    /// the last depths read back, None when only background was seen
+    depths: Option<(f32, f32)>,
+}
+
+impl DepthRange {
+    pub fn new(device: &Device) -> Self {
+        let source = include_str!("depth_range.wgsl");
+        let layout = BindGroupLayoutBuilder::new("depth range bind group layout")
+            .storage(ShaderStages::COMPUTE, false)
+            .texture(ShaderStages::COMPUTE, TextureSampleType::Depth);
+        layout.validate(0, &binding::reflect("depth_range.wgsl", source)).unwrap();
+        let binding = layout.build(device);
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Depth range shader"),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Depth range pipeline layout"),
+            bind_group_layouts: &[&binding.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Depth range pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        Self {
+            enabled: true,
+            margin: 0.1,
+            pipeline,
+            binding,
+            range: GpuBuffer::new(device, "Depth range buffer", BufferUsages::STORAGE | BufferUsages::COPY_SRC, 2),
+            recorded: None,
+            pending: None,
+            depths: None,
+        }
+    }
+
+    /// reduces `depth`, the camera's reversed z depth buffer, and records the copy of the result,
+    /// unless the last one is still being read back
+    pub fn record(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        depth: &Texture,
+        depth_view: &TextureView,
+    ) {
+        if !self.enabled || self.recorded.is_some() || self.pending.is_some() {
+            return;
+        }
+        self.range.write(queue, &[u32::MAX, 0]);
+
+        let bind_group = self.binding.create_bind_group(
+            device,
+            "depth range bind group",
+            &[self.range.as_entire_binding(), BindingResource::TextureView(depth_view)],
+        );
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("depth range pass"),
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(
+                depth.width().div_ceil(WORKGROUP_SIZE),
+                depth.height().div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        self.recorded = Some(Readback::buffer(device, encoder, self.range.buffer(), 0, 2));
+    }
+
+    /// call with the submission of the encoder passed to `record`
+    pub fn submitted(&mut self, submission: &SubmissionIndex) {
+        if let Some(mut readback) = self.recorded.take() {
+            readback.map(submission.clone());
+            self.pending = Some(readback);
+        }
+    }
+
+    /// takes in the last reduction once it's read back
+    pub fn poll(&mut self, device: &Device) {
+        let Some(pending) = &mut self.pending else {
+            return;
+        };
+        if let Some(depths) = pending.try_read(device, |bits| {
+            (bits[0] <= bits[1]).then(|| (f32::from_bits(bits[0]), f32::from_bits(bits[1])))
+        }) {
+            self.depths = depths;
+            self.pending = None;
+        }
+    }
+
+    /// the near and far z to cut the camera's frustum at, within its own
+    pub fn view_z_range(&self, near_z: f32, far_z: f32) -> (f32, f32) {
+        match self.depths.filter(|_| self.enabled) {
+            Some(depths) => depths_to_view_z_range(depths, near_z, far_z, self.margin),
+            None => (near_z, far_z),
+        }
+    }
+}
+
+/// the view z range of reversed infinite z `depths`, where z = `near_z` / depth,
+/// padded by `margin` of each end's z and kept within `near_z` to `far_z`
+fn depths_to_view_z_range((min, max): (f32, f32), near_z: f32, far_z: f32, margin: f32) -> (f32, f32) {
+    let near = (near_z / max / (1.0 + margin)).clamp(near_z, far_z);
+    let far = (near_z / min * (1.0 + margin)).clamp(near_z, far_z);
+    (near, far)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depths_map_to_padded_view_z() {
+        // seen from 2 to 10 with the near plane at 0.5
+        let (near, far) = depths_to_view_z_range((0.05, 0.25), 0.5, 100.0, 0.0);
+        assert!((near - 2.0).abs() < 1e-5 && (far - 10.0).abs() < 1e-4);
+
+        let (near, far) = depths_to_view_z_range((0.05, 0.25), 0.5, 100.0, 0.1);
+        assert!(near < 2.0 && far > 10.0);
+
+        // clamped to the camera's own range
+        assert_eq!(depths_to_view_z_range((0.001, 1.0), 0.5, 100.0, 0.1), (0.5, 100.0));
+    }
+}
@@ -0,0 +1,45 @@
+// depth_range::DepthRange::range, the min then max non background depth as bits.
+// the depth is positive so its bits order like it
+@group(0) @binding(0) var<storage, read_write> range: array<atomic<u32>, 2>;
+@group(0) @binding(1) var depth_texture: texture_depth_2d;
+
+// depth_range::WORKGROUP_SIZE squared
+const INVOCATIONS: u32 = 256u;
+
+var<workgroup> mins: array<u32, INVOCATIONS>;
+var<workgroup> maxs: array<u32, INVOCATIONS>;
+
+@compute @workgroup_size(16, 16)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    let size = textureDimensions(depth_texture);
+    // the background and pixels past the edge leave the range as it is
+    var min_bits = 0xffffffffu;
+    var max_bits = 0u;
+    if all(id.xy < size) {
+        let depth = textureLoad(depth_texture, vec2<i32>(id.xy), 0);
+        if depth > 0.0 {
+            min_bits = bitcast<u32>(depth);
+            max_bits = min_bits;
+        }
+    }
+    mins[index] = min_bits;
+    maxs[index] = max_bits;
+    workgroupBarrier();
+
+    // halves the live invocations every step, each folding in the one half the count away
+    for (var stride = INVOCATIONS / 2u; stride > 0u; stride /= 2u) {
+        if index < stride {
+            mins[index] = min(mins[index], mins[index + stride]);
+            maxs[index] = max(maxs[index], maxs[index + stride]);
+        }
+        workgroupBarrier();
+    }
+
+    if index == 0u && maxs[0] != 0u {
+        atomicMin(&range[0], mins[0]);
+        atomicMax(&range[1], maxs[0]);
+    }
+}
@@ -0,0 +1,58 @@
+// post::ResolveParams
+struct ResolveParams {
+    // one of the TONEMAP_ constants
+    tonemap: u32,
+    // scales the scene color before it's tonemapped
+    exposure: f32,
+    // one of the DITHER_ constants
+    dither: u32,
+    // amplitude in steps of the 8 bit output
+    dither_strength: f32,
+    // whether the output is stored as srgb, and so quantized after encoding
+    srgb_output: u32,
+}
+
+@group(0) @binding(2) var<uniform> resolve_params: ResolveParams;
+// noise::blue_noise, tiled over the screen
+@group(0) @binding(3) var blue_noise: texture_2d<f32>;
+
+const DITHER_OFF: u32 = 0u;
+const DITHER_ORDERED: u32 = 1u;
+const DITHER_BLUE_NOISE: u32 = 2u;
+
+// threshold of the 8 by 8 bayer matrix in 0 to 1, built up from the 2 by 2 one
+fn bayer(pixel: vec2<u32>) -> f32 {
+    var index = 0u;
+    for (var bit = 0u; bit < 3u; bit++) {
+        let x = (pixel.x >> bit) & 1u;
+        let y = (pixel.y >> bit) & 1u;
+        index |= (((x ^ y) << 1u) | y) << (2u * (2u - bit));
+    }
+    return (f32(index) + 0.5) / 64.0;
+}
+
+// offsets `color` by up to half a step of the output either way, so gradients finer than
+// its steps come out as a mix of the two nearest instead of bands
+fn dither(color: vec4<f32>, position: vec2<f32>) -> vec4<f32> {
+    let pixel = vec2<u32>(position);
+    var threshold = 0.5;
+    if resolve_params.dither == DITHER_ORDERED {
+        threshold = bayer(pixel);
+    } else if resolve_params.dither == DITHER_BLUE_NOISE {
+        threshold = textureLoad(blue_noise, pixel % textureDimensions(blue_noise), 0).r;
+    }
+    let offset = (threshold - 0.5) * resolve_params.dither_strength / 255.0;
+
+    if resolve_params.srgb_output == 0u {
+        return vec4<f32>(color.rgb + offset, color.a);
+    }
+    let encoded = to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+    return vec4<f32>(from_srgb(encoded + offset), color.a);
+}
+
+// copy.wgsl, tonemapped and dithered
+@fragment
+fn fs_copy(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(input, input_sampler, in.uv, 0.0);
+    return dither(vec4<f32>(tonemap(color.rgb), color.a), in.position.xy);
+}
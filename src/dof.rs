@@ -0,0 +1,112 @@
+use wgpu::*;
+
+use crate::buffer::GpuBuffer;
+use crate::post::{self, FullscreenPass, PostInput, PostNode, RenderTarget, HDR_FORMAT};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DofParams {
+    /// view space distance which is in focus
+    pub focus_distance: f32,
+    /// circle of confusion per unit of relative distance from the focus
+    pub aperture: f32,
+    /// blur radius in pixels of a full circle of confusion
+    pub max_radius: f32,
+    /// of the camera, to linearize depth
+    pub near_z: f32,
+}
+
+impl Default for DofParams {
+    fn default() -> Self {
+        Self {
+            focus_distance: 10.0,
+            aperture: 0.5,
+            max_radius: 12.0,
+            near_z: 1.0,
+        }
+    }
+}
+
+/// circle of confusion from the depth buffer, separable bokeh blur, then composite over the sharp input
+pub struct DepthOfField {
+    pub params: DofParams,
+    params_buffer: GpuBuffer<DofParams>,
+    blur_x: FullscreenPass,
+    blur_y: FullscreenPass,
+    composite: FullscreenPass,
+    blurred_x: RenderTarget,
+    blurred_y: RenderTarget,
+    /// blur x, blur y and composite
+    bind_groups: Option<[BindGroup; 3]>,
+}
+
+impl DepthOfField {
+    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+        let pass = |label, entry_point| FullscreenPass::new(
+            device,
+            label,
+            include_str!("dof.wgsl"),
+            entry_point,
+            HDR_FORMAT,
+            post::input_layout("dof bind group layout")
+                .uniform(ShaderStages::FRAGMENT)
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Depth)
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: true }),
+        );
+
+        Self {
+            params: DofParams::default(),
+            params_buffer: GpuBuffer::new_uniform(device, "dof params buffer"),
+            blur_x: pass("dof blur x", "fs_blur_x"),
+            blur_y: pass("dof blur y", "fs_blur_y"),
+            composite: pass("dof composite", "fs_composite"),
+            blurred_x: RenderTarget::new(device, "dof blurred x", HDR_FORMAT, width, height),
+            blurred_y: RenderTarget::new(device, "dof blurred y", HDR_FORMAT, width, height),
+            bind_groups: None,
+        }
+    }
+}
+
+impl PostNode for DepthOfField {
+    fn name(&self) -> &'static str {
+        "depth of field"
+    }
+
+    fn inputs(&self) -> &'static [PostInput] {
+        &[PostInput::Previous, PostInput::SceneDepth]
+    }
+
+    fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        self.blurred_x = RenderTarget::new(device, "dof blurred x", HDR_FORMAT, width, height);
+        self.blurred_y = RenderTarget::new(device, "dof blurred y", HDR_FORMAT, width, height);
+    }
+
+    /// every pass shares the layout, so the unused `blurred` slot is filled with a
+    /// texture the pass isn't rendering to
+    fn bind(&mut self, device: &Device, sampler: &Sampler, inputs: &[&TextureView]) {
+        let (input, depth) = (inputs[0], inputs[1]);
+        let bind_group = |pass: &FullscreenPass, input: &TextureView, blurred: &TextureView| {
+            pass.binding.create_bind_group(device, "dof bind group", &[
+                BindingResource::TextureView(input),
+                BindingResource::Sampler(sampler),
+                self.params_buffer.as_entire_binding(),
+                BindingResource::TextureView(depth),
+                BindingResource::TextureView(blurred),
+            ])
+        };
+
+        self.bind_groups = Some([
+            bind_group(&self.blur_x, input, &self.blurred_y.view),
+            bind_group(&self.blur_y, &self.blurred_x.view, input),
+            bind_group(&self.composite, input, &self.blurred_y.view),
+        ]);
+    }
+
+    fn run(&mut self, queue: &Queue, encoder: &mut CommandEncoder, output: &TextureView) {
+        let [blur_x, blur_y, composite] = self.bind_groups.as_ref().unwrap();
+        self.params_buffer.write(queue, &[self.params]);
+        self.blur_x.draw(encoder, "dof blur x pass", &self.blurred_x.view, blur_x);
+        self.blur_y.draw(encoder, "dof blur y pass", &self.blurred_y.view, blur_y);
+        self.composite.draw(encoder, "dof composite pass", output, composite);
+    }
+}
@@ -0,0 +1,70 @@
+struct DofParams {
+    // view space distance which is in focus
+    focus_distance: f32,
+    // circle of confusion per unit of relative distance from the focus
+    aperture: f32,
+    // blur radius in pixels of a full circle of confusion
+    max_radius: f32,
+    near_z: f32,
+}
+
+@group(0) @binding(2) var<uniform> params: DofParams;
+@group(0) @binding(3) var depth: texture_depth_2d;
+@group(0) @binding(4) var blurred: texture_2d<f32>;
+
+// taps on each side of the center
+const TAPS: i32 = 8;
+
+// in 0 to 1
+fn circle_of_confusion(uv: vec2<f32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(depth));
+    let coords = clamp(vec2<i32>(uv * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    let d = textureLoad(depth, coords, 0);
+
+    // infinite reversed z: depth is near_z / view_z, 0 being infinitely far away
+    var relative = 1.0;
+    if d > 0.0 {
+        let view_z = params.near_z / d;
+        relative = abs(1.0 - params.focus_distance / view_z);
+    }
+    return clamp(params.aperture * relative, 0.0, 1.0);
+}
+
+// disc weighted gather along `dir`, in texels
+fn blur(uv: vec2<f32>, dir: vec2<f32>, coc: f32) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input));
+    let step = dir * texel * coc * params.max_radius / f32(TAPS);
+
+    var sum = vec3<f32>(0.0);
+    var weight_sum = 0.0;
+    for (var i = -TAPS; i <= TAPS; i++) {
+        let t = f32(i) / f32(TAPS + 1);
+        // a disc's width at t, so two passes approximate a round bokeh
+        let weight = sqrt(1.0 - t * t);
+        sum += textureSampleLevel(input, input_sampler, uv + step * f32(i), 0.0).rgb * weight;
+        weight_sum += weight;
+    }
+    return sum / weight_sum;
+}
+
+// scene color -> horizontally blurred color, with the circle of confusion in alpha
+@fragment
+fn fs_blur_x(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let coc = circle_of_confusion(in.uv);
+    return vec4<f32>(blur(in.uv, vec2<f32>(1.0, 0.0), coc), coc);
+}
+
+@fragment
+fn fs_blur_y(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let coc = textureSampleLevel(input, input_sampler, in.uv, 0.0).a;
+    return vec4<f32>(blur(in.uv, vec2<f32>(0.0, 1.0), coc), coc);
+}
+
+// scene color and fully blurred color blended by the sharp circle of confusion
+@fragment
+fn fs_composite(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let sharp = textureSampleLevel(input, input_sampler, in.uv, 0.0);
+    let soft = textureSampleLevel(blurred, input_sampler, in.uv, 0.0);
+    let coc = circle_of_confusion(in.uv);
+    return vec4<f32>(mix(sharp.rgb, soft.rgb, smoothstep(0.0, 0.5, coc)), sharp.a);
+}
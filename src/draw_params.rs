@@ -0,0 +1,202 @@
+use std::{mem::size_of, num::NonZeroU64};
+
+use wgpu::*;
+
+use crate::binding::{BindGroupLayoutBuilder, BindingLayout};
+use crate::pipeline_cache::Cull;
+
+/// small per-draw data, declared as `draw` in shaders by `wgsl_declaration`
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct DrawParams {
+    pub material: u32,
+    pub cascade: u32,
+    pub _padding: [u32; 2],
+}
+
+pub const MATERIAL_LIT: u32 = 0;
+pub const MATERIAL_UNLIT: u32 = 1;
+/// shaded by slope and height, see `terrain_albedo` in `common.wgsl`
+pub const MATERIAL_TERRAIN: u32 = 2;
+/// lit and seen from both sides, for thin geometry
+pub const MATERIAL_TWO_SIDED: u32 = 3;
+
+/// faces a material's pipelines cull
+pub fn cull(material: u32) -> Cull {
+    match material {
+        MATERIAL_TWO_SIDED => Cull::None,
+        _ => Cull::Back,
+    }
+}
+
+/// maximum draws per frame on the uniform fallback path
+const MAX_DRAWS: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DrawParamsMode {
+    PushConstants,
+    /// one dynamic offset into a uniform buffer per draw, bound at `group`
+    Uniform { group: u32 },
+}
+
+/// handed out by `DrawParamsSet::push` and used to bind the params during the pass
+#[derive(Clone, Copy)]
+pub struct DrawSlot {
+    index: usize,
+    params: DrawParams,
+}
+
+/// per-draw data through push constants when `Features::PUSH_CONSTANTS` is available,
+/// otherwise through a dynamically offset uniform buffer.
+/// params are pushed before recording a pass, then bound per draw with `bind`,
+/// and `flush` uploads the fallback buffer before the frame is submitted.
+pub struct DrawParamsSet {
+    pub mode: DrawParamsMode,
+    fallback: Option<UniformFallback>,
+    params: Vec<DrawParams>,
+}
+
+struct UniformFallback {
+    binding: BindingLayout,
+    buffer: Buffer,
+    bind_group: BindGroup,
+    stride: usize,
+}
+
+impl DrawSlot {
+    pub fn material(&self) -> u32 {
+        self.params.material
+    }
+}
+
+impl DrawParamsSet {
+    /// features and limits to request so the push constant path can be used
+    pub fn required_features(adapter: &Adapter) -> (Features, Limits) {
+        let mut limits = Limits::default();
+        if adapter.features().contains(Features::PUSH_CONSTANTS)
+            && adapter.limits().max_push_constant_size >= size_of::<DrawParams>() as u32
+        {
+            limits.max_push_constant_size = size_of::<DrawParams>() as u32;
+            (Features::PUSH_CONSTANTS, limits)
+        } else {
+            (Features::empty(), limits)
+        }
+    }
+
+    /// `group` is where the uniform fallback gets bound
+    pub fn new(device: &Device, group: u32) -> Self {
+        if device.features().contains(Features::PUSH_CONSTANTS) {
+            return Self {
+                mode: DrawParamsMode::PushConstants,
+                fallback: None,
+                params: vec![],
+            };
+        }
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as usize;
+        let stride = size_of::<DrawParams>().div_ceil(alignment) * alignment;
+        let binding = BindGroupLayoutBuilder::new("draw params bind group layout")
+            .uniform_dynamic(ShaderStages::VERTEX_FRAGMENT)
+            .build(device);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Draw params buffer"),
+            size: (stride * MAX_DRAWS) as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = binding.create_bind_group(device, "draw params bind group", &[
+            BindingResource::Buffer(BufferBinding {
+                buffer: &buffer,
+                offset: 0,
+                size: NonZeroU64::new(size_of::<DrawParams>() as u64),
+            }),
+        ]);
+
+        Self {
+            mode: DrawParamsMode::Uniform { group },
+            fallback: Some(UniformFallback {
+                binding,
+                buffer,
+                bind_group,
+                stride,
+            }),
+            params: vec![],
+        }
+    }
+
+    /// extra bind group layout to append to pipeline layouts, at the fallback's group
+    pub fn bind_group_layout(&self) -> Option<&BindGroupLayout> {
+        self.fallback.as_ref().map(|f| &f.binding.layout)
+    }
+
+    pub fn push_constant_ranges(&self) -> Vec<PushConstantRange> {
+        match self.mode {
+            DrawParamsMode::PushConstants => vec![PushConstantRange {
+                stages: ShaderStages::VERTEX_FRAGMENT,
+                range: 0..size_of::<DrawParams>() as u32,
+            }],
+            DrawParamsMode::Uniform { .. } => vec![],
+        }
+    }
+
+    /// to be prepended to shaders reading `draw`
+    #[allow(dead_code)]
+    pub fn wgsl_declaration(&self) -> String {
+        wgsl_declaration(self.mode)
+    }
+
+    /// forgets the previous frame's params
+    pub fn clear(&mut self) {
+        self.params.clear();
+    }
+
+    pub fn push(&mut self, params: DrawParams) -> DrawSlot {
+        assert!(self.params.len() < MAX_DRAWS, "too many draws with params in one frame");
+        self.params.push(params);
+        DrawSlot {
+            index: self.params.len() - 1,
+            params,
+        }
+    }
+
+    pub fn bind<'a>(&'a self, pass: &mut RenderPass<'a>, slot: DrawSlot) {
+        match (self.mode, &self.fallback) {
+            (DrawParamsMode::Uniform { group }, Some(fallback)) => {
+                pass.set_bind_group(group, &fallback.bind_group, &[(slot.index * fallback.stride) as u32]);
+            }
+            _ => pass.set_push_constants(
+                ShaderStages::VERTEX_FRAGMENT,
+                0,
+                bytemuck::bytes_of(&slot.params),
+            ),
+        }
+    }
+
+    /// uploads the fallback uniforms, call before submitting the passes using them
+    pub fn flush(&self, queue: &Queue) {
+        let Some(fallback) = &self.fallback else {
+            return;
+        };
+
+        let mut data = vec![0u8; self.params.len() * fallback.stride];
+        for (params, chunk) in self.params.iter().zip(data.chunks_mut(fallback.stride)) {
+            chunk[..size_of::<DrawParams>()].copy_from_slice(bytemuck::bytes_of(params));
+        }
+        if !data.is_empty() {
+            queue.write_buffer(&fallback.buffer, 0, &data);
+        }
+    }
+}
+
+pub fn wgsl_declaration(mode: DrawParamsMode) -> String {
+    let var = match mode {
+        DrawParamsMode::PushConstants => "var<push_constant> draw: DrawParams;".to_string(),
+        DrawParamsMode::Uniform { group } => {
+            format!("@group({}) @binding(0) var<uniform> draw: DrawParams;", group)
+        }
+    };
+    format!(
+        "struct DrawParams {{\n    material: u32,\n    cascade: u32,\n}};\n{}\n\n",
+        var,
+    )
+}
@@ -0,0 +1,165 @@
+use crate::fog::Fog;
+use crate::math::{Color, Vector3};
+
+/// lighting and atmosphere of a scene, saved as lines of `name values`
+#[derive(Clone, PartialEq, Debug)]
+pub struct Environment {
+    /// of the background where nothing is drawn, linear
+    pub clear_color: [f32; 3],
+    /// light reaching every lit surface, shadowed or not
+    pub ambient: [f32; 3],
+    pub fog_color: [f32; 3],
+    /// extinction per unit of distance
+    pub fog_density: f32,
+    /// how fast height fog thins out going up
+    pub fog_height_falloff: f32,
+    /// height at which height fog has `fog_density`
+    pub fog_height_base: f32,
+    /// strength of the in-scattering lobe around the light
+    pub fog_scattering: f32,
+    /// direction the light shines along while the sun is off, normalized when set
+    pub sun_direction: [f32; 3],
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            clear_color: [0.05, 0.02, 0.07],
+            ambient: [0.0; 3],
+            fog_color: [0.05, 0.02, 0.07],
+            fog_density: 0.02,
+            fog_height_falloff: 0.3,
+            fog_height_base: 0.0,
+            fog_scattering: 0.5,
+            sun_direction: [0.0, 0.0, 1.0],
+        }
+    }
+}
+
+impl Environment {
+    pub const FILE: &'static str = "environment.txt";
+
+    fn values(&mut self) -> [(&'static str, &mut [f32]); 8] {
+        [
+            ("clear_color", &mut self.clear_color),
+            ("ambient", &mut self.ambient),
+            ("fog_color", &mut self.fog_color),
+            ("fog_density", std::slice::from_mut(&mut self.fog_density)),
+            ("fog_height_falloff", std::slice::from_mut(&mut self.fog_height_falloff)),
+            ("fog_height_base", std::slice::from_mut(&mut self.fog_height_base)),
+            ("fog_scattering", std::slice::from_mut(&mut self.fog_scattering)),
+            ("sun_direction", &mut self.sun_direction),
+        ]
+    }
+
+    /// sets the setting `name` to the whitespace separated `values`
+    pub fn set(&mut self, name: &str, values: &str) -> Result<(), String> {
+        let mut settings = self.values();
+        let (_, setting) = settings
+            .iter_mut()
+            .find(|(n, _)| *n == name)
+            .ok_or_else(|| format!("no setting `{}`", name))?;
+
+        let parsed: Vec<f32> = values
+            .split_whitespace()
+            .map(|v| v.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("`{}` isn't a list of numbers", values))?;
+        if parsed.len() != setting.len() {
+            return Err(format!("`{}` takes {} numbers, not {}", name, setting.len(), parsed.len()));
+        }
+        setting.copy_from_slice(&parsed);
+
+        if name == "sun_direction" {
+            let direction = self.sun().normalized();
+            if !direction.x.is_finite() {
+                self.sun_direction = Self::default().sun_direction;
+                return Err("the sun direction can't be zero".to_string());
+            }
+            self.sun_direction = [direction.x, direction.y, direction.z];
+        }
+        Ok(())
+    }
+
+    /// `line` as `name values`
+    pub fn set_line(&mut self, line: &str) -> Result<(), String> {
+        let (name, values) = line.trim().split_once(char::is_whitespace).unwrap_or((line.trim(), ""));
+        self.set(name, values)
+    }
+
+    /// the settings given by `text`, defaults for the others. lines starting with `#` are comments
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut environment = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            environment.set_line(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        }
+        Ok(environment)
+    }
+
+    pub fn to_text(&self) -> String {
+        self.clone()
+            .values()
+            .iter()
+            .map(|(name, values)| {
+                let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+                format!("{} {}\n", name, values.join(" "))
+            })
+            .collect()
+    }
+
+    /// `FILE` in the working directory, the defaults when there's none
+    pub fn load() -> Result<Self, String> {
+        match std::fs::read_to_string(Self::FILE) {
+            Ok(text) => Self::from_text(&text).map_err(|e| format!("{}: {}", Self::FILE, e)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        std::fs::write(Self::FILE, self.to_text()).map_err(|e| format!("{}: {}", Self::FILE, e))
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        Color::from(self.clear_color).to_wgpu()
+    }
+
+    pub fn ambient(&self) -> Color {
+        self.ambient.into()
+    }
+
+    pub fn sun(&self) -> Vector3 {
+        let [x, y, z] = self.sun_direction;
+        Vector3::new(x, y, z)
+    }
+
+    pub fn apply_fog(&self, fog: &mut Fog) {
+        fog.color = self.fog_color.into();
+        fog.density = self.fog_density;
+        fog.height_falloff = self.fog_height_falloff;
+        fog.height_base = self.fog_height_base;
+        fog.scattering = self.fog_scattering;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment_round_trips_through_text() {
+        let mut environment = Environment::default();
+        environment.set_line("ambient 0.1 0.2 0.3").unwrap();
+        environment.set("fog_density", "0.5").unwrap();
+        environment.set("sun_direction", "0 -2 0").unwrap();
+        assert_eq!(environment.sun_direction, [0.0, -1.0, 0.0]);
+        assert_eq!(Environment::from_text(&environment.to_text()).unwrap(), environment);
+
+        assert!(environment.set("ambient", "1 2").is_err());
+        assert!(environment.set("sun_direction", "0 0 0").is_err());
+        let error = Environment::from_text("# comment\nfog_density 1\nfog thick").unwrap_err();
+        assert!(error.starts_with("line 3: "), "{}", error);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::math::{DVector3, Vector3};
+
+/// where the local space everything is placed in sits in the absolute world, moved along the xz
+/// plane once the camera gets too far from it, so translations near the camera stay small enough
+/// for single precision. heights stay small anyway and aren't rebased
+pub struct FloatingOrigin {
+    /// absolute position of the local origin
+    origin: DVector3,
+    /// how far the camera gets from the local origin along x or z before it's rebased
+    pub rebase_distance: f32,
+    /// shifts are multiples of it, so grids laid out in the world such as the streamed
+    /// chunks stay aligned with the local origin
+    pub grid: f32,
+}
+
+impl FloatingOrigin {
+    pub fn new(rebase_distance: f32, grid: f32) -> Self {
+        Self {
+            origin: DVector3::IDENTITY,
+            rebase_distance,
+            grid,
+        }
+    }
+
+    pub fn origin(&self) -> DVector3 {
+        self.origin
+    }
+
+    #[allow(dead_code)]
+    pub fn to_absolute(&self, local: Vector3) -> DVector3 {
+        self.origin + local.into()
+    }
+
+    pub fn to_local(&self, absolute: DVector3) -> Vector3 {
+        (absolute - self.origin).to_f32()
+    }
+
+    /// when `camera` is too far from the local origin, moves the origin under it and returns by
+    /// how much, which everything placed in local space has to be moved back by
+    pub fn update(&mut self, camera: Vector3) -> Option<Vector3> {
+        if camera.x.abs().max(camera.z.abs()) <= self.rebase_distance {
+            return None;
+        }
+        let snap = |x: f32| (x / self.grid).round() * self.grid;
+        let shift = Vector3::new(snap(camera.x), 0.0, snap(camera.z));
+        self.origin = self.origin + shift.into();
+        Some(shift)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebasing_keeps_absolute_positions() {
+        let mut origin = FloatingOrigin::new(100.0, 16.0);
+        assert_eq!(origin.update(Vector3::new(99.0, 1000.0, -99.0)), None);
+
+        let camera = Vector3::new(130.0, 2.0, -20.0);
+        let absolute = origin.to_absolute(camera);
+        let shift = origin.update(camera).unwrap();
+        assert_eq!(shift, Vector3::new(128.0, 0.0, -16.0));
+        let camera = camera - shift;
+        assert_eq!(origin.to_absolute(camera), absolute);
+        assert_eq!(origin.to_local(absolute), camera);
+        assert_eq!(origin.update(camera), None);
+    }
+}
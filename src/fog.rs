@@ -0,0 +1,110 @@
+use wgpu::*;
+
+use crate::buffer::GpuBuffer;
+use crate::math::{Affine3, Color, Vector3};
+use crate::post::{self, FullscreenPass, PostInput, PostNode, HDR_FORMAT};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FogParams {
+    inverse_view: Affine3,
+    camera_position: [f32; 3],
+    near_z: f32,
+    color: [f32; 3],
+    density: f32,
+    light_direction: [f32; 3],
+    height_falloff: f32,
+    light_color: [f32; 3],
+    height_base: f32,
+    scattering: f32,
+    _padding: [f32; 3],
+}
+
+/// exponential distance and height fog from the depth buffer,
+/// brightened when looking against the light
+pub struct Fog {
+    pub color: Color,
+    /// extinction per unit of distance
+    pub density: f32,
+    /// how fast height fog thins out going up
+    pub height_falloff: f32,
+    /// height at which height fog has `density`
+    pub height_base: f32,
+    /// direction the light travels in
+    pub light_direction: Vector3,
+    pub light_color: Color,
+    /// strength of the in-scattering lobe around the light
+    pub scattering: f32,
+    params: FogParams,
+    params_buffer: GpuBuffer<FogParams>,
+    pass: FullscreenPass,
+    bind_group: Option<BindGroup>,
+}
+
+impl Fog {
+    pub fn new(device: &Device) -> Self {
+        let pass = FullscreenPass::new(
+            device,
+            "fog.wgsl",
+            include_str!("fog.wgsl"),
+            "fs_main",
+            HDR_FORMAT,
+            post::input_layout("fog bind group layout")
+                .uniform(ShaderStages::FRAGMENT)
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Depth),
+        );
+
+        Self {
+            color: Color::rgb(0.05, 0.02, 0.07),
+            density: 0.02,
+            height_falloff: 0.3,
+            height_base: 0.0,
+            light_direction: Vector3::new(0.0, 0.0, 1.0),
+            light_color: Color::rgb(1.0, 0.8, 0.5),
+            scattering: 0.5,
+            params: bytemuck::Zeroable::zeroed(),
+            params_buffer: GpuBuffer::new_uniform(device, "fog params buffer"),
+            pass,
+            bind_group: None,
+        }
+    }
+
+    /// `view` as uploaded in the camera buffer
+    pub fn set_view(&mut self, view: &Affine3, camera_position: Vector3, near_z: f32) {
+        self.params.inverse_view = view.inverse();
+        self.params.camera_position = [camera_position.x, camera_position.y, camera_position.z];
+        self.params.near_z = near_z;
+    }
+}
+
+impl PostNode for Fog {
+    fn name(&self) -> &'static str {
+        "fog"
+    }
+
+    fn inputs(&self) -> &'static [PostInput] {
+        &[PostInput::Previous, PostInput::SceneDepth]
+    }
+
+    fn bind(&mut self, device: &Device, sampler: &Sampler, inputs: &[&TextureView]) {
+        self.bind_group = Some(self.pass.binding.create_bind_group(device, "fog bind group", &[
+            BindingResource::TextureView(inputs[0]),
+            BindingResource::Sampler(sampler),
+            self.params_buffer.as_entire_binding(),
+            BindingResource::TextureView(inputs[1]),
+        ]));
+    }
+
+    fn run(&mut self, queue: &Queue, encoder: &mut CommandEncoder, output: &TextureView) {
+        let direction = self.light_direction;
+        self.params.color = self.color.to_rgb_array();
+        self.params.density = self.density;
+        self.params.height_falloff = self.height_falloff;
+        self.params.height_base = self.height_base;
+        self.params.light_direction = [direction.x, direction.y, direction.z];
+        self.params.light_color = self.light_color.to_rgb_array();
+        self.params.scattering = self.scattering;
+        self.params_buffer.write(queue, &[self.params]);
+        self.pass.draw(encoder, "fog pass", output, self.bind_group.as_ref().unwrap());
+    }
+}
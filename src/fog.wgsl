@@ -0,0 +1,67 @@
+struct FogParams {
+    // camera view to world
+    inverse_view_0: vec4<f32>,
+    inverse_view_1: vec4<f32>,
+    inverse_view_2: vec4<f32>,
+    camera_position: vec3<f32>,
+    near_z: f32,
+    color: vec3<f32>,
+    // extinction per unit of distance
+    density: f32,
+    // direction the light travels in
+    light_direction: vec3<f32>,
+    // how fast height fog thins out going up
+    height_falloff: f32,
+    light_color: vec3<f32>,
+    // height at which height fog has `density`
+    height_base: f32,
+    // strength of the in-scattering lobe around the light
+    scattering: f32,
+}
+
+@group(0) @binding(2) var<uniform> params: FogParams;
+@group(0) @binding(3) var depth: texture_depth_2d;
+
+// distance given to the background
+const FAR_DISTANCE: f32 = 10000.0;
+
+fn to_world(v: vec3<f32>, w: f32) -> vec3<f32> {
+    return vec3<f32>(
+        dot(params.inverse_view_0.xyz, v) + params.inverse_view_0.w * w,
+        dot(params.inverse_view_1.xyz, v) + params.inverse_view_1.w * w,
+        dot(params.inverse_view_2.xyz, v) + params.inverse_view_2.w * w,
+    );
+}
+
+@fragment
+fn fs_main(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(depth));
+    let coords = clamp(vec2<i32>(in.uv * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    let d = textureLoad(depth, coords, 0);
+
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let ray = normalize(to_world(vec3<f32>(ndc, 1.0), 0.0));
+    var distance = FAR_DISTANCE;
+    if d > 0.0 {
+        let view_z = params.near_z / d;
+        distance = length(to_world(vec3<f32>(ndc * view_z, view_z), 1.0) - params.camera_position);
+    }
+
+    // optical depth of the uniform part plus the exponential height part integrated along the ray
+    var optical_depth = params.density * distance;
+    let camera_height_density = params.density * exp(-params.height_falloff * (params.camera_position.y - params.height_base));
+    let falloff = params.height_falloff * ray.y * distance;
+    if abs(falloff) > 0.0001 {
+        optical_depth += camera_height_density * distance * (1.0 - exp(-falloff)) / falloff;
+    } else {
+        optical_depth += camera_height_density * distance;
+    }
+    let fog = 1.0 - exp(-optical_depth);
+
+    // light scattered towards the camera when looking against the light
+    let in_scattering = pow(max(dot(ray, -params.light_direction), 0.0), 8.0) * params.scattering;
+    let fog_color = params.color + params.light_color * in_scattering;
+
+    let color = textureSampleLevel(input, input_sampler, in.uv, 0.0);
+    return vec4<f32>(mix(color.rgb, fog_color, fog), color.a);
+}
@@ -0,0 +1,104 @@
+use std::ops::{Index, IndexMut};
+
+use wgpu::*;
+
+/// bounds how many frames the cpu records ahead of the gpu.
+/// each frame in flight has its own slot of per-frame resources, and `begin` waits until
+/// the gpu finished the frame that last used the slot, so writing them never races the gpu.
+pub struct FramesInFlight {
+    /// the submission of the last frame using each slot
+    submissions: Vec<Option<SubmissionIndex>>,
+    frame: u64,
+}
+
+impl FramesInFlight {
+    pub fn new(max_in_flight: usize) -> Self {
+        assert!(max_in_flight > 0, "at least one frame must be in flight");
+        Self {
+            submissions: vec![None; max_in_flight],
+            frame: 0,
+        }
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.submissions.len()
+    }
+
+    /// frames ended so far
+    #[allow(dead_code)]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// the slot of the frame being recorded
+    pub fn slot(&self) -> usize {
+        (self.frame % self.submissions.len() as u64) as usize
+    }
+
+    /// blocks until the slot of the next frame is free again and returns it
+    pub fn begin(&mut self, device: &Device) -> usize {
+        profiling::scope!("wait for frame in flight");
+        let slot = self.slot();
+        if let Some(submission) = self.submissions[slot].take() {
+            device.poll(Maintain::WaitForSubmissionIndex(submission));
+        }
+        slot
+    }
+
+    /// `submission` is the last one of the frame's slot
+    pub fn end(&mut self, submission: SubmissionIndex) {
+        let slot = self.slot();
+        self.submissions[slot] = Some(submission);
+        self.frame += 1;
+    }
+
+    /// blocks until every frame in flight finished, e.g. before destroying what they use
+    #[allow(dead_code)]
+    pub fn wait_all(&mut self, device: &Device) {
+        for submission in self.submissions.iter_mut().filter_map(Option::take) {
+            device.poll(Maintain::WaitForSubmissionIndex(submission));
+        }
+    }
+}
+
+/// one `T` per slot of `FramesInFlight`
+pub struct PerFrame<T>(Vec<T>);
+
+impl<T> PerFrame<T> {
+    pub fn new(frames: &FramesInFlight, f: impl FnMut(usize) -> T) -> Self {
+        Self((0..frames.max_in_flight()).map(f).collect())
+    }
+
+    #[allow(dead_code)]
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.0.iter()
+    }
+}
+
+impl<T> Index<usize> for PerFrame<T> {
+    type Output = T;
+
+    fn index(&self, slot: usize) -> &T {
+        &self.0[slot]
+    }
+}
+
+impl<T> IndexMut<usize> for PerFrame<T> {
+    fn index_mut(&mut self, slot: usize) -> &mut T {
+        &mut self.0[slot]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_frame_has_a_value_per_slot() {
+        let frames = FramesInFlight::new(3);
+        assert_eq!(frames.slot(), 0);
+        let per_frame = PerFrame::new(&frames, |slot| slot * 10);
+        assert_eq!(per_frame.iter().copied().collect::<Vec<_>>(), [0, 10, 20]);
+        assert_eq!(per_frame[2], 20);
+    }
+}
@@ -0,0 +1,18 @@
+struct FullscreenOut {
+    @builtin(position) position: vec4<f32>,
+    // (0, 0) top left to (1, 1) bottom right
+    @location(0) uv: vec2<f32>,
+}
+
+// a single triangle covering the screen
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> FullscreenOut {
+    var out: FullscreenOut;
+    out.uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.position = vec4<f32>(out.uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+@group(0) @binding(0) var input: texture_2d<f32>;
+@group(0) @binding(1) var input_sampler: sampler;
+
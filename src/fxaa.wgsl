@@ -0,0 +1,55 @@
+// fxaa along the lines of Timothy Lottes' "FXAA 3.11 console" variant:
+// blur along the local edge direction, falling back to a narrower blur
+// when the wide one pulls in colors outside the local luma range
+
+const SPAN_MAX: f32 = 8.0;
+const REDUCE_MUL: f32 = 0.125;
+const REDUCE_MIN: f32 = 0.0078125;
+
+fn luma(color: vec3<f32>) -> f32 {
+    // perceptual, the input is tonemapped but still linear
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+// tonemapped, so edges against bright highlights blend like they're displayed
+fn sample_rgb(uv: vec2<f32>) -> vec3<f32> {
+    return tonemap(textureSampleLevel(input, input_sampler, uv, 0.0).rgb);
+}
+
+@fragment
+fn fs_main(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input));
+
+    let rgb_m = sample_rgb(in.uv);
+    let luma_m = luma(rgb_m);
+    let luma_nw = luma(sample_rgb(in.uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample_rgb(in.uv + vec2<f32>( 1.0, -1.0) * texel));
+    let luma_sw = luma(sample_rgb(in.uv + vec2<f32>(-1.0,  1.0) * texel));
+    let luma_se = luma(sample_rgb(in.uv + vec2<f32>( 1.0,  1.0) * texel));
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2<f32>(-SPAN_MAX), vec2<f32>(SPAN_MAX)) * texel;
+
+    let rgb_a = 0.5 * (
+        sample_rgb(in.uv + dir * (1.0 / 3.0 - 0.5)) +
+        sample_rgb(in.uv + dir * (2.0 / 3.0 - 0.5))
+    );
+    let rgb_b = rgb_a * 0.5 + 0.25 * (
+        sample_rgb(in.uv + dir * -0.5) +
+        sample_rgb(in.uv + dir * 0.5)
+    );
+
+    let luma_b = luma(rgb_b);
+    if luma_b < luma_min || luma_b > luma_max {
+        return dither(vec4<f32>(rgb_a, 1.0), in.position.xy);
+    }
+    return dither(vec4<f32>(rgb_b, 1.0), in.position.xy);
+}
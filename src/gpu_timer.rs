@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+
+use wgpu::*;
+
+use crate::readback::Readback;
+
+/// the most passes timed per frame
+const MAX_PASSES: u32 = 64;
+
+/// gpu durations of the render graph's passes, from timestamps written before and after each.
+/// the timestamps are read back some frames later, and with the `tracy` feature also sent as gpu zones.
+pub struct GpuTimer {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    /// nanoseconds per tick
+    period: f32,
+    /// the passes timed in the frame being recorded, in the order of their queries
+    names: Vec<&'static str>,
+    recorded: Option<Frame>,
+    pending: VecDeque<Frame>,
+    /// milliseconds of each pass of the latest frame read back
+    timings: Vec<(&'static str, f32)>,
+    #[cfg(feature = "tracy")]
+    tracy: profiling::tracy_client::GpuContext,
+}
+
+struct Frame {
+    names: Vec<&'static str>,
+    readback: Readback<u64>,
+    #[cfg(feature = "tracy")]
+    spans: Vec<profiling::tracy_client::GpuSpan>,
+}
+
+impl GpuTimer {
+    /// None without `Features::TIMESTAMP_QUERY`
+    pub fn new(device: &Device, queue: &Queue) -> Option<Self> {
+        if !device.features().contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: Some("gpu timer query set"),
+            ty: QueryType::Timestamp,
+            count: 2 * MAX_PASSES,
+        });
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("gpu timer resolve buffer"),
+            size: (2 * MAX_PASSES) as BufferAddress * QUERY_SIZE as BufferAddress,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        // tracy lines the gpu clock up with its own from a timestamp taken now
+        #[cfg(feature = "tracy")]
+        let tracy = {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("gpu timer calibration encoder"),
+            });
+            encoder.write_timestamp(&query_set, 0);
+            encoder.resolve_query_set(&query_set, 0..1, &resolve_buffer, 0);
+            let mut readback = Readback::<u64>::buffer(device, &mut encoder, &resolve_buffer, 0, 1);
+            readback.map(queue.submit([encoder.finish()]));
+            let timestamp = readback.read_blocking(device, |timestamps| timestamps[0]);
+            profiling::tracy_client::Client::running()
+                .expect("the tracy client isn't running")
+                .new_gpu_context(
+                    Some("render graph"),
+                    profiling::tracy_client::GpuContextType::Invalid,
+                    timestamp as i64,
+                    queue.get_timestamp_period(),
+                )
+                .unwrap()
+        };
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            period: queue.get_timestamp_period(),
+            names: vec![],
+            recorded: None,
+            pending: VecDeque::new(),
+            timings: vec![],
+            #[cfg(feature = "tracy")]
+            tracy,
+        })
+    }
+
+    /// the query to write the timestamp before the pass with, the one after it follows.
+    /// None once every query of the frame is taken
+    pub fn push(&mut self, name: &'static str) -> Option<u32> {
+        if self.names.len() as u32 == MAX_PASSES {
+            return None;
+        }
+        self.names.push(name);
+        Some(2 * (self.names.len() as u32 - 1))
+    }
+
+    pub fn query_set(&self) -> &QuerySet {
+        &self.query_set
+    }
+
+    /// records the copy of this frame's timestamps, to be submitted after the passes writing them
+    pub fn resolve(&mut self, device: &Device) -> CommandBuffer {
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("gpu timer resolve encoder"),
+        });
+        let names = std::mem::take(&mut self.names);
+        let count = 2 * names.len() as u32;
+        if count > 0 {
+            encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+            #[cfg(feature = "tracy")]
+            let spans = names
+                .iter()
+                .map(|name| {
+                    let mut span = self.tracy.span_alloc(name, "", file!(), line!()).unwrap();
+                    span.end_zone();
+                    span
+                })
+                .collect();
+            self.recorded = Some(Frame {
+                names,
+                readback: Readback::buffer(device, &mut encoder, &self.resolve_buffer, 0, count as usize),
+                #[cfg(feature = "tracy")]
+                spans,
+            });
+        }
+        encoder.finish()
+    }
+
+    /// call with the submission of the command buffer returned by `resolve`
+    pub fn submitted(&mut self, submission: &SubmissionIndex) {
+        if let Some(mut frame) = self.recorded.take() {
+            frame.readback.map(submission.clone());
+            self.pending.push_back(frame);
+        }
+    }
+
+    /// reads back the timestamps of finished frames without waiting
+    pub fn poll(&mut self, device: &Device) {
+        while let Some(frame) = self.pending.front_mut() {
+            let period = self.period;
+            let Some(timestamps) = frame.readback.try_read(device, |timestamps| timestamps.to_vec()) else {
+                return;
+            };
+            let frame = self.pending.pop_front().unwrap();
+            self.timings = frame.names
+                .iter()
+                .zip(timestamps.chunks(2))
+                .map(|(&name, ticks)| (name, ticks[1].wrapping_sub(ticks[0]) as f32 * period / 1_000_000.0))
+                .collect();
+
+            #[cfg(feature = "tracy")]
+            for (span, ticks) in frame.spans.into_iter().zip(timestamps.chunks(2)) {
+                span.upload_timestamp_start(ticks[0] as i64);
+                span.upload_timestamp_end(ticks[1] as i64);
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn timings(&self) -> &[(&'static str, f32)] {
+        &self.timings
+    }
+
+    /// the timings as `name: duration` in milliseconds, with the total first
+    pub fn report(&self) -> String {
+        let total: f32 = self.timings.iter().map(|&(_, ms)| ms).sum();
+        let passes: Vec<_> = self.timings
+            .iter()
+            .map(|(name, ms)| format!("{}: {:.3}ms", name, ms))
+            .collect();
+        format!("gpu: {:.3}ms ({})", total, passes.join(", "))
+    }
+}
@@ -0,0 +1,427 @@
+// tracy scopes named at runtime use `core`, which would be wgpu's through the glob import
+#[cfg(feature = "tracy")]
+use ::core;
+
+use rayon::prelude::*;
+use wgpu::*;
+
+use crate::gpu_timer::GpuTimer;
+
+/// a logical resource declared in a `RenderGraph`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ResourceId(usize);
+
+/// a transient texture sized like the graph, its memory may be shared with
+/// other transients whose lifetimes don't overlap
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TextureDesc {
+    pub label: &'static str,
+    pub format: TextureFormat,
+    pub usage: TextureUsages,
+}
+
+enum Resource {
+    /// owned outside the graph, only used to order and cull passes
+    Imported { label: &'static str, output: bool },
+    Transient(TextureDesc),
+}
+
+/// reads and writes of a pass, in terms of logical resources
+#[derive(Clone, Default, Debug)]
+pub struct PassDesc {
+    pub name: &'static str,
+    pub reads: Vec<ResourceId>,
+    pub writes: Vec<ResourceId>,
+    /// kept even if nothing reads what it writes, like readbacks
+    pub side_effect: bool,
+}
+
+type Record<'a> = Box<dyn FnOnce(&mut CommandEncoder, &GraphResources) + Send + 'a>;
+
+/// a pass recorded into its own command encoder
+pub struct Pass<'a> {
+    desc: PassDesc,
+    record: Record<'a>,
+}
+
+impl<'a> Pass<'a> {
+    pub fn new(name: &'static str, record: impl FnOnce(&mut CommandEncoder, &GraphResources) + Send + 'a) -> Self {
+        Self {
+            desc: PassDesc {
+                name,
+                ..Default::default()
+            },
+            record: Box::new(record),
+        }
+    }
+
+    pub fn read(mut self, resource: ResourceId) -> Self {
+        self.desc.reads.push(resource);
+        self
+    }
+
+    pub fn write(mut self, resource: ResourceId) -> Self {
+        self.desc.writes.push(resource);
+        self
+    }
+
+    pub fn side_effect(mut self) -> Self {
+        self.desc.side_effect = true;
+        self
+    }
+}
+
+struct PhysicalTexture {
+    texture: Texture,
+    view: TextureView,
+    /// the depth aspect alone of depth stencil formats, which can't be sampled together
+    depth_view: Option<TextureView>,
+}
+
+/// the transient textures of the frame being recorded
+pub struct GraphResources<'g> {
+    textures: Vec<Option<&'g PhysicalTexture>>,
+    reallocated: bool,
+}
+
+impl GraphResources<'_> {
+    fn physical(&self, resource: ResourceId) -> &PhysicalTexture {
+        self.textures[resource.0].expect("not a transient used by a live pass")
+    }
+
+    pub fn texture(&self, resource: ResourceId) -> &Texture {
+        &self.physical(resource).texture
+    }
+
+    /// of all aspects, as attached to render passes
+    pub fn view(&self, resource: ResourceId) -> &TextureView {
+        &self.physical(resource).view
+    }
+
+    /// for sampling depth, the depth aspect alone when the format also has stencil
+    pub fn depth_view(&self, resource: ResourceId) -> &TextureView {
+        let physical = self.physical(resource);
+        physical.depth_view.as_ref().unwrap_or(&physical.view)
+    }
+
+    /// whether transients were recreated this frame, so bind groups on them are stale
+    pub fn reallocated(&self) -> bool {
+        self.reallocated
+    }
+}
+
+/// execution order of the live passes and the physical texture of every transient
+#[derive(Debug, PartialEq)]
+struct Plan {
+    order: Vec<usize>,
+    /// per resource, None for imports and culled transients
+    slots: Vec<Option<usize>>,
+    /// format and usage of every physical texture
+    slot_keys: Vec<(TextureFormat, TextureUsages)>,
+}
+
+/// schedules the passes of a frame from their declared reads and writes:
+/// passes not contributing to an output or a side effect are culled,
+/// all writers of a resource run in declaration order before the passes only reading it,
+/// and transients are allocated, sharing textures between disjoint lifetimes
+pub struct RenderGraph {
+    resources: Vec<Resource>,
+    width: u32,
+    height: u32,
+    slots: Vec<Option<usize>>,
+    slot_keys: Vec<(TextureFormat, TextureUsages)>,
+    physical: Vec<PhysicalTexture>,
+}
+
+impl RenderGraph {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            resources: Vec::new(),
+            width,
+            height,
+            slots: Vec::new(),
+            slot_keys: Vec::new(),
+            physical: Vec::new(),
+        }
+    }
+
+    pub fn import(&mut self, label: &'static str) -> ResourceId {
+        self.resources.push(Resource::Imported { label, output: false });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// an imported resource which keeps the passes writing it alive, like the surface
+    pub fn import_output(&mut self, label: &'static str) -> ResourceId {
+        self.resources.push(Resource::Imported { label, output: true });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn create(&mut self, desc: TextureDesc) -> ResourceId {
+        self.resources.push(Resource::Transient(desc));
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn label(&self, resource: ResourceId) -> &'static str {
+        match self.resources[resource.0] {
+            Resource::Imported { label, .. } => label,
+            Resource::Transient(desc) => desc.label,
+        }
+    }
+
+    /// transients are recreated at the next `execute`
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.slot_keys.clear();
+    }
+
+    /// records the live passes in parallel, each between timestamps when given a `timer`,
+    /// returning their command buffers in execution order
+    pub fn execute(&mut self, device: &Device, passes: Vec<Pass>, timer: Option<&mut GpuTimer>) -> Vec<CommandBuffer> {
+        let descs: Vec<_> = passes.iter().map(|pass| &pass.desc).collect();
+        let plan = plan(&self.resources, &descs);
+
+        let reallocated = plan.slot_keys != self.slot_keys || plan.slots != self.slots;
+        if plan.slot_keys != self.slot_keys {
+            self.physical = plan.slot_keys
+                .iter()
+                .enumerate()
+                .map(|(slot, &(format, usage))| {
+                    // labelled after the first transient using it
+                    let label = plan.slots
+                        .iter()
+                        .position(|&s| s == Some(slot))
+                        .map(|resource| self.label(ResourceId(resource)))
+                        .unwrap();
+                    create_texture(device, label, format, usage, self.width, self.height)
+                })
+                .collect();
+            self.slot_keys = plan.slot_keys;
+        }
+        self.slots = plan.slots;
+
+        let resources = GraphResources {
+            textures: self.slots.iter().map(|slot| slot.map(|s| &self.physical[s])).collect(),
+            reallocated,
+        };
+
+        let mut passes: Vec<_> = passes.into_iter().map(Some).collect();
+        let mut timer = timer;
+        let ordered: Vec<_> = plan.order
+            .iter()
+            .map(|&i| {
+                let pass = passes[i].take().unwrap();
+                let query = timer.as_mut().and_then(|timer| timer.push(pass.desc.name));
+                (pass, query)
+            })
+            .collect();
+        let query_set = timer.map(|timer| timer.query_set());
+        ordered
+            .into_par_iter()
+            .map(|(pass, query)| {
+                profiling::scope!(pass.desc.name);
+                let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some(pass.desc.name),
+                });
+                let query = query_set.zip(query);
+                if let Some((query_set, query)) = query {
+                    encoder.write_timestamp(query_set, query);
+                }
+                (pass.record)(&mut encoder, &resources);
+                if let Some((query_set, query)) = query {
+                    encoder.write_timestamp(query_set, query + 1);
+                }
+                encoder.finish()
+            })
+            .collect()
+    }
+}
+
+fn plan(resources: &[Resource], passes: &[&PassDesc]) -> Plan {
+    let writers = |resource: usize| {
+        (0..passes.len()).filter(move |&p| passes[p].writes.contains(&ResourceId(resource)))
+    };
+
+    // culling, walking back from outputs and side effects
+    let mut live: Vec<bool> = passes
+        .iter()
+        .map(|pass| {
+            pass.side_effect || pass.writes.iter().any(|w| {
+                matches!(resources[w.0], Resource::Imported { output: true, .. })
+            })
+        })
+        .collect();
+    let mut stack: Vec<usize> = (0..passes.len()).filter(|&p| live[p]).collect();
+    while let Some(p) = stack.pop() {
+        for read in &passes[p].reads {
+            for writer in writers(read.0) {
+                if !live[writer] {
+                    live[writer] = true;
+                    stack.push(writer);
+                }
+            }
+        }
+    }
+
+    // a pass waits for the writers before it of what it writes,
+    // and for every writer of what it only reads
+    let dependencies: Vec<Vec<usize>> = (0..passes.len())
+        .map(|p| {
+            let mut dependencies = Vec::new();
+            for write in &passes[p].writes {
+                dependencies.extend(writers(write.0).filter(|&w| w < p && live[w]));
+            }
+            for read in passes[p].reads.iter().filter(|r| !passes[p].writes.contains(r)) {
+                dependencies.extend(writers(read.0).filter(|&w| live[w]));
+            }
+            dependencies
+        })
+        .collect();
+
+    // topological order, preferring declaration order
+    let mut order = Vec::new();
+    let mut scheduled = vec![false; passes.len()];
+    let live_count = live.iter().filter(|&&l| l).count();
+    while order.len() < live_count {
+        let next = (0..passes.len())
+            .find(|&p| live[p] && !scheduled[p] && dependencies[p].iter().all(|&d| scheduled[d]))
+            .expect("render graph has a cycle");
+        scheduled[next] = true;
+        order.push(next);
+    }
+
+    // lifetimes of transients in execution order, then greedy aliasing
+    let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; resources.len()];
+    for (position, &p) in order.iter().enumerate() {
+        for resource in passes[p].reads.iter().chain(&passes[p].writes) {
+            let lifetime = &mut lifetimes[resource.0];
+            *lifetime = Some(lifetime.map_or((position, position), |(first, _)| (first, position)));
+        }
+    }
+    let mut transients: Vec<usize> = (0..resources.len())
+        .filter(|&r| matches!(resources[r], Resource::Transient(_)) && lifetimes[r].is_some())
+        .collect();
+    transients.sort_by_key(|&r| lifetimes[r].unwrap().0);
+
+    let mut slots = vec![None; resources.len()];
+    let mut slot_keys = Vec::new();
+    // last use of each slot
+    let mut slot_ends: Vec<usize> = Vec::new();
+    for r in transients {
+        let Resource::Transient(desc) = resources[r] else { unreachable!() };
+        let key = (desc.format, desc.usage);
+        let (first, last) = lifetimes[r].unwrap();
+        let slot = match (0..slot_keys.len()).find(|&s| slot_keys[s] == key && slot_ends[s] < first) {
+            Some(slot) => slot,
+            None => {
+                slot_keys.push(key);
+                slot_ends.push(0);
+                slot_keys.len() - 1
+            }
+        };
+        slot_ends[slot] = last;
+        slots[r] = Some(slot);
+    }
+
+    Plan {
+        order,
+        slots,
+        slot_keys,
+    }
+}
+
+fn create_texture(
+    device: &Device,
+    label: &str,
+    format: TextureFormat,
+    usage: TextureUsages,
+    width: u32,
+    height: u32,
+) -> PhysicalTexture {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        format,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        usage,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    let depth_view = format.is_combined_depth_stencil_format().then(|| texture.create_view(&TextureViewDescriptor {
+        aspect: TextureAspect::DepthOnly,
+        ..Default::default()
+    }));
+
+    PhysicalTexture {
+        texture,
+        view,
+        depth_view,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR: TextureDesc = TextureDesc {
+        label: "color",
+        format: TextureFormat::Rgba16Float,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+    };
+
+    fn pass(name: &'static str, reads: &[usize], writes: &[usize]) -> PassDesc {
+        PassDesc {
+            name,
+            reads: reads.iter().map(|&r| ResourceId(r)).collect(),
+            writes: writes.iter().map(|&w| ResourceId(w)).collect(),
+            side_effect: false,
+        }
+    }
+
+    #[test]
+    fn orders_writers_before_readers_and_culls_unused() {
+        let resources = [
+            Resource::Imported { label: "surface", output: true },
+            Resource::Transient(COLOR),
+            Resource::Transient(COLOR),
+        ];
+        let post = pass("post", &[1], &[0]);
+        let scene = pass("scene", &[], &[1]);
+        let unused = pass("unused", &[], &[2]);
+        let mut readback = pass("readback", &[2], &[]);
+        readback.side_effect = true;
+
+        let culled = plan(&resources, &[&post, &scene, &unused]);
+        assert_eq!(culled.order, vec![1, 0]);
+        assert_eq!(culled.slots[2], None);
+
+        let kept = plan(&resources, &[&post, &scene, &unused, &readback]);
+        assert_eq!(kept.order, vec![1, 0, 2, 3]);
+    }
+
+    #[test]
+    fn aliases_disjoint_transients() {
+        let resources = [
+            Resource::Imported { label: "surface", output: true },
+            Resource::Transient(COLOR),
+            Resource::Transient(COLOR),
+            Resource::Transient(COLOR),
+        ];
+        let a = pass("a", &[], &[1]);
+        let b = pass("b", &[1], &[2]);
+        let c = pass("c", &[2], &[3]);
+        let d = pass("d", &[3], &[0]);
+
+        let plan = plan(&resources, &[&a, &b, &c, &d]);
+        assert_eq!(plan.order, vec![0, 1, 2, 3]);
+        // 1 is dead once 2 is written, so 3 can take its texture
+        assert_eq!(plan.slots, vec![None, Some(0), Some(1), Some(0)]);
+        assert_eq!(plan.slot_keys.len(), 2);
+    }
+}
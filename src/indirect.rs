@@ -0,0 +1,159 @@
+use std::mem::size_of;
+use std::ops::Range;
+
+use wgpu::*;
+
+use crate::buffer::GpuBuffer;
+use crate::mesh::Mesh;
+use crate::scene::DrawItem;
+
+/// as `RenderPass::draw_indexed_indirect` reads it
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawIndexedArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+/// how the scene's draws are issued, by what the device supports
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DrawMode {
+    /// `multi_draw_indexed_indirect` once per batch
+    MultiIndirect,
+    /// `draw_indexed_indirect` once per item, without `Features::MULTI_DRAW_INDIRECT`
+    Indirect,
+    /// `draw_indexed` once per item, without `Features::INDIRECT_FIRST_INSTANCE`,
+    /// as indirect draws can't start past the first instance without it
+    Direct,
+}
+
+/// the draw items of a frame with their arguments in an indirect buffer, grouped into batches of
+/// the same mesh and staticness so each batch binds its mesh once and takes a single multi draw.
+/// the arguments are written from the cpu, there being no gpu culling to write them
+pub struct SceneDraws {
+    pub mode: DrawMode,
+    args: GpuBuffer<DrawIndexedArgs>,
+    /// by batch, then in upload order, `args` alike
+    items: Vec<DrawItem>,
+    /// ranges of `items` with the same mesh and staticness
+    batches: Vec<Range<usize>>,
+}
+
+impl SceneDraws {
+    pub fn new(device: &Device) -> Self {
+        let features = device.features();
+        let mode = if !features.contains(Features::INDIRECT_FIRST_INSTANCE) {
+            DrawMode::Direct
+        } else if features.contains(Features::MULTI_DRAW_INDIRECT) {
+            DrawMode::MultiIndirect
+        } else {
+            DrawMode::Indirect
+        };
+        Self {
+            mode,
+            args: GpuBuffer::new(device, "Scene draw args buffer", BufferUsages::INDIRECT, 16),
+            items: vec![],
+            batches: vec![],
+        }
+    }
+
+    /// batches `items`, as returned by `Scene::draw_items`, and uploads their arguments
+    pub fn update(&mut self, device: &Device, queue: &Queue, meshes: &[Mesh], items: &[DrawItem]) {
+        (self.items, self.batches) = batch(items);
+        if self.mode == DrawMode::Direct {
+            return;
+        }
+
+        let args: Vec<DrawIndexedArgs> = self.items
+            .iter()
+            .map(|item| DrawIndexedArgs {
+                index_count: meshes[item.mesh.0 as usize].index_count,
+                instance_count: item.instances.len() as u32,
+                first_index: 0,
+                base_vertex: 0,
+                first_instance: item.instances.start,
+            })
+            .collect();
+        self.args.reserve(device, args.len());
+        self.args.write(queue, &args);
+    }
+
+    /// draws the batches whose items pass `filter`, the instances being bound to vertex slot 1
+    pub fn draw<'a>(&'a self, pass: &mut RenderPass<'a>, meshes: &'a [Mesh], filter: impl Fn(&DrawItem) -> bool) {
+        const STRIDE: BufferAddress = size_of::<DrawIndexedArgs>() as BufferAddress;
+
+        for batch in &self.batches {
+            let first = &self.items[batch.start];
+            if !filter(first) {
+                continue;
+            }
+            let mesh = &meshes[first.mesh.0 as usize];
+            mesh.bind(pass);
+            match self.mode {
+                DrawMode::MultiIndirect => {
+                    pass.multi_draw_indexed_indirect(self.args.buffer(), batch.start as BufferAddress * STRIDE, batch.len() as u32);
+                }
+                DrawMode::Indirect => {
+                    for i in batch.clone() {
+                        pass.draw_indexed_indirect(self.args.buffer(), i as BufferAddress * STRIDE);
+                    }
+                }
+                DrawMode::Direct => {
+                    for item in &self.items[batch.clone()] {
+                        pass.draw_indexed(0..mesh.index_count, 0, item.instances.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `items` ordered by staticness then mesh, and the ranges of them sharing both
+fn batch(items: &[DrawItem]) -> (Vec<DrawItem>, Vec<Range<usize>>) {
+    let mut items = items.to_vec();
+    // stable, so each batch stays in upload order
+    items.sort_by_key(|item| (!item.is_static, item.mesh));
+
+    let mut batches: Vec<Range<usize>> = vec![];
+    for (i, item) in items.iter().enumerate() {
+        match batches.last_mut() {
+            Some(batch) if items[batch.start].mesh == item.mesh && items[batch.start].is_static == item.is_static => {
+                batch.end += 1;
+            }
+            _ => batches.push(i..i + 1),
+        }
+    }
+    (items, batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::MeshId;
+
+    #[test]
+    fn batches_share_mesh_and_staticness() {
+        let item = |mesh, instances, is_static| DrawItem { mesh, instances, is_static };
+        let (items, batches) = batch(&[
+            item(MeshId::CUBE, 0..1, true),
+            item(MeshId::WEDGE, 1..2, true),
+            item(MeshId::CUBE, 2..4, true),
+            item(MeshId::WEDGE, 4..5, false),
+            item(MeshId::CUBE, 5..6, false),
+            item(MeshId::WEDGE, 6..8, false),
+        ]);
+        assert_eq!(items, [
+            item(MeshId::CUBE, 0..1, true),
+            item(MeshId::CUBE, 2..4, true),
+            item(MeshId::WEDGE, 1..2, true),
+            item(MeshId::CUBE, 5..6, false),
+            item(MeshId::WEDGE, 4..5, false),
+            item(MeshId::WEDGE, 6..8, false),
+        ]);
+        assert_eq!(batches, [0..2, 2..3, 3..4, 4..6]);
+        assert_eq!(batch(&[]), (vec![], vec![]));
+    }
+}
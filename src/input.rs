@@ -1,10 +1,53 @@
+use winit::event::{ModifiersState, TouchPhase, VirtualKeyCode};
+use winit::window::{CursorGrabMode, Window};
+
 const KEY_CODE_COUNT: usize = 128;
 type KeysBitmask = u128;
+/// physical pixels a touch may move by and still be a tap
+const TAP_SLOP: f32 = 10.0;
+
+/// what the fingers on the window did, pens touching it come as fingers too
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TouchGesture {
+    /// a single finger lifted before moving further than `TAP_SLOP`, where it was
+    Tap([f32; 2]),
+    /// a single finger moved by this many physical pixels
+    Drag([f32; 2]),
+    /// two fingers moved apart, by this factor of the distance between them
+    Pinch(f32),
+}
+
+/// a finger on the window
+struct Touch {
+    id: u64,
+    start: [f32; 2],
+    position: [f32; 2],
+    /// moved further than `TAP_SLOP`, or joined by another finger
+    dragging: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum MouseGrab {
+    Released,
+    /// locked, or confined where locking isn't supported, and hidden
+    Grabbed,
+    /// grabbed, but let go while the window is unfocused and grabbed again once it's focused
+    Suspended,
+}
 
 pub struct InputState {
     pub keys_pressed_bitmask: KeysBitmask,
     pub previous_keys_pressed_bitmask: KeysBitmask,
+    /// as last reported by `WindowEvent::ModifiersChanged`, either key of a pair counting
+    pub modifiers: ModifiersState,
+    /// raw mouse motion since `take_mouse_delta`, only gathered while the mouse is relative
     pub delta_mouse_pos: [f32; 2],
+    /// in physical pixels from the top left of the window
+    pub cursor_position: [f32; 2],
+    mouse_grab: MouseGrab,
+    /// in the order they touched
+    touches: Vec<Touch>,
+    gestures: Vec<TouchGesture>,
 }
 
 impl InputState {
@@ -12,12 +55,132 @@ impl InputState {
         Self {
             keys_pressed_bitmask: 0,
             previous_keys_pressed_bitmask: 0,
+            modifiers: ModifiersState::empty(),
             delta_mouse_pos: [0.0, 0.0],
+            cursor_position: [0.0, 0.0],
+            mouse_grab: MouseGrab::Released,
+            touches: vec![],
+            gestures: vec![],
+        }
+    }
+
+    /// tracks the finger `id` through a `WindowEvent::Touch`, at `position` in physical pixels
+    pub fn touch(&mut self, id: u64, phase: TouchPhase, position: [f32; 2]) {
+        let index = self.touches.iter().position(|touch| touch.id == id);
+        match (phase, index) {
+            (TouchPhase::Started, None) => {
+                // a second finger makes neither a tap nor a drag of the first
+                for touch in &mut self.touches {
+                    touch.dragging = true;
+                }
+                self.touches.push(Touch {
+                    id,
+                    start: position,
+                    position,
+                    dragging: !self.touches.is_empty(),
+                });
+            }
+            (TouchPhase::Moved, Some(i)) => {
+                let distance = |touches: &[Touch]| {
+                    let [a, b] = [touches[0].position, touches[1].position];
+                    (a[0] - b[0]).hypot(a[1] - b[1])
+                };
+                let before = (self.touches.len() == 2).then(|| distance(&self.touches));
+
+                let touch = &mut self.touches[i];
+                let delta = [position[0] - touch.position[0], position[1] - touch.position[1]];
+                touch.position = position;
+                touch.dragging |= (position[0] - touch.start[0]).hypot(position[1] - touch.start[1]) > TAP_SLOP;
+
+                match before {
+                    Some(before) if before > 0.0 => self.gestures.push(TouchGesture::Pinch(distance(&self.touches) / before)),
+                    _ if self.touches.len() == 1 && self.touches[0].dragging => self.gestures.push(TouchGesture::Drag(delta)),
+                    _ => {}
+                }
+            }
+            (TouchPhase::Ended, Some(i)) => {
+                let touch = self.touches.remove(i);
+                if !touch.dragging && self.touches.is_empty() {
+                    self.gestures.push(TouchGesture::Tap(touch.position));
+                }
+            }
+            (TouchPhase::Cancelled, Some(i)) => {
+                self.touches.remove(i);
+            }
+            _ => {}
         }
     }
 
+    /// the gestures since the last call, in the order they were made
+    pub fn take_touch_gestures(&mut self) -> Vec<TouchGesture> {
+        std::mem::take(&mut self.gestures)
+    }
+
+    #[allow(dead_code)]
+    pub fn touch_count(&self) -> usize {
+        self.touches.len()
+    }
+
+    /// whether the cursor is grabbed and hidden, the mouse reporting motion rather than a position
+    pub fn is_mouse_relative(&self) -> bool {
+        self.mouse_grab == MouseGrab::Grabbed
+    }
+
+    /// grabs and hides the cursor, returns false when the platform grabs neither way
+    pub fn grab_mouse(&mut self, window: &Window) -> bool {
+        let grabbed = window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+            .is_ok();
+        if grabbed {
+            window.set_cursor_visible(false);
+            self.mouse_grab = MouseGrab::Grabbed;
+        }
+        grabbed
+    }
+
+    /// lets go of the cursor and shows it
+    pub fn release_mouse(&mut self, window: &Window) {
+        if self.mouse_grab == MouseGrab::Grabbed {
+            // releasing can't fail in a way worth reporting, the cursor is shown either way
+            let _ = window.set_cursor_grab(CursorGrabMode::None);
+            window.set_cursor_visible(true);
+        }
+        self.mouse_grab = MouseGrab::Released;
+        self.delta_mouse_pos = [0.0, 0.0];
+    }
+
+    /// lets go of a grabbed cursor while the window is unfocused, grabbing it again on focus
+    pub fn set_focused(&mut self, window: &Window, focused: bool) {
+        match (self.mouse_grab, focused) {
+            (MouseGrab::Grabbed, false) => {
+                self.release_mouse(window);
+                self.mouse_grab = MouseGrab::Suspended;
+            }
+            (MouseGrab::Suspended, true) => {
+                // stays released if the grab fails
+                self.mouse_grab = MouseGrab::Released;
+                self.grab_mouse(window);
+            }
+            _ => {}
+        }
+    }
+
+    /// raw motion from `DeviceEvent::MouseMotion`, ignored unless the mouse is relative
+    pub fn add_mouse_motion(&mut self, (x, y): (f64, f64)) {
+        if self.is_mouse_relative() {
+            self.delta_mouse_pos[0] += x as f32;
+            self.delta_mouse_pos[1] += y as f32;
+        }
+    }
+
+    /// the motion gathered since the last call
+    pub fn take_mouse_delta(&mut self) -> [f32; 2] {
+        std::mem::take(&mut self.delta_mouse_pos)
+    }
+
     #[inline(always)]
-    pub fn is_key_pressed(&mut self, key_code: winit::event::VirtualKeyCode) -> bool {
+    pub fn is_key_pressed(&mut self, key_code: VirtualKeyCode) -> bool {
         let key_code_usize = key_code as usize;
         assert!(
             key_code_usize < KEY_CODE_COUNT,
@@ -28,7 +191,7 @@ impl InputState {
     }
 
     #[inline(always)]
-    pub fn was_key_pressed(&mut self, key_code: winit::event::VirtualKeyCode) -> bool {
+    pub fn was_key_pressed(&mut self, key_code: VirtualKeyCode) -> bool {
         let key_code_usize = key_code as usize;
         assert!(
             key_code_usize < KEY_CODE_COUNT,
@@ -38,8 +201,13 @@ impl InputState {
         self.previous_keys_pressed_bitmask & (1 << key_code_usize) != 0
     }
 
+    /// whether `key_code` went down since the last frame with exactly `modifiers` held
+    pub fn is_chord_pressed(&mut self, modifiers: ModifiersState, key_code: VirtualKeyCode) -> bool {
+        self.modifiers == modifiers && self.is_key_pressed(key_code) && !self.was_key_pressed(key_code)
+    }
+
     #[inline(always)]
-    pub fn set_key_pressed(&mut self, key_code: winit::event::VirtualKeyCode, pressed: bool) {
+    pub fn set_key_pressed(&mut self, key_code: VirtualKeyCode, pressed: bool) {
         let key_code_usize = key_code as usize;
         assert!(
             key_code_usize < KEY_CODE_COUNT,
@@ -49,4 +217,54 @@ impl InputState {
         self.keys_pressed_bitmask &= !(1 << key_code_usize);
         self.keys_pressed_bitmask |= (pressed as KeysBitmask) << key_code_usize;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn touches_tap_drag_and_pinch() {
+        let mut input = InputState::new();
+        input.touch(1, TouchPhase::Started, [100.0, 100.0]);
+        input.touch(1, TouchPhase::Moved, [104.0, 100.0]);
+        input.touch(1, TouchPhase::Ended, [104.0, 100.0]);
+        assert_eq!(input.take_touch_gestures(), [TouchGesture::Tap([104.0, 100.0])]);
+
+        input.touch(2, TouchPhase::Started, [100.0, 100.0]);
+        input.touch(2, TouchPhase::Moved, [120.0, 100.0]);
+        input.touch(2, TouchPhase::Moved, [120.0, 90.0]);
+        input.touch(2, TouchPhase::Ended, [120.0, 90.0]);
+        assert_eq!(input.take_touch_gestures(), [
+            TouchGesture::Drag([20.0, 0.0]),
+            TouchGesture::Drag([0.0, -10.0]),
+        ]);
+
+        // neither finger of a pinch taps when lifted
+        input.touch(3, TouchPhase::Started, [0.0, 0.0]);
+        input.touch(4, TouchPhase::Started, [100.0, 0.0]);
+        input.touch(4, TouchPhase::Moved, [200.0, 0.0]);
+        assert_eq!(input.touch_count(), 2);
+        input.touch(3, TouchPhase::Ended, [0.0, 0.0]);
+        input.touch(4, TouchPhase::Cancelled, [200.0, 0.0]);
+        assert_eq!(input.take_touch_gestures(), [TouchGesture::Pinch(2.0)]);
+        assert_eq!(input.touch_count(), 0);
+    }
+
+    #[test]
+    fn chords_need_their_exact_modifiers() {
+        let mut input = InputState::new();
+        input.set_key_pressed(VirtualKeyCode::S, true);
+        assert!(!input.is_chord_pressed(ModifiersState::CTRL, VirtualKeyCode::S));
+
+        input.modifiers = ModifiersState::CTRL;
+        assert!(input.is_chord_pressed(ModifiersState::CTRL, VirtualKeyCode::S));
+        input.modifiers = ModifiersState::CTRL | ModifiersState::SHIFT;
+        assert!(!input.is_chord_pressed(ModifiersState::CTRL, VirtualKeyCode::S));
+
+        // held since the last frame
+        input.modifiers = ModifiersState::CTRL;
+        input.previous_keys_pressed_bitmask = input.keys_pressed_bitmask;
+        assert!(!input.is_chord_pressed(ModifiersState::CTRL, VirtualKeyCode::S));
+    }
+}
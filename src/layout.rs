@@ -0,0 +1,261 @@
+use crate::math::Vector3;
+
+/// a `vec3<f32>` with the 4 bytes wgsl pads it to in uniform and storage buffers
+/// when the next member is 16 byte aligned
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PadVec3 {
+    pub xyz: [f32; 3],
+    _padding: u32,
+}
+
+impl From<Vector3> for PadVec3 {
+    fn from(v: Vector3) -> Self {
+        Self {
+            xyz: [v.x, v.y, v.z],
+            _padding: 0,
+        }
+    }
+}
+
+/// fails to compile unless `$ty` is `$size` bytes, a multiple of the 16 bytes
+/// wgsl rounds uniform structs up to
+macro_rules! assert_uniform_size {
+    ($ty:ty, $size:expr) => {
+        const _: () = assert!(
+            std::mem::size_of::<$ty>() == $size && $size % 16 == 0,
+            concat!("unexpected size of ", stringify!($ty)),
+        );
+    };
+}
+pub(crate) use assert_uniform_size;
+
+// `Affine3` is uploaded as is, its rows declared as three `vec4<f32>`
+assert_uniform_size!(crate::math::Affine3, 48);
+
+/// checks that struct `name` of `shader` has the size of the rust struct it's written from,
+/// `size`, and its members in `offsets` at the same offsets, as (member name, rust offset).
+/// members of the wgsl struct may be left out, e.g. several vec4 rows making up an `Affine3`
+#[allow(dead_code)]
+pub fn validate_struct(
+    shader: &naga::Module,
+    name: &str,
+    size: usize,
+    offsets: &[(&str, usize)],
+) -> Result<(), String> {
+    let (members, span) = shader.types
+        .iter()
+        .find_map(|(_, ty)| match &ty.inner {
+            naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some(name) => {
+                Some((members, *span))
+            }
+            _ => None,
+        })
+        .ok_or_else(|| format!("no struct `{}` in the shader", name))?;
+
+    if span as usize != size {
+        return Err(format!("`{}` is {} bytes in wgsl and {} in rust", name, span, size));
+    }
+    for &(member_name, offset) in offsets {
+        let member = members
+            .iter()
+            .find(|m| m.name.as_deref() == Some(member_name))
+            .ok_or_else(|| format!("`{}` has no member `{}`", name, member_name))?;
+        if member.offset as usize != offset {
+            return Err(format!(
+                "`{}.{}` is at {} in wgsl and {} in rust",
+                name, member_name, member.offset, offset,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// attributes of a vertex buffer of `T`, at consecutive shader locations from `start`, given as
+/// (format, offset of the field in `T`). fails to compile when one overlaps the one before it
+/// or ends past `T`. see `vertex_layout`
+pub const fn vertex_attributes<T, const N: usize>(
+    start: u32,
+    fields: [(wgpu::VertexFormat, usize); N],
+) -> [wgpu::VertexAttribute; N] {
+    let mut attributes = [wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float32,
+        offset: 0,
+        shader_location: 0,
+    }; N];
+    let mut end = 0;
+    let mut i = 0;
+    while i < N {
+        let (format, offset) = fields[i];
+        assert!(offset as u64 >= end, "vertex attribute overlaps the one before it");
+        end = offset as u64 + format.size();
+        assert!(end <= std::mem::size_of::<T>() as u64, "vertex attribute ends past its struct");
+        attributes[i] = wgpu::VertexAttribute {
+            format,
+            offset: offset as u64,
+            shader_location: start + i as u32,
+        };
+        i += 1;
+    }
+    attributes
+}
+
+/// the `VertexBufferLayout` of a buffer of `$ty`, whose `$field`s are read as `$format`s at
+/// consecutive shader locations from `$start`, so neither locations nor offsets are kept by hand:
+/// `vertex_layout!(Vertex, Vertex, 0, [position => Float32x3, normal => Float32x3])`
+macro_rules! vertex_layout {
+    ($ty:ty, $step_mode:ident, $start:expr, [$($($field:ident).+ => $format:ident),+ $(,)?]) => {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<$ty>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::$step_mode,
+            attributes: &$crate::layout::vertex_attributes::<$ty, _>($start, [
+                $((wgpu::VertexFormat::$format, std::mem::offset_of!($ty, $($field).+))),+
+            ]),
+        }
+    };
+}
+pub(crate) use vertex_layout;
+
+/// scalar kind and component count a vertex format is read as
+fn vertex_format_type(format: wgpu::VertexFormat) -> (naga::ScalarKind, u32) {
+    use naga::ScalarKind::{Float, Sint, Uint};
+    use wgpu::VertexFormat::*;
+
+    match format {
+        Uint8x2 | Uint16x2 | Uint32x2 => (Uint, 2),
+        Uint8x4 | Uint16x4 | Uint32x4 => (Uint, 4),
+        Uint32 => (Uint, 1),
+        Uint32x3 => (Uint, 3),
+        Sint8x2 | Sint16x2 | Sint32x2 => (Sint, 2),
+        Sint8x4 | Sint16x4 | Sint32x4 => (Sint, 4),
+        Sint32 => (Sint, 1),
+        Sint32x3 => (Sint, 3),
+        Float32 | Float64 => (Float, 1),
+        Unorm8x2 | Snorm8x2 | Unorm16x2 | Snorm16x2 | Float16x2 | Float32x2 | Float64x2 => (Float, 2),
+        Float32x3 | Float64x3 => (Float, 3),
+        Unorm8x4 | Snorm8x4 | Unorm16x4 | Snorm16x4 | Float16x4 | Float32x4 | Float64x4 => (Float, 4),
+    }
+}
+
+/// checks every location input of `entry_point` in `shader` is read from exactly one attribute
+/// of `buffers`, of the same scalar kind and component count
+pub fn validate_vertex_input(
+    shader: &naga::Module,
+    entry_point: &str,
+    buffers: &[wgpu::VertexBufferLayout],
+) -> Result<(), String> {
+    let function = &shader.entry_points
+        .iter()
+        .find(|e| e.name == entry_point)
+        .ok_or_else(|| format!("no entry point `{}` in the shader", entry_point))?
+        .function;
+
+    // (location, name, type) of the arguments and of the members of struct arguments
+    let mut inputs = vec![];
+    for argument in &function.arguments {
+        match (&argument.binding, &shader.types[argument.ty].inner) {
+            (Some(naga::Binding::Location { location, .. }), inner) => {
+                inputs.push((*location, argument.name.clone(), inner));
+            }
+            (None, naga::TypeInner::Struct { members, .. }) => {
+                for member in members {
+                    if let Some(naga::Binding::Location { location, .. }) = member.binding {
+                        inputs.push((location, member.name.clone(), &shader.types[member.ty].inner));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (location, name, inner) in inputs {
+        let name = name.unwrap_or_default();
+        let mut attributes = buffers
+            .iter()
+            .flat_map(|b| b.attributes)
+            .filter(|a| a.shader_location == location);
+        let attribute = attributes
+            .next()
+            .ok_or_else(|| format!("`{}` at location {} isn't in any vertex buffer", name, location))?;
+        if attributes.next().is_some() {
+            return Err(format!("location {} is in more than one vertex attribute", location));
+        }
+
+        let shader_type = match *inner {
+            naga::TypeInner::Scalar { kind, .. } => (kind, 1),
+            naga::TypeInner::Vector { size, kind, .. } => (kind, size as u32),
+            _ => return Err(format!("`{}` at location {} isn't a scalar or vector", name, location)),
+        };
+        if vertex_format_type(attribute.format) != shader_type {
+            return Err(format!(
+                "`{}` at location {} is read as {:?} but declared as {:?}",
+                name, location, attribute.format, shader_type,
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::{offset_of, size_of};
+
+    #[repr(C)]
+    struct Packed {
+        a: f32,
+        b: [f32; 3],
+    }
+
+    #[repr(C)]
+    struct Padded {
+        a: f32,
+        _padding: [u32; 3],
+        b: PadVec3,
+    }
+
+    #[repr(C)]
+    struct TestVertex {
+        position: [f32; 3],
+        id: u32,
+    }
+
+    const TEST_LAYOUT: wgpu::VertexBufferLayout = vertex_layout!(TestVertex, Vertex, 2, [
+        position => Float32x3,
+        id => Uint32,
+    ]);
+
+    #[test]
+    fn vertex_layouts_are_checked_against_the_shader() {
+        assert_eq!(TEST_LAYOUT.array_stride, 16);
+        let attributes: Vec<_> = TEST_LAYOUT.attributes.iter().map(|a| (a.shader_location, a.offset)).collect();
+        assert_eq!(attributes, [(2, 0), (3, 12)]);
+
+        let shader = |inputs: &str| crate::binding::reflect("test", &format!(
+            "struct In {{ {} }};
+            @vertex fn vs_main(in: In) -> @builtin(position) vec4<f32> {{ return vec4<f32>(0.0); }}",
+            inputs,
+        ));
+        let matching = shader("@location(2) position: vec3<f32>, @location(3) id: u32,");
+        assert!(validate_vertex_input(&matching, "vs_main", &[TEST_LAYOUT]).is_ok());
+        assert!(validate_vertex_input(&matching, "vs_other", &[TEST_LAYOUT]).is_err());
+        assert!(validate_vertex_input(&matching, "vs_main", &[TEST_LAYOUT, TEST_LAYOUT]).is_err());
+
+        let wrong_type = shader("@location(2) position: vec3<f32>, @location(3) id: f32,");
+        assert!(validate_vertex_input(&wrong_type, "vs_main", &[TEST_LAYOUT]).is_err());
+        let missing = shader("@location(2) position: vec3<f32>, @location(4) id: u32,");
+        assert!(validate_vertex_input(&missing, "vs_main", &[TEST_LAYOUT]).is_err());
+    }
+
+    #[test]
+    fn vec3_members_are_padded() {
+        let shader = crate::binding::reflect("test", "struct S { a: f32, b: vec3<f32> };");
+        let padded = [("a", offset_of!(Padded, a)), ("b", offset_of!(Padded, b))];
+        assert!(validate_struct(&shader, "S", size_of::<Padded>(), &padded).is_ok());
+
+        // b is 16 byte aligned in wgsl
+        let packed = [("a", offset_of!(Packed, a)), ("b", offset_of!(Packed, b))];
+        assert!(validate_struct(&shader, "S", 32, &packed).is_err());
+        assert!(validate_struct(&shader, "T", 32, &[]).is_err());
+    }
+}
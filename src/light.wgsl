@@ -1,38 +1,91 @@
+#include "draw_params.wgsl"
+#include "common.wgsl"
+
 struct Camera {
     view_0: vec4<f32>,
     view_1: vec4<f32>,
     view_2: vec4<f32>,
     near_z: f32,
+    // 1 for the view the traced shadow mask's pixels are from
+    traced_shadow_mask: u32,
+    // world space plane, fragments on its negative side are discarded
+    clip_plane: vec4<f32>,
+    // the view of the frame before, what the instances' own motion is measured in
+    previous_view_0: vec4<f32>,
+    previous_view_1: vec4<f32>,
+    previous_view_2: vec4<f32>,
 };
 @group(0) @binding(0)
 var<uniform> camera: Camera;
+struct Light {
+    view_0: vec4<f32>,
+    view_1: vec4<f32>,
+    view_2: vec4<f32>,
+    near_z: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    // texels to offset the shadow lookup by along the geometric normal, 0 when disabled
+    normal_offset: f32,
+    // whether the depth compare follows the receiver's plane across the texel
+    receiver_plane_bias: u32,
+    // one of the SHADOW_FILTER_ constants
+    shadow_filter: u32,
+    // world space radius of the soft shadow kernel, the largest one for pcss
+    shadow_radius: f32,
+    // world space size of the light's area, width and height
+    size: vec2<f32>,
+    // lights every surface the light does, shadowed or not
+    ambient: vec3<f32>,
+};
 @group(0) @binding(1)
-var<uniform> light: Camera;
+var<uniform> light: Light;
 
 struct VertexIn {
     @location(0) position: vec3<f32>,
+    @location(1) uv2: vec2<f32>,
 }
 
 struct InstanceIn {
     @location(5) model_0: vec4<f32>,
     @location(6) model_1: vec4<f32>,
     @location(7) model_2: vec4<f32>,
+    @location(8) flags: u32,
+    // lightmap::Tile, offset then size
+    @location(9) lightmap: vec3<f32>,
+}
+
+// the instance's model in the frame before, transform::TransformPass::previous_instances
+struct PreviousInstanceIn {
+    @location(10) model_0: vec4<f32>,
+    @location(11) model_1: vec4<f32>,
+    @location(12) model_2: vec4<f32>,
 }
 
 struct VertexOut {
     @builtin(position) clip_position: vec4<f32>,
-    // xy are texture coordinates for light in range (0, 0) to (1, 1)
-    // +y is downwards in texture coordinates
-    // z is depth value from light
-    @location(0) from_light: vec3<f32>,
+    @location(0) world_position: vec3<f32>,
+    // instance index + 1, 0 is left for the background
+    @location(1) @interpolate(flat) id: u32,
+    @location(2) @interpolate(flat) flags: u32,
+    // zero for instances, which use the geometric normal
+    @location(3) normal: vec3<f32>,
+    // where the lightmap has the instance's lighting, when it's LIGHTMAPPED
+    @location(4) lightmap_uv: vec2<f32>,
+    // in the previous view, where the surface is now and where it was in the frame before
+    @location(5) previous_view_position: vec3<f32>,
+    @location(6) moved_from: vec3<f32>,
 }
 
 @vertex
 fn vs_main(
     vertex: VertexIn,
     instance: InstanceIn,
+    previous: PreviousInstanceIn,
+    @builtin(instance_index) instance_index: u32,
 ) -> VertexOut {
     var out: VertexOut;
+    out.id = instance_index + 1u;
+    out.flags = instance.flags;
 
     let position = apply_affine(
         instance.model_0,
@@ -41,38 +94,130 @@ fn vs_main(
         vertex.position,
     );
 
-    out.clip_position = vec4<f32>(
-        apply_affine(
-            camera.view_0,
-            camera.view_1,
-            camera.view_2,
-            position,
-        ),
-        1.0,
+    let previous_position = apply_affine(
+        previous.model_0,
+        previous.model_1,
+        previous.model_2,
+        vertex.position,
     );
 
-    out.from_light = apply_affine(
-        light.view_0,
-        light.view_1,
-        light.view_2,
+    out.world_position = position;
+    out.clip_position = to_clip(position);
+    out.lightmap_uv = instance.lightmap.xy + vertex.uv2 * instance.lightmap.z;
+    out.previous_view_position = to_previous_view(position);
+    out.moved_from = to_previous_view(previous_position);
+    return out;
+}
+
+struct TerrainVertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+// terrain::TerrainVertex, already in world space
+@vertex
+fn vs_terrain(vertex: TerrainVertexIn) -> VertexOut {
+    var out: VertexOut;
+    // not pickable
+    out.id = 0u;
+    out.flags = CASTS_SHADOW | RECEIVES_SHADOW;
+    out.world_position = vertex.position;
+    out.normal = vertex.normal;
+    out.clip_position = to_clip(vertex.position);
+    // doesn't move
+    out.previous_view_position = to_previous_view(vertex.position);
+    out.moved_from = out.previous_view_position;
+    return out;
+}
+
+fn to_previous_view(position: vec3<f32>) -> vec3<f32> {
+    return apply_affine(
+        camera.previous_view_0,
+        camera.previous_view_1,
+        camera.previous_view_2,
         position,
     );
+}
 
-    // prepare (-1.0, 1.0) range to (0.0, 1.0) range
-    out.from_light.x = ( out.from_light.x + out.from_light.z) * 0.5;
-    out.from_light.y = (-out.from_light.y + out.from_light.z) * 0.5;
-    
-    out.clip_position.w = out.clip_position.z;
+fn to_clip(position: vec3<f32>) -> vec4<f32> {
+    let view = apply_affine(
+        camera.view_0,
+        camera.view_1,
+        camera.view_2,
+        position,
+    );
     // using infinite reversed z for better f32 depth precision
-    out.clip_position.z = camera.near_z;
-    return out;
+    return vec4<f32>(view.xy, camera.near_z, view.z);
 }
 
 @group(0) @binding(2) var shadow_texture: texture_depth_2d;
 @group(0) @binding(3) var shadow_sampler: sampler;
+@group(0) @binding(4) var detail_texture: texture_2d<f32>;
+@group(0) @binding(5) var detail_sampler: sampler;
+// traced_shadow::TracedShadows, 1 where the pixel's surface sees the light and 0 where it doesn't
+@group(0) @binding(6) var traced_shadow_mask: texture_2d<f32>;
+// lightmap::Lightmap, the irradiance from the light and its first bounce
+@group(0) @binding(7) var lightmap_texture: texture_2d<f32>;
+@group(0) @binding(8) var lightmap_sampler: sampler;
+// point_shadow::PointLightRaw
+struct PointLight {
+    position: vec3<f32>,
+    near_z: f32,
+    color: vec3<f32>,
+    // 0 when it's off
+    intensity: f32,
+    // no light reaches past this distance
+    range: f32,
+    // one of the POINT_SHADOW_ constants
+    shadow: u32,
+};
+@group(0) @binding(9) var<uniform> point_light: PointLight;
+// point_shadow::PointShadow, reversed depth like the other shadow maps, a layer per hemisphere
+@group(0) @binding(10) var point_shadow_texture: texture_depth_2d_array;
+
+// terrain::detail_texture repeats every this many world units
+const DETAIL_SIZE: f32 = 4.0;
+
+// draw_params::MATERIAL_
+const MATERIAL_UNLIT: u32 = 1u;
+const MATERIAL_TERRAIN: u32 = 2u;
+// MATERIAL_TWO_SIDED is shaded as lit, its geometric normal is flipped to face the light either way
+
+const SHADOW_FILTER_HARD: u32 = 0u;
+const SHADOW_FILTER_POISSON: u32 = 1u;
+const SHADOW_FILTER_PCSS: u32 = 2u;
+
+// point_shadow::PointShadowMode
+const POINT_SHADOW_NONE: u32 = 0u;
+const POINT_SHADOW_DUAL_PARABOLOID: u32 = 1u;
+// of the receiver's depth, the paraboloid projection is only exact at the casters' vertices
+const POINT_SHADOW_BIAS: f32 = 0.03;
+
+// well spread points in the unit disk
+var<private> POISSON_DISK: array<vec2<f32>, 16> = array<vec2<f32>, 16>(
+    vec2<f32>(-0.94201624, -0.39906216),
+    vec2<f32>( 0.94558609, -0.76890725),
+    vec2<f32>(-0.09418410, -0.92938870),
+    vec2<f32>( 0.34495938,  0.29387760),
+    vec2<f32>(-0.91588581,  0.45771432),
+    vec2<f32>(-0.81544232, -0.87912464),
+    vec2<f32>(-0.38277543,  0.27676845),
+    vec2<f32>( 0.97484398,  0.75648379),
+    vec2<f32>( 0.44323325, -0.97511554),
+    vec2<f32>( 0.53742981, -0.47373420),
+    vec2<f32>(-0.26496911, -0.41893023),
+    vec2<f32>( 0.79197514,  0.19090188),
+    vec2<f32>(-0.24188840,  0.99706507),
+    vec2<f32>(-0.81409955,  0.91437590),
+    vec2<f32>( 0.19984126,  0.78641367),
+    vec2<f32>( 0.14383161, -0.14100790),
+);
 
 struct FragmentOut {
     @location(0) color: vec4<f32>,
+    @location(1) id: u32,
+    // motion_blur::MOTION_FORMAT
+    @location(2) motion: vec2<f32>,
 }
 
 @fragment
@@ -83,35 +228,258 @@ fn fs_main(
 
     var lighting = 0.0;
 
-    if in.from_light.z > light.near_z
-    && 0.0 < in.from_light.x && in.from_light.x < in.from_light.z 
-    && 0.0 < in.from_light.y && in.from_light.y < in.from_light.z {
-        let depth = textureSampleLevel(
-            shadow_texture, 
-            shadow_sampler,
-            in.from_light.xy / in.from_light.z,
-            0.0,
-        );
-
-        if light.near_z + 0.001 > depth * in.from_light.z {
+    // geometric normal, flipped to face the light
+    var normal = normalize(cross(dpdx(in.world_position), dpdy(in.world_position)));
+    if any(in.normal != vec3<f32>(0.0)) {
+        normal = normalize(in.normal);
+    }
+    if dot(normal, light_direction()) > 0.0 {
+        normal = -normal;
+    }
+
+    let texel_size = 1.0 / vec2<f32>(textureDimensions(shadow_texture));
+    var from_light = to_light(in.world_position);
+    if light.normal_offset > 0.0 {
+        // world size of a shadow map texel at the receiver's distance from the light
+        let texel_world_size = 2.0 * from_light.z * texel_size.x / length(light.view_0.xyz);
+        from_light = to_light(in.world_position + normal * light.normal_offset * texel_world_size);
+    }
+
+    // sampled before branching, which would make the mip level undefined
+    let detail = textureSample(detail_texture, detail_sampler, in.world_position.xz / DETAIL_SIZE).rgb;
+    let baked = textureSample(lightmap_texture, lightmap_sampler, in.lightmap_uv).rgb;
+
+    // derivatives are taken before branching on the lookup
+    let uv = from_light.xy / from_light.z;
+    let receiver_depth = light.near_z / from_light.z;
+    var depth_gradient = receiver_plane_depth_gradient(uv, receiver_depth);
+
+    if from_light.z > light.near_z
+    && 0.0 < uv.x && uv.x < 1.0
+    && 0.0 < uv.y && uv.y < 1.0 {
+#ifdef SHADOWS
+        var bias = 0.001 / from_light.z;
+        if light.receiver_plane_bias != 0u {
+            // the receiver's depth may change by this much within the sampled texel
+            bias += dot(abs(depth_gradient), texel_size);
+        } else {
+            depth_gradient = vec2<f32>(0.0);
+        }
+
+        // the light view is scaled by the fit, so this many uv cover a world unit
+        // at the receiver's distance
+        let uv_per_world = vec2<f32>(length(light.view_0.xyz), length(light.view_1.xyz))
+            / (2.0 * from_light.z);
+        let max_radius = light.shadow_radius * uv_per_world;
+
+        // rotate the kernel per pixel, trading banding for noise
+        let angle = 6.2831853 * interleaved_gradient_noise(in.clip_position.xy);
+        let rotation = mat2x2<f32>(cos(angle), sin(angle), -sin(angle), cos(angle));
+
+        if (in.flags & RECEIVES_SHADOW) == 0u {
             lighting = 1.0;
+#ifdef TRACED_SHADOWS
+        } else if camera.traced_shadow_mask != 0u {
+            // traced from the frame before's depth, so a moving camera sees it trail a frame
+            lighting = textureLoad(traced_shadow_mask, vec2<i32>(in.clip_position.xy), 0).r;
+#endif
+#ifdef PCF
+        } else if light.shadow_filter == SHADOW_FILTER_POISSON {
+            lighting = poisson_pcf(uv, receiver_depth + bias, depth_gradient, max_radius, rotation);
+        } else if light.shadow_filter == SHADOW_FILTER_PCSS {
+            // the light's area as seen from the receiver, in uv at the near plane
+            let light_size = light.size * uv_per_world * from_light.z / light.near_z;
+            // blockers between the light and the receiver are found within the area's projection
+            let search_radius = min(
+                0.5 * light_size * (from_light.z - light.near_z) / from_light.z,
+                max_radius,
+            );
+            let blocker_z = average_blocker_z(
+                uv,
+                receiver_depth + bias,
+                depth_gradient,
+                search_radius,
+                rotation,
+            );
+
+            if blocker_z > 0.0 {
+                // penumbra widens with the distance between blocker and receiver
+                let penumbra = light.size * (from_light.z - blocker_z) / blocker_z;
+                let radius = min(0.5 * penumbra * uv_per_world, max_radius);
+                lighting = poisson_pcf(uv, receiver_depth + bias, depth_gradient, radius, rotation);
+            } else {
+                lighting = 1.0;
+            }
+#endif
+        } else {
+            // also every filter without PCF
+            lighting = shadow_tap(uv, receiver_depth + bias);
         }
+#else
+        lighting = 1.0;
+#endif
     }
 
-    out.color = vec4(1.0) * lighting;
+    var albedo = vec3<f32>(1.0);
+    if draw.material == MATERIAL_TERRAIN {
+        // the detail averages 0.5
+        albedo = terrain_albedo(in.world_position.y, normalize(in.normal)) * detail * 2.0;
+        // terrain has smooth normals, so it's shaded by them as well
+        lighting *= max(dot(normalize(in.normal), -light_direction()), 0.0);
+    }
+    let point = point_lighting(in.world_position, in.normal, in.flags);
+
+    out.color = vec4(albedo * (light.color * light.intensity * lighting + point + light.ambient), 1.0);
+    if (in.flags & LIGHTMAPPED) != 0u {
+        // baked with the bounce but without the ambient or the point light, which can still change
+        out.color = vec4(albedo * (baked + point + light.ambient), 1.0);
+    }
+    if draw.material == MATERIAL_UNLIT {
+        // unlit, flat color
+        out.color = vec4(1.0, 0.8, 0.2, 1.0);
+    }
+    out.id = in.id;
+    // from behind the previous view, it's left to the camera's motion
+    if in.previous_view_position.z > 0.0 && in.moved_from.z > 0.0 {
+        let ndc_motion = in.previous_view_position.xy / in.previous_view_position.z - in.moved_from.xy / in.moved_from.z;
+        out.motion = vec2<f32>(ndc_motion.x, -ndc_motion.y) * 0.5;
+    }
+
+    // after the derivatives, which need every pixel of the quad
+    if dot(camera.clip_plane, vec4<f32>(in.world_position, 1.0)) < 0.0 {
+        discard;
+    }
 
     return out;
 }
 
-fn apply_affine(
-    _0: vec4<f32>,
-    _1: vec4<f32>,
-    _2: vec4<f32>,
-    pos: vec3<f32>
-) -> vec3<f32> {
-    return vec3<f32>(
-        dot(_0.xyz, pos) + _0.w,
-        dot(_1.xyz, pos) + _1.w,
-        dot(_2.xyz, pos) + _2.w,
+// the point light reaching `position`, shaded like the light: the terrain by its smooth
+// `normal`, the instances only by their shadows
+fn point_lighting(position: vec3<f32>, normal: vec3<f32>, flags: u32) -> vec3<f32> {
+    let to_point = position - point_light.position;
+    let distance = length(to_point);
+    if point_light.intensity <= 0.0 || distance >= point_light.range {
+        return vec3<f32>(0.0);
+    }
+    // inverse square, windowed down to 0 at the range
+    let window = 1.0 - pow(distance / point_light.range, 4.0);
+    var lit = window * window / (distance * distance + 1.0);
+    if draw.material == MATERIAL_TERRAIN {
+        lit *= max(dot(normalize(normal), -to_point / distance), 0.0);
+    }
+#ifdef SHADOWS
+    if point_light.shadow == POINT_SHADOW_DUAL_PARABOLOID && (flags & RECEIVES_SHADOW) != 0u {
+        lit *= paraboloid_shadow_tap(to_point, distance);
+    }
+#endif
+    return point_light.color * point_light.intensity * lit;
+}
+
+// 1 when the point shadow sees `to_point`, from the point light, as point_shadow.wgsl projects it
+fn paraboloid_shadow_tap(to_point: vec3<f32>, distance: f32) -> f32 {
+    var layer = 0;
+    var direction = 1.0;
+    if to_point.z < 0.0 {
+        layer = 1;
+        direction = -1.0;
+    }
+    let normal = to_point / distance;
+    let projected = vec2<f32>(normal.x * direction, normal.y) / (1.0 + normal.z * direction);
+    let uv = vec2<f32>(projected.x * 0.5 + 0.5, 0.5 - projected.y * 0.5);
+    let depth = textureSampleLevel(point_shadow_texture, shadow_sampler, uv, layer, 0.0);
+    let receiver_depth = point_light.near_z / distance;
+    return select(0.0, 1.0, receiver_depth * (1.0 + POINT_SHADOW_BIAS) > depth);
+}
+
+// 1 when `receiver_depth` is in front of the depth stored at `uv`, reversed z
+fn shadow_tap(uv: vec2<f32>, receiver_depth: f32) -> f32 {
+    let depth = textureSampleLevel(shadow_texture, shadow_sampler, uv, 0.0);
+    return select(0.0, 1.0, receiver_depth > depth);
+}
+
+// fraction of the rotated poisson disk of `radius` around `uv` that is lit
+fn poisson_pcf(
+    uv: vec2<f32>,
+    receiver_depth: f32,
+    depth_gradient: vec2<f32>,
+    radius: vec2<f32>,
+    rotation: mat2x2<f32>,
+) -> f32 {
+    var lit = 0.0;
+    for (var i = 0u; i < 16u; i++) {
+        let offset = rotation * POISSON_DISK[i] * radius;
+        lit += shadow_tap(uv + offset, receiver_depth + dot(depth_gradient, offset));
+    }
+    return lit / 16.0;
+}
+
+// average light space z of the shadow map texels in front of the receiver
+// within `radius` of `uv`, 0 when there are none
+fn average_blocker_z(
+    uv: vec2<f32>,
+    receiver_depth: f32,
+    depth_gradient: vec2<f32>,
+    radius: vec2<f32>,
+    rotation: mat2x2<f32>,
+) -> f32 {
+    var blocker_z = 0.0;
+    var blockers = 0.0;
+    for (var i = 0u; i < 16u; i++) {
+        let offset = rotation * POISSON_DISK[i] * radius;
+        let depth = textureSampleLevel(shadow_texture, shadow_sampler, uv + offset, 0.0);
+        if depth > receiver_depth + dot(depth_gradient, offset) {
+            blocker_z += light.near_z / depth;
+            blockers += 1.0;
+        }
+    }
+    if blockers == 0.0 {
+        return 0.0;
+    }
+    return blocker_z / blockers;
+}
+
+// Jimenez's noise, in 0 to 1, well distributed over neighbouring pixels
+fn interleaved_gradient_noise(pixel: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+// xy are texture coordinates for light in range (0, 0) to (z, z), scaled by z
+// +y is downwards in texture coordinates
+// z is depth value from light
+fn to_light(position: vec3<f32>) -> vec3<f32> {
+    var from_light = apply_affine(
+        light.view_0,
+        light.view_1,
+        light.view_2,
+        position,
     );
-}
\ No newline at end of file
+
+    // prepare (-1.0, 1.0) range to (0.0, 1.0) range
+    from_light.x = ( from_light.x + from_light.z) * 0.5;
+    from_light.y = (-from_light.y + from_light.z) * 0.5;
+    return from_light;
+}
+
+// world space direction the light travels in, its view space z axis
+fn light_direction() -> vec3<f32> {
+    return normalize(light.view_2.xyz);
+}
+
+// how the receiver's depth changes with shadow map uv, assuming the receiver is planar
+// within the pixel, from the screen space derivatives of both
+fn receiver_plane_depth_gradient(uv: vec2<f32>, depth: f32) -> vec2<f32> {
+    let uv_dx = dpdx(uv);
+    let uv_dy = dpdy(uv);
+    let depth_dx = dpdx(depth);
+    let depth_dy = dpdy(depth);
+
+    let determinant = uv_dx.x * uv_dy.y - uv_dx.y * uv_dy.x;
+    if abs(determinant) < 1e-12 {
+        return vec2<f32>(0.0);
+    }
+    // inverse transpose of the uv jacobian applied to the depth derivatives
+    return vec2<f32>(
+        uv_dy.y * depth_dx - uv_dx.y * depth_dy,
+        uv_dx.x * depth_dy - uv_dy.x * depth_dx,
+    ) / determinant;
+}
@@ -0,0 +1,94 @@
+use crate::debug_draw::DebugDraw;
+use crate::math::{Affine2, Affine3, Color, Vector2, Vector3};
+use crate::polygon::Rect;
+
+/// pixels from the light's gizmo within which a click grabs it
+pub const GRAB_RADIUS: f32 = 20.0;
+
+/// pixel position of `point` in a window of `size`, y down, None when behind the camera.
+/// `view` is the camera's as uploaded, scaled so its frustum is the unit one
+pub fn project(point: Vector3, view: &Affine3, size: [f32; 2]) -> Option<[f32; 2]> {
+    let view_point = point.apply(view);
+    if view_point.z <= 0.0 {
+        return None;
+    }
+    let ndc = view_point.xy() * (1.0 / view_point.z);
+    Some([(ndc.x * 0.5 + 0.5) * size[0], (0.5 - ndc.y * 0.5) * size[1]])
+}
+
+/// the point under the pixel `cursor` at the view space `depth`, undoing `project`
+pub fn unproject(cursor: [f32; 2], depth: f32, view: &Affine3, size: [f32; 2]) -> Vector3 {
+    let ndc = Vector2::new(cursor[0] / size[0] * 2.0 - 1.0, 1.0 - cursor[1] / size[1] * 2.0);
+    Vector3::new(ndc.x * depth, ndc.y * depth, depth).apply(&view.inverse())
+}
+
+/// drags the light across the screen by its gizmo, keeping its distance along the camera's view
+pub struct LightEditor {
+    pub enabled: bool,
+    /// view space depth of the light while it's dragged
+    dragging: Option<f32>,
+}
+
+impl LightEditor {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            dragging: None,
+        }
+    }
+
+    /// grabs the light at `position` when `cursor` is within `GRAB_RADIUS` of it, returning whether it did
+    pub fn press(&mut self, cursor: [f32; 2], size: [f32; 2], view: &Affine3, position: Vector3) -> bool {
+        let Some(projected) = project(position, view, size).filter(|_| self.enabled) else {
+            return false;
+        };
+        let distance = Vector2::new(projected[0] - cursor[0], projected[1] - cursor[1]).length();
+        if distance > GRAB_RADIUS {
+            return false;
+        }
+        self.dragging = Some(position.apply(view).z);
+        true
+    }
+
+    pub fn release(&mut self) {
+        self.dragging = None;
+    }
+
+    #[allow(dead_code)]
+    pub fn is_dragging(&self) -> bool {
+        self.dragging.is_some()
+    }
+
+    /// where the dragged light moves to under `cursor`
+    pub fn drag(&self, cursor: [f32; 2], size: [f32; 2], view: &Affine3) -> Option<Vector3> {
+        self.dragging.map(|depth| unproject(cursor, depth, view, size))
+    }
+}
+
+/// the rect of the light's near plane the camera's view is fitted to,
+/// `view` being the light's before it's fitted or scaled
+pub fn draw_fit(debug_draw: &mut DebugDraw, view: &Affine3, near_z: f32, fit: &Affine2, light_size: Vector2) {
+    let to_plane = fit.inverse();
+    let to_world = view.inverse();
+    let light_rect = Rect { max: light_size * 0.5, min: light_size * -0.5 };
+    let corners = light_rect.corners().map(|corner| corner.apply(&to_plane).extend(near_z).apply(&to_world));
+    debug_draw.quad(corners, Color::rgb(0.2, 0.9, 0.4));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unproject_undoes_project() {
+        let mut view = Affine3::IDENTITY;
+        view.translate(&Vector3::new(1.0, -2.0, 3.0))
+            .scale(&crate::math::Scale3::new(0.5, 1.0, 1.0));
+        let size = [800.0, 600.0];
+        let point = Vector3::new(0.5, 1.0, 4.0);
+        let pixel = project(point, &view, size).unwrap();
+        let depth = point.apply(&view).z;
+        assert!((unproject(pixel, depth, &view, size) - point).length() < 1e-4);
+        assert!(project(Vector3::new(0.0, 0.0, -10.0), &view, size).is_none());
+    }
+}
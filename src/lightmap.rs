@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+use wgpu::*;
+
+use crate::bvh::Bvh;
+use crate::math::{self, Affine3, Vector2, Vector3};
+use crate::scene::Instance;
+
+/// side of the atlas every baked instance gets a square tile of
+pub const ATLAS_SIZE: u32 = 1024;
+pub const FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+/// the space around the charts of a mesh's uv2, as a fraction of its unit square, so bilinear
+/// lookups near one chart's edge don't reach into another's even in the smallest tiles
+const CHART_PADDING: f32 = 1.0 / 16.0;
+/// a tile's side per meter of the square root of the instance's surface area, before rounding
+/// up to a power of two
+const TEXELS_PER_METER: f32 = 8.0;
+const MIN_TILE_SIZE: u32 = 16;
+const MAX_TILE_SIZE: u32 = 128;
+/// cosine distributed rays gathering the first bounce of each texel
+const BOUNCE_SAMPLES: u32 = 64;
+/// how far the bounce rays look for surfaces
+const BOUNCE_DISTANCE: f32 = 30.0;
+/// of the light surfaces reflect, white like `light.wgsl` draws instances
+const ALBEDO: f32 = 1.0;
+/// rays start this far off the surface they leave, so they don't hit it
+const SURFACE_OFFSET: f32 = 1e-3;
+
+/// a mesh's vertex with the coordinates its instances' tiles are looked up by
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct LightmappedVertex {
+    pub position: [f32; 3],
+    /// in the unit square, charts don't overlap
+    pub uv2: [f32; 2],
+}
+
+pub struct UnwrappedMesh {
+    pub vertices: Vec<LightmappedVertex>,
+    /// wound as they were
+    pub indices: Vec<u32>,
+}
+
+impl UnwrappedMesh {
+    fn position(&self, i: u32) -> Vector3 {
+        let [x, y, z] = self.vertices[i as usize].position;
+        Vector3::new(x, y, z)
+    }
+
+    fn uv2(&self, i: u32) -> Vector2 {
+        let [x, y] = self.vertices[i as usize].uv2;
+        Vector2::new(x, y)
+    }
+
+    /// the triangles of an instance of the mesh, in world space
+    pub fn world_triangles<'a>(&'a self, instance: &Instance) -> impl Iterator<Item = [Vector3; 3]> + 'a {
+        let model = Affine3::from(instance.scale, instance.rotation, instance.translation);
+        self.indices
+            .chunks_exact(3)
+            .map(move |triangle| [0, 1, 2].map(|corner| self.position(triangle[corner]).apply(&model)))
+    }
+}
+
+/// an orthonormal pair spanning the plane perpendicular to the unit `normal`
+fn tangents(normal: Vector3) -> (Vector3, Vector3) {
+    let helper = if normal.x.abs() < 0.9 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::new(0.0, 1.0, 0.0) };
+    let u = normal.cross(&helper).normalized();
+    (u, normal.cross(&u))
+}
+
+/// generates the uv2 of a mesh: its coplanar triangles make up charts, each laid flat and packed
+/// into the unit square without overlapping, keeping their relative sizes. vertices are
+/// duplicated along the seams between charts
+pub fn unwrap(positions: &[[f32; 3]], indices: &[u32]) -> UnwrappedMesh {
+    let position = |i: u32| {
+        let [x, y, z] = positions[i as usize];
+        Vector3::new(x, y, z)
+    };
+    let triangles: Vec<[u32; 3]> = indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+
+    let mut charts: Vec<(math::Plane, Vec<usize>)> = vec![];
+    for (i, &[a, b, c]) in triangles.iter().enumerate() {
+        let plane = math::Plane::from_points(position(a), position(b), position(c)).normalized();
+        let chart = charts.iter_mut().find(|(chart, _)| {
+            chart.normal.dot(&plane.normal) > 1.0 - 1e-4 && (chart.distance - plane.distance).abs() < 1e-4
+        });
+        match chart {
+            Some((_, chart)) => chart.push(i),
+            None => charts.push((plane, vec![i])),
+        }
+    }
+
+    // each chart's corners flattened onto its plane, from its lowest corner
+    let flattened: Vec<(HashMap<u32, Vector2>, Vector2)> = charts
+        .iter()
+        .map(|(plane, chart)| {
+            let (u, v) = tangents(plane.normal);
+            let corners: HashMap<u32, Vector2> = chart
+                .iter()
+                .flat_map(|&i| triangles[i])
+                .map(|i| (i, Vector2::new(position(i).dot(&u), position(i).dot(&v))))
+                .collect();
+            let min = corners.values().fold(Vector2::new(f32::MAX, f32::MAX), |min, &p| min.min(p));
+            let max = corners.values().fold(Vector2::new(f32::MIN, f32::MIN), |max, &p| max.max(p));
+            (corners.into_iter().map(|(i, p)| (i, p - min)).collect(), max - min)
+        })
+        .collect();
+
+    let sizes: Vec<Vector2> = flattened.iter().map(|&(_, size)| size).collect();
+    let area: f32 = sizes.iter().map(|size| size.x * size.y).sum();
+    let widest = sizes.iter().fold(0.0f32, |widest, size| widest.max(size.x).max(size.y));
+    let mut side = area.sqrt().max(widest).max(f32::MIN_POSITIVE);
+    let offsets = loop {
+        if let Some(offsets) = shelf_pack(&sizes, side, side * CHART_PADDING) {
+            break offsets;
+        }
+        side *= 1.1;
+    };
+
+    let mut vertices = vec![];
+    let mut new_indices = vec![0; indices.len()];
+    for (((_, chart), (corners, _)), offset) in charts.iter().zip(&flattened).zip(offsets) {
+        let mut remapped = HashMap::new();
+        for &triangle in chart {
+            for corner in 0..3 {
+                let i = triangles[triangle][corner];
+                new_indices[3 * triangle + corner] = *remapped.entry(i).or_insert_with(|| {
+                    let uv2 = (corners[&i] + offset) / side;
+                    vertices.push(LightmappedVertex { position: positions[i as usize], uv2: [uv2.x, uv2.y] });
+                    vertices.len() as u32 - 1
+                });
+            }
+        }
+    }
+    UnwrappedMesh {
+        vertices,
+        indices: new_indices,
+    }
+}
+
+/// lowest corners of `sizes` packed in rows into a square of `side` with `padding` around each,
+/// tallest first, or None when they don't fit
+fn shelf_pack(sizes: &[Vector2], side: f32, padding: f32) -> Option<Vec<Vector2>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| sizes[b].y.total_cmp(&sizes[a].y));
+
+    let mut offsets = vec![Vector2::new(0.0, 0.0); sizes.len()];
+    let (mut x, mut y, mut row_height) = (padding, padding, 0.0f32);
+    for i in order {
+        let size = sizes[i];
+        if x + size.x + padding > side {
+            (x, y, row_height) = (padding, y + row_height + padding, 0.0);
+        }
+        if x + size.x + padding > side || y + size.y + padding > side {
+            return None;
+        }
+        offsets[i] = Vector2::new(x, y);
+        x += size.x + padding;
+        row_height = row_height.max(size.y);
+    }
+    Some(offsets)
+}
+
+/// where an instance's uv2 is in the atlas: the unit square scaled by `size` and moved by `offset`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tile {
+    pub offset: [f32; 2],
+    pub size: f32,
+}
+
+impl Tile {
+    pub fn to_array(self) -> [f32; 3] {
+        [self.offset[0], self.offset[1], self.size]
+    }
+}
+
+/// the light as baked, a point shining into the half space in front of it
+pub struct BakeLight {
+    pub position: Vector3,
+    /// unit
+    pub direction: Vector3,
+    /// color times intensity
+    pub radiance: [f32; 3],
+}
+
+/// an atlas of the irradiance from the light and its first bounce, and each baked instance's tile
+pub struct Bake {
+    /// `ATLAS_SIZE` rows of `ATLAS_SIZE`
+    pub texels: Vec<[f32; 3]>,
+    pub tiles: Vec<Tile>,
+}
+
+/// bakes the lighting of `instances`, with the uv2 of their `meshes`, into tiles of one atlas in
+/// the same order. `casters` shadow them and the bounce is gathered from `surfaces`, world space
+/// triangles both. fails when there are too many instances to fit even at the smallest tiles
+pub fn bake(
+    meshes: &[UnwrappedMesh],
+    instances: &[&Instance],
+    casters: &Bvh,
+    surfaces: &Bvh,
+    light: &BakeLight,
+) -> Result<Bake, String> {
+    let mut sizes: Vec<u32> = instances
+        .iter()
+        .map(|instance| {
+            let area: f32 = meshes[instance.mesh.0 as usize]
+                .world_triangles(instance)
+                .map(|[a, b, c]| (b - a).cross(&(c - a)).length() / 2.0)
+                .sum();
+            let size = (area.sqrt() * TEXELS_PER_METER) as u32;
+            size.next_power_of_two().clamp(MIN_TILE_SIZE, MAX_TILE_SIZE)
+        })
+        .collect();
+    let corners = loop {
+        if let Some(corners) = pack_tiles(&sizes) {
+            break corners;
+        }
+        if sizes.iter().all(|&size| size == MIN_TILE_SIZE) {
+            return Err(format!("{} instances don't fit a {}² lightmap", instances.len(), ATLAS_SIZE));
+        }
+        sizes = sizes.iter().map(|&size| (size / 2).max(MIN_TILE_SIZE)).collect();
+    };
+
+    let blocks: Vec<Vec<f32>> = instances
+        .par_iter()
+        .zip(&sizes)
+        .enumerate()
+        .map(|(i, (instance, &size))| {
+            let mesh = &meshes[instance.mesh.0 as usize];
+            bake_tile(mesh, instance, size as usize, i as u32, casters, surfaces, light)
+        })
+        .collect();
+
+    let mut texels = vec![[0.0; 3]; (ATLAS_SIZE * ATLAS_SIZE) as usize];
+    for ((block, &size), &[x, y]) in blocks.iter().zip(&sizes).zip(&corners) {
+        for (row, block_row) in block.chunks_exact(size as usize).enumerate() {
+            let start = ((y as usize + row) * ATLAS_SIZE as usize) + x as usize;
+            for (texel, &irradiance) in texels[start..start + size as usize].iter_mut().zip(block_row) {
+                *texel = light.radiance.map(|channel| channel * irradiance);
+            }
+        }
+    }
+    let tiles = sizes
+        .iter()
+        .zip(&corners)
+        .map(|(&size, &[x, y])| Tile {
+            offset: [x as f32 / ATLAS_SIZE as f32, y as f32 / ATLAS_SIZE as f32],
+            size: size as f32 / ATLAS_SIZE as f32,
+        })
+        .collect();
+    Ok(Bake { texels, tiles })
+}
+
+/// texel corners of the square tiles of power of two `sizes` packed in rows into the atlas,
+/// largest first, or None when they don't fit
+fn pack_tiles(sizes: &[u32]) -> Option<Vec<[u32; 2]>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i]));
+
+    let mut corners = vec![[0, 0]; sizes.len()];
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    for i in order {
+        let size = sizes[i];
+        if x + size > ATLAS_SIZE {
+            (x, y, row_height) = (0, y + row_height, 0);
+        }
+        if y + size > ATLAS_SIZE {
+            return None;
+        }
+        corners[i] = [x, y];
+        x += size;
+        row_height = row_height.max(size);
+    }
+    Some(corners)
+}
+
+/// the irradiance of a `size` squared tile relative to the light's radiance, rows of texels
+fn bake_tile(
+    mesh: &UnwrappedMesh,
+    instance: &Instance,
+    size: usize,
+    seed: u32,
+    casters: &Bvh,
+    surfaces: &Bvh,
+    light: &BakeLight,
+) -> Vec<f32> {
+    let mut texels = vec![None; size * size];
+    for (corners, triangle) in mesh.world_triangles(instance).zip(mesh.indices.chunks_exact(3)) {
+        let [a, b, c] = corners;
+        // the meshes are wound so the cross product points into them
+        let normal = -(b - a).cross(&(c - a)).normalized();
+        let [uv_a, uv_b, uv_c] = [0, 1, 2].map(|corner| mesh.uv2(triangle[corner]) * size as f32);
+        let determinant = (uv_b - uv_a).wedge(uv_c - uv_a).xy;
+        if determinant.abs() < 1e-12 {
+            continue;
+        }
+
+        let min = uv_a.min(uv_b).min(uv_c);
+        let max = uv_a.max(uv_b).max(uv_c);
+        for y in (min.y.floor() as usize)..(max.y.ceil() as usize).min(size) {
+            for x in (min.x.floor() as usize)..(max.x.ceil() as usize).min(size) {
+                let center = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let weight_b = (center - uv_a).wedge(uv_c - uv_a).xy / determinant;
+                let weight_c = (uv_b - uv_a).wedge(center - uv_a).xy / determinant;
+                if weight_b < 0.0 || weight_c < 0.0 || weight_b + weight_c > 1.0 {
+                    continue;
+                }
+                let position = a + (b - a) * weight_b + (c - a) * weight_c;
+                let random_seed = math::noise::hash(seed.wrapping_mul(0x9e37_79b9) ^ (y * size + x) as u32);
+                texels[y * size + x] = Some(
+                    direct(position, normal, casters, light) + bounce(position, normal, random_seed, casters, surfaces, light),
+                );
+            }
+        }
+    }
+    dilate(&mut texels, size);
+    texels.into_iter().map(|texel| texel.unwrap_or(0.0)).collect()
+}
+
+/// the cosine of the light's incidence at `position` facing `normal`, 0 where it's shadowed
+fn direct(position: Vector3, normal: Vector3, casters: &Bvh, light: &BakeLight) -> f32 {
+    let to_light = light.position - position;
+    if to_light.dot(&light.direction) >= 0.0 {
+        return 0.0;
+    }
+    let cosine = normal.dot(&to_light.normalized());
+    if cosine <= 0.0 || casters.occluded(position + normal * SURFACE_OFFSET, to_light) {
+        return 0.0;
+    }
+    cosine
+}
+
+/// the irradiance at `position` of the light reflected once off `surfaces`, relative to the light's
+fn bounce(position: Vector3, normal: Vector3, seed: u32, casters: &Bvh, surfaces: &Bvh, light: &BakeLight) -> f32 {
+    let random = |i: u32| math::noise::hash(seed.wrapping_add(math::noise::hash(i))) as f32 / u32::MAX as f32;
+    let (u, v) = tangents(normal);
+    let origin = position + normal * SURFACE_OFFSET;
+    let mut gathered = 0.0;
+    for sample in 0..BOUNCE_SAMPLES {
+        // cosine distributed, which cancels the cosine of the incidence out of the average
+        let (angle, radius_sqr) = (std::f32::consts::TAU * random(2 * sample), random(2 * sample + 1));
+        let radius = radius_sqr.sqrt();
+        let direction = u * (radius * angle.cos()) + v * (radius * angle.sin()) + normal * (1.0 - radius_sqr).sqrt();
+        if let Some((t, [a, b, c])) = surfaces.closest_hit(origin, direction * BOUNCE_DISTANCE) {
+            let hit = origin + direction * (BOUNCE_DISTANCE * t);
+            // lit on either face, towards the ray
+            let mut hit_normal = (b - a).cross(&(c - a)).normalized();
+            if hit_normal.dot(&direction) > 0.0 {
+                hit_normal = -hit_normal;
+            }
+            gathered += ALBEDO * direct(hit, hit_normal, casters, light);
+        }
+    }
+    gathered / BOUNCE_SAMPLES as f32
+}
+
+/// fills the texels no triangle covers with the average of their covered neighbours, twice, so
+/// bilinear lookups along the charts' edges don't blend in black
+fn dilate(texels: &mut [Option<f32>], size: usize) {
+    for _ in 0..2 {
+        let source = texels.to_vec();
+        for y in 0..size {
+            for x in 0..size {
+                if source[y * size + x].is_some() {
+                    continue;
+                }
+                let neighbours: Vec<f32> = (y.saturating_sub(1)..(y + 2).min(size))
+                    .flat_map(|y| (x.saturating_sub(1)..(x + 2).min(size)).map(move |x| (x, y)))
+                    .filter_map(|(x, y)| source[y * size + x])
+                    .collect();
+                if !neighbours.is_empty() {
+                    texels[y * size + x] = Some(neighbours.iter().sum::<f32>() / neighbours.len() as f32);
+                }
+            }
+        }
+    }
+}
+
+/// the closest half float, flushing what's too small for its normal range to zero
+fn to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent <= 0 {
+        sign
+    } else if exponent >= 31 {
+        sign | 0x7c00
+    } else {
+        // rounded to nearest, a carry into the exponent is still the right value
+        sign + ((exponent as u32) << 10 | mantissa >> 13) as u16 + ((mantissa >> 12) & 1) as u16
+    }
+}
+
+/// the atlas `light.wgsl` looks lightmapped instances' lighting up in, black until baked
+pub struct Lightmap {
+    texture: Texture,
+    pub view: TextureView,
+    pub sampler: Sampler,
+}
+
+impl Lightmap {
+    pub fn new(device: &Device) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Lightmap"),
+            size: Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: FORMAT,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Lightmap sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        Self { texture, view, sampler }
+    }
+
+    pub fn upload(&self, queue: &Queue, bake: &Bake) {
+        let texels: Vec<[u16; 4]> = bake
+            .texels
+            .iter()
+            .map(|&[r, g, b]| [to_f16(r), to_f16(g), to_f16(b), to_f16(1.0)])
+            .collect();
+        queue.write_texture(
+            self.texture.as_image_copy(),
+            bytemuck::cast_slice(&texels),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(ATLAS_SIZE * 8),
+                rows_per_image: Some(ATLAS_SIZE),
+            },
+            self.texture.size(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::MeshId;
+
+    fn unwrapped_cube() -> UnwrappedMesh {
+        unwrap(&crate::CUBE_VERTICES.map(|v| v.position), &crate::CUBE_INDICES)
+    }
+
+    fn cube(translation: Vector3, scale: math::Scale3) -> Instance {
+        Instance {
+            mesh: MeshId::CUBE,
+            translation,
+            rotation: math::Rotor::IDENTITY,
+            scale,
+            casts_shadow: true,
+            receives_shadow: true,
+            lightmap: None,
+        }
+    }
+
+    #[test]
+    fn unwrapped_charts_keep_their_shape_without_overlapping() {
+        let mesh = unwrapped_cube();
+        // a chart per face, a vertex per face corner
+        assert_eq!((mesh.vertices.len(), mesh.indices.len()), (24, 36));
+        for (triangle, original) in mesh.indices.chunks(3).zip(crate::CUBE_INDICES.chunks(3)) {
+            for corner in 0..3 {
+                assert_eq!(mesh.vertices[triangle[corner] as usize].position, crate::CUBE_VERTICES[original[corner] as usize].position);
+            }
+        }
+
+        let cells = 64;
+        let mut covered = vec![None; cells * cells];
+        let mut face_sides = vec![];
+        for (i, triangle) in mesh.indices.chunks(3).enumerate() {
+            let [a, b, c] = [0, 1, 2].map(|corner| mesh.uv2(triangle[corner]));
+            assert!([a, b, c].iter().all(|uv| (0.0..=1.0).contains(&uv.x) && (0.0..=1.0).contains(&uv.y)));
+            face_sides.push((b - a).length().min((c - a).length()));
+            let determinant = (b - a).wedge(c - a).xy;
+            for (cell, covering) in covered.iter_mut().enumerate() {
+                let p = Vector2::new((cell % cells) as f32 + 0.5, (cell / cells) as f32 + 0.5) / cells as f32;
+                let weight_b = (p - a).wedge(c - a).xy / determinant;
+                let weight_c = (b - a).wedge(p - a).xy / determinant;
+                if weight_b > 0.0 && weight_c > 0.0 && weight_b + weight_c < 1.0 {
+                    // only the two triangles of one face share their cells
+                    assert!(covering.is_none_or(|other: usize| other / 2 == i / 2), "{} and {:?}", i, covering);
+                    *covering = Some(i);
+                }
+            }
+        }
+        // the faces are squares of the same size
+        assert!(face_sides.iter().all(|side| (side - face_sides[0]).abs() < 1e-5));
+    }
+
+    #[test]
+    fn baking_shadows_and_bounces_the_light() {
+        let meshes = [unwrapped_cube()];
+        let floor = cube(Vector3::new(0.0, -0.5, 0.0), math::Scale3::new(8.0, 1.0, 8.0));
+        let blocker = cube(Vector3::new(0.0, 2.0, 0.0), math::Scale3::new(2.0, 0.2, 2.0));
+        let triangles: Vec<_> = [&floor, &blocker].iter().flat_map(|instance| meshes[0].world_triangles(instance)).collect();
+        let bvh = Bvh::build(&triangles);
+        let light = BakeLight {
+            position: Vector3::new(0.0, 10.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 0.0),
+            radiance: [2.0, 1.0, 0.5],
+        };
+        let bake = bake(&meshes, &[&floor, &blocker], &bvh, &bvh, &light).unwrap();
+        assert_eq!(bake.tiles.len(), 2);
+        assert!(bake.texels.iter().all(|&[r, g, b]| (r - 2.0 * g).abs() < 1e-4 && (r - 4.0 * b).abs() < 1e-4));
+
+        // the texel at the middle of a face of the cube, the one of its triangles at `face * 6`
+        let face_center = |tile: Tile, face: usize| {
+            let uv = meshes[0].indices[face * 6..face * 6 + 6]
+                .iter()
+                .fold(Vector2::new(0.0, 0.0), |sum, &i| sum + meshes[0].uv2(i))
+                / 6.0;
+            let [x, y] = [0, 1].map(|axis| ((tile.offset[axis] + uv[axis] * tile.size) * ATLAS_SIZE as f32) as usize);
+            bake.texels[y * ATLAS_SIZE as usize + x][0]
+        };
+        let (bottom, top) = (2, 5);
+        // the floor's top is lit around the blocker's shadow, and shadowed in the middle where it
+        // faces only the blocker's underside, which the light doesn't reach
+        let texels = bake.texels.iter().map(|texel| texel[0]);
+        assert!(texels.fold(0.0, f32::max) > 1.9);
+        assert!(face_center(bake.tiles[0], top) < 0.05);
+        // which sees the light bounced off the floor around the shadow
+        let bounced = face_center(bake.tiles[1], bottom);
+        assert!(0.1 < bounced && bounced < 1.0, "{}", bounced);
+
+        assert_eq!(to_f16(1.0), 0x3c00);
+        assert_eq!(to_f16(-2.5), 0xc100);
+        assert_eq!(to_f16(1e6), 0x7c00);
+    }
+}
@@ -0,0 +1,324 @@
+use std::path::Path;
+
+use wgpu::*;
+
+use crate::buffer::GpuBuffer;
+use crate::post::{self, FullscreenPass, PostInput, PostNode, HDR_FORMAT};
+use crate::texture::{TexelFormat, TextureData};
+
+/// a 3d color lookup table over the displayed, srgb encoded, colors
+#[derive(Clone, Debug)]
+pub struct Lut {
+    pub name: String,
+    /// entries along each axis
+    pub size: u32,
+    /// rgb in 0 to 1, red varying fastest, then green, then blue
+    pub entries: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    /// `f` maps each color in 0 to 1 to its graded color
+    pub fn from_fn(name: &str, size: u32, f: impl Fn([f32; 3]) -> [f32; 3]) -> Self {
+        let scale = 1.0 / (size - 1) as f32;
+        let entries = (0..size * size * size)
+            .map(|i| f([
+                (i % size) as f32 * scale,
+                (i / size % size) as f32 * scale,
+                (i / (size * size)) as f32 * scale,
+            ]))
+            .collect();
+        Self {
+            name: name.to_string(),
+            size,
+            entries,
+        }
+    }
+
+    /// an adobe .cube 3d lut, its `TITLE` replacing `name` when given
+    pub fn from_cube(name: &str, text: &str) -> Result<Self, String> {
+        let mut name = name.to_string();
+        let mut size = None;
+        let mut entries = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: &str| format!("line {}: {}", i + 1, message);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match keyword {
+                "TITLE" => name = rest.trim().trim_matches('"').to_string(),
+                "LUT_3D_SIZE" => {
+                    size = Some(rest.trim().parse::<u32>().map_err(|_| error("invalid size"))?);
+                }
+                "LUT_1D_SIZE" => return Err(error("1d luts aren't supported")),
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    let domain: Vec<f32> = rest.split_whitespace().filter_map(|v| v.parse().ok()).collect();
+                    if domain != [expected; 3] {
+                        return Err(error("only the domain from 0 to 1 is supported"));
+                    }
+                }
+                _ => {
+                    let entry: Vec<f32> = line
+                        .split_whitespace()
+                        .map(|v| v.parse())
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| error("expected a keyword or 3 numbers"))?;
+                    let [r, g, b] = entry[..] else {
+                        return Err(error("expected a keyword or 3 numbers"));
+                    };
+                    entries.push([r, g, b]);
+                }
+            }
+        }
+
+        let size = size.ok_or("no LUT_3D_SIZE")?;
+        if size < 2 || entries.len() != (size * size * size) as usize {
+            return Err(format!("expected {} entries for size {}, found {}", size * size * size, size, entries.len()));
+        }
+        Ok(Self {
+            name,
+            size,
+            entries,
+        })
+    }
+
+    /// a strip of `size` square slices side by side, blue increasing from slice to slice,
+    /// red to the right and green downwards within each
+    pub fn from_strip(name: &str, texture: &TextureData) -> Result<Self, String> {
+        let size = texture.height;
+        if size < 2 || texture.width != size * size {
+            return Err(format!("a {} by {} strip isn't made of square slices", texture.width, texture.height));
+        }
+
+        let texture = match texture.format {
+            TexelFormat::Rgba8 => texture.clone(),
+            _ => texture.decompress(),
+        };
+        let texels = &texture.levels[0];
+        let entries = (0..size * size * size)
+            .map(|i| {
+                let (r, g, b) = (i % size, i / size % size, i / (size * size));
+                let texel = 4 * (g * texture.width + b * size + r) as usize;
+                [0, 1, 2].map(|c| texels[texel + c] as f32 / 255.0)
+            })
+            .collect();
+        Ok(Self {
+            name: name.to_string(),
+            size,
+            entries,
+        })
+    }
+
+    /// every .cube file and .ktx2 or .dds strip in `directory`, printing those that fail to load
+    pub fn load_directory(directory: impl AsRef<Path>) -> Vec<Lut> {
+        let Ok(files) = std::fs::read_dir(directory) else {
+            return vec![];
+        };
+        let mut paths: Vec<_> = files.filter_map(|file| file.ok()).map(|file| file.path()).collect();
+        paths.sort();
+
+        paths
+            .iter()
+            .filter_map(|path| {
+                let name = path.file_stem()?.to_string_lossy();
+                let lut = match path.extension()?.to_str()? {
+                    "cube" => std::fs::read_to_string(path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|text| Lut::from_cube(&name, &text)),
+                    "ktx2" | "dds" => std::fs::read(path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|bytes| TextureData::load(&bytes))
+                        .and_then(|texture| Lut::from_strip(&name, &texture)),
+                    _ => return None,
+                };
+                lut.map_err(|e| println!("skipping lut {}: {}", path.display(), e)).ok()
+            })
+            .collect()
+    }
+
+    fn upload(&self, device: &Device, queue: &Queue) -> TextureView {
+        let size = Extent3d {
+            width: self.size,
+            height: self.size,
+            depth_or_array_layers: self.size,
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(&self.name),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D3,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let texels: Vec<u8> = self.entries
+            .iter()
+            .flat_map(|&[r, g, b]| [r, g, b, 1.0].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+            .collect();
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &texels,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * self.size),
+                rows_per_image: Some(self.size),
+            },
+            size,
+        );
+        texture.create_view(&TextureViewDescriptor::default())
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LutParams {
+    size: f32,
+    strength: f32,
+    _padding: [f32; 2],
+}
+crate::layout::assert_uniform_size!(LutParams, 16);
+
+/// grades the colors as displayed with one of several luts, meant to run last in the chain
+pub struct ColorGrading {
+    /// 0 leaves the input as is, 1 fully graded
+    pub strength: f32,
+    /// name and size of each lut, in the order they were added
+    luts: Vec<(String, u32, TextureView)>,
+    selected: Option<usize>,
+    lut_sampler: Sampler,
+    params_buffer: GpuBuffer<LutParams>,
+    pass: FullscreenPass,
+    /// one per lut
+    bind_groups: Vec<BindGroup>,
+}
+
+impl ColorGrading {
+    pub fn new(device: &Device) -> Self {
+        let pass = FullscreenPass::new(
+            device,
+            "lut.wgsl",
+            &(include_str!("srgb.wgsl").to_string() + include_str!("lut.wgsl")),
+            "fs_main",
+            HDR_FORMAT,
+            post::input_layout("lut bind group layout")
+                .uniform(ShaderStages::FRAGMENT)
+                .texture_3d(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: true })
+                .sampler(ShaderStages::FRAGMENT, SamplerBindingType::Filtering),
+        );
+
+        Self {
+            strength: 1.0,
+            luts: vec![],
+            selected: None,
+            lut_sampler: device.create_sampler(&SamplerDescriptor {
+                label: Some("lut sampler"),
+                mag_filter: FilterMode::Linear,
+                min_filter: FilterMode::Linear,
+                ..Default::default()
+            }),
+            params_buffer: GpuBuffer::new_uniform(device, "lut params buffer"),
+            pass,
+            bind_groups: vec![],
+        }
+    }
+
+    /// luts added once the chain has bound it are only used after `PostChain::invalidate`
+    pub fn add(&mut self, device: &Device, queue: &Queue, lut: &Lut) {
+        self.luts.push((lut.name.clone(), lut.size, lut.upload(device, queue)));
+    }
+
+    /// the name of the lut `run` grades with
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.map(|i| self.luts[i].0.as_str())
+    }
+
+    /// selects the next lut, None after the last one, then the first one again
+    pub fn next(&mut self) -> Option<&str> {
+        self.selected = match self.selected {
+            None if !self.luts.is_empty() => Some(0),
+            Some(i) if i + 1 < self.luts.len() => Some(i + 1),
+            _ => None,
+        };
+        self.selected()
+    }
+}
+
+impl PostNode for ColorGrading {
+    fn name(&self) -> &'static str {
+        "color grading"
+    }
+
+    fn inputs(&self) -> &'static [PostInput] {
+        &[PostInput::Previous]
+    }
+
+    fn bind(&mut self, device: &Device, sampler: &Sampler, inputs: &[&TextureView]) {
+        self.bind_groups = self.luts
+            .iter()
+            .map(|(_, _, lut)| self.pass.binding.create_bind_group(device, "lut bind group", &[
+                BindingResource::TextureView(inputs[0]),
+                BindingResource::Sampler(sampler),
+                self.params_buffer.as_entire_binding(),
+                BindingResource::TextureView(lut),
+                BindingResource::Sampler(&self.lut_sampler),
+            ]))
+            .collect();
+    }
+
+    fn run(&mut self, queue: &Queue, encoder: &mut CommandEncoder, output: &TextureView) {
+        let selected = self.selected.unwrap_or(0);
+        self.params_buffer.write(queue, &[LutParams {
+            size: self.luts[selected].1 as f32,
+            strength: self.strength,
+            _padding: [0.0; 2],
+        }]);
+        self.pass.draw(encoder, "color grading pass", output, &self.bind_groups[selected]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cube_and_strip_luts_are_parsed() {
+        let cube = "# comment\nTITLE \"invert\"\nLUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\n\
+            1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        let lut = Lut::from_cube("file", cube).unwrap();
+        let invert = Lut::from_fn("invert", 2, |c| c.map(|v| 1.0 - v));
+        assert_eq!(lut.name, "invert");
+        assert_eq!(lut.entries, invert.entries);
+
+        assert!(Lut::from_cube("short", "LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        assert!(Lut::from_cube("domain", "LUT_3D_SIZE 2\nDOMAIN_MAX 2 2 2\n").is_err());
+
+        // two 2 by 2 slices of the inverting lut
+        let mut texels = vec![];
+        for g in 0..2 {
+            for b in 0..2 {
+                for r in 0..2 {
+                    texels.extend([r, g, b].map(|v| 255 - 255 * v as u8));
+                    texels.push(255);
+                }
+            }
+        }
+        let strip = TextureData {
+            width: 4,
+            height: 2,
+            format: TexelFormat::Rgba8,
+            srgb: false,
+            levels: vec![texels],
+        };
+        assert_eq!(Lut::from_strip("strip", &strip).unwrap().entries, invert.entries);
+    }
+}
@@ -0,0 +1,21 @@
+struct LutParams {
+    // entries along each axis
+    size: f32,
+    // 0 leaves the input as is, 1 fully graded
+    strength: f32,
+}
+
+@group(0) @binding(2) var<uniform> params: LutParams;
+@group(0) @binding(3) var lut: texture_3d<f32>;
+@group(0) @binding(4) var lut_sampler: sampler;
+
+@fragment
+fn fs_main(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(input, input_sampler, in.uv, 0.0);
+    // luts map the colors as displayed, which the surface clamps and encodes as srgb
+    let displayed = to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+    // through texel centers, so 0 and 1 land on the first and last entries
+    let uvw = (displayed * (params.size - 1.0) + 0.5) / params.size;
+    let graded = from_srgb(textureSampleLevel(lut, lut_sampler, uvw, 0.0).rgb);
+    return vec4<f32>(mix(color.rgb, graded, params.strength), color.a);
+}
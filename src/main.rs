@@ -1,19 +1,186 @@
-use std::{mem::size_of, f32::consts::TAU, cmp::Ordering};
 
-use bytemuck::{bytes_of};
 use wgpu::*;
 use winit::dpi::PhysicalSize;
-use math::{Vector3, BiVector3, Vector2, Scale2, Rotor};
+use math::{Vector3, BiVector3, Vector2, Scale2};
 
 use crate::math::Scale3;
-
-use {Extent3d, util::DeviceExt};
+use scene::{Instance, InstanceRaw};
+
+mod binding;
+mod buffer;
+mod bvh;
+mod camera_path;
+mod capture;
+mod console;
+mod depth_range;
+mod dof;
+mod debug_draw;
+mod draw_params;
+mod environment;
+mod floating_origin;
+mod fog;
+mod frames;
+mod gpu_timer;
+mod graph;
+mod indirect;
 mod input;
+mod layout;
+mod light_editor;
+mod lightmap;
+mod lut;
 mod math;
+mod mesh;
+mod mirror;
+mod mipmap;
+mod motion_blur;
+mod outline;
+mod picking;
+mod pipeline_cache;
+mod point_shadow;
 mod polygon;
+mod portal;
+mod post;
+mod preprocess;
+mod readback;
+mod rsm;
+mod scene;
+mod shader_check;
+mod shadow_cache;
+mod shadow_fit;
+mod shadow_throttle;
+mod stats;
+mod stereo;
+mod streaming;
+mod stress;
+mod sun;
+mod terrain;
+mod texture;
+mod throttle;
+mod traced_shadow;
+mod transform;
+mod vegetation;
+mod volumetric;
+mod window_mode;
+
+/// the shaders built through `preprocess`, `draw_params.wgsl` declaring `draw` for `draw_params`
+fn shader_preprocessor(draw_params: draw_params::DrawParamsMode) -> preprocess::Preprocessor {
+    let mut preprocessor = preprocess::Preprocessor::new();
+    preprocessor
+        .add("common.wgsl", include_str!("common.wgsl"))
+        .add("draw_params.wgsl", draw_params::wgsl_declaration(draw_params))
+        .add("light.wgsl", include_str!("light.wgsl"))
+        .add("shadow.wgsl", include_str!("shadow.wgsl"));
+    preprocessor
+}
+
+/// the scene's pipelines for one variant of `light.wgsl`
+struct LightPipelines {
+    /// by material and whether they draw the reflected scene, which is culled the other way
+    /// around and drawn only where the mirror is
+    pipelines: std::collections::HashMap<(u32, bool), RenderPipeline>,
+}
+
+impl LightPipelines {
+    fn new(device: &Device, layout: &PipelineLayout, light_shader: &ShaderModule) -> Self {
+        let create_light_pipeline = |
+            entry_point,
+            buffers,
+            cull: pipeline_cache::Cull,
+            stencil,
+        | device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Light Pipeline"),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: light_shader,
+                entry_point, // 1.
+                buffers, // 2.
+            },
+            fragment: Some(FragmentState { // 3.
+                module: light_shader,
+                entry_point: "fs_main",
+                targets: &[
+                    Some(ColorTargetState { // 4.
+                        format: post::HDR_FORMAT,
+                        blend: Some(BlendState::REPLACE),
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    Some(ColorTargetState {
+                        format: picking::ID_FORMAT,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    Some(ColorTargetState {
+                        format: motion_blur::MOTION_FORMAT,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                ],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList, // 1.
+                strip_index_format: None,
+                front_face: FrontFace::Ccw, // 2.
+                cull_mode: cull.face(),
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                polygon_mode: PolygonMode::Fill,
+                // Requires Features::DEPTH_CLIP_CONTROL
+                unclipped_depth: false,
+                // Requires Features::CONSERVATIVE_RASTERIZATION
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: SCENE_DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Greater, // 1.
+                stencil,
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None, // 5.
+        });
+        let mirror_stencil = StencilState {
+            front: pipeline_cache::StencilKey::equal().face,
+            back: pipeline_cache::StencilKey::equal().face,
+            read_mask: !0,
+            write_mask: 0,
+        };
+
+        let mut pipelines = std::collections::HashMap::new();
+        for material in [
+            draw_params::MATERIAL_LIT,
+            draw_params::MATERIAL_UNLIT,
+            draw_params::MATERIAL_TWO_SIDED,
+            draw_params::MATERIAL_TERRAIN,
+        ] {
+            let (entry_point, buffers): (_, &[VertexBufferLayout]) = match material {
+                draw_params::MATERIAL_TERRAIN => ("vs_terrain", &[terrain::TERRAIN_VERTEX_LAYOUT]),
+                _ => ("vs_main", &[VERTEX_LAYOUT, INSTANCE_LAYOUT, PREVIOUS_INSTANCE_LAYOUT]),
+            };
+            let cull = draw_params::cull(material);
+            pipelines.insert(
+                (material, false),
+                create_light_pipeline(entry_point, buffers, cull, StencilState::default()),
+            );
+            pipelines.insert(
+                (material, true),
+                create_light_pipeline(entry_point, buffers, cull.mirrored(), mirror_stencil.clone()),
+            );
+        }
+
+        Self { pipelines }
+    }
+
+    fn get(&self, material: u32, reflected: bool) -> &RenderPipeline {
+        &self.pipelines[&(material, reflected)]
+    }
+}
 
 fn main() {
     // env_logger::init();
+    if let Err(e) = shader_check::validate_all() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
     pollster::block_on(run());
 }
 
@@ -23,6 +190,64 @@ struct Vertex {
     position: [f32; 3],
 }
 
+/// corner `0bxyz` at +0.5 along the axes whose bit is set, -0.5 along the others
+const CUBE_VERTICES: [Vertex; 8] = [
+    Vertex { position: [-0.5, -0.5, -0.5] },
+    Vertex { position: [-0.5, -0.5, 0.5] },
+    Vertex { position: [-0.5, 0.5, -0.5] },
+    Vertex { position: [-0.5, 0.5, 0.5] },
+    Vertex { position: [0.5, -0.5, -0.5] },
+    Vertex { position: [0.5, -0.5, 0.5] },
+    Vertex { position: [0.5, 0.5, -0.5] },
+    Vertex { position: [0.5, 0.5, 0.5] },
+];
+/// wound so the cross product of each triangle's edges points into the mesh
+#[allow(clippy::identity_op)]
+const CUBE_INDICES: [u32; 36] = [
+    0b000, 0b100, 0b010,
+    0b110, 0b010, 0b100,
+
+    0b000, 0b010, 0b001,
+    0b011, 0b001, 0b010,
+
+    0b000, 0b001, 0b100,
+    0b101, 0b100, 0b001,
+
+    0b110 ^ 0b111, 0b100 ^ 0b111, 0b010 ^ 0b111,
+    0b000 ^ 0b111, 0b010 ^ 0b111, 0b100 ^ 0b111,
+
+    0b011 ^ 0b111, 0b010 ^ 0b111, 0b001 ^ 0b111,
+    0b000 ^ 0b111, 0b001 ^ 0b111, 0b010 ^ 0b111,
+
+    0b101 ^ 0b111, 0b001 ^ 0b111, 0b100 ^ 0b111,
+    0b000 ^ 0b111, 0b100 ^ 0b111, 0b001 ^ 0b111,
+];
+/// the cube's bottom, back, and its corners along the top back edge
+const WEDGE_VERTICES: [Vertex; 6] = [
+    Vertex { position: [-0.5, -0.5, -0.5] },
+    Vertex { position: [0.5, -0.5, -0.5] },
+    Vertex { position: [-0.5, -0.5, 0.5] },
+    Vertex { position: [0.5, -0.5, 0.5] },
+    Vertex { position: [-0.5, 0.5, -0.5] },
+    Vertex { position: [0.5, 0.5, -0.5] },
+];
+/// wound like the cube
+const WEDGE_INDICES: [u32; 24] = [
+    // bottom
+    0, 2, 1,
+    1, 2, 3,
+    // back
+    0, 1, 4,
+    5, 4, 1,
+    // sides
+    0, 4, 2,
+    1, 3, 5,
+    // slope
+    2, 4, 3,
+    3, 4, 5,
+];
+
+#[derive(Clone)]
 struct Camera {
     translation: Vector3,
     // vector rotated along xz plane from the z-axis by z_to_x
@@ -39,17 +264,45 @@ struct Camera {
     height: f32,
 }
 
-struct Instance {
-    translation: Vector3,
-    rotation: math::Rotor,
-    scale: math::Scale3,
-}
-
 struct Light {
     translation: Vector3,
+    /// angle from local-z to local-x axis, like the camera
+    z_to_x: f32,
+    /// angle from local-xz plane to local-y
+    xz_to_y: f32,
     near_z: f32,
     width: f32,
     height: f32,
+    color: math::Color,
+    intensity: f32,
+    /// offsets the shadow lookup along the receiver's normal by `NORMAL_OFFSET_TEXELS`
+    normal_offset_bias: bool,
+    /// biases the depth compare by the slope of the receiver within a texel
+    receiver_plane_bias: bool,
+    shadow_filter: ShadowFilter,
+    /// world space radius of the soft shadow kernel, the largest one for `ShadowFilter::Pcss`
+    shadow_radius: f32,
+}
+
+/// how `light.wgsl` filters the shadow map, the values of its `SHADOW_FILTER_` constants
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ShadowFilter {
+    Hard = 0,
+    /// rotated poisson disk of `Light::shadow_radius`
+    Poisson = 1,
+    /// percentage closer soft shadows, treating the light as an area of `Light::width`
+    /// by `Light::height` so shadows soften with the distance from their caster
+    Pcss = 2,
+}
+
+impl ShadowFilter {
+    const ALL: [ShadowFilter; 3] = [Self::Hard, Self::Poisson, Self::Pcss];
+
+    /// wraps around
+    fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&f| f == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
 }
 
 #[repr(C)]
@@ -58,29 +311,71 @@ struct LightRaw {
     view: math::Affine3,
     near_z: f32,
     _padding: [u32; 3],
+    color: [f32; 3],
+    intensity: f32,
+    normal_offset: f32,
+    receiver_plane_bias: u32,
+    shadow_filter: u32,
+    shadow_radius: f32,
+    size: [f32; 2],
+    _padding1: [u32; 2],
+    ambient: [f32; 3],
+    _padding2: u32,
 }
+layout::assert_uniform_size!(LightRaw, 128);
 
 impl Light {
+    #[allow(const_item_mutation)]
     pub fn compute_view(&self) -> math::Affine3 {
+        let forward = Vector3::new(self.z_to_x.sin(), 0.0, self.z_to_x.cos());
+        let plane = forward.wedge(&Vector3::new(0.0, 1.0, 0.0));
         *math::Affine3::IDENTITY
             .translate(&(-self.translation))
+            .rotate(-self.xz_to_y, &plane)
+            .rotate(-self.z_to_x, &BiVector3::new(0.0, 0.0, 1.0))
+    }
+
+    /// the light's local-z axis in world space
+    fn direction(&self) -> Vector3 {
+        let (sin_x, cos_x) = self.z_to_x.sin_cos();
+        let (sin_y, cos_y) = self.xz_to_y.sin_cos();
+        Vector3::new(sin_x * cos_y, sin_y, cos_x * cos_y)
     }
 
-    fn into_raw(&self, view: &math::Affine3) -> LightRaw {
+    /// places the light `distance` away from `focus`, shining along the unit `direction`
+    fn point_along(&mut self, direction: Vector3, focus: Vector3, distance: f32) {
+        self.translation = focus - direction * distance;
+        self.look_at(focus);
+    }
+
+    /// turns the light to shine at `target`, which must not be at its translation
+    fn look_at(&mut self, target: Vector3) {
+        (self.z_to_x, self.xz_to_y) = angles_towards((target - self.translation).normalized());
+    }
+
+    /// `ambient` lights every surface the light does, shadowed or not
+    fn to_raw(&self, view: &math::Affine3, ambient: [f32; 3]) -> LightRaw {
         LightRaw {
             view: *view,
             near_z: self.near_z,
             _padding: Default::default(),
+            color: self.color.to_rgb_array(),
+            intensity: self.intensity,
+            normal_offset: if self.normal_offset_bias { NORMAL_OFFSET_TEXELS } else { 0.0 },
+            receiver_plane_bias: self.receiver_plane_bias as u32,
+            shadow_filter: self.shadow_filter as u32,
+            shadow_radius: self.shadow_radius,
+            size: [self.width, self.height],
+            _padding1: Default::default(),
+            ambient,
+            _padding2: 0,
         }
     }
 }
 
-impl Instance {
-    fn to_raw(&self) -> InstanceRaw {
-        InstanceRaw {
-            affine: math::Affine3::from(self.scale, self.rotation, self.translation)
-        }
-    }
+/// `z_to_x` and `xz_to_y` turning local-z to the unit `direction`
+fn angles_towards(direction: Vector3) -> (f32, f32) {
+    (direction.x.atan2(direction.z), direction.y.clamp(-1.0, 1.0).asin())
 }
 
 impl Camera {
@@ -88,6 +383,66 @@ impl Camera {
         self.forward.z = self.z_to_x.cos();
         self.forward.x = self.z_to_x.sin();
     }
+
+    /// turns the camera to face `target`, which must not be at its translation
+    fn look_at(&mut self, target: Vector3) {
+        (self.z_to_x, self.xz_to_y) = angles_towards((target - self.translation).normalized());
+        self.update_forward();
+    }
+
+    /// unit vector the camera looks along, as `angles_towards` takes it
+    fn direction(&self) -> Vector3 {
+        Vector3::new(
+            self.xz_to_y.cos() * self.z_to_x.sin(),
+            self.xz_to_y.sin(),
+            self.xz_to_y.cos() * self.z_to_x.cos(),
+        )
+    }
+
+    /// swings the camera around `pivot` by the angles, facing it from the same distance.
+    /// `pivot` must not be at its translation
+    fn orbit(&mut self, pivot: Vector3, z_to_x: f32, xz_to_y: f32) {
+        let distance = (pivot - self.translation).length();
+        self.look_at(pivot);
+        self.z_to_x += z_to_x;
+        // short of straight up or down, where the turn about y would be lost
+        self.xz_to_y = (self.xz_to_y + xz_to_y).clamp(-1.5, 1.5);
+        self.update_forward();
+        self.translation = pivot - self.direction() * distance;
+    }
+
+    /// moves towards `goal`'s translation and angles by `math::damp`, turning the shorter way
+    fn damp_towards(&mut self, goal: &Camera, lambda: f32, dt: f32) {
+        self.translation = math::damp(self.translation, goal.translation, lambda, dt);
+        self.z_to_x = math::damp_angle(self.z_to_x, goal.z_to_x, lambda, dt).rem_euclid(std::f32::consts::TAU);
+        self.xz_to_y = math::damp(self.xz_to_y, goal.xz_to_y, lambda, dt);
+        self.update_forward();
+    }
+
+    /// same orientation as `compute_model`, chained rotations compose right to left as rotors
+    fn compute_rotation(&self) -> math::Rotor {
+        let plane = self.forward.wedge(&Vector3::new(0.0, 1.0, 0.0));
+        *((plane * (self.xz_to_y / 2.0)).exp() * (BiVector3::new(0.0, 0.0, 1.0) * (self.z_to_x / 2.0)).exp())
+            .renormalize()
+    }
+
+    /// whether damping towards `goal` has come close enough to stop redrawing for it
+    fn is_settled_at(&self, goal: &Camera) -> bool {
+        use std::f32::consts::{PI, TAU};
+        const EPSILON: f32 = 1e-4;
+        (self.translation - goal.translation).length() < EPSILON
+            && ((goal.z_to_x - self.z_to_x + PI).rem_euclid(TAU) - PI).abs() < EPSILON
+            && (self.xz_to_y - goal.xz_to_y).abs() < EPSILON
+    }
+
+    /// turns the camera to `rotation`'s view direction, dropping any roll about it
+    fn set_rotation(&mut self, rotation: math::Rotor) {
+        let model = math::Affine3::from(Scale3::IDENTITY, rotation, Vector3::IDENTITY);
+        (self.z_to_x, self.xz_to_y) = angles_towards(Vector3::new(0.0, 0.0, 1.0).apply(&model));
+        self.update_forward();
+    }
+
+    #[allow(const_item_mutation)]
     fn compute_model(&self) -> math::Affine3 {
         let plane = self.forward.wedge(&Vector3::new(0.0, 1.0, 0.0));
         *math::Affine3::IDENTITY
@@ -96,6 +451,7 @@ impl Camera {
             .translate(&self.translation)
     }
 
+    #[allow(const_item_mutation)]
     fn to_raw(&self) -> CameraRaw {
         let plane = self.forward.wedge(&Vector3::new(0.0, 1.0, 0.0));
 
@@ -106,60 +462,132 @@ impl Camera {
                 .rotate(-self.z_to_x, &BiVector3::new(0.0, 0.0, 1.0))
                 .scale(&Scale3::new(2.0 * self.near_z / self.width, 2.0 * self.near_z / self.height, 1.0)),
             near_z: self.near_z,
+            traced_shadow_mask: 0,
             _padding: Default::default(),
+            // clips nothing
+            clip_plane: [0.0, 0.0, 0.0, 1.0],
+            previous_view: math::Affine3::IDENTITY,
         }
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
-struct InstanceRaw {
-    affine: math::Affine3,
-}
-
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 struct CameraRaw {
     view: math::Affine3,
     near_z: f32,
-    // projection plane size
-    _padding: [u32; 3],
+    /// 1 for the view the traced shadow mask was traced from, as the light shader reads it
+    traced_shadow_mask: u32,
+    _padding: [u32; 2],
+    /// world space plane, what's on its negative side is not rendered
+    clip_plane: [f32; 4],
+    /// `view` of the frame before, which the instances' own motion is measured in
+    previous_view: math::Affine3,
 }
+layout::assert_uniform_size!(CameraRaw, 128);
 
 const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
-const INSTANCE_LAYOUT: VertexBufferLayout = VertexBufferLayout {
-    array_stride: size_of::<InstanceRaw>() as BufferAddress,
-    step_mode: VertexStepMode::Instance,
-    attributes: &vertex_attr_array![
-        5 => Float32x4,
-        6 => Float32x4,
-        7 => Float32x4,
-    ],
-};
-const VERTEX_LAYOUT: VertexBufferLayout = VertexBufferLayout {
-    array_stride: size_of::<Vertex>() as BufferAddress,
-    step_mode: VertexStepMode::Vertex,
-    attributes: &vertex_attr_array![
-        0 => Float32x3,
-    ],
-};
-
-fn compute_depth_divs(width: f32, height: f32, near: f32, far: f32, divs: &mut [f32]) {
+/// cameras of the views rendering the scene in a frame: the camera itself, the mirror's
+/// reflection and the portal's levels, or the camera and the stereo eyes
+const MAX_VIEWS: usize = 2 + portal::MAX_DEPTH;
+/// frames recorded before waiting on the gpu, each with its own camera ring and light bind group
+const MAX_FRAMES_IN_FLIGHT: usize = 2;
+/// of the light pass, with the stencil the mirror is masked with.
+/// Depth32Float is more precise, but leaves the mirror disabled
+const SCENE_DEPTH_FORMAT: TextureFormat = TextureFormat::Depth24PlusStencil8;
+/// how far the light frustum gizmo extends
+const LIGHT_GIZMO_FAR_Z: f32 = 20.0;
+/// what the sun light is pointed at, and from how far
+const SUN_FOCUS: Vector3 = Vector3 { x: 0.0, y: 0.0, z: 5.0 };
+const SUN_DISTANCE: f32 = 100.0;
+/// side of the streamed world's chunks, the floating origin moves by whole chunks
+const WORLD_CHUNK_SIZE: f32 = 16.0;
+
+/// what the light pass draws, shared by every view of the scene
+struct SceneDraw<'a> {
+    meshes: &'a [mesh::Mesh],
+    instances: BufferSlice<'a>,
+    previous_instances: BufferSlice<'a>,
+    draws: &'a indirect::SceneDraws,
+    draw_params: &'a draw_params::DrawParamsSet,
+    scene_draw: draw_params::DrawSlot,
+    terrain_draw: draw_params::DrawSlot,
+    /// None while the world is streamed
+    terrain: Option<&'a terrain::Terrain>,
+    streamed_terrain: &'a [&'a terrain::Chunk],
+    bind_group: &'a BindGroup,
+}
+
+impl<'a> SceneDraw<'a> {
+    /// `camera` is the view's offset into the camera ring, `frustum` its camera's for culling
+    fn draw(
+        &self,
+        pass: &mut RenderPass<'a>,
+        pipelines: &'a LightPipelines,
+        reflected: bool,
+        camera: u32,
+        frustum: &math::Frustum,
+    ) {
+        pass.set_pipeline(pipelines.get(self.scene_draw.material(), reflected));
+        pass.set_bind_group(0, self.bind_group, &[camera]);
+
+        pass.set_vertex_buffer(1, self.instances);
+        pass.set_vertex_buffer(2, self.previous_instances);
+        self.draw_params.bind(pass, self.scene_draw);
+        self.draws.draw(pass, self.meshes, |_| true);
+
+        pass.set_pipeline(pipelines.get(self.terrain_draw.material(), reflected));
+        self.draw_params.bind(pass, self.terrain_draw);
+        if let Some(terrain) = self.terrain {
+            terrain.draw(pass, |chunk| frustum.intersects_aabb(&chunk.bounds));
+        }
+        for chunk in self.streamed_terrain {
+            if frustum.intersects_aabb(&chunk.bounds) {
+                chunk.draw(pass);
+            }
+        }
+    }
+}
+
+/// how far the shadow lookup is moved along the receiver's normal, in shadow map texels
+const NORMAL_OFFSET_TEXELS: f32 = 1.5;
+/// the model as its three rows, then the flags and the lightmap tile
+const INSTANCE_LAYOUT: VertexBufferLayout = layout::vertex_layout!(InstanceRaw, Instance, 5, [
+    affine.xx => Float32x4,
+    affine.xy => Float32x4,
+    affine.xz => Float32x4,
+    flags => Uint32,
+    lightmap => Float32x3,
+]);
+/// the model of the frame before, for the light pass' `vs_main` next to `INSTANCE_LAYOUT`
+const PREVIOUS_INSTANCE_LAYOUT: VertexBufferLayout = layout::vertex_layout!(InstanceRaw, Instance, 10, [
+    affine.xx => Float32x4,
+    affine.xy => Float32x4,
+    affine.xz => Float32x4,
+]);
+const VERTEX_LAYOUT: VertexBufferLayout = layout::vertex_layout!(lightmap::LightmappedVertex, Vertex, 0, [
+    position => Float32x3,
+    uv2 => Float32x2,
+]);
+
+#[allow(dead_code)]
+fn compute_depth_divs(_width: f32, _height: f32, _near: f32, _far: f32, _divs: &mut [f32]) {
     
 }
 
+#[allow(dead_code, clippy::too_many_arguments)]
 fn compute_fits(
     camera_model: &math::Affine3,
-    camera_near_z: f32,
-    camera_far_z: f32,
+    _camera_near_z: f32,
+    _camera_far_z: f32,
     camera_width: f32,
     camera_height: f32,
     light_view: &math::Affine3,
     light_near_z: f32,
-    light_width: f32,
-    light_height: f32,
-    max_fits: usize,
-    out_fits: &mut [(Vector2, Scale2)],
+    _light_width: f32,
+    _light_height: f32,
+    _max_fits: usize,
+    _out_fits: &mut [math::Affine2],
 ) -> usize {
     let transform = camera_model.compose(light_view);
 
@@ -194,14 +622,11 @@ fn compute_fits(
 
         // origin.z + z * ray.z = near_z
         // t = (near_z - origin.z) / ray.z
-        for ray_i in 0..4 {
+        for ray in camera_rays.iter() {
             // handle div by 0.0
-            let z = (light_near_z - camera_origin.z) / camera_rays[ray_i].z;
+            let z = (light_near_z - camera_origin.z) / ray.z;
             if z > 0.0 {
-                intersects[intersect_len] = (z, Vector2::new(
-                    camera_origin.x + z * camera_rays[ray_i].x,
-                    camera_origin.y + z * camera_rays[ray_i].y,
-                ));
+                intersects[intersect_len] = (z, (camera_origin + *ray * z).xy());
                 intersect_len += 1;
             }
         }
@@ -225,103 +650,221 @@ fn compute_fits(
 /// cuts camera view volume and light view plane,
 /// projects cut volume onto light view plane,
 /// intersects projection with light view frame.
+/// the fit takes the intersection, on the light plane, to the whole light view frame
+#[allow(dead_code)]
 fn compute_camera_fit_on_light_plane(
-    camera_model: &math::Affine3,
-    camera_far_z: f32,
-    camera_near_z: f32,
-    camera_width: f32,
-    camera_height: f32,
+    camera_frustum: &math::Frustum,
     light_view: &math::Affine3,
     light_near_z: f32,
     light_width: f32,
     light_height: f32,
-) -> Option<(Vector2, Scale2)> {
-    let near_right = camera_width / 2.0;
-    let near_top = camera_height / 2.0;
-    let near_left = -near_right;
-    let near_bottom = -near_top;
-
-    let factor = camera_far_z / camera_near_z;
-    let far_right = near_right * factor;
-    let far_bottom = near_bottom * factor;
-    let far_left = -far_right;
-    let far_top = -far_bottom;    
-
-    // camera view volume corners
-    let mut corners = [
-        Vector3::new(near_left, near_bottom, camera_near_z),
-        Vector3::new(near_right, near_bottom, camera_near_z),
-        Vector3::new(near_left, near_top, camera_near_z),
-        Vector3::new(near_right,  near_top, camera_near_z),
-        Vector3::new(far_left, far_bottom, camera_far_z),
-        Vector3::new(far_right, far_bottom, camera_far_z),
-        Vector3::new(far_left, far_top, camera_far_z),
-        Vector3::new(far_right, far_top, camera_far_z),
-    ];
-    
-    let affine = camera_model.compose(light_view);
-    for corner in corners.iter_mut() {
-        *corner = corner.apply(&affine);
-    }
+) -> Option<math::Affine2> {
+    compute_camera_rect_on_light_plane(camera_frustum, light_view, light_near_z, light_width, light_height)
+        .map(|rect| fit_to_rect(&rect, light_width, light_height))
+}
+
+/// the light view frame centered on the light plane
+fn light_rect(light_width: f32, light_height: f32) -> polygon::Rect {
+    let corner = Vector2::new(light_width / 2.0, light_height / 2.0);
+    polygon::Rect { max: corner, min: -corner }
+}
+
+/// takes `rect`, on the light plane, to the whole light view frame
+fn fit_to_rect(rect: &polygon::Rect, light_width: f32, light_height: f32) -> math::Affine2 {
+    let mut fit = math::Affine2::IDENTITY;
+    *fit.translate(&-rect.min)
+        .scale(&(Scale2::new(light_width, light_height) * Scale2::new(rect.width(), rect.height()).inverse()))
+        .translate(&light_rect(light_width, light_height).min)
+}
+
+/// the rect of the light view frame `compute_camera_fit_on_light_plane` fits to
+#[profiling::function]
+fn compute_camera_rect_on_light_plane(
+    camera_frustum: &math::Frustum,
+    light_view: &math::Affine3,
+    light_near_z: f32,
+    light_width: f32,
+    light_height: f32,
+) -> Option<polygon::Rect> {
+    // camera view volume corners, in light view space.
+    // transformed in double precision, the light can be far from the corners
+    let light_view = math::DAffine3::from(*light_view);
+    let corners = camera_frustum
+        .corners()
+        .expect("the camera's frustum is cut at its far plane")
+        .map(|corner| math::DVector3::from(corner).apply(&light_view).to_f32());
+
+    // facing away from the light, in light view space
+    let light_near_plane = math::Plane::new(Vector3::new(0.0, 0.0, 1.0), -light_near_z);
 
     /// maximum amount of projected cut camera view volume corners
     const MAX_CORNERS: usize = 10;
-    let mut cut_corners = [Vector2::IDENTITY; MAX_CORNERS];
-    let mut cut_corners_len = 0;
+    let mut cut_corners = polygon::Polygon::<MAX_CORNERS>::new();
     for i in 0..corners.len() {
         let corner = corners[i];
 
-        if corner.z < light_near_z {
-            println!("AAA");
+        if light_near_plane.signed_distance(corner) < 0.0 {
             let mut axis_mask = 0b100;
             while axis_mask != 0b000 {
                 let other_corner = corners[i ^ axis_mask];
-                if other_corner.z > light_near_z {
-                    let t = (light_near_z - corner.z) / (other_corner.z - corner.z);
-                    cut_corners[cut_corners_len] = Vector2::new(
-                        (other_corner.x - corner.x) * t + corner.x, 
-                        (other_corner.y - corner.y) * t + corner.y, 
-                    );
-                    cut_corners_len += 1;
+                if light_near_plane.signed_distance(other_corner) > 0.0 {
+                    let cut = light_near_plane.intersect_segment(corner, other_corner).unwrap();
+                    cut_corners.push(cut.xy());
                 }
                 axis_mask >>= 1;
             }
         } else {
-            cut_corners[cut_corners_len] = Vector2::new(
-                corner.x,  
-                corner.y,
-            ) * (light_near_z / corner.z);
-            cut_corners_len += 1;
+            cut_corners.push(corner.xy() * (light_near_z / corner.z));
         }
     }
-    if cut_corners_len == 0 {
+    if cut_corners.is_empty() {
         return None;
     }
 
-    use polygon::Rect;
+    // rect of projected camera view volume
+    let camera_rect = polygon::Rect::from_points(cut_corners.points());
+    camera_rect.intersect(&light_rect(light_width, light_height))
+}
+
+/// texels of a `width` by `height` shadow map covered by `fit`, as returned by
+/// `compute_camera_fit_on_light_plane` for the unfitted light view.
+/// returns x, y, width, height, with y down as the shadow map is sampled.
+fn fit_to_shadow_scissor(
+    fit: &math::Affine2,
+    light_width: f32,
+    light_height: f32,
+    width: u32,
+    height: u32,
+) -> (u32, u32, u32, u32) {
+    // the fitted rect on the light plane
+    let polygon::Rect { min, max } = light_rect(light_width, light_height).transform(&fit.inverse());
+    let (u_min, u_max) = (0.5 + min.x / light_width, 0.5 + max.x / light_width);
+    let (v_min, v_max) = (0.5 - max.y / light_height, 0.5 - min.y / light_height);
+
+    let to_texels = |min: f32, max: f32, size: u32| {
+        let min = ((min * size as f32).floor().max(0.0) as u32).min(size);
+        let max = ((max * size as f32).ceil().max(0.0) as u32).min(size);
+        (min, max.saturating_sub(min))
+    };
+    let (x, w) = to_texels(u_min, u_max, width);
+    let (y, h) = to_texels(v_min, v_max, height);
+    (x, y, w, h)
+}
+
+/// height of the streamed world, hills like the fixed terrain's but without an edge
+fn world_height(x: f32, z: f32) -> f32 {
+    -6.0 + 3.0 * math::noise::Fbm::default().warped2(Vector2::new(x, z) * (1.0 / 24.0), 0.8)
+}
+
+/// respawns it, reporting how long that took
+fn respawn_stress_test(stress_test: &mut stress::StressTest, scene: &mut scene::Scene) {
+    let start = std::time::Instant::now();
+    stress_test.respawn(scene);
+    println!(
+        "stress test: {:?}, {} instances spawned in {:.1}ms",
+        stress_test.layout,
+        stress_test.spawned(),
+        start.elapsed().as_secs_f64() * 1000.0,
+    );
+}
 
-    let light_right = light_width / 2.0;
-    let light_top = light_height / 2.0;
-    let light_rect = Rect {
-        max:  Vector2::new(light_right, light_top),
-        min: -Vector2::new(light_right, light_top),
+/// bakes the static instances' lighting into `lightmap` and lights them by it, reporting how long
+/// that took
+fn bake_lightmap(
+    scene: &mut scene::Scene,
+    meshes: &[lightmap::UnwrappedMesh],
+    terrain_triangles: Vec<[Vector3; 3]>,
+    light: &lightmap::BakeLight,
+    lightmap: &lightmap::Lightmap,
+    queue: &Queue,
+) {
+    let start = std::time::Instant::now();
+    let handles = scene.static_handles();
+    let instances: Vec<&Instance> = handles.iter().map(|&handle| scene.get(handle).unwrap()).collect();
+    let triangles = |instances: &[&Instance]| -> Vec<[Vector3; 3]> {
+        instances
+            .iter()
+            .flat_map(|instance| meshes[instance.mesh.0 as usize].world_triangles(instance))
+            .chain(terrain_triangles.iter().copied())
+            .collect()
     };
+    let casting: Vec<&Instance> = instances.iter().copied().filter(|instance| instance.casts_shadow).collect();
+    let casters = bvh::Bvh::build(&triangles(&casting));
+    let surfaces = bvh::Bvh::build(&triangles(&instances));
+
+    match lightmap::bake(meshes, &instances, &casters, &surfaces, light) {
+        Ok(bake) => {
+            lightmap.upload(queue, &bake);
+            for (&handle, tile) in handles.iter().zip(bake.tiles) {
+                scene.get_mut(handle).unwrap().lightmap = Some(tile);
+            }
+            println!(
+                "lightmap: {} instances baked in {:.1}s",
+                handles.len(),
+                start.elapsed().as_secs_f64(),
+            );
+        }
+        Err(e) => println!("{}", e),
+    }
+}
 
-    // rect of projected camera view volume
-    let camera_rect = Rect::from_points(&cut_corners[..cut_corners_len]);
-    if let Some(rect) = camera_rect.intersect(&light_rect) {
-        Some((
-            -rect.min,
-            Scale2::new(light_width / rect.width(), light_height / rect.height()),
-        ))
-    } else {
-        None
+/// a tile of `world_height` with a few boulders scattered on it, called on a worker thread
+fn load_world_chunk(coord: streaming::ChunkCoord, size: f32) -> streaming::ChunkData {
+    const QUADS: usize = 16;
+    let spacing = size / QUADS as f32;
+    let (origin_x, origin_z) = (coord.x as f32 * size, coord.z as f32 * size);
+
+    // the heights are sampled at absolute positions, the chunk is placed relative to its corner
+    let terrain = terrain::ChunkGeometry::grid(QUADS, QUADS, |x, z| {
+        let (local_x, local_z) = (x as f32 * spacing, z as f32 * spacing);
+        let (x, z) = (origin_x + local_x, origin_z + local_z);
+        // central differences, so neighbouring chunks agree on their shared edge
+        let e = 0.5 * spacing;
+        let normal = Vector3::new(
+            world_height(x - e, z) - world_height(x + e, z),
+            2.0 * e,
+            world_height(x, z - e) - world_height(x, z + e),
+        );
+        (Vector3::new(local_x, world_height(x, z), local_z), normal.normalized())
+    });
+
+    // the same boulders every time the chunk is loaded
+    let seed = math::noise::hash((coord.x as u32).wrapping_add(math::noise::hash(coord.z as u32)));
+    let random = |i: u32| math::noise::hash(seed.wrapping_add(i)) as f32 / u32::MAX as f32;
+    let instances = (0..seed % 4)
+        .map(|i| {
+            let (local_x, local_z) = (random(4 * i) * size, random(4 * i + 1) * size);
+            let height = world_height(origin_x + local_x, origin_z + local_z);
+            let s = 0.5 + 1.5 * random(4 * i + 2);
+            Instance {
+                mesh: scene::MeshId::CUBE,
+                translation: Vector3::new(local_x, height + 0.3 * s, local_z),
+                rotation: BiVector3::new(0.0, 0.0, 3.0 * random(4 * i + 3)).exp(),
+                scale: Scale3::new(s, 0.6 * s, s),
+                casts_shadow: true,
+                receives_shadow: true,
+                lightmap: None,
+            }
+        })
+        .collect();
+
+    streaming::ChunkData {
+        instances,
+        terrain: Some(terrain),
     }
 }
 
 async fn run() {
     use winit::*;
 
+    #[cfg(feature = "tracy")]
+    profiling::tracy_client::Client::start();
+    #[cfg(feature = "puffin")]
+    let _puffin_server = {
+        profiling::puffin::set_scopes_on(true);
+        puffin_http::Server::new("127.0.0.1:8585").unwrap()
+    };
+
     let event_loop = event_loop::EventLoop::new();
     let window = window::Window::new(&event_loop).unwrap();
     window.set_inner_size(PhysicalSize::new(1000, 1000));
@@ -331,12 +874,25 @@ async fn run() {
     let surface = unsafe { instance.create_surface(&window) }.unwrap();
     let adapter = instance.request_adapter(&RequestAdapterOptions::default()).await.unwrap();
 
-    let (device, queue) = adapter.request_device(&DeviceDescriptor::default(), None).await.unwrap();
-    device.limits().min_storage_buffer_offset_alignment;
+    let (mut features, limits) = draw_params::DrawParamsSet::required_features(&adapter);
+    // lets shadow casters behind the light's near plane be clamped to it instead of clipped
+    features |= adapter.features() & Features::DEPTH_CLIP_CONTROL;
+    // bcn textures are decompressed on the cpu without it
+    features |= adapter.features() & Features::TEXTURE_COMPRESSION_BC;
+    // times the passes on the gpu
+    features |= adapter.features() & Features::TIMESTAMP_QUERY;
+    // renders both hemispheres of a point light's shadow in one pass
+    features |= adapter.features() & Features::MULTIVIEW;
+    // draws the scene's instances from an argument buffer, batched per mesh with both
+    features |= adapter.features() & (Features::INDIRECT_FIRST_INSTANCE | Features::MULTI_DRAW_INDIRECT);
+    let (device, queue) = adapter.request_device(&DeviceDescriptor {
+        label: None,
+        features,
+        limits,
+    }, None).await.unwrap();
     let surface_caps = surface.get_capabilities(&adapter);
-    // Shader code in this tutorial assumes an sRGB surface texture. Using a different
-    // one will result all the colors coming out darker. If you want to support non
-    // sRGB surfaces, you'll need to account for that when drawing to the frame.
+    // colors are linear (`math::Color`) all the way to the surface, which has to be srgb to
+    // encode them for display. on other formats everything comes out darker
     let surface_format = surface_caps.formats.iter()
         .copied()
         .find(|f| f.is_srgb())            
@@ -353,88 +909,107 @@ async fn run() {
     };
     surface.configure(&device, &config);
 
-    let (mut depth_texture, mut depth_texture_view) = create_depth_texture(&device, size.width, size.height);
-    
-    let light_bind_group_layout =
-    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-        entries: &[
-            BindGroupLayoutEntry { // camera bind group
-                binding: 0,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-            BindGroupLayoutEntry { // light bind group
-                binding: 1,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-            BindGroupLayoutEntry { // shadow map bind group
-                binding: 2,
-                visibility: ShaderStages::FRAGMENT,
-                ty: BindingType::Texture {
-                    sample_type: TextureSampleType::Depth,
-                    view_dimension: TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            },
-            BindGroupLayoutEntry { // shadow sampler bind group
-                binding: 3,
-                visibility: ShaderStages::FRAGMENT,
-                ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                count: None,
-            },
-        ],
-        label: Some("light bind group layout"),
+    let mut picking = picking::Picking::new(size.width, size.height);
+
+    let mut render_graph = graph::RenderGraph::new(size.width, size.height);
+    let instances_resource = render_graph.import("instances");
+    let shadow_map_resource = render_graph.import("shadow map");
+    let static_shadow_map_resource = render_graph.import("static shadow map");
+    let point_shadow_map_resource = render_graph.import("point shadow map");
+    let rsm_resource = render_graph.import("reflective shadow map");
+    let surface_resource = render_graph.import_output("surface");
+    let scene_color_resource = render_graph.create(graph::TextureDesc {
+        label: "scene color",
+        format: post::HDR_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
     });
-
-    let shadow_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-        entries: &[
-            BindGroupLayoutEntry { // light bind group
-                binding: 0,
-                visibility: ShaderStages::VERTEX,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-        ],
-        label: Some("shadow bind group layout"),
+    let scene_depth_resource = render_graph.create(graph::TextureDesc {
+        label: "scene depth",
+        format: SCENE_DEPTH_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
     });
-
-    let light_shader = device.create_shader_module(ShaderModuleDescriptor {
-        label: Some("Lighting Shader"),
-        source: ShaderSource::Wgsl(include_str!("light.wgsl").into()),
+    let object_id_resource = render_graph.create(graph::TextureDesc {
+        label: "object id",
+        format: picking::ID_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC | TextureUsages::TEXTURE_BINDING,
     });
-
-    let shadow_shader = device.create_shader_module(ShaderModuleDescriptor {
-        label: Some("Full shadow Shader"),
-        source: ShaderSource::Wgsl(include_str!("shadow.wgsl").into()),
+    let portal_level_resources = [(); portal::MAX_DEPTH].map(|_| render_graph.create(graph::TextureDesc {
+        label: "portal level",
+        format: post::HDR_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    }));
+    // shared by the levels, which are rendered one after the other
+    let portal_depth_resource = render_graph.create(graph::TextureDesc {
+        label: "portal depth",
+        format: SCENE_DEPTH_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+    });
+    let portal_object_id_resource = render_graph.create(graph::TextureDesc {
+        label: "portal object id",
+        format: picking::ID_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+    });
+    let object_motion_resource = render_graph.create(graph::TextureDesc {
+        label: "object motion",
+        format: motion_blur::MOTION_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+    let portal_object_motion_resource = render_graph.create(graph::TextureDesc {
+        label: "portal object motion",
+        format: motion_blur::MOTION_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT,
     });
+    
+    let light_binding = binding::BindGroupLayoutBuilder::new("light bind group layout")
+        .uniform_dynamic(ShaderStages::VERTEX | ShaderStages::FRAGMENT) // camera, one per view
+        .uniform(ShaderStages::VERTEX | ShaderStages::FRAGMENT) // light
+        .texture(ShaderStages::FRAGMENT, TextureSampleType::Depth) // shadow map
+        .sampler(ShaderStages::FRAGMENT, SamplerBindingType::Filtering) // shadow sampler
+        .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: true }) // terrain detail
+        .sampler(ShaderStages::FRAGMENT, SamplerBindingType::Filtering) // terrain detail sampler
+        .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: false }) // traced shadow mask
+        .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: true }) // lightmap
+        .sampler(ShaderStages::FRAGMENT, SamplerBindingType::Filtering) // lightmap sampler
+        .uniform(ShaderStages::FRAGMENT) // point light
+        .texture_array(ShaderStages::FRAGMENT, TextureSampleType::Depth); // point shadow
+
+    let shadow_binding = binding::BindGroupLayoutBuilder::new("shadow bind group layout")
+        .uniform(ShaderStages::VERTEX | ShaderStages::FRAGMENT); // light
+
+    let mut draw_params = draw_params::DrawParamsSet::new(&device, 1);
+    let mut shaders = preprocess::ShaderCache::new(shader_preprocessor(draw_params.mode));
+    let unclipped_depth = device.features().contains(Features::DEPTH_CLIP_CONTROL);
+    let shadow_defines = preprocess::Defines::new().with_if("PANCAKE_IN_SHADER", !unclipped_depth);
+    // every variant binds the same, the one with every feature uses all bindings
+    let all_light_defines = preprocess::ShaderVariant::all().defines();
+    let light_module = binding::reflect("light.wgsl", &shaders.source("light.wgsl", &all_light_defines));
+    light_binding.validate(0, &light_module).unwrap();
+    layout::validate_vertex_input(&light_module, "vs_main", &[VERTEX_LAYOUT, INSTANCE_LAYOUT, PREVIOUS_INSTANCE_LAYOUT]).unwrap();
+    layout::validate_vertex_input(&light_module, "vs_terrain", &[terrain::TERRAIN_VERTEX_LAYOUT]).unwrap();
+    let shadow_module = binding::reflect("shadow.wgsl", &shaders.source("shadow.wgsl", &shadow_defines));
+    shadow_binding.validate(0, &shadow_module).unwrap();
+    layout::validate_vertex_input(&shadow_module, "vs_main", &[VERTEX_LAYOUT, INSTANCE_LAYOUT]).unwrap();
+    layout::validate_vertex_input(&shadow_module, "vs_terrain", &[terrain::TERRAIN_VERTEX_LAYOUT]).unwrap();
+    layout::validate_vertex_input(&shadow_module, "vs_rsm", &[VERTEX_LAYOUT, INSTANCE_LAYOUT]).unwrap();
+
+    let light_bind_group_layout = light_binding.build(&device);
+    let shadow_bind_group_layout = shadow_binding.build(&device);
+
+    let shadow_shader = shaders.get(&device, "shadow.wgsl", &shadow_defines);
 
 
     let shadow_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
         label: Some("Shadow Render Pipeline Layout"),
-        bind_group_layouts: &[&shadow_bind_group_layout],
+        bind_group_layouts: &[&shadow_bind_group_layout.layout],
         push_constant_ranges: &[],
     });
 
+    let mut light_bind_group_layouts = vec![&light_bind_group_layout.layout];
+    light_bind_group_layouts.extend(draw_params.bind_group_layout());
     let light_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
         label: Some("Light Render Pipeline Layout"),
-        bind_group_layouts: &[&light_bind_group_layout],
-        push_constant_ranges: &[],
+        bind_group_layouts: &light_bind_group_layouts,
+        push_constant_ranges: &draw_params.push_constant_ranges(),
     });
 
     let depth_stencil = DepthStencilState {
@@ -450,140 +1025,139 @@ async fn run() {
         alpha_to_coverage_enabled: false, // 4.
     };
 
-    let shadow_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+    // the rsm's pipelines have its `targets`, the shadow map's only depth
+    let create_shadow_pipeline = |entry_point, buffers, cull: pipeline_cache::Cull, targets: &[Option<ColorTargetState>]| device.create_render_pipeline(&RenderPipelineDescriptor {
         label: Some("Shadow mapping pipeline"),
         layout: Some(&shadow_pipeline_layout),
         vertex: VertexState {
             module: &shadow_shader,
-            entry_point: "vs_main",
-            buffers: &[
-                VERTEX_LAYOUT,
-                INSTANCE_LAYOUT,
-            ],
+            entry_point,
+            buffers,
         },
         primitive: PrimitiveState {
             topology: PrimitiveTopology::TriangleList, // 1.
             strip_index_format: None,
             front_face: FrontFace::Ccw, // 2.
-            cull_mode: Some(Face::Back),
+            cull_mode: cull.face(),
             // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
             polygon_mode: PolygonMode::Fill,
-            // Requires Features::DEPTH_CLIP_CONTROL
-            unclipped_depth: false,
+            // Requires Features::DEPTH_CLIP_CONTROL,
+            // casters behind the near plane get their depth clamped to it (pancaking)
+            unclipped_depth,
             // Requires Features::CONSERVATIVE_RASTERIZATION
             conservative: false,
         },
         depth_stencil: Some(depth_stencil.clone()),
         multisample,
-        fragment: None,
-        multiview: None,
-    });
-
-    let light_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-        label: Some("Light Pipeline"),
-        layout: Some(&light_pipeline_layout),
-        vertex: VertexState {
-            module: &light_shader,
-            entry_point: "vs_main", // 1.
-            buffers: &[
-                VERTEX_LAYOUT,
-                INSTANCE_LAYOUT,
-            ], // 2.
-        },
-        fragment: Some(FragmentState { // 3.
-            module: &light_shader,
-            entry_point: "fs_main",
-            targets: &[Some(ColorTargetState { // 4.
-                format: config.format,
-                blend: Some(BlendState::REPLACE),
-                write_mask: ColorWrites::ALL,
-            })],
+        fragment: (!targets.is_empty()).then(|| FragmentState {
+            module: &shadow_shader,
+            entry_point: "fs_rsm",
+            targets,
         }),
-        primitive: PrimitiveState {
-            topology: PrimitiveTopology::TriangleList, // 1.
-            strip_index_format: None,
-            front_face: FrontFace::Ccw, // 2.
-            cull_mode: Some(Face::Back),
-            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-            polygon_mode: PolygonMode::Fill,
-            // Requires Features::DEPTH_CLIP_CONTROL
-            unclipped_depth: false,
-            // Requires Features::CONSERVATIVE_RASTERIZATION
-            conservative: false,
-        },
-        depth_stencil: Some(depth_stencil.clone()), // 1.
-        multisample,
-        multiview: None, // 5.
-    });
-
-    let vertex_buffer = device.create_buffer_init(&util::BufferInitDescriptor {
-        label: Some("Vertex buffer"),
-        contents: bytemuck::cast_slice(&[
-            Vertex {
-                position: [-0.5, -0.5, -0.5],
-            },
-            Vertex {
-                position: [-0.5, -0.5, 0.5],
-            },
-            Vertex {
-                position: [-0.5, 0.5, -0.5],
-            },
-            Vertex {
-                position: [-0.5, 0.5, 0.5],
-            },
-            Vertex {
-                position: [0.5, -0.5, -0.5],
-            },
-            Vertex {
-                position: [0.5, -0.5, 0.5],
-            },
-            Vertex {
-                position: [0.5, 0.5, -0.5],
-            },
-            Vertex {
-                position: [0.5, 0.5, 0.5],
-            },
-        ]),
-        usage: BufferUsages::VERTEX,
+        multiview: None,
     });
-
-    let indices: &[u16] = &[
-        0b000, 0b100, 0b010,
-        0b110, 0b010, 0b100,
-
-        0b000, 0b010, 0b001,
-        0b011, 0b001, 0b010,
-
-        0b000, 0b001, 0b100,
-        0b101, 0b100, 0b001,
-
-        0b110 ^ 0b111, 0b100 ^ 0b111, 0b010 ^ 0b111,
-        0b000 ^ 0b111, 0b010 ^ 0b111, 0b100 ^ 0b111,
-
-        0b011 ^ 0b111, 0b010 ^ 0b111, 0b001 ^ 0b111,
-        0b000 ^ 0b111, 0b001 ^ 0b111, 0b010 ^ 0b111,
-
-        0b101 ^ 0b111, 0b001 ^ 0b111, 0b100 ^ 0b111,
-        0b000 ^ 0b111, 0b100 ^ 0b111, 0b001 ^ 0b111,
+    // two sided materials cast shadows from their back faces too
+    let shadow_pipeline = create_shadow_pipeline("vs_main", &[VERTEX_LAYOUT, INSTANCE_LAYOUT], pipeline_cache::Cull::Back, &[]);
+    let two_sided_shadow_pipeline = create_shadow_pipeline("vs_main", &[VERTEX_LAYOUT, INSTANCE_LAYOUT], pipeline_cache::Cull::None, &[]);
+    let terrain_shadow_pipeline = create_shadow_pipeline(
+        "vs_terrain",
+        &[terrain::TERRAIN_VERTEX_LAYOUT],
+        draw_params::cull(draw_params::MATERIAL_TERRAIN),
+        &[],
+    );
+    // the light's view of every surface for the indirect light, rendered while it's enabled
+    let rsm_pipeline = create_shadow_pipeline("vs_rsm", &[VERTEX_LAYOUT, INSTANCE_LAYOUT], pipeline_cache::Cull::Back, &rsm::ReflectiveShadowMap::targets());
+    let terrain_rsm_pipeline = create_shadow_pipeline(
+        "vs_terrain",
+        &[terrain::TERRAIN_VERTEX_LAYOUT],
+        draw_params::cull(draw_params::MATERIAL_TERRAIN),
+        &rsm::ReflectiveShadowMap::targets(),
+    );
+    // what the cubes are drawn as, cycled through lit, two sided and unlit
+    let mut instance_material = draw_params::MATERIAL_LIT;
+
+    // one set of light pipelines for each variant used so far
+    let mut light_variant = preprocess::ShaderVariant::SHADOWS | preprocess::ShaderVariant::PCF;
+    let mut light_variants = std::collections::HashMap::new();
+
+    // indexed by scene::MeshId, with the uv2 their lightmap tiles are looked up by
+    let unwrapped_meshes = [
+        lightmap::unwrap(&CUBE_VERTICES.map(|v| v.position), &CUBE_INDICES),
+        lightmap::unwrap(&WEDGE_VERTICES.map(|v| v.position), &WEDGE_INDICES),
     ];
-    let index_buffer = device.create_buffer_init(&util::BufferInitDescriptor {
-        label: Some("Index buffer"),
-        contents: bytemuck::cast_slice(indices),
-        usage: BufferUsages::INDEX,
-    });
-
-    let camera_buffer = device.create_buffer(&BufferDescriptor {
-        label: Some("Camera Uniform Buffer"),
-        size: size_of::<CameraRaw>() as BufferAddress,
-        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-        mapped_at_creation: false,
-    });
-    let light_buffer = device.create_buffer(&BufferDescriptor {
-        label: Some("Light Uniform Buffer"),
-        size: size_of::<CameraRaw>() as BufferAddress,
-        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-        mapped_at_creation: false,
+    let meshes = [("Cube", &unwrapped_meshes[0]), ("Wedge", &unwrapped_meshes[1])]
+        .map(|(label, unwrapped)| mesh::Mesh::new(&device, label, &unwrapped.vertices, &unwrapped.indices));
+    // `lightmap bake` lights the static instances by it instead of the light, `lightmap clear` undoes it
+    let lightmap = lightmap::Lightmap::new(&device);
+
+    // `terrain.pgm` in the working directory when there's one, generated hills otherwise
+    let heightmap = std::fs::read("terrain.pgm")
+        .ok()
+        .and_then(|bytes| terrain::Heightmap::from_pgm(&bytes, 0.5, 3.0)
+            .map_err(|e| println!("terrain.pgm: {}", e))
+            .ok())
+        .unwrap_or_else(|| terrain::Heightmap::generate(129, 129, 0.5, 3.0));
+    let mut terrain = terrain::Terrain::new(&device, &heightmap, Vector3::new(-32.0, -6.0, -32.0), 32);
+    // `shadows traced` lights the camera's view by it instead of the shadow map, `shadows mapped` back
+    let mut traced_shadows = traced_shadow::TracedShadows::new(
+        &device,
+        size.width,
+        size.height,
+        vec![
+            traced_shadow::triangles(&CUBE_VERTICES.map(|v| v.position), &CUBE_INDICES),
+            traced_shadow::triangles(&WEDGE_VERTICES.map(|v| v.position), &WEDGE_INDICES),
+        ],
+        heightmap.triangles(terrain.origin),
+    );
+
+    let mut camera_buffer = buffer::GpuBuffer::<CameraRaw>::new_uniform(&device, "Camera Uniform Buffer");
+    let mut pipeline_cache = pipeline_cache::PipelineCache::new();
+    let mut debug_draw = debug_draw::DebugDraw::new(
+        &device,
+        &mut pipeline_cache,
+        camera_buffer.as_entire_binding(),
+        post::HDR_FORMAT,
+        SCENE_DEPTH_FORMAT,
+    );
+    let mut frames = frames::FramesInFlight::new(MAX_FRAMES_IN_FLIGHT);
+    // the camera of every view rendering the scene, pushed each frame
+    let mut camera_rings = frames::PerFrame::new(&frames, |_| {
+        buffer::UniformRing::<CameraRaw>::new(&device, "Camera Ring Buffer", MAX_VIEWS)
     });
+    // K toggles it
+    let mut mirror = SCENE_DEPTH_FORMAT.has_stencil_aspect().then(|| mirror::Mirror::new(
+        &device,
+        &mut pipeline_cache,
+        camera_buffer.as_entire_binding(),
+        post::HDR_FORMAT,
+        SCENE_DEPTH_FORMAT,
+        Vector3::new(0.0, -1.0, 12.0),
+        Vector3::new(0.0, 0.0, -1.0),
+        Vector3::new(4.0, 0.0, 0.0),
+        Vector3::new(0.0, 3.0, 0.0),
+    ));
+    let mut mirror_enabled = mirror.is_some();
+    // O cycles through its recursion depths
+    let mut portal = portal::Portal::new(
+        &device,
+        &mut pipeline_cache,
+        post::HDR_FORMAT,
+        SCENE_DEPTH_FORMAT,
+        portal::PortalFrame {
+            center: Vector3::new(-8.0, 0.0, 10.0),
+            normal: Vector3::new(0.0, 0.0, -1.0),
+            right: Vector3::new(1.5, 0.0, 0.0),
+            up: Vector3::new(0.0, 2.0, 0.0),
+        },
+        // facing the entrance from behind it, so the entrance is seen through itself
+        portal::PortalFrame {
+            center: Vector3::new(-8.0, 0.0, 4.0),
+            normal: Vector3::new(0.0, 0.0, 1.0),
+            right: Vector3::new(-1.5, 0.0, 0.0),
+            up: Vector3::new(0.0, 2.0, 0.0),
+        },
+    );
+    let mut light_buffer = buffer::GpuBuffer::<LightRaw>::new_uniform(&device, "Light Uniform Buffer");
 
     let shadow_texture_width = 1024;
     let shadow_texture_height = 1024;
@@ -598,53 +1172,140 @@ async fn run() {
         sample_count: 1,
         dimension: TextureDimension::D2,
         format: DEPTH_FORMAT,
-        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        usage: TextureUsages::RENDER_ATTACHMENT
+            | TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_SRC
+            | TextureUsages::COPY_DST,
         view_formats: &[],
     });
+    let mut shadow_cache = shadow_cache::ShadowCache::new(
+        &device,
+        DEPTH_FORMAT,
+        shadow_texture_width,
+        shadow_texture_height,
+    );
     let shadow_texture_view = shadow_texture.create_view(&TextureViewDescriptor::default());
+    // `shadows throttle on|off` compares against rendering the shadow map every frame
+    let mut shadow_throttle = shadow_throttle::ShadowThrottle::new();
+    // among the cubes, `point on|off` and `point shadows none|paraboloid` on the console set it,
+    // `point here` moves it to the camera
+    let mut point_light = point_shadow::PointLight {
+        enabled: true,
+        position: Vector3::new(1.5, 1.5, 6.0),
+        near_z: 0.1,
+        color: math::Color::from_srgb(1.0, 0.75, 0.45),
+        intensity: 8.0,
+        range: 12.0,
+        shadow: point_shadow::PointShadowMode::DualParaboloid,
+    };
+    let mut point_light_buffer = buffer::GpuBuffer::<point_shadow::PointLightRaw>::new_uniform(&device, "Point light buffer");
+    // `point multiview on|off` compares its single multiview pass with a pass per hemisphere
+    let mut point_shadow = point_shadow::PointShadow::new(
+        &device,
+        &queue,
+        DEPTH_FORMAT,
+        512,
+        &[VERTEX_LAYOUT, INSTANCE_LAYOUT],
+    );
+
+    let reflective_shadow_map = rsm::ReflectiveShadowMap::new(&device, DEPTH_FORMAT);
+    // the resolve tonemaps the result, `tonemap off|reinhard|aces` and `exposure <x>` set how
+    let mut post_chain = post::PostChain::new(&device, config.format, size.width, size.height);
+    // lights the scene before anything is layered over it, `gi on|off` compares it against the direct light
+    post_chain.push(rsm::IndirectLight::new(&device, &reflective_shadow_map), false);
+    post_chain.push(fog::Fog::new(&device), false);
+    post_chain.push(volumetric::Volumetric::new(&device, &shadow_texture), false);
+    post_chain.push(dof::DepthOfField::new(&device, size.width, size.height), false);
+    post_chain.push(motion_blur::MotionBlur::new(&device), false);
+    // enabled while an instance is selected
+    post_chain.push(outline::Outline::new(&device), false);
+    // physical pixels per logical pixel, the pixel sizes of overlays like the outline are scaled by it
+    post_chain.get_mut::<outline::Outline>().unwrap().scale_factor = window.scale_factor() as f32;
+    // grades the colors as displayed, so after every other effect
+    let mut color_grading = lut::ColorGrading::new(&device);
+    let builtin_luts = [
+        lut::Lut::from_fn("warm", 16, |[r, g, b]| [(r * 1.08 + 0.02).min(1.0), g, b * 0.9]),
+        lut::Lut::from_fn("faded", 16, |[r, g, b]| {
+            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            [r, g, b].map(|c| 0.08 + 0.84 * (luma + 0.5 * (c - luma)))
+        }),
+    ];
+    for lut in builtin_luts.iter().chain(&lut::Lut::load_directory("luts")) {
+        color_grading.add(&device, &queue, lut);
+    }
+    post_chain.push(color_grading, false);
     let shadow_sampler = device.create_sampler(&SamplerDescriptor {
         label: Some("Shadow sampler"),
         ..Default::default()
     });
 
-    let shadow_bind_group = device.create_bind_group(&BindGroupDescriptor {
-        label: Some("shadow bind group"),
-        layout: &shadow_bind_group_layout,
-        entries: &[
-            BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }
-        ],
-    });
-    let light_bind_group = device.create_bind_group(&BindGroupDescriptor {
-        label: Some("light bind group"),
-        layout: &light_bind_group_layout,
-        entries: &[
-            BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 1,
-                resource: light_buffer.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 2,
-                resource: BindingResource::TextureView(&shadow_texture_view),
-            },
-            BindGroupEntry {
-                binding: 3,
-                resource: BindingResource::Sampler(&shadow_sampler),
-            },
-        ],
+    let shadow_bind_group = shadow_bind_group_layout.create_bind_group(
+        &device,
+        "shadow bind group",
+        &[light_buffer.as_entire_binding()],
+    );
+
+    let texture_support = texture::FormatSupport::new(&adapter, &device);
+    let mut mipmaps = mipmap::MipmapGenerator::new(&device);
+    let detail_texture = terrain::detail_texture(256)
+        .upload(&device, &queue, &texture_support, &mut mipmaps, "terrain detail texture");
+    // applies to every texture sampled by the scene, F1 cycles through it
+    let mut texture_filtering = texture::Filtering::Anisotropic(16);
+
+    let mut detail_sampler = texture_filtering.create_sampler(&device, &texture_support, "terrain detail sampler");
+    // grass over the fixed terrain, 0 toggles it
+    let mut vegetation_blades = {
+        let origin = terrain.origin;
+        let size = (heightmap.width - 1) as f32 * heightmap.spacing;
+        vegetation::scatter(
+            Vector2::new(origin.x, origin.z),
+            Vector2::new(origin.x + size, origin.z + size),
+            0.35,
+            0,
+            |x, z| origin.y + heightmap.height_at(x - origin.x, z - origin.z),
+        )
+    };
+    // `vegetation shadows opaque|alpha-tested` picks how it casts shadows
+    let mut vegetation = vegetation::Vegetation::new(
+        &device,
+        &queue,
+        &texture_support,
+        &mut mipmaps,
+        (camera_buffer.as_entire_binding(), light_buffer.as_entire_binding()),
+        &detail_sampler,
+        [post::HDR_FORMAT, picking::ID_FORMAT, motion_blur::MOTION_FORMAT],
+        SCENE_DEPTH_FORMAT,
+        DEPTH_FORMAT,
+        &vegetation_blades,
+    );
+    // one per frame in flight binding its camera ring, recreated once stale when
+    // `texture_filtering` changes the detail sampler or a resize the traced shadow mask
+    let mut light_bind_groups = frames::PerFrame::new(&frames, |slot| {
+        light_bind_group_layout.create_bind_group(
+            &device,
+            "light bind group",
+            &[
+                camera_rings[slot].binding(),
+                light_buffer.as_entire_binding(),
+                BindingResource::TextureView(&shadow_texture_view),
+                BindingResource::Sampler(&shadow_sampler),
+                BindingResource::TextureView(&detail_texture.view),
+                BindingResource::Sampler(&detail_sampler),
+                BindingResource::TextureView(&traced_shadows.mask_view),
+                BindingResource::TextureView(&lightmap.view),
+                BindingResource::Sampler(&lightmap.sampler),
+                point_light_buffer.as_entire_binding(),
+                BindingResource::TextureView(&point_shadow.view),
+            ],
+        )
     });
+    let mut light_bind_groups_stale = false;
 
     let instant = std::time::Instant::now();
     let mut last_frame_time = instant.elapsed().as_secs_f32();
+    // U cycles what rendering does while the window is unfocused
+    let mut throttle = throttle::Throttle::new(throttle::Background::Throttled, throttle::RenderMode::Continuous);
     let mut delta_frame_time = 0.0;
-    let mut time_rendered = 0.0;
-    let mut frames = 0;
 
     let mut input = input::InputState::new();
 
@@ -661,130 +1322,354 @@ async fn run() {
     };
     let mut light = Light {
         translation: Vector3::new(0.0, 0.0, -100.0),
+        z_to_x: 0.0,
+        xz_to_y: 0.0,
         near_z: 4.0,
         width: 1.0,
         height: 1.0,
+        color: math::Color::WHITE,
+        intensity: 1.0,
+        normal_offset_bias: true,
+        receiver_plane_bias: true,
+        shadow_filter: ShadowFilter::Poisson,
+        shadow_radius: 0.05,
     };
-
-    let mut instances = vec![
-        Instance { 
-            translation: Vector3::IDENTITY, 
-            rotation: math::Rotor::IDENTITY,
-            scale: math::Scale3::new(light.width * 1.01, light.height * 1.01, 0.1)
-        },
-        Instance {
-            translation: Vector3::new(0.0, 0.0, 4.0), 
-            rotation: math::BiVector3::new(0.0, -0.05, 0.0).exp(), 
-            scale: math::Scale3::new(4.0, 4.0, 1.0)
-        },
-        Instance {
-            translation: Vector3::new(-3.0, -1.0, 6.0), 
-            rotation: math::BiVector3::new(0.8, 0.3, 0.9).exp(), 
-            scale: math::Scale3::new(4.0, 4.0, 1.0)
-        },
-        Instance {
-            translation: Vector3::new(0.0, 0.0, 10.0), 
-            rotation: math::BiVector3::new(0.0, 0.0, 0.0).exp(), 
-            scale: math::Scale3::new(10.0, 30.0, 0.1)
-        },
-        Instance {
-            translation: Vector3::new(0.0, 10.0, -3.0), 
-            rotation: math::BiVector3::new(0.3, -0.4, 0.2).exp(), 
-            scale: math::Scale3::new(5.0, 2.0, 1.0)
-        },
-        Instance {
-            translation: Vector3::new(2.0, 5.0, -3.0), 
-            rotation: math::BiVector3::new(0.7, -0.4, -0.3).exp(), 
-            scale: math::Scale3::new(4.0, 3.0, 1.0)
-        },
-        Instance {
-            translation: Vector3::new(-3.0, 5.0, 0.0), 
-            rotation: math::BiVector3::new(-0.3, 0.2, -0.7).exp(), 
-            scale: math::Scale3::new(4.0, 1.0, 2.0)
-        },
-        Instance {
-            translation: Vector3::new(3.0, 1.0, 4.0), 
-            rotation: math::BiVector3::new(0.1, -0.05, 0.0).exp(), 
-            scale: math::Scale3::new(1.0, 5.0, 0.2)
-        },
-    ];
-    
-    let instance_buffer = device.create_buffer(&BufferDescriptor {
-        label: Some("Instance buffer"),
-        size: (instances.len() * size_of::<InstanceRaw>()) as BufferAddress,
-        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-        mapped_at_creation: false,
+    // clear color, ambient light, fog and the light's direction, edited by typing `name values`
+    // into the console, `env` to print them and `save` to write them to `Environment::FILE`
+    let mut environment = environment::Environment::load().unwrap_or_else(|e| {
+        println!("{}", e);
+        environment::Environment::default()
+    });
+    light.point_along(environment.sun(), SUN_FOCUS, SUN_DISTANCE);
+    let console_proxy = event_loop.create_proxy();
+    let console = console::Console::new(move || {
+        let _ = console_proxy.send_event(());
+    });
+    // when enabled, drives the light from the time of day
+    let mut sun: Option<sun::Sun> = None;
+    // when enabled, replaces the fixed terrain with chunks loaded around the camera
+    let mut streamer: Option<streaming::Streamer> = None;
+    // moves everything back towards the local origin once the camera is far from it, so what
+    // is near the camera is placed precisely
+    let mut floating_origin = floating_origin::FloatingOrigin::new(512.0, WORLD_CHUNK_SIZE);
+
+    let mut scene = scene::Scene::new();
+    scene.spawn_static(Instance {
+        mesh: scene::MeshId::CUBE,
+        translation: Vector3::new(0.0, 0.0, 4.0), 
+        rotation: math::BiVector3::new(0.0, -0.05, 0.0).exp(), 
+        scale: math::Scale3::new(4.0, 4.0, 1.0),
+        casts_shadow: true,
+        receives_shadow: true,
+        lightmap: None,
+    });
+    scene.spawn_static(Instance {
+        mesh: scene::MeshId::CUBE,
+        translation: Vector3::new(-3.0, -1.0, 6.0), 
+        rotation: math::BiVector3::new(0.8, 0.3, 0.9).exp(), 
+        scale: math::Scale3::new(4.0, 4.0, 1.0),
+        casts_shadow: true,
+        receives_shadow: true,
+        lightmap: None,
+    });
+    scene.spawn_static(Instance {
+        mesh: scene::MeshId::CUBE,
+        translation: Vector3::new(0.0, 0.0, 10.0), 
+        rotation: math::BiVector3::new(0.0, 0.0, 0.0).exp(), 
+        scale: math::Scale3::new(10.0, 30.0, 0.1),
+        // the ground, nothing is behind it to shadow
+        casts_shadow: false,
+        receives_shadow: true,
+        lightmap: None,
+    });
+    scene.spawn_static(Instance {
+        mesh: scene::MeshId::CUBE,
+        translation: Vector3::new(0.0, 10.0, -3.0), 
+        rotation: math::BiVector3::new(0.3, -0.4, 0.2).exp(), 
+        scale: math::Scale3::new(5.0, 2.0, 1.0),
+        casts_shadow: true,
+        receives_shadow: true,
+        lightmap: None,
+    });
+    scene.spawn_static(Instance {
+        mesh: scene::MeshId::WEDGE,
+        translation: Vector3::new(2.0, 5.0, -3.0), 
+        rotation: math::BiVector3::new(0.7, -0.4, -0.3).exp(), 
+        scale: math::Scale3::new(4.0, 3.0, 1.0),
+        casts_shadow: true,
+        receives_shadow: true,
+        lightmap: None,
     });
+    scene.spawn_static(Instance {
+        mesh: scene::MeshId::CUBE,
+        translation: Vector3::new(-3.0, 5.0, 0.0), 
+        rotation: math::BiVector3::new(-0.3, 0.2, -0.7).exp(), 
+        scale: math::Scale3::new(4.0, 1.0, 2.0),
+        casts_shadow: true,
+        receives_shadow: true,
+        lightmap: None,
+    });
+    scene.spawn_static(Instance {
+        mesh: scene::MeshId::CUBE,
+        translation: Vector3::new(3.0, 1.0, 4.0), 
+        rotation: math::BiVector3::new(0.1, -0.05, 0.0).exp(), 
+        scale: math::Scale3::new(1.0, 5.0, 0.2),
+        casts_shadow: true,
+        receives_shadow: true,
+        lightmap: None,
+    });
+    // swings back and forth along the screw motion between two poses
+    let mut screw_poses = (
+        math::Motor::new(math::Rotor::IDENTITY, Vector3::new(6.0, 0.0, 2.0)),
+        math::Motor::new(math::BiVector3::new(0.4, 0.0, 1.2).exp(), Vector3::new(5.0, 3.0, 6.0)),
+    );
+    let screw_instance = scene.spawn(Instance {
+        mesh: scene::MeshId::CUBE,
+        translation: screw_poses.0.translation(),
+        rotation: screw_poses.0.rotor(),
+        scale: math::Scale3::IDENTITY,
+        casts_shadow: true,
+        receives_shadow: true,
+        lightmap: None,
+    });
+    
+    let mut transform_pass = transform::TransformPass::new(&device, scene.len());
+    let mut scene_draws = indirect::SceneDraws::new(&device);
 
     let mut shadow_fit = false;
+    // `shadows margin <fraction>` and `shadows hysteresis <fraction>` tune it
+    let mut stable_fit = shadow_fit::StableFit::new();
+    // cuts the camera's frustum the fit is computed from to the depths it sees, `shadows sdsm on|off` toggles it
+    let mut depth_range = depth_range::DepthRange::new(&device);
+    // `stereo on|off` on the console toggles it
+    let mut stereo = stereo::Stereo::new();
+    // Q toggles it: dragging the light's gizmo moves it, 1 to 6 shrink and grow its near_z, width and height
+    let mut light_editor = light_editor::LightEditor::new();
+    // set by M, prints the depth range of the shadow map once read back
+    let mut shadow_readback_requested = false;
+    let mut shadow_readback: Option<readback::Readback<f32>> = None;
+    let mut selected: Option<scene::Handle> = None;
+    let mut previous_camera_view = camera.to_raw().view;
+    let mut stats = stats::FrameStats::new();
+    // 9 cycles the stress test's layout, `stress <count>` on the console sets its instance count
+    let mut stress_test = stress::StressTest::new(20_000);
+    let mut gpu_timer = gpu_timer::GpuTimer::new(&device, &queue);
+    // F11 cycles through windowed, borderless and exclusive fullscreen
+    let mut window_modes = window_mode::WindowModes::new(&window);
+    // I captures a frame when launched from renderdoc
+    let mut frame_capture = capture::FrameCapture::new();
 
     let camera_translation_speed = 3.0;
     let camera_rotation_speed = 1.5;
+    // of the translation speed while Shift is held
+    let camera_sprint_factor = 4.0;
+    // Alt and a left drag orbit the camera around it, the selected instance or a point ahead
+    let mut orbit_pivot: Option<Vector3> = None;
+    // radians per pixel of mouse motion while the mouse is grabbed
+    let mouse_sensitivity = 0.003;
+    // the keys move the goal, which the camera follows smoothly, the same at any frame rate.
+    // only its translation and angles are used
+    let mut camera_goal = camera.clone();
+    let camera_damping = 12.0;
+    // X adds the camera's placement to the path, Y flies it or stops, Z clears it,
+    // `path catmull-rom|bspline|bezier` picks its curve
+    let mut camera_path = camera_path::CameraPath::new(4.0);
+    // 7 bookmarks the camera's placement, 8 eases the camera back to it
+    let mut camera_bookmark = None;
+    let mut camera_recall: Option<math::easing::Tween<(Vector3, math::Rotor)>> = None;
+    // `recall <ease> in|out|in-out` changes how it eases
+    let mut recall_easing = math::easing::Easing::new(math::easing::Ease::Cubic, math::easing::EaseDirection::InOut);
     event_loop.run(move |event: event::Event<'_, ()>, _, control_flow| {
         use winit::{event_loop::*, event::*};
 
         match event {
             Event::RedrawRequested(..) => {
-                queue.write_buffer(
-                    &camera_buffer, 
-                    0, 
-                    bytes_of(&camera.to_raw()),
-                );
-
-                queue.write_buffer(
-                    &instance_buffer, 
-                    0,
-                    bytemuck::cast_slice(&instances
-                        .iter()
-                        .map(|i| i.to_raw())
-                        .collect::<Vec<_>>()
-                    )
-                );
-
-                frames += 1;
+                profiling::scope!("redraw");
+                // waits for the gpu to finish the frame that last used this frame's ring
+                let slot = frames.begin(&device);
+                if std::mem::take(&mut light_bind_groups_stale) {
+                    light_bind_groups = frames::PerFrame::new(&frames, |slot| {
+                        light_bind_group_layout.create_bind_group(
+                            &device,
+                            "light bind group",
+                            &[
+                                camera_rings[slot].binding(),
+                                light_buffer.as_entire_binding(),
+                                BindingResource::TextureView(&shadow_texture_view),
+                                BindingResource::Sampler(&shadow_sampler),
+                                BindingResource::TextureView(&detail_texture.view),
+                                BindingResource::Sampler(&detail_sampler),
+                                BindingResource::TextureView(&traced_shadows.mask_view),
+                                BindingResource::TextureView(&lightmap.view),
+                                BindingResource::Sampler(&lightmap.sampler),
+                                point_light_buffer.as_entire_binding(),
+                                BindingResource::TextureView(&point_shadow.view),
+                            ],
+                        )
+                    });
+                }
+                let camera_ring = &mut camera_rings[slot];
+                let traced = light_variant.contains(preprocess::ShaderVariant::TRACED_SHADOWS);
+                let camera_raw = CameraRaw {
+                    traced_shadow_mask: traced as u32,
+                    previous_view: previous_camera_view,
+                    ..camera.to_raw()
+                };
+                camera_buffer.write(&queue, &[camera_raw]);
+                camera_ring.clear();
+                let camera_offset = camera_ring.push(camera_raw);
+                // drawn instead of the camera's own view while stereo, each with its view and viewport
+                let eyes = match stereo.enabled {
+                    true => {
+                        let right = Vector3::new(1.0, 0.0, 0.0).apply(&camera.compute_model()) - camera.translation;
+                        stereo::Eye::ALL.map(|eye| {
+                            let eye_camera = Camera {
+                                translation: camera.translation + stereo.eye_offset(eye, right),
+                                width: camera.width / 2.0,
+                                ..camera.clone()
+                            };
+                            let view = eye_camera.to_raw().view;
+                            let offset = camera_ring.push(CameraRaw {
+                                view,
+                                traced_shadow_mask: 0,
+                                // the eyes' own views of the frame before aren't kept
+                                previous_view: view,
+                                ..camera_raw
+                            });
+                            (view, offset, eye.viewport(config.width, config.height))
+                        }).to_vec()
+                    }
+                    false => vec![],
+                };
+                // the reflection is drawn only while the mirror's front is towards the camera
+                let reflection = mirror.as_mut()
+                    .filter(|mirror| mirror_enabled && !stereo.enabled && mirror.is_facing(camera.translation))
+                    .map(|mirror| {
+                        mirror.upload(&queue);
+                        let view = mirror.reflection().compose(&camera_raw.view);
+                        let offset = camera_ring.push(CameraRaw {
+                            view,
+                            clip_plane: mirror.clip_plane(),
+                            traced_shadow_mask: 0,
+                            // the reflection's own view of the frame before isn't kept
+                            previous_view: view,
+                            ..camera_raw
+                        });
+                        (view, offset)
+                    });
+                portal.upload(&queue);
+                let portal_levels: Vec<_> = portal.level_views(&camera_raw.view)
+                    .into_iter()
+                    .filter(|_| !stereo.enabled)
+                    .map(|view| (view, camera_ring.push(CameraRaw {
+                        view,
+                        clip_plane: portal.clip_plane(),
+                        traced_shadow_mask: 0,
+                        previous_view: view,
+                        ..camera_raw
+                    })))
+                    .collect();
+                camera_ring.flush(&queue);
+                post_chain.get_mut::<motion_blur::MotionBlur>().unwrap()
+                    .set_views(&previous_camera_view, &camera_raw.view, camera.near_z);
+                let fog = post_chain.get_mut::<fog::Fog>().unwrap();
+                environment.apply_fog(fog);
+                fog.set_view(&camera_raw.view, camera.translation, camera.near_z);
+                post_chain.get_mut::<volumetric::Volumetric>().unwrap()
+                    .set_view(&camera_raw.view, camera.translation, camera.near_z);
+                post_chain.get_mut::<rsm::IndirectLight>().unwrap().set_view(&camera_raw.view, camera.near_z);
+                previous_camera_view = camera_raw.view;
+                if let Some(streamer) = &mut streamer {
+                    // the streamed terrain isn't part of the scene's static generation
+                    if streamer.update(&device, &mut scene, camera.translation) {
+                        shadow_cache.invalidate();
+                    }
+                }
+                if let Some(instance) = scene.get_mut(screw_instance) {
+                    let t = 0.5 - 0.5 * (0.5 * instant.elapsed().as_secs_f32()).cos();
+                    let pose = screw_poses.0.interpolate(&screw_poses.1, t);
+                    instance.translation = pose.translation();
+                    instance.rotation = pose.rotor();
+                }
+                let upload_start = std::time::Instant::now();
+                transform_pass.upload(&device, &queue, &mut scene);
+                stats.upload += upload_start.elapsed();
+                stats.instances = scene.len();
+
+                let shadow_depth_range = shadow_readback.as_mut().and_then(|r| r.try_read(&device, |depths| {
+                    depths.iter().fold((f32::MAX, f32::MIN), |(min, max), &d| (min.min(d), max.max(d)))
+                }));
+                if let Some((min, max)) = shadow_depth_range {
+                    println!("shadow map depth range: {} to {}", min, max);
+                    shadow_readback = None;
+                }
+
+                depth_range.poll(&device);
+                if let Some(gpu_timer) = &mut gpu_timer {
+                    gpu_timer.poll(&device);
+                }
+                if let Some(picked) = picking.poll(&device) {
+                    selected = picked.and_then(|i| scene.handle_at(i as usize));
+                    println!("picked: {:?}", selected);
+                }
+                // indices shift as instances are spawned and despawned
+                let selected_index = selected.and_then(|handle| scene.index_of(handle));
+                post_chain.set_enabled::<outline::Outline>(selected_index.is_some());
+                post_chain.get_mut::<outline::Outline>().unwrap().selected = selected_index.map(|i| i as u32);
+
                 let frame_time = instant.elapsed().as_secs_f32();
+                // the camera would otherwise jump by however long rendering was suspended
+                if throttle.take_resumed() {
+                    last_frame_time = frame_time;
+                }
                 delta_frame_time = frame_time - last_frame_time;
                 last_frame_time = frame_time;
-                time_rendered += delta_frame_time;
-
-                // window.set_title(&format!("fps: {}, average fps: {}, time rendered: {}", 
-                //     (1.0 / delta_frame_time) as u32,
-                //     (frames as f32 / time_rendered) as u32,
-                //     time_rendered,
-                // ));
 
-                let output = surface.get_current_texture().unwrap();
+                let output = {
+                    profiling::scope!("acquire surface texture");
+                    surface.get_current_texture().unwrap()
+                };
                 let output_view = output.texture.create_view(&TextureViewDescriptor::default());
-                let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
-                    label: Some("command block")
+                draw_params.clear();
+                let scene_draw = draw_params.push(draw_params::DrawParams {
+                    material: instance_material,
+                    ..Default::default()
                 });
+                let terrain_draw = draw_params.push(draw_params::DrawParams {
+                    material: draw_params::MATERIAL_TERRAIN,
+                    ..Default::default()
+                });
+                draw_params.flush(&queue);
+                let instance_shadow_pipeline = match draw_params::cull(instance_material) {
+                    pipeline_cache::Cull::None => &two_sided_shadow_pipeline,
+                    _ => &shadow_pipeline,
+                };
 
                 let mut light_view = light.compute_view();
-                let fit = compute_camera_fit_on_light_plane(
-                    &camera.compute_model(), 
-                    camera.far_z, 
-                    camera.near_z, 
-                    camera.width, 
-                    camera.height, 
+                let (fit_near_z, fit_far_z) = depth_range.view_z_range(camera.near_z, camera.far_z);
+                let camera_frustum = math::Frustum::new(&camera_raw.view, fit_near_z, Some(fit_far_z));
+                let fit = compute_camera_rect_on_light_plane(
+                    &camera_frustum,
                     &light_view, 
                     light.near_z, 
                     light.width, 
                     light.height,
-                );
+                ).map(|rect| {
+                    let rect = stable_fit.update(&rect, &light_rect(light.width, light.height));
+                    fit_to_rect(&rect, light.width, light.height)
+                });
 
-                if let Some((trans, scale)) = fit {
+                let unfitted_light_view = light_view;
+                if let Some(fit) = fit {
                     if shadow_fit {
-                        light_view = *light_view
-                        .translate(&Vector3::new(trans.x, trans.y, 0.0))
-                        .scale(&Scale3::new(scale.x, scale.y, 1.0))
-                        .translate(&(Vector3::new(-light.width / 2.0, -light.height / 2.0, 0.0)));
+                        light_view = light_view.compose(&fit.to_affine3());
 
                         window.set_title(&format!("trans: ({}, {}), scale: ({}, {})",
-                            trans.x, trans.y,
-                            scale.x, scale.y,
+                            fit._x, fit._y,
+                            fit.xx, fit.yy,
                         ));
                     } else {
-                        window.set_title(&format!(""));
+                        window.set_title("");
                     }
                 }
 
@@ -795,176 +1680,1284 @@ async fn run() {
                         1.0
                     ));
 
-                queue.write_buffer(
-                    &light_buffer, 
-                    0,
-                    bytes_of(&light.into_raw(&light_view)), 
-                );
-
-                if fit.is_some() {
-                    let mut shadow_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                        label: None,
-                        color_attachments: &[
-                        ],
-                        depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                            view: &shadow_texture_view,
-                            depth_ops: Some(Operations {
-                                load: LoadOp::Clear(0.0),
-                                store: true,
-                            }),
-                            stencil_ops: None,
-                        }),
-                    });
-
-                    shadow_pass.set_pipeline(&shadow_pipeline);
-                    shadow_pass.set_bind_group(0, &shadow_bind_group, &[]);
-
-                    shadow_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                    shadow_pass.set_vertex_buffer(1, instance_buffer.slice(..));
-                    shadow_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint16);
-
-                    shadow_pass.draw_indexed(
-                        0..indices.len() as u32,
-                        0,
-                        1..instances.len() as u32,
+                light_buffer.write(&queue, &[light.to_raw(&light_view, environment.ambient)]);
+                point_light_buffer.write(&queue, &[point_light.to_raw()]);
+                point_shadow.set_light(&queue, &point_light);
+                // nothing the camera sees is in the point light's range
+                let point_light_unseen = !math::Frustum::new(&camera_raw.view, camera.near_z, None)
+                    .intersects_sphere(point_light.position, point_light.range);
+
+                debug_draw.clear();
+                debug_draw.light(&light_view, light.near_z, LIGHT_GIZMO_FAR_Z);
+                if light_editor.enabled {
+                    if let Some(fit) = fit {
+                        let light_size = Vector2::new(light.width, light.height);
+                        light_editor::draw_fit(&mut debug_draw, &unfitted_light_view, light.near_z, &fit, light_size);
+                    }
+                    window.set_title(&format!(
+                        "light near_z: {:.2}, width: {:.2}, height: {:.2}",
+                        light.near_z,
+                        light.width,
+                        light.height,
+                    ));
+                }
+                debug_draw.upload(&device, &queue);
+                post_chain.get_mut::<volumetric::Volumetric>().unwrap().set_light(&light_view, light.near_z);
+                post_chain.get_mut::<rsm::IndirectLight>().unwrap().set_light(&light_view, light.near_z);
+
+                scene_draws.update(&device, &queue, &meshes, &scene.draw_items());
+                let streamed_terrain: Vec<&terrain::Chunk> = streamer.iter().flat_map(|s| s.terrain_chunks()).collect();
+                let fixed_terrain = streamer.is_none();
+                let indirect_light = post_chain.is_enabled::<rsm::IndirectLight>();
+                if traced {
+                    traced_shadows.update(
+                        &device,
+                        &queue,
+                        &scene,
+                        fixed_terrain,
+                        &camera_raw.view,
+                        camera.near_z,
+                        light.translation,
                     );
                 }
+                post_chain.get_mut::<dof::DepthOfField>().unwrap().params.near_z = camera.near_z;
 
-                {
-                    let mut light_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                        label: Some("light pass"),
-                        color_attachments: &[
-                            Some(RenderPassColorAttachment {
-                                view: &output_view,
+                // the fitted view already covers the whole shadow map
+                let shadow_scissor = fit.filter(|_| !shadow_fit).map(|fit| fit_to_shadow_scissor(
+                    &fit,
+                    light.width,
+                    light.height,
+                    shadow_texture_width,
+                    shadow_texture_height,
+                ));
+
+                let static_shadow_stale = fit.is_some()
+                    && shadow_cache.update(&light_view, scene.static_generation());
+                // a re-rendered cache has to be copied in, the throttle is kept up to date either way
+                let throttled_shadow_stale = shadow_throttle.update(&light_view, shadow_scissor, &scene);
+                let shadow_stale = fit.is_some() && (throttled_shadow_stale || static_shadow_stale);
+                stats.shadows_skipped += !shadow_stale as u32;
+
+                let encode_start = std::time::Instant::now();
+                let background = environment.clear_color();
+                let mut recorded_readback = None;
+                let camera_near_z = camera.near_z;
+                let light_pipelines = &*light_variants.entry(light_variant).or_insert_with(|| {
+                    let light_shader = shaders.get(&device, "light.wgsl", &light_variant.defines());
+                    LightPipelines::new(&device, &light_pipeline_layout, &light_shader)
+                });
+                let scene_drawing = SceneDraw {
+                    meshes: &meshes,
+                    instances: transform_pass.instances.slice(),
+                    previous_instances: transform_pass.previous_instances.slice(),
+                    draws: &scene_draws,
+                    draw_params: &draw_params,
+                    scene_draw,
+                    terrain_draw,
+                    terrain: fixed_terrain.then_some(&terrain),
+                    streamed_terrain: &streamed_terrain,
+                    bind_group: &light_bind_groups[slot],
+                };
+                let mut passes = vec![
+                    graph::Pass::new("transform", |encoder, _| transform_pass.dispatch(encoder))
+                        .write(instances_resource),
+                    graph::Pass::new("shadow", |encoder, _| {
+                        if !shadow_stale {
+                            return;
+                        }
+                        // static casters come from the cache, dynamic ones are drawn over them
+                        shadow_cache.copy_to(encoder, &shadow_texture);
+                        let mut shadow_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                            label: None,
+                            color_attachments: &[
+                            ],
+                            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                                view: &shadow_texture_view,
+                                depth_ops: Some(Operations {
+                                    load: LoadOp::Load,
+                                    store: true,
+                                }),
+                                stencil_ops: None,
+                            }),
+                        });
+
+                        // texels outside the camera's view are left as cached
+                        if let Some((x, y, width, height)) = shadow_scissor {
+                            if width == 0 || height == 0 {
+                                return;
+                            }
+                            shadow_pass.set_scissor_rect(x, y, width, height);
+                        }
+
+                        shadow_pass.set_pipeline(instance_shadow_pipeline);
+                        shadow_pass.set_bind_group(0, &shadow_bind_group, &[]);
+
+                        shadow_pass.set_vertex_buffer(1, transform_pass.instances.slice());
+                        scene_draws.draw(&mut shadow_pass, &meshes, |item| !item.is_static);
+                        // the grass sways, so it's drawn every frame rather than cached
+                        if fixed_terrain {
+                            vegetation.draw_shadow(&mut shadow_pass);
+                        }
+                    })
+                        .read(instances_resource)
+                        .read(static_shadow_map_resource)
+                        .write(shadow_map_resource),
+                    // both hemispheres in one multiview pass where supported, one pass each without
+                    graph::Pass::new("point shadow", |encoder, _| {
+                        if !point_light.enabled
+                            || point_light.shadow != point_shadow::PointShadowMode::DualParaboloid
+                            || point_light_unseen
+                        {
+                            return;
+                        }
+                        for &hemisphere in point_shadow.passes() {
+                            let mut pass = point_shadow.begin_pass(encoder, hemisphere);
+                            pass.set_vertex_buffer(1, transform_pass.instances.slice());
+                            scene_draws.draw(&mut pass, &meshes, |_| true);
+                        }
+                    })
+                        .read(instances_resource)
+                        .write(point_shadow_map_resource),
+                    // the levels rendered this frame, the light pass shows the first one
+                    portal_level_resources[..portal_levels.len()].iter().fold(graph::Pass::new("portal", |encoder, resources| {
+                        // deepest first, every level but the deepest shows the next on its entrance
+                        for (level, &(view, camera)) in portal_levels.iter().enumerate().rev() {
+                            let mut level_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                                label: Some("portal level pass"),
+                                color_attachments: &[
+                                    Some(RenderPassColorAttachment {
+                                        view: resources.view(portal_level_resources[level]),
+                                        resolve_target: None,
+                                        ops: Operations {
+                                            load: LoadOp::Clear(background),
+                                            store: true,
+                                        },
+                                    }),
+                                    Some(RenderPassColorAttachment {
+                                        view: resources.view(portal_object_id_resource),
+                                        resolve_target: None,
+                                        ops: Operations {
+                                            load: LoadOp::Clear(Color::TRANSPARENT),
+                                            store: false,
+                                        },
+                                    }),
+                                    Some(RenderPassColorAttachment {
+                                        view: resources.view(portal_object_motion_resource),
+                                        resolve_target: None,
+                                        ops: Operations {
+                                            load: LoadOp::Clear(Color::TRANSPARENT),
+                                            store: false,
+                                        },
+                                    }),
+                                ],
+                                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                                    view: resources.view(portal_depth_resource),
+                                    depth_ops: Some(Operations {
+                                        load: LoadOp::Clear(0.0),
+                                        store: true,
+                                    }),
+                                    stencil_ops: Some(Operations {
+                                        load: LoadOp::Clear(0),
+                                        store: true,
+                                    }),
+                                }),
+                            });
+                            let frustum = math::Frustum::new(&view, camera_near_z, None);
+                            scene_drawing.draw(&mut level_pass, light_pipelines, false, camera, &frustum);
+                            drop(level_pass);
+
+                            if level + 1 < portal_levels.len() {
+                                let bind_group = portal.bind_group(
+                                    &device,
+                                    camera_ring.binding(),
+                                    resources.view(portal_level_resources[level + 1]),
+                                );
+                                let mut surface_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                                    label: Some("portal surface pass"),
+                                    color_attachments: &[Some(RenderPassColorAttachment {
+                                        view: resources.view(portal_level_resources[level]),
+                                        resolve_target: None,
+                                        ops: Operations {
+                                            load: LoadOp::Load,
+                                            store: true,
+                                        },
+                                    })],
+                                    depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                                        view: resources.view(portal_depth_resource),
+                                        depth_ops: Some(Operations {
+                                            load: LoadOp::Load,
+                                            store: false,
+                                        }),
+                                        stencil_ops: None,
+                                    }),
+                                });
+                                portal.draw(&mut surface_pass, &bind_group, camera);
+                            }
+                        }
+                    })
+                        .read(instances_resource)
+                        .read(shadow_map_resource)
+                        .read(point_shadow_map_resource)
+                        .write(portal_depth_resource)
+                        .write(portal_object_id_resource)
+                        .write(portal_object_motion_resource), |pass, &level| pass.write(level)),
+                    portal_level_resources[..portal_levels.len().min(1)].iter().fold(graph::Pass::new("light", |encoder, resources| {
+                        let reflection = mirror.as_ref().zip(reflection);
+                        if let Some((mirror, _)) = reflection {
+                            let mut mask_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                                label: Some("mirror mask pass"),
+                                color_attachments: &[],
+                                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                                    view: resources.view(scene_depth_resource),
+                                    depth_ops: Some(Operations {
+                                        load: LoadOp::Clear(0.0),
+                                        store: true,
+                                    }),
+                                    stencil_ops: Some(Operations {
+                                        load: LoadOp::Clear(0),
+                                        store: true,
+                                    }),
+                                }),
+                            });
+                            mirror.mark(&mut mask_pass);
+                        }
+
+                        // the reflected scene first, then the scene itself over the mirror's depth
+                        let scene_passes = reflection
+                            .map(|(_, (view, camera))| (
+                                "reflected light pass",
+                                true,
+                                view,
+                                camera,
+                                None,
+                            ))
+                            .into_iter()
+                            .chain(eyes.is_empty().then_some((
+                                "light pass",
+                                false,
+                                camera_raw.view,
+                                camera_offset,
+                                None,
+                            )))
+                            .chain(eyes.iter().map(|&(view, camera, viewport)| (
+                                "eye light pass",
+                                false,
+                                view,
+                                camera,
+                                Some(viewport),
+                            )));
+                        for (i, (label, reflected, view, camera, viewport)) in scene_passes.enumerate() {
+                            if let (1, Some((mirror, _))) = (i, reflection) {
+                                let mut surface_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                                    label: Some("mirror surface pass"),
+                                    color_attachments: &[Some(RenderPassColorAttachment {
+                                        view: resources.view(scene_color_resource),
+                                        resolve_target: None,
+                                        ops: Operations {
+                                            load: LoadOp::Load,
+                                            store: true,
+                                        },
+                                    })],
+                                    depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                                        view: resources.view(scene_depth_resource),
+                                        depth_ops: Some(Operations {
+                                            load: LoadOp::Load,
+                                            store: true,
+                                        }),
+                                        stencil_ops: Some(Operations {
+                                            load: LoadOp::Load,
+                                            store: true,
+                                        }),
+                                    }),
+                                });
+                                mirror.draw_surface(&mut surface_pass);
+                            }
+
+                            let color_load = |clear| if i == 0 { LoadOp::Clear(clear) } else { LoadOp::Load };
+                            // the mask pass cleared them when there's a reflection
+                            let clear_depth = i == 0 && reflection.is_none();
+                            let mut light_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                                label: Some(label),
+                                color_attachments: &[
+                                    Some(RenderPassColorAttachment {
+                                        view: resources.view(scene_color_resource),
+                                        resolve_target: None,
+                                        ops: Operations {
+                                            load: color_load(background),
+                                            store: true,
+                                        },
+                                    }),
+                                    Some(RenderPassColorAttachment {
+                                        view: resources.view(object_id_resource),
+                                        resolve_target: None,
+                                        ops: Operations {
+                                            load: color_load(Color::TRANSPARENT),
+                                            store: true,
+                                        },
+                                    }),
+                                    Some(RenderPassColorAttachment {
+                                        view: resources.view(object_motion_resource),
+                                        resolve_target: None,
+                                        ops: Operations {
+                                            load: color_load(Color::TRANSPARENT),
+                                            store: true,
+                                        },
+                                    }),
+                                ],
+                                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                                    view: resources.view(scene_depth_resource),
+                                    depth_ops: Some(Operations {
+                                        load: if clear_depth { LoadOp::Clear(0.0) } else { LoadOp::Load },
+                                        store: true,
+                                    }),
+                                    stencil_ops: Some(Operations {
+                                        load: if clear_depth { LoadOp::Clear(0) } else { LoadOp::Load },
+                                        store: true,
+                                    }),
+                                }),
+                            });
+                            light_pass.set_stencil_reference(mirror::STENCIL_REFERENCE);
+                            if let Some((x, y, width, height)) = viewport {
+                                light_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                            }
+                            let frustum = math::Frustum::new(&view, camera_near_z, None);
+                            scene_drawing.draw(&mut light_pass, light_pipelines, reflected, camera, &frustum);
+                            // the grass is drawn with the camera's own view, and grows on the fixed terrain
+                            if camera == camera_offset && fixed_terrain {
+                                vegetation.draw(&mut light_pass);
+                            }
+                        }
+
+                        if let Some(&first_level) = portal_level_resources.first().filter(|_| !portal_levels.is_empty()) {
+                            let bind_group = portal.bind_group(&device, camera_ring.binding(), resources.view(first_level));
+                            let mut surface_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                                label: Some("portal surface pass"),
+                                color_attachments: &[Some(RenderPassColorAttachment {
+                                    view: resources.view(scene_color_resource),
+                                    resolve_target: None,
+                                    ops: Operations {
+                                        load: LoadOp::Load,
+                                        store: true,
+                                    },
+                                })],
+                                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                                    view: resources.view(scene_depth_resource),
+                                    depth_ops: Some(Operations {
+                                        load: LoadOp::Load,
+                                        store: true,
+                                    }),
+                                    stencil_ops: None,
+                                }),
+                            });
+                            portal.draw(&mut surface_pass, &bind_group, camera_offset);
+                        }
+                    })
+                        .read(instances_resource)
+                        .read(shadow_map_resource)
+                        .read(point_shadow_map_resource)
+                        .write(scene_color_resource)
+                        .write(scene_depth_resource)
+                        .write(object_id_resource)
+                        .write(object_motion_resource), |pass, &level| pass.read(level)),
+                    graph::Pass::new("debug draw", |encoder, resources| {
+                        let mut debug_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                            label: Some("debug draw pass"),
+                            color_attachments: &[Some(RenderPassColorAttachment {
+                                view: resources.view(scene_color_resource),
                                 resolve_target: None,
                                 ops: Operations {
-                                    load: LoadOp::Clear(Color{
-                                        r: 0.05,
-                                        g: 0.02,
-                                        b: 0.07,
-                                        a: 1.0,
-                                    }),
+                                    load: LoadOp::Load,
                                     store: true,
                                 },
+                            })],
+                            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                                view: resources.view(scene_depth_resource),
+                                depth_ops: Some(Operations {
+                                    load: LoadOp::Load,
+                                    store: true,
+                                }),
+                                stencil_ops: None,
                             }),
-                        ],
-                        depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                            view: &depth_texture_view,
-                            depth_ops: Some(Operations {
-                                load: LoadOp::Clear(0.0),
-                                store: true,
-                            }),
-                            stencil_ops: None,
-                        }),
-                    });
+                        });
+                        debug_draw.draw(&mut debug_pass);
+                    })
+                        .read(scene_depth_resource)
+                        .write(scene_color_resource)
+                        .write(scene_depth_resource),
+                    graph::Pass::new("post process", |encoder, resources| {
+                        if resources.reallocated() {
+                            post_chain.invalidate();
+                        }
+                        post_chain.run(
+                            &device,
+                            &queue,
+                            encoder,
+                            resources.view(scene_color_resource),
+                            resources.depth_view(scene_depth_resource),
+                            resources.view(object_id_resource),
+                            resources.view(object_motion_resource),
+                            &output_view,
+                        );
+                    })
+                        .read(scene_color_resource)
+                        .read(scene_depth_resource)
+                        .read(object_id_resource)
+                        .read(object_motion_resource)
+                        .read(shadow_map_resource)
+                        .read(rsm_resource)
+                        .write(surface_resource),
+                    graph::Pass::new("picking", |encoder, resources| {
+                        picking.record(&device, encoder, resources.texture(object_id_resource));
+                    })
+                        .read(object_id_resource)
+                        .side_effect(),
+                ];
+
+                if depth_range.enabled {
+                    // for a later frame's fit, once read back
+                    passes.push(graph::Pass::new("depth range", |encoder, resources| {
+                        depth_range.record(
+                            &device,
+                            &queue,
+                            encoder,
+                            resources.texture(scene_depth_resource),
+                            resources.depth_view(scene_depth_resource),
+                        );
+                    })
+                        .read(scene_depth_resource)
+                        .side_effect());
+                }
+
+                if traced {
+                    // for the next frame's light pass, which is why it can come after this one's
+                    passes.push(graph::Pass::new("traced shadows", |encoder, resources| {
+                        traced_shadows.dispatch(&device, encoder, resources.depth_view(scene_depth_resource));
+                    })
+                        .read(scene_depth_resource)
+                        .side_effect());
+                }
 
-                    light_pass.set_pipeline(&light_pipeline);
-                    light_pass.set_bind_group(0, &light_bind_group, &[]);
+                if indirect_light {
+                    passes.push(graph::Pass::new("reflective shadow map", |encoder, _| {
+                        let mut rsm_pass = reflective_shadow_map.begin_pass(encoder);
+                        rsm_pass.set_pipeline(&rsm_pipeline);
+                        rsm_pass.set_bind_group(0, &shadow_bind_group, &[]);
+                        rsm_pass.set_vertex_buffer(1, transform_pass.instances.slice());
+                        scene_draws.draw(&mut rsm_pass, &meshes, |_| true);
+
+                        rsm_pass.set_pipeline(&terrain_rsm_pipeline);
+                        if fixed_terrain {
+                            terrain.draw(&mut rsm_pass, |_| true);
+                        }
+                        for chunk in &streamed_terrain {
+                            chunk.draw(&mut rsm_pass);
+                        }
+                    })
+                        .read(instances_resource)
+                        .write(rsm_resource));
+                }
 
-                    light_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                    light_pass.set_vertex_buffer(1, instance_buffer.slice(..));
-                    light_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint16);
+                if static_shadow_stale {
+                    passes.push(graph::Pass::new("static shadow", |encoder, _| {
+                        let mut shadow_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                            label: Some("static shadow pass"),
+                            color_attachments: &[],
+                            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                                view: &shadow_cache.view,
+                                depth_ops: Some(Operations {
+                                    load: LoadOp::Clear(0.0),
+                                    store: true,
+                                }),
+                                stencil_ops: None,
+                            }),
+                        });
+
+                        shadow_pass.set_pipeline(instance_shadow_pipeline);
+                        shadow_pass.set_bind_group(0, &shadow_bind_group, &[]);
+
+                        shadow_pass.set_vertex_buffer(1, transform_pass.instances.slice());
+                        scene_draws.draw(&mut shadow_pass, &meshes, |item| item.is_static);
+
+                        // the terrain is static, and its shadows may fall into view from anywhere
+                        shadow_pass.set_pipeline(&terrain_shadow_pipeline);
+                        if fixed_terrain {
+                            terrain.draw(&mut shadow_pass, |_| true);
+                        }
+                        for chunk in &streamed_terrain {
+                            chunk.draw(&mut shadow_pass);
+                        }
+                    })
+                        .read(instances_resource)
+                        .write(static_shadow_map_resource));
+                }
 
-                    light_pass.draw_indexed(
-                        0..indices.len() as u32, 
-                        0, 
-                        0..instances.len() as u32
-                    );
+                if shadow_readback_requested && shadow_readback.is_none() {
+                    passes.push(graph::Pass::new("shadow readback", |encoder, _| {
+                        recorded_readback = Some(readback::Readback::texture(
+                            &device,
+                            encoder,
+                            &shadow_texture,
+                            TextureAspect::DepthOnly,
+                            Origin3d::ZERO,
+                            shadow_texture_width,
+                            shadow_texture_height,
+                        ));
+                    })
+                        .read(shadow_map_resource)
+                        .side_effect());
                 }
 
-                
-                queue.submit(std::iter::once(encoder.finish()));
+                let mut command_buffers = render_graph.execute(&device, passes, gpu_timer.as_mut());
+                command_buffers.extend(gpu_timer.as_mut().map(|gpu_timer| gpu_timer.resolve(&device)));
+                let submission = queue.submit(command_buffers);
+                stats.encode += encode_start.elapsed();
                 output.present();
+                profiling::finish_frame!();
+                for path in frame_capture.new_captures() {
+                    println!("captured frame to {}", path.display());
+                }
+                picking.submitted(&submission);
+                depth_range.submitted(&submission);
+                if let Some(gpu_timer) = &mut gpu_timer {
+                    gpu_timer.submitted(&submission);
+                }
+
+                if let Some(report) = stats.end_frame(delta_frame_time) {
+                    println!("{}", report);
+                    if let Some(gpu_timer) = &gpu_timer {
+                        println!("{}", gpu_timer.report());
+                    }
+                }
+
+                if let Some(mut readback) = recorded_readback {
+                    readback.map(submission.clone());
+                    shadow_readback = Some(readback);
+                }
+                shadow_readback_requested = false;
+                frames.end(submission);
             }
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                WindowEvent::Resized(size) => {
-                    if config.width == 0 && config.height == 0 {
-                        last_frame_time = instant.elapsed().as_secs_f32();
+            Event::WindowEvent { event, .. } => {
+                throttle.request_redraw();
+                // physical size of the window, after a resize or a change of scale factor
+                let mut resized = None;
+                match event {
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::CursorMoved { position, .. } => {
+                        let [x, y] = [position.x as f32, position.y as f32];
+                        let [dx, dy] = [x - input.cursor_position[0], y - input.cursor_position[1]];
+                        input.cursor_position = [x, y];
+                        let size = [config.width as f32, config.height as f32];
+                        if let Some(pivot) = orbit_pivot {
+                            camera_goal.orbit(pivot, -dx * mouse_sensitivity, dy * mouse_sensitivity);
+                        } else if let Some(translation) = light_editor.drag(input.cursor_position, size, &camera.to_raw().view) {
+                            light.translation = translation;
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } if input.modifiers.alt() => {
+                        const ORBIT_DISTANCE: f32 = 5.0;
+                        let ahead = camera_goal.translation + camera_goal.direction() * ORBIT_DISTANCE;
+                        let pivot = selected.and_then(|handle| scene.get(handle)).map_or(ahead, |instance| instance.translation);
+                        orbit_pivot = Some(pivot).filter(|&pivot| (pivot - camera_goal.translation).length() > 1e-3);
                     }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        let size = [config.width as f32, config.height as f32];
+                        let view = camera.to_raw().view;
+                        if !light_editor.press(input.cursor_position, size, &view, light.translation) {
+                            picking.request(input.cursor_position[0], input.cursor_position[1]);
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Released,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        light_editor.release();
+                        orbit_pivot = None;
+                    }
+                    // mouse look until Escape
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Right,
+                        ..
+                    } => {
+                        let grabbed = input.is_mouse_relative() || input.grab_mouse(&window);
+                        if !grabbed {
+                            println!("the cursor can't be grabbed");
+                        }
+                    }
+                    WindowEvent::ModifiersChanged(modifiers) => input.modifiers = modifiers,
+                    WindowEvent::Touch(touch) => {
+                        input.touch(touch.id, touch.phase, [touch.location.x as f32, touch.location.y as f32]);
+                    }
+                    WindowEvent::Resized(size) => resized = Some(size),
+                    WindowEvent::Focused(focused) => {
+                        throttle.set_focused(focused);
+                        input.set_focused(&window, focused);
+                    }
+                    WindowEvent::Occluded(occluded) => throttle.set_occluded(occluded),
+                    WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } => {
+                        post_chain.get_mut::<outline::Outline>().unwrap().scale_factor = scale_factor as f32;
+                        let logical_size = new_inner_size.to_logical::<f32>(scale_factor);
+                        println!(
+                            "scale factor: {}, logical size: {}x{}",
+                            scale_factor,
+                            logical_size.width,
+                            logical_size.height,
+                        );
+                        resized = Some(*new_inner_size);
+                    }
+                    _ => {}
+                }
 
+                if let Some(size) = resized {
+                    throttle.set_minimized(size.width == 0 || size.height == 0);
                     config.width = size.width;
                     config.height = size.height;
                     if size.width > 0 && size.height > 0 {
-
                         surface.configure(&device, &config);
-                        (depth_texture, depth_texture_view) = create_depth_texture(&device, size.width, size.height);
-                        camera.width = camera.height * config.width as f32 / size.height as f32;
+                        picking.resize(size.width, size.height);
+                        traced_shadows.resize(&device, size.width, size.height);
+                        light_bind_groups_stale = true;
+                        render_graph.resize(size.width, size.height);
+                        post_chain.resize(&device, size.width, size.height);
+                        camera.width = camera.height * size.width as f32 / size.height as f32;
                     }
                 }
-                _ => {}
             }
-            Event::DeviceEvent {event, ..} => match event {
-                DeviceEvent::Key(KeyboardInput {
+            Event::DeviceEvent {
+                event: DeviceEvent::Key(KeyboardInput {
                     virtual_keycode: Some(virtual_keycode),
                     state,
                     ..
-                }) => {
-                    input.set_key_pressed(virtual_keycode, state == ElementState::Pressed);
-                },
-                _ => {}
+                }),
+                ..
+            } => {
+                input.set_key_pressed(virtual_keycode, state == ElementState::Pressed);
+                throttle.request_redraw();
+            }
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                input.add_mouse_motion(delta);
+                if input.is_mouse_relative() {
+                    throttle.request_redraw();
+                }
             }
             Event::MainEventsCleared => {
-                if config.width == 0 || config.height == 0 {
-                    return;
+                for line in console.lines() {
+                    throttle.request_redraw();
+                    match line.trim() {
+                        "env" => print!("{}", environment.to_text()),
+                        line if line.starts_with("render") => match throttle::RenderMode::parse(line["render".len()..].trim()) {
+                            Some(mode) => {
+                                throttle.mode = mode;
+                                println!("render mode: {:?}", mode);
+                            }
+                            None => println!("usage: render continuous|reactive"),
+                        },
+                        line if line.starts_with("stress") => match line["stress".len()..].trim().parse() {
+                            Ok(count) => {
+                                stress_test.count = count;
+                                respawn_stress_test(&mut stress_test, &mut scene);
+                            }
+                            Err(_) => println!("usage: stress <instance count>"),
+                        },
+                        line if line.starts_with("shadows") => match line["shadows".len()..].trim() {
+                            mode @ ("traced" | "mapped") => {
+                                light_variant.set(preprocess::ShaderVariant::TRACED_SHADOWS, mode == "traced");
+                                println!("shadows: {}", mode);
+                            }
+                            state @ ("throttle on" | "throttle off") => {
+                                shadow_throttle.enabled = state == "throttle on";
+                                println!("shadow throttling: {}", shadow_throttle.enabled);
+                            }
+                            state @ ("sdsm on" | "sdsm off") => {
+                                depth_range.enabled = state == "sdsm on";
+                                println!("shadow fit to the seen depths: {}", depth_range.enabled);
+                            }
+                            args if matches!(args.split_whitespace().next(), Some("margin" | "hysteresis")) => {
+                                let (name, value) = args.split_once(' ').unwrap_or((args, ""));
+                                let setting = match name {
+                                    "margin" => &mut stable_fit.margin,
+                                    _ => &mut stable_fit.hysteresis,
+                                };
+                                match value.trim().parse::<f32>() {
+                                    Ok(value) if value >= 0.0 => {
+                                        *setting = value;
+                                        println!("shadow fit {}: {}", name, value);
+                                    }
+                                    _ => println!("usage: shadows {} <fraction of the fitted size>", name),
+                                }
+                            }
+                            _ => println!("usage: shadows traced|mapped|throttle on|throttle off|sdsm on|sdsm off|margin <x>|hysteresis <x>"),
+                        },
+                        line if line.starts_with("point") => match line["point".len()..].trim() {
+                            state @ ("on" | "off") => {
+                                point_light.enabled = state == "on";
+                                println!("point light: {}", state);
+                            }
+                            state @ ("multiview on" | "multiview off") => {
+                                point_shadow.use_multiview = state == "multiview on";
+                                match point_shadow.supports_multiview() {
+                                    true => println!("point shadow in a single multiview pass: {}", point_shadow.use_multiview),
+                                    false => println!("point shadow: multiview isn't supported, a pass per hemisphere"),
+                                }
+                            }
+                            "here" => {
+                                point_light.position = camera.translation;
+                                println!("point light at {:?}", point_light.position);
+                            }
+                            args if args.starts_with("shadows") => match point_shadow::PointShadowMode::parse(args["shadows".len()..].trim()) {
+                                Some(mode) => {
+                                    point_light.shadow = mode;
+                                    println!("point light shadows: {:?}", mode);
+                                }
+                                None => println!("usage: point shadows none|paraboloid"),
+                            },
+                            args if args.starts_with("color") => {
+                                let hsv: Result<Vec<f32>, _> = args["color".len()..].split_whitespace().map(str::parse).collect();
+                                match hsv.as_deref() {
+                                    Ok(&[hue, saturation, value]) => {
+                                        point_light.color = math::Color::from_hsv(hue, saturation, value);
+                                    }
+                                    _ => println!("usage: point color <hue in degrees> <saturation> <value>"),
+                                }
+                                let (hue, saturation, value) = point_light.color.to_hsv();
+                                println!("point light color: hue {:.0}, saturation {:.2}, value {:.2}", hue, saturation, value);
+                            }
+                            _ => println!("usage: point on|off|here|color <h> <s> <v>|shadows none|paraboloid|multiview on|off"),
+                        },
+                        line if line.starts_with("recall") => match math::easing::Easing::parse(&line["recall".len()..]) {
+                            Some(easing) => {
+                                recall_easing = easing;
+                                println!("camera recall easing: {:?} {:?}", easing.ease, easing.direction);
+                            }
+                            None => println!("usage: recall linear|quad|cubic|expo|back|elastic in|out|in-out"),
+                        },
+                        line if line.starts_with("path") => match math::curves::Basis::parse(line["path".len()..].trim()) {
+                            Some(basis) => {
+                                camera_path.set_basis(basis);
+                                println!("camera path basis: {:?}", basis);
+                            }
+                            None => println!("usage: path catmull-rom|bspline|bezier"),
+                        },
+                        line if line.starts_with("vegetation") => match line["vegetation".len()..]
+                            .trim()
+                            .strip_prefix("shadows")
+                            .and_then(|casting| vegetation::ShadowCasting::parse(casting.trim()))
+                        {
+                            Some(casting) => {
+                                vegetation.shadow_casting = casting;
+                                println!("vegetation shadows: {:?}", casting);
+                                shadow_throttle.invalidate();
+                            }
+                            None => println!("usage: vegetation shadows opaque|alpha-tested"),
+                        },
+                        line if line.starts_with("stereo") => match line["stereo".len()..].trim() {
+                            state @ ("on" | "off") => {
+                                stereo.enabled = state == "on";
+                                println!("stereo: {}", state);
+                            }
+                            _ => println!("usage: stereo on|off"),
+                        },
+                        line if line.starts_with("tonemap") => match post::Tonemap::parse(line["tonemap".len()..].trim()) {
+                            Some(tonemap) => {
+                                post_chain.resolve.tonemap = tonemap;
+                                println!("tonemap: {:?}", tonemap);
+                            }
+                            None => println!("usage: tonemap off|reinhard|aces"),
+                        },
+                        line if line.starts_with("exposure") => match line["exposure".len()..].trim().parse::<f32>() {
+                            Ok(exposure) if exposure > 0.0 => {
+                                post_chain.resolve.exposure = exposure;
+                                println!("exposure: {}", exposure);
+                            }
+                            _ => println!("usage: exposure <scale of the scene color>"),
+                        },
+                        line if line.starts_with("gi") => match line["gi".len()..].trim() {
+                            state @ ("on" | "off") => {
+                                post_chain.set_enabled::<rsm::IndirectLight>(state == "on");
+                                println!("indirect light: {}", state);
+                            }
+                            _ => println!("usage: gi on|off"),
+                        },
+                        "lightmap bake" => {
+                            // the fixed terrain shadows and reflects, the streamed one comes and goes
+                            let terrain_triangles = match streamer {
+                                None => heightmap.triangles(terrain.origin),
+                                Some(_) => vec![],
+                            };
+                            let light = lightmap::BakeLight {
+                                position: light.translation,
+                                direction: light.direction(),
+                                radiance: light.color.to_rgb_array().map(|channel| channel * light.intensity),
+                            };
+                            bake_lightmap(&mut scene, &unwrapped_meshes, terrain_triangles, &light, &lightmap, &queue);
+                        }
+                        "lightmap clear" => {
+                            for handle in scene.static_handles() {
+                                scene.get_mut(handle).unwrap().lightmap = None;
+                            }
+                            println!("lightmap: cleared");
+                        }
+                        "save" => match environment.save() {
+                            Ok(()) => println!("saved {}", environment::Environment::FILE),
+                            Err(e) => println!("{}", e),
+                        },
+                        line => match environment.set_line(line) {
+                            Ok(()) if line.starts_with("sun_direction") => {
+                                light.point_along(environment.sun(), floating_origin.to_local(SUN_FOCUS.into()), SUN_DISTANCE);
+                            }
+                            Ok(()) => {}
+                            Err(e) => println!("{}", e),
+                        },
+                    }
                 }
 
-                instances[0].translation = light.translation;
-                instances[0].translation.z += light.near_z + 0.001;
+                if !throttle.should_render(std::time::Instant::now(), control_flow) {
+                    return;
+                }
 
-                camera.update_forward();
+                camera_goal.update_forward();
 
                 use VirtualKeyCode::*;
-                let w_pressed = input.is_key_pressed(W);
-                let s_pressed = input.is_key_pressed(S);
-                let d_pressed = input.is_key_pressed(D);
-                let a_pressed = input.is_key_pressed(A);
+                // held Ctrl makes chords of the keys instead, such as Ctrl+S
+                let moving_keys = !input.modifiers.ctrl();
+                let w_pressed = moving_keys && input.is_key_pressed(W);
+                let s_pressed = moving_keys && input.is_key_pressed(S);
+                let d_pressed = moving_keys && input.is_key_pressed(D);
+                let a_pressed = moving_keys && input.is_key_pressed(A);
 
                 let up_pressed = input.is_key_pressed(Up);
                 let down_pressed = input.is_key_pressed(Down);
                 let right_pressed = input.is_key_pressed(Right);
                 let left_pressed = input.is_key_pressed(Left);
 
-                let delta_translation = camera.forward * camera_translation_speed * delta_frame_time;
+                let sprint = if input.modifiers.shift() { camera_sprint_factor } else { 1.0 };
+                let delta_translation = camera_goal.forward * camera_translation_speed * sprint * delta_frame_time;
                 let delta_rotation = camera_rotation_speed * delta_frame_time;
 
                 let e_pressed = input.is_key_pressed(E);
                 let r_pressed = input.is_key_pressed(R);
 
                 if w_pressed && !s_pressed {
-                    camera.translation += delta_translation;
+                    camera_goal.translation += delta_translation;
                 } else if !w_pressed && s_pressed {
-                    camera.translation -= delta_translation;
+                    camera_goal.translation -= delta_translation;
                 }
                 if d_pressed && !a_pressed {
-                    camera.translation.z -= delta_translation.x;
-                    camera.translation.x += delta_translation.z;
+                    camera_goal.translation.z -= delta_translation.x;
+                    camera_goal.translation.x += delta_translation.z;
                 } else if !d_pressed && a_pressed {
-                    camera.translation.z += delta_translation.x;
-                    camera.translation.x -= delta_translation.z;
+                    camera_goal.translation.z += delta_translation.x;
+                    camera_goal.translation.x -= delta_translation.z;
                 }
                 if up_pressed && !down_pressed {
-                    camera.xz_to_y += delta_rotation;
+                    camera_goal.xz_to_y += delta_rotation;
                 } else if !up_pressed && down_pressed {
-                    camera.xz_to_y -= delta_rotation;
+                    camera_goal.xz_to_y -= delta_rotation;
                 }
                 if right_pressed && !left_pressed {
-                    camera.z_to_x += delta_rotation;
+                    camera_goal.z_to_x += delta_rotation;
                 } else if !right_pressed && left_pressed {
-                    camera.z_to_x -= delta_rotation;
+                    camera_goal.z_to_x -= delta_rotation;
+                }
+                let [mouse_x, mouse_y] = input.take_mouse_delta();
+                camera_goal.z_to_x += mouse_x * mouse_sensitivity;
+                camera_goal.xz_to_y -= mouse_y * mouse_sensitivity;
+                // a tap picks like a click, a drag looks around like the grabbed mouse, a pinch zooms
+                for gesture in input.take_touch_gestures() {
+                    match gesture {
+                        input::TouchGesture::Tap([x, y]) => picking.request(x, y),
+                        input::TouchGesture::Drag([x, y]) => {
+                            camera_goal.z_to_x -= x * mouse_sensitivity;
+                            camera_goal.xz_to_y += y * mouse_sensitivity;
+                        }
+                        input::TouchGesture::Pinch(scale) => {
+                            camera.height = (camera.height / scale).clamp(0.2, 8.0);
+                            camera.width = camera.height * config.width as f32 / config.height.max(1) as f32;
+                        }
+                    }
+                }
+                if input.is_key_pressed(Escape) && !input.was_key_pressed(Escape) {
+                    input.release_mouse(&window);
+                }
+                // like `save` on the console
+                if input.is_chord_pressed(ModifiersState::CTRL, S) {
+                    match environment.save() {
+                        Ok(()) => println!("saved {}", environment::Environment::FILE),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+                // kept in one turn, so turning around for long doesn't cost precision
+                camera_goal.z_to_x = camera_goal.z_to_x.rem_euclid(std::f32::consts::TAU);
+                camera.damp_towards(&camera_goal, camera_damping, delta_frame_time);
+                if input.is_key_pressed(X) && !input.was_key_pressed(X) {
+                    camera_path.push(camera.translation, camera.compute_rotation());
+                    println!("camera path: {} placements", camera_path.len());
+                }
+                if input.is_key_pressed(Y) && !input.was_key_pressed(Y) {
+                    println!("camera path playing: {}", camera_path.toggle_playing());
+                }
+                if input.is_key_pressed(Z) && !input.was_key_pressed(Z) {
+                    camera_path.clear();
+                    println!("camera path cleared");
+                }
+                if input.is_key_pressed(Key7) && !input.was_key_pressed(Key7) {
+                    camera_bookmark = Some((camera.translation, camera.compute_rotation()));
+                    println!("camera bookmarked");
+                }
+                if input.is_key_pressed(Key8) && !input.was_key_pressed(Key8) {
+                    if let Some(bookmark) = camera_bookmark {
+                        let from = (camera.translation, camera.compute_rotation());
+                        camera_recall = Some(math::easing::Tween::new(from, bookmark, 1.0, recall_easing));
+                    }
+                }
+                let recalled = camera_recall.as_mut().map(|recall| recall.advance(delta_frame_time));
+                if camera_recall.as_ref().is_some_and(|recall| recall.is_finished()) {
+                    camera_recall = None;
+                }
+                if let Some((translation, rotation)) = recalled.or_else(|| camera_path.advance(delta_frame_time)) {
+                    camera.translation = translation;
+                    camera.set_rotation(rotation);
+                    camera_goal = camera.clone();
                 }
                 if e_pressed && !r_pressed {
                     light.translation.z += 10.0 * delta_frame_time;
                 } else if !e_pressed && r_pressed {
                     light.translation.z -= 10.0 * delta_frame_time;
                 }
+                if let Some(shift) = floating_origin.update(camera.translation) {
+                    let by = -shift;
+                    camera.translation += by;
+                    camera_goal.translation += by;
+                    camera_path.translate(by);
+                    if let Some((translation, _)) = &mut camera_bookmark {
+                        *translation += by;
+                    }
+                    if let Some(recall) = &mut camera_recall {
+                        recall.from.0 += by;
+                        recall.to.0 += by;
+                    }
+                    // the frame before as seen from where the world is now
+                    let mut shift_view = math::Affine3::IDENTITY;
+                    previous_camera_view = shift_view.translate(&shift).compose(&previous_camera_view);
+                    light.translation += by;
+                    point_light.position += by;
+
+                    scene.translate_all(by);
+                    transform_pass.invalidate_previous();
+                    for pose in [&mut screw_poses.0, &mut screw_poses.1] {
+                        *pose = math::Motor::new(pose.rotor(), pose.translation() + by);
+                    }
+                    stress_test.center += by;
+                    if let Some(mirror) = &mut mirror {
+                        mirror.center += by;
+                    }
+                    portal.entrance.center += by;
+                    portal.exit.center += by;
+
+                    terrain.translate(&device, &heightmap, by);
+                    traced_shadows.set_terrain(heightmap.triangles(terrain.origin));
+                    for blade in &mut vegetation_blades {
+                        blade.position[0] += by.x;
+                        blade.position[2] += by.z;
+                    }
+                    vegetation.set_blades(&device, &queue, &vegetation_blades);
+                    if let Some(streamer) = &mut streamer {
+                        streamer.rebase(&device, shift);
+                    }
+                    shadow_cache.invalidate();
+                    shadow_throttle.invalidate();
+                    let origin = floating_origin.origin();
+                    println!("floating origin moved to {:.0}, {:.0}", origin.x, origin.z);
+                }
 
                 if input.is_key_pressed(Space) && !input.was_key_pressed(Space) {
                     shadow_fit = !shadow_fit;
                 }
 
+                if input.is_key_pressed(Q) && !input.was_key_pressed(Q) {
+                    light_editor.enabled = !light_editor.enabled;
+                    light_editor.release();
+                    window.set_title("");
+                    println!("light editor: {}", light_editor.enabled);
+                }
+                if light_editor.enabled {
+                    // grows by half per second
+                    let growth = 1.5f32.powf(delta_frame_time);
+                    for (shrink, grow, value) in [
+                        (Key1, Key2, &mut light.near_z),
+                        (Key3, Key4, &mut light.width),
+                        (Key5, Key6, &mut light.height),
+                    ] {
+                        if input.is_key_pressed(shrink) && !input.is_key_pressed(grow) {
+                            *value = (*value / growth).max(0.01);
+                        } else if input.is_key_pressed(grow) && !input.is_key_pressed(shrink) {
+                            *value *= growth;
+                        }
+                    }
+                }
+
+                if input.is_key_pressed(F2) && !input.was_key_pressed(F2) {
+                    match streamer.take() {
+                        Some(streamer) => streamer.unload_all(&mut scene),
+                        None => streamer = Some(streaming::Streamer::new(WORLD_CHUNK_SIZE, 3, load_world_chunk)),
+                    }
+                    shadow_cache.invalidate();
+                    println!("world streaming: {}", streamer.is_some());
+                }
+
+                // compare the terrain's sharpness at grazing angles
+                if input.is_key_pressed(F1) && !input.was_key_pressed(F1) {
+                    texture_filtering = texture_filtering.next();
+                    detail_sampler = texture_filtering
+                        .create_sampler(&device, &texture_support, "terrain detail sampler");
+                    light_bind_groups_stale = true;
+                    vegetation.set_sampler(&device, &detail_sampler);
+                    if texture_support.anisotropic_filtering {
+                        println!("texture filtering: {:?}", texture_filtering);
+                    } else {
+                        println!("texture filtering: {:?}, anisotropy isn't supported", texture_filtering);
+                    }
+                }
+
+                if input.is_key_pressed(K) && !input.was_key_pressed(K) {
+                    if mirror.is_some() {
+                        mirror_enabled = !mirror_enabled;
+                        println!("mirror: {}", mirror_enabled);
+                    } else {
+                        println!("mirror needs a scene depth format with stencil");
+                    }
+                }
+
+                if input.is_key_pressed(O) && !input.was_key_pressed(O) {
+                    portal.max_depth = (portal.max_depth + 1) % (portal::MAX_DEPTH + 1);
+                    println!("portal depth: {}", portal.max_depth);
+                }
+
+                if input.is_key_pressed(I) && !input.was_key_pressed(I) && !frame_capture.capture_next_frame() {
+                    println!("frame capture needs the renderdoc feature and launching from renderdoc");
+                }
+
+                if input.is_key_pressed(Insert) && !input.was_key_pressed(Insert) {
+                    let name;
+                    (instance_material, name) = match instance_material {
+                        draw_params::MATERIAL_LIT => (draw_params::MATERIAL_TWO_SIDED, "two sided"),
+                        draw_params::MATERIAL_TWO_SIDED => (draw_params::MATERIAL_UNLIT, "unlit"),
+                        _ => (draw_params::MATERIAL_LIT, "lit"),
+                    };
+                    println!("instances material: {name}");
+                    shadow_throttle.invalidate();
+                }
+
+                if input.is_key_pressed(Key0) && !input.was_key_pressed(Key0) {
+                    vegetation.enabled = !vegetation.enabled;
+                    println!("vegetation: {}", vegetation.enabled);
+                    shadow_throttle.invalidate();
+                }
+
+                if input.is_key_pressed(Key9) && !input.was_key_pressed(Key9) {
+                    stress_test.layout = stress::Layout::next(stress_test.layout);
+                    respawn_stress_test(&mut stress_test, &mut scene);
+                }
+
+                if input.is_key_pressed(U) && !input.was_key_pressed(U) {
+                    throttle.background = throttle.background.next();
+                    println!("unfocused rendering: {:?}", throttle.background);
+                }
+
+                if input.is_key_pressed(F3) && !input.was_key_pressed(F3) {
+                    stats.enabled = !stats.enabled;
+                }
+
+                if input.is_key_pressed(F4) && !input.was_key_pressed(F4) {
+                    let resolve = &mut post_chain.resolve;
+                    resolve.anti_aliasing = resolve.anti_aliasing.next();
+                    println!("anti aliasing: {:?}", resolve.anti_aliasing);
+                }
+
+                // V cycles the dithering of the output, to compare the banding without it
+                if input.is_key_pressed(V) && !input.was_key_pressed(V) {
+                    let resolve = &mut post_chain.resolve;
+                    resolve.dither = resolve.dither.next();
+                    println!("dither: {:?}", resolve.dither);
+                }
+
+                if input.is_key_pressed(F5) && !input.was_key_pressed(F5) {
+                    println!("depth of field: {}", post_chain.toggle::<dof::DepthOfField>());
+                }
+
+                // page down and up move the focus, - and = close and open the aperture
+                let dof = &mut post_chain.get_mut::<dof::DepthOfField>().unwrap().params;
+                if input.is_key_pressed(PageDown) {
+                    dof.focus_distance = (dof.focus_distance - 5.0 * delta_frame_time).max(camera.near_z);
+                }
+                if input.is_key_pressed(PageUp) {
+                    dof.focus_distance += 5.0 * delta_frame_time;
+                }
+                if input.is_key_pressed(Minus) {
+                    dof.aperture = (dof.aperture - 0.5 * delta_frame_time).max(0.0);
+                }
+                if input.is_key_pressed(Equals) {
+                    dof.aperture += 0.5 * delta_frame_time;
+                }
+
+                if input.is_key_pressed(F6) && !input.was_key_pressed(F6) {
+                    println!("motion blur: {}", post_chain.toggle::<motion_blur::MotionBlur>());
+                }
+
+                // runs the first effect last
+                if input.is_key_pressed(F7) && !input.was_key_pressed(F7) {
+                    post_chain.move_effect(0, post_chain.effects().count() - 1);
+                    println!("post effects: {:?}", post_chain.effects().collect::<Vec<_>>());
+                }
+
+                // home and end open and close the shutter
+                let motion_blur = post_chain.get_mut::<motion_blur::MotionBlur>().unwrap();
+                if input.is_key_pressed(Home) {
+                    motion_blur.shutter = (motion_blur.shutter + delta_frame_time).min(1.0);
+                }
+                if input.is_key_pressed(End) {
+                    motion_blur.shutter = (motion_blur.shutter - delta_frame_time).max(0.0);
+                }
+
+                if input.is_key_pressed(F8) && !input.was_key_pressed(F8) {
+                    println!("fog: {}", post_chain.toggle::<fog::Fog>());
+                }
+
+                // C cycles through the color grading luts, then turns grading off
+                if input.is_key_pressed(C) && !input.was_key_pressed(C) {
+                    let lut = post_chain.get_mut::<lut::ColorGrading>().unwrap().next().map(str::to_string);
+                    post_chain.set_enabled::<lut::ColorGrading>(lut.is_some());
+                    println!("color grading: {}", lut.as_deref().unwrap_or("off"));
+                }
+
+                if input.is_key_pressed(F9) && !input.was_key_pressed(F9) {
+                    println!("volumetric light: {}", post_chain.toggle::<volumetric::Volumetric>());
+                }
+
+                if input.is_key_pressed(F10) && !input.was_key_pressed(F10) {
+                    light.normal_offset_bias = !light.normal_offset_bias;
+                    println!("normal offset bias: {}", light.normal_offset_bias);
+                }
+
+                if input.is_key_pressed(B) && !input.was_key_pressed(B) {
+                    light.receiver_plane_bias = !light.receiver_plane_bias;
+                    println!("receiver plane bias: {}", light.receiver_plane_bias);
+                }
+
+                if input.is_key_pressed(F11) && !input.was_key_pressed(F11) {
+                    println!("window mode: {:?}", window_modes.next(&window));
+                }
+
+                if input.is_key_pressed(F12) && !input.was_key_pressed(F12) {
+                    light.shadow_filter = light.shadow_filter.next();
+                    println!("shadow filter: {:?}", light.shadow_filter);
+                }
+
+                // H toggles shadows and J their soft filters, each swapping to another variant
+                // of the light pipelines
+                if input.is_key_pressed(H) && !input.was_key_pressed(H) {
+                    light_variant.toggle(preprocess::ShaderVariant::SHADOWS);
+                    println!("light variant: {:?}", light_variant);
+                }
+                if input.is_key_pressed(J) && !input.was_key_pressed(J) {
+                    light_variant.toggle(preprocess::ShaderVariant::PCF);
+                    println!("light variant: {:?}", light_variant);
+                }
+
+                // L switches between the sun and the manually moved light,
+                // T and G scrub the time of day, P pauses it
+                if input.is_key_pressed(L) && !input.was_key_pressed(L) {
+                    sun = match sun {
+                        Some(_) => {
+                            light.color = math::Color::WHITE;
+                            light.intensity = 1.0;
+                            None
+                        }
+                        None => Some(sun::Sun::new()),
+                    };
+                }
+                if let Some(sun) = &mut sun {
+                    if input.is_key_pressed(T) {
+                        sun.scrub(3.0 * delta_frame_time);
+                    }
+                    if input.is_key_pressed(G) {
+                        sun.scrub(-3.0 * delta_frame_time);
+                    }
+                    if input.is_key_pressed(P) && !input.was_key_pressed(P) {
+                        sun.paused = !sun.paused;
+                    }
+                    sun.advance(delta_frame_time);
+
+                    light.point_along(sun.direction(), floating_origin.to_local(SUN_FOCUS.into()), SUN_DISTANCE);
+                    light.color = sun.color();
+                    light.intensity = sun.intensity();
+                }
+
+                let light_direction = light.direction();
+                let light_color = light.color.scaled(light.intensity);
+                let fog = post_chain.get_mut::<fog::Fog>().unwrap();
+                fog.light_direction = light_direction;
+                fog.light_color = light_color;
+                post_chain.get_mut::<volumetric::Volumetric>().unwrap().light_color = light_color;
+                let time = instant.elapsed().as_secs_f32();
+                vegetation.update(&queue, time, light_direction, light_color, environment.ambient());
+
+                if input.is_key_pressed(M) && !input.was_key_pressed(M) {
+                    shadow_readback_requested = true;
+                }
+
+                if input.is_key_pressed(Delete) && !input.was_key_pressed(Delete) {
+                    if let Some(handle) = selected.take() {
+                        scene.despawn(handle);
+                    }
+                }
+
+                if input.is_key_pressed(N) && !input.was_key_pressed(N) {
+                    use rand::Rng;
+                    let mut rng = rand::thread_rng();
+                    scene.spawn(Instance {
+                        mesh: if rng.gen_bool(0.5) { scene::MeshId::CUBE } else { scene::MeshId::WEDGE },
+                        translation: camera.translation,
+                        rotation: camera.compute_rotation(),
+                        scale: Scale3::new(
+                            rng.gen_range(0.2..3.0),
+                            rng.gen_range(0.2..3.0),
+                            rng.gen_range(0.2..3.0),
+                        ),
+                        casts_shadow: true,
+                        receives_shadow: true,
+                        lightmap: None,
+                    });
+                }
+
                 input.previous_keys_pressed_bitmask = input.keys_pressed_bitmask;
 
+                // in reactive mode, whatever's still moving asks for the next frame. the wind
+                // and the swinging instance only move while something else is redrawing
+                let moving = input.keys_pressed_bitmask != 0
+                    || !camera.is_settled_at(&camera_goal)
+                    || camera_recall.is_some()
+                    || camera_path.is_playing()
+                    || sun.as_ref().is_some_and(|sun| !sun.paused)
+                    || streamer.is_some()
+                    || picking.is_busy()
+                    || shadow_readback.is_some();
+                if moving {
+                    throttle.request_redraw();
+                }
+
                 window.request_redraw();
             }
             _ => {}
@@ -972,32 +2965,231 @@ async fn run() {
     });
 }
 
-fn create_depth_texture(device: &Device, width: u32, height: u32) -> (Texture, TextureView) {  
-    let texture = device.create_texture(&TextureDescriptor {
-        label: Some("depth texture"),
-        size: Extent3d {
-            width: width,
-            height: height,
-            depth_or_array_layers: 1,
-        },
-        format: DEPTH_FORMAT,
-        mip_level_count: 1,
-        sample_count: 1,
-        dimension: TextureDimension::D2,
-        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
-        view_formats: &[],
-    });  
-
-    let texture_view = texture.create_view(&TextureViewDescriptor::default());
-
-    (texture, texture_view)
-}
-
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn meshes_are_closed_and_wound_inwards() {
+        for (vertices, indices) in [(&CUBE_VERTICES[..], &CUBE_INDICES[..]), (&WEDGE_VERTICES, &WEDGE_INDICES)] {
+            let position = |i: u32| {
+                let [x, y, z] = vertices[i as usize].position;
+                Vector3::new(x, y, z)
+            };
+            let center = (0..vertices.len() as u32).fold(Vector3::IDENTITY, |sum, i| sum + position(i))
+                / vertices.len() as f32;
+
+            let mut edges = std::collections::HashMap::new();
+            for triangle in indices.chunks(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| position(triangle[i]));
+                let inwards = (b - a).cross(&(c - a));
+                assert!(inwards.dot(&(center - (a + b + c) / 3.0)) > 0.0, "{:?}", triangle);
+                for i in 0..3 {
+                    *edges.entry((triangle[i], triangle[(i + 1) % 3])).or_insert(0) += 1;
+                }
+            }
+            // every edge is shared with a triangle running along it the other way
+            for (&(a, b), &count) in &edges {
+                assert_eq!((count, edges.get(&(b, a))), (1, Some(&1)));
+            }
+        }
+    }
+
+    #[test]
+    fn light_pointed_along_direction_looks_at_focus() {
+        let mut light = Light {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            z_to_x: 0.0,
+            xz_to_y: 0.0,
+            near_z: 1.0,
+            width: 1.0,
+            height: 1.0,
+            color: math::Color::WHITE,
+            intensity: 1.0,
+            normal_offset_bias: false,
+            receiver_plane_bias: false,
+            shadow_filter: ShadowFilter::Hard,
+            shadow_radius: 0.0,
+        };
+        let direction = Vector3::new(0.3, -0.8, 0.52);
+        let direction = direction.normalized();
+        let focus = Vector3::new(1.0, 2.0, 3.0);
+        light.point_along(direction, focus, 50.0);
+
+        let in_view = focus.apply(&light.compute_view());
+        assert!((in_view - Vector3::new(0.0, 0.0, 50.0)).norm_sqr() < 1e-6, "{:?}", in_view);
+        assert!((light.direction() - direction).norm_sqr() < 1e-10);
+    }
+
+    /// looking along z from `translation`
+    fn test_camera(translation: Vector3) -> Camera {
+        Camera {
+            translation,
+            forward: Vector3::new(0.0, 0.0, 1.0),
+            z_to_x: 0.0,
+            xz_to_y: 0.0,
+            near_z: 1.0,
+            far_z: 10.0,
+            width: 2.0,
+            height: 2.0,
+        }
+    }
+
+    #[test]
+    fn camera_rotation_round_trips_through_angles() {
+        let mut camera = test_camera(Vector3::IDENTITY);
+        camera.z_to_x = 2.0;
+        camera.xz_to_y = -0.4;
+        camera.update_forward();
+        let rotation = camera.compute_rotation();
+        camera.z_to_x = 0.0;
+        camera.xz_to_y = 0.0;
+        camera.set_rotation(rotation);
+        assert!((camera.z_to_x - 2.0).abs() < 1e-4 && (camera.xz_to_y + 0.4).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orbiting_keeps_facing_the_pivot() {
+        let mut camera = test_camera(Vector3::new(1.0, 2.0, -3.0));
+        let pivot = Vector3::new(-1.0, 0.0, 4.0);
+        let distance = (pivot - camera.translation).length();
+        camera.orbit(pivot, 0.8, -0.3);
+
+        assert!(((pivot - camera.translation).length() - distance).abs() < 1e-4);
+        let facing = (pivot - camera.translation).normalized();
+        assert!((facing - camera.direction()).norm_sqr() < 1e-8);
+        assert!((Vector3::new(0.0, 0.0, 1.0).apply(&camera.compute_model()) - camera.translation - facing).norm_sqr() < 1e-8);
+
+        // stops short of looking straight down
+        camera.orbit(pivot, 0.0, -3.0);
+        assert_eq!(camera.xz_to_y, -1.5);
+    }
+
+    #[test]
+    fn camera_looking_at_target_matches_affine_look_at() {
+        let mut camera = test_camera(Vector3::new(1.0, 2.0, -3.0));
+        let target = Vector3::new(-4.0, 0.5, 6.0);
+        camera.look_at(target);
+
+        let view = math::Affine3::look_at(&camera.translation, &target, &Vector3::new(0.0, 1.0, 0.0));
+        let camera_view = camera.compute_model().inverse();
+        for p in [target, Vector3::new(3.0, -1.0, 2.0), Vector3::new(0.0, 5.0, 0.0)] {
+            assert!((p.apply(&view) - p.apply(&camera_view)).norm_sqr() < 1e-8, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn preprocessed_shaders_parse() {
+        use preprocess::{Defines, ShaderVariant};
+
+        let preprocessor = shader_preprocessor(draw_params::DrawParamsMode::PushConstants);
+        for bits in 0..=ShaderVariant::all().bits() {
+            let defines = ShaderVariant::from_bits(bits).unwrap().defines();
+            let shader = binding::reflect("light.wgsl", &preprocessor.process("light.wgsl", &defines).unwrap());
+            layout::validate_vertex_input(&shader, "vs_main", &[VERTEX_LAYOUT, INSTANCE_LAYOUT, PREVIOUS_INSTANCE_LAYOUT]).unwrap();
+            layout::validate_vertex_input(&shader, "vs_terrain", &[terrain::TERRAIN_VERTEX_LAYOUT]).unwrap();
+        }
+        for defines in [Defines::new(), Defines::new().with("PANCAKE_IN_SHADER")] {
+            let shader = binding::reflect("shadow.wgsl", &preprocessor.process("shadow.wgsl", &defines).unwrap());
+            layout::validate_vertex_input(&shader, "vs_main", &[VERTEX_LAYOUT, INSTANCE_LAYOUT]).unwrap();
+        }
+    }
+
+    #[test]
+    fn camera_and_light_match_light_wgsl() {
+        use std::mem::{offset_of, size_of};
+
+        let source = shader_preprocessor(draw_params::DrawParamsMode::Uniform { group: 1 })
+            .process("light.wgsl", &preprocess::ShaderVariant::all().defines())
+            .unwrap();
+        let shader = binding::reflect("light.wgsl", &source);
+        layout::validate_struct(&shader, "Camera", size_of::<CameraRaw>(), &[
+            ("view_0", offset_of!(CameraRaw, view)),
+            ("near_z", offset_of!(CameraRaw, near_z)),
+            ("clip_plane", offset_of!(CameraRaw, clip_plane)),
+            ("previous_view_0", offset_of!(CameraRaw, previous_view)),
+        ]).unwrap();
+        layout::validate_struct(&shader, "Light", size_of::<LightRaw>(), &[
+            ("view_0", offset_of!(LightRaw, view)),
+            ("near_z", offset_of!(LightRaw, near_z)),
+            ("color", offset_of!(LightRaw, color)),
+            ("intensity", offset_of!(LightRaw, intensity)),
+            ("normal_offset", offset_of!(LightRaw, normal_offset)),
+            ("receiver_plane_bias", offset_of!(LightRaw, receiver_plane_bias)),
+            ("shadow_filter", offset_of!(LightRaw, shadow_filter)),
+            ("shadow_radius", offset_of!(LightRaw, shadow_radius)),
+            ("size", offset_of!(LightRaw, size)),
+            ("ambient", offset_of!(LightRaw, ambient)),
+        ]).unwrap();
+    }
+
+    /// the fit for a light at the origin looking along z, its near plane at 1 and 4 by 4,
+    /// and a camera at `eye` looking along z, its sides at |x| and |y| = `spread` * z
+    fn fit_from(eye: Vector3, spread: f32, near_z: f32, far_z: f32) -> Option<math::Affine2> {
+        let mut view = math::Affine3::IDENTITY;
+        view.translate(&-eye)
+            .scale(&Scale3::new(1.0 / spread, 1.0 / spread, 1.0));
+        let frustum = math::Frustum::new(&view, near_z, Some(far_z));
+        compute_camera_fit_on_light_plane(&frustum, &math::Affine3::IDENTITY, 1.0, 4.0, 4.0)
+    }
+
+    fn assert_fit(fit: math::Affine2, scale: Scale2, translation: Vector2) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        assert!(
+            close(fit.xx, scale.x) && close(fit.yy, scale.y) && close(fit.yx, 0.0) && close(fit.xy, 0.0)
+                && close(fit._x, translation.x) && close(fit._y, translation.y),
+            "{:?}",
+            fit,
+        );
+    }
+
+    #[test]
+    fn camera_fits_on_light_plane() {
+        // the near corners project to |x| = 1 / 2, the far ones to 2 / 3
+        let fit = fit_from(Vector3::new(0.0, 0.0, 1.0), 1.0, 1.0, 2.0).unwrap();
+        assert_fit(fit, Scale2::new(3.0, 3.0), Vector2::IDENTITY);
+
+        // moved right, x from -1 / 3 to 1
+        let fit = fit_from(Vector3::new(1.0, 0.0, 1.0), 1.0, 1.0, 2.0).unwrap();
+        assert_fit(fit, Scale2::new(3.0, 3.0), Vector2::new(-1.0, 0.0));
+        let corner = Vector2::new(-1.0 / 3.0, -2.0 / 3.0).apply(&fit);
+        assert!((corner - Vector2::new(-2.0, -2.0)).length() < 1e-5);
+
+        // the near corners are in front of the light's near plane, their edges are cut at
+        // |x| = 1 there, where the far corners project to too
+        let fit = fit_from(Vector3::IDENTITY, 1.0, 0.5, 4.0).unwrap();
+        assert_fit(fit, Scale2::new(2.0, 2.0), Vector2::IDENTITY);
+
+        // enclosing the light's frame, which is kept whole
+        let fit = fit_from(Vector3::new(0.0, 0.0, 1.0), 10.0, 1.0, 2.0).unwrap();
+        assert_fit(fit, Scale2::IDENTITY, Vector2::IDENTITY);
+    }
+
+    #[test]
+    fn camera_off_the_light_plane_has_no_fit() {
+        // entirely behind the light
+        assert!(fit_from(Vector3::new(0.0, 0.0, -10.0), 1.0, 1.0, 2.0).is_none());
+        // entirely above the light's frame
+        assert!(fit_from(Vector3::new(0.0, 20.0, 1.0), 1.0, 1.0, 2.0).is_none());
+        // only touching its top edge: the lowest corner projects to y = (12 - 4) / 4 = 2
+        assert!(fit_from(Vector3::new(0.0, 12.0, 0.0), 1.0, 2.0, 4.0).is_none());
+    }
+
+    #[test]
+    fn shadow_scissor_covers_fit_rect() {
+        // rect from (-0.5, 0.0) to (0.0, 0.5) on a light plane of 2 by 2
+        let mut fit = math::Affine2::IDENTITY;
+        fit.translate(&Vector2::new(0.5, 0.0))
+            .scale(&Scale2::new(4.0, 4.0))
+            .translate(&Vector2::new(-1.0, -1.0));
+        assert_eq!(fit_to_shadow_scissor(&fit, 2.0, 2.0, 100, 100), (25, 25, 25, 25));
+
+        assert_eq!(fit_to_shadow_scissor(&math::Affine2::IDENTITY, 2.0, 2.0, 64, 32), (0, 0, 64, 32));
+    }
 }
\ No newline at end of file
@@ -1,5 +1,28 @@
 //implement own sin cos
-use std::{ops::*, process::Output};
+use std::ops::*;
+
+mod affine2;
+mod bounds;
+mod color;
+pub mod coordinate_system;
+pub mod curves;
+mod damp;
+mod double;
+pub mod easing;
+mod frustum;
+mod interop;
+mod motor;
+pub mod noise;
+mod plane;
+
+pub use affine2::Affine2;
+pub use bounds::{Aabb3, Obb3};
+pub use color::Color;
+pub use damp::{damp, damp_angle};
+pub use double::{DAffine3, DVector3};
+pub use frustum::Frustum;
+pub use motor::Motor;
+pub use plane::Plane;
 
 //Plan: Explore R3,3
 //generates 6 shears, 3 pseudo-projections, 3 scales, 3 translation, 3 rotations
@@ -176,6 +199,142 @@ impl Affine3 {
             _z: translation.z,
         }
     }
+
+    /// world to view of an eye at `eye` looking at `target`, with view z towards the target,
+    /// y as close to `up` as it can be and x to the right, like the camera's view.
+    /// `up` must not be parallel to the view direction
+    pub fn look_at(eye: &Vector3, target: &Vector3, up: &Vector3) -> Self {
+        let z = (*target - *eye).normalized();
+        let x = up.cross(&z).normalized();
+        let y = z.cross(&x);
+        Self {
+            xx: x.x,
+            yx: x.y,
+            zx: x.z,
+            _x: -x.dot(eye),
+
+            xy: y.x,
+            yy: y.y,
+            zy: y.z,
+            _y: -y.dot(eye),
+
+            xz: z.x,
+            yz: z.y,
+            zz: z.z,
+            _z: -z.dot(eye),
+        }
+    }
+
+    /// makes the linear part a rotation again, or a mirroring one if it was, removing any
+    /// scale and the shear that chained `rotate` calls accumulate. gram schmidt, the image
+    /// of x keeps its direction and the image of y stays in the plane it spans with it
+    pub fn orthonormalize(&mut self) -> &mut Self {
+        let image_y = Vector3::new(self.yx, self.yy, self.yz);
+        let image_z = Vector3::new(self.zx, self.zy, self.zz);
+        let x = Vector3::new(self.xx, self.xy, self.xz).normalized();
+        let y = (image_y - x * x.dot(&image_y)).normalized();
+        let z = x.cross(&y);
+        let z = if image_z.dot(&z) < 0.0 { -z } else { z };
+
+        (self.xx, self.xy, self.xz) = (x.x, x.y, x.z);
+        (self.yx, self.yy, self.yz) = (y.x, y.y, y.z);
+        (self.zx, self.zy, self.zz) = (z.x, z.y, z.z);
+        self
+    }
+
+    /// the scale, rotor and translation `Affine3::from` builds `self` out of. any shear is
+    /// dropped: the rotation keeps the image of x and the plane of the images of x and y.
+    /// a mirroring linear part comes out with a negative x scale
+    pub fn decompose(&self) -> (Scale3, Rotor, Vector3) {
+        let image_x = Vector3::new(self.xx, self.xy, self.xz);
+        let image_y = Vector3::new(self.yx, self.yy, self.yz);
+        let image_z = Vector3::new(self.zx, self.zy, self.zz);
+
+        // gram schmidt, in the order of the images
+        let x = image_x.normalized();
+        let y = (image_y - x * x.dot(&image_y)).normalized();
+        // mirrored when the image of z is behind the plane, flip x so the rotation stays one
+        let (x, scale_x) = if image_z.dot(&x.cross(&y)) < 0.0 {
+            (-x, -image_x.length())
+        } else {
+            (x, image_x.length())
+        };
+        let z = x.cross(&y);
+
+        let scale = Scale3::new(scale_x, image_y.dot(&y), image_z.dot(&z));
+        let rotor = Rotor::from_rotated_axes(&x, &y, &z);
+        (scale, rotor, Vector3::new(self._x, self._y, self._z))
+    }
+
+    /// the inverse transpose of the linear part, without translation, which takes normals
+    /// to normals: under a non uniform scale they tilt the other way than the surface does.
+    /// assumes non singular, see `Vector3::apply_normal` and `apply_normal` in `common.wgsl`
+    pub fn normal_matrix(&self) -> Self {
+        // the rows of the inverse transpose are the cross products of the other two rows
+        let rows = [
+            Vector3::new(self.xx, self.yx, self.zx),
+            Vector3::new(self.xy, self.yy, self.zy),
+            Vector3::new(self.xz, self.yz, self.zz),
+        ];
+        let inv_det = 1.0 / rows[0].dot(&rows[1].cross(&rows[2]));
+        let [x, y, z] = [0, 1, 2].map(|i| rows[(i + 1) % 3].cross(&rows[(i + 2) % 3]) * inv_det);
+        Self {
+            xx: x.x,
+            yx: x.y,
+            zx: x.z,
+            _x: 0.0,
+
+            xy: y.x,
+            yy: y.y,
+            zy: y.z,
+            _y: 0.0,
+
+            xz: z.x,
+            yz: z.y,
+            zz: z.z,
+            _z: 0.0,
+        }
+    }
+
+    // self.compose(&self.inverse()) = IDENTITY, assumes non singular
+    pub fn inverse(&self) -> Self {
+        // cofactors of the linear part
+        let c_xx = self.yy * self.zz - self.zy * self.yz;
+        let c_yx = self.zy * self.xz - self.xy * self.zz;
+        let c_zx = self.xy * self.yz - self.yy * self.xz;
+
+        let det = self.xx * c_xx + self.yx * c_yx + self.zx * c_zx;
+        let inv_det = 1.0 / det;
+
+        let xx = c_xx * inv_det;
+        let yx = (self.zx * self.yz - self.yx * self.zz) * inv_det;
+        let zx = (self.yx * self.zy - self.zx * self.yy) * inv_det;
+
+        let xy = c_yx * inv_det;
+        let yy = (self.xx * self.zz - self.zx * self.xz) * inv_det;
+        let zy = (self.zx * self.xy - self.xx * self.zy) * inv_det;
+
+        let xz = c_zx * inv_det;
+        let yz = (self.yx * self.xz - self.xx * self.yz) * inv_det;
+        let zz = (self.xx * self.yy - self.yx * self.xy) * inv_det;
+
+        Self {
+            xx,
+            yx,
+            zx,
+            _x: -(xx * self._x + yx * self._y + zx * self._z),
+
+            xy,
+            yy,
+            zy,
+            _y: -(xy * self._x + yy * self._y + zy * self._z),
+
+            xz,
+            yz,
+            zz,
+            _z: -(xz * self._x + yz * self._y + zz * self._z),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -185,14 +344,14 @@ pub struct BiVector3 {
     pub zx: f32,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Vector3 {
     pub x: f32,
     pub y: f32,
     pub z: f32,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Scale3 {
     pub x: f32,
     pub y: f32,
@@ -200,7 +359,7 @@ pub struct Scale3 {
 }
 
 impl Scale3 {
-    pub const IDENTITY: Vector3 = Vector3 {
+    pub const IDENTITY: Scale3 = Scale3 {
         x: 1.0,
         y: 1.0,
         z: 1.0,
@@ -211,6 +370,23 @@ impl Scale3 {
             x, y, z
         }
     }
+
+    /// undoes it, infinite along axes it scales to 0
+    pub fn inverse(&self) -> Self {
+        Self {
+            x: self.x.recip(),
+            y: self.y.recip(),
+            z: self.z.recip(),
+        }
+    }
+}
+
+impl Mul<Vector3> for Scale3 {
+    type Output = Vector3;
+
+    fn mul(self, rhs: Vector3) -> Self::Output {
+        Vector3::new(self.x * rhs.x, self.y * rhs.y, self.z * rhs.z)
+    }
 }
 
 impl MulAssign<f32> for Scale3 {
@@ -280,10 +456,64 @@ impl Vector3 {
         }
     }
 
+    /// the wedge's dual, perpendicular to both along the right hand rule
+    pub fn cross(&self, rhs: &Vector3) -> Vector3 {
+        let w = self.wedge(rhs);
+        Vector3::new(w.yz, w.zx, w.xy)
+    }
+
     pub fn dot(&self, rhs: &Vector3) -> f32 {
         self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
     }
 
+    pub fn length(&self) -> f32 {
+        self.norm_sqr().sqrt()
+    }
+
+    /// scales to unit length, assumes non zero
+    pub fn normalize(&mut self) -> &mut Self {
+        *self = *self / self.length();
+        self
+    }
+
+    pub fn normalized(&self) -> Self {
+        *self / self.length()
+    }
+
+    /// `self` at 0, `rhs` at 1
+    pub fn lerp(&self, rhs: &Vector3, t: f32) -> Self {
+        *self + (*rhs - *self) * t
+    }
+
+    /// component wise
+    pub fn min(&self, rhs: &Vector3) -> Self {
+        Self::new(self.x.min(rhs.x), self.y.min(rhs.y), self.z.min(rhs.z))
+    }
+
+    /// component wise
+    pub fn max(&self, rhs: &Vector3) -> Self {
+        Self::new(self.x.max(rhs.x), self.y.max(rhs.y), self.z.max(rhs.z))
+    }
+
+    /// of each component
+    pub fn recip(&self) -> Self {
+        Vector3::new(self.x.recip(), self.y.recip(), self.z.recip())
+    }
+
+    /// component wise
+    pub fn abs(&self) -> Self {
+        Self::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+
+    pub fn xy(&self) -> Vector2 {
+        Vector2::new(self.x, self.y)
+    }
+
+    /// with `w` appended, 1 for points and 0 for directions
+    pub fn extend(&self, w: f32) -> Vector4 {
+        Vector4::new(self.x, self.y, self.z, w)
+    }
+
     pub fn apply(&self, a: &Affine3) -> Self {
         Self {
             x: self.x * a.xx + self.y * a.yx + self.z * a.zx + a._x,
@@ -291,6 +521,11 @@ impl Vector3 {
             z: self.x * a.xz + self.y * a.yz + self.z * a.zz + a._z,
         }
     }
+
+    /// the unit normal of the surface that had this normal before `a` moved it
+    pub fn apply_normal(&self, a: &Affine3) -> Self {
+        self.apply(&a.normal_matrix()).normalized()
+    }
 }
 
 impl Div<f32> for Vector3 {
@@ -317,6 +552,39 @@ impl Mul<f32> for Vector3 {
     }
 }
 
+impl Mul<Vector3> for f32 {
+    type Output = Vector3;
+
+    fn mul(self, rhs: Vector3) -> Self::Output {
+        rhs * self
+    }
+}
+
+/// x, y, z at 0, 1, 2
+impl Index<usize> for Vector3 {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("index {} out of range for Vector3", index),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vector3 {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("index {} out of range for Vector3", index),
+        }
+    }
+}
+
 impl SubAssign for Vector3 {
     fn sub_assign(&mut self, rhs: Self) {
         self.x -= rhs.x;
@@ -448,6 +716,71 @@ impl Rotor {
     pub fn norm_sqr(&self) -> f32 {
         self._1 * self._1 + self.xy * self.xy + self.yz * self.yz + self.zx * self.zx
     }
+
+    /// back to unit norm, undoing the drift of long chains of products
+    pub fn renormalize(&mut self) -> &mut Self {
+        *self /= self.norm_sqr().sqrt();
+        self
+    }
+
+    /// rotating like `Affine3::from` the x, y and z axes to `x`, `y` and `z`,
+    /// which are unit, perpendicular and right handed
+    pub fn from_rotated_axes(x: &Vector3, y: &Vector3, z: &Vector3) -> Self {
+        // the largest of the four components is divided by, so it can't be near 0
+        let trace = x.x + y.y + z.z;
+        if trace > 0.0 {
+            let s = 2.0 * (1.0 + trace).sqrt();
+            Rotor { _1: s / 4.0, yz: (y.z - z.y) / s, zx: (z.x - x.z) / s, xy: (x.y - y.x) / s }
+        } else if x.x > y.y && x.x > z.z {
+            let s = 2.0 * (1.0 + x.x - y.y - z.z).sqrt();
+            Rotor { _1: (y.z - z.y) / s, yz: s / 4.0, zx: (y.x + x.y) / s, xy: (z.x + x.z) / s }
+        } else if y.y > z.z {
+            let s = 2.0 * (1.0 + y.y - x.x - z.z).sqrt();
+            Rotor { _1: (z.x - x.z) / s, yz: (y.x + x.y) / s, zx: s / 4.0, xy: (z.y + y.z) / s }
+        } else {
+            let s = 2.0 * (1.0 + z.z - x.x - y.y).sqrt();
+            Rotor { _1: (x.y - y.x) / s, yz: (z.x + x.z) / s, zx: (z.y + y.z) / s, xy: s / 4.0 }
+        }
+    }
+
+    /// from a unit quaternion in glTF's `[x, y, z, w]` order. the rotor's planes are dual to the
+    /// quaternion's axes, yz to x, zx to y and xy to z, so `[0, 0, sin(a / 2), cos(a / 2)]` turns x
+    /// towards y by `a` like `BiVector3::new(a / 2, 0, 0).exp()` does. `q` and `-q` rotate the same
+    pub fn from_quaternion([x, y, z, w]: [f32; 4]) -> Self {
+        Rotor { _1: w, xy: z, yz: x, zx: y }
+    }
+
+    /// undoes `from_quaternion`, keeping the sign, so either of the two quaternions rotating alike
+    pub fn to_quaternion(self) -> [f32; 4] {
+        [self.yz, self.zx, self.xy, self._1]
+    }
+
+    /// components in `[_1, xy, yz, zx]` order, as read by the shaders
+    pub fn to_array(self) -> [f32; 4] {
+        [self._1, self.xy, self.yz, self.zx]
+    }
+
+    /// the inverse of a unit rotor
+    pub fn reverse(&self) -> Self {
+        Rotor { _1: self._1, xy: -self.xy, yz: -self.yz, zx: -self.zx }
+    }
+
+    /// undoes `BiVector3::exp` of a unit rotor, taking the shorter way around
+    pub fn log(&self) -> BiVector3 {
+        let sign = if self._1 < 0.0 { -1.0 } else { 1.0 };
+        let plane = BiVector3::new(self.xy, self.yz, self.zx) * sign;
+        let sin = plane.norm_sqr().sqrt();
+        if sin < 1e-6 {
+            return plane;
+        }
+        plane * ((sin.atan2(self._1 * sign)) / sin)
+    }
+
+    /// rotates at a constant rate from `self` at 0 to `other` at 1
+    pub fn interpolate(&self, other: &Rotor, t: f32) -> Self {
+        let delta = *other * self.reverse();
+        (delta.log() * t).exp() * *self
+    }
 }
 
 impl Mul for Rotor {
@@ -485,7 +818,7 @@ impl Vector2 {
     pub const NAN: Self = Vector2{ x: f32::NAN, y: f32::NAN };
     pub const IDENTITY: Self = Vector2{ x: 0.0, y: 0.0 };
 
-    pub fn new(x: f32, y: f32) -> Self {
+    pub const fn new(x: f32, y: f32) -> Self {
         Self {
             x, y
         }
@@ -497,6 +830,145 @@ impl Vector2 {
             xy: self.x * rhs.y - self.y * rhs.x,
         }
     }
+
+    #[inline]
+    pub fn dot(self, rhs: Vector2) -> f32 {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    pub fn norm_sqr(self) -> f32 {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> f32 {
+        self.norm_sqr().sqrt()
+    }
+
+    /// scales to unit length, assumes non zero
+    pub fn normalize(&mut self) -> &mut Self {
+        *self = self.normalized();
+        self
+    }
+
+    pub fn normalized(self) -> Self {
+        self / self.length()
+    }
+
+    /// turned a quarter counter clockwise, so `self.wedge(self.perp())` is positive
+    pub fn perp(self) -> Self {
+        Vector2::new(-self.y, self.x)
+    }
+
+    /// `self` at 0, `rhs` at 1
+    pub fn lerp(self, rhs: Vector2, t: f32) -> Self {
+        self + (rhs - self) * t
+    }
+
+    /// component wise
+    pub fn min(self, rhs: Vector2) -> Self {
+        Vector2::new(self.x.min(rhs.x), self.y.min(rhs.y))
+    }
+
+    /// component wise
+    pub fn max(self, rhs: Vector2) -> Self {
+        Vector2::new(self.x.max(rhs.x), self.y.max(rhs.y))
+    }
+
+    /// of each component
+    pub fn recip(self) -> Self {
+        Vector2::new(self.x.recip(), self.y.recip())
+    }
+
+    /// component wise
+    pub fn abs(self) -> Self {
+        Vector2::new(self.x.abs(), self.y.abs())
+    }
+
+    /// when either component is, e.g. a `NAN` placeholder
+    pub fn is_nan(self) -> bool {
+        self.x.is_nan() || self.y.is_nan()
+    }
+
+    pub fn apply(self, a: &Affine2) -> Self {
+        Self {
+            x: self.x * a.xx + self.y * a.yx + a._x,
+            y: self.x * a.xy + self.y * a.yy + a._y,
+        }
+    }
+
+    pub fn extend(self, z: f32) -> Vector3 {
+        Vector3::new(self.x, self.y, z)
+    }
+}
+
+impl Div<f32> for Vector2 {
+    type Output = Vector2;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        Vector2 {
+            x: self.x / rhs,
+            y: self.y / rhs,
+        }
+    }
+}
+
+impl MulAssign<f32> for Vector2 {
+    fn mul_assign(&mut self, rhs: f32) {
+        self.x *= rhs;
+        self.y *= rhs;
+    }
+}
+
+impl DivAssign<f32> for Vector2 {
+    fn div_assign(&mut self, rhs: f32) {
+        self.x /= rhs;
+        self.y /= rhs;
+    }
+}
+
+impl Mul<Vector2> for f32 {
+    type Output = Vector2;
+
+    fn mul(self, rhs: Vector2) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl AddAssign for Vector2 {
+    fn add_assign(&mut self, rhs: Self) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+    }
+}
+
+impl SubAssign for Vector2 {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+    }
+}
+
+/// x, y at 0, 1
+impl Index<usize> for Vector2 {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("index {} out of range for Vector2", index),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vector2 {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            _ => panic!("index {} out of range for Vector2", index),
+        }
+    }
 }
 
 impl Mul<f32> for Vector2 {
@@ -537,19 +1009,120 @@ impl Sub for Vector2 {
     }
 }
 
+/// homogeneous, e.g. a clip space position or a plane as (normal, distance)
 #[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
+pub struct Vector4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Vector4 {
+    pub const IDENTITY: Self = Vector4::new(0.0, 0.0, 0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    pub fn dot(self, rhs: Vector4) -> f32 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    pub fn xy(self) -> Vector2 {
+        Vector2::new(self.x, self.y)
+    }
+
+    /// drops `w`, see `project` to divide by it instead
+    pub fn truncate(self) -> Vector3 {
+        Vector3::new(self.x, self.y, self.z)
+    }
+
+    /// perspective divide, assumes `w` is non zero
+    pub fn project(self) -> Vector3 {
+        self.truncate() / self.w
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.x, self.y, self.z, self.w]
+    }
+}
+
+impl Add for Vector4 {
+    type Output = Vector4;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Vector4::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z, self.w + rhs.w)
+    }
+}
+
+impl Sub for Vector4 {
+    type Output = Vector4;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vector4::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z, self.w - rhs.w)
+    }
+}
+
+impl Mul<f32> for Vector4 {
+    type Output = Vector4;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Vector4::new(self.x * rhs, self.y * rhs, self.z * rhs, self.w * rhs)
+    }
+}
+
+impl Neg for Vector4 {
+    type Output = Vector4;
+
+    fn neg(self) -> Self::Output {
+        Vector4::new(-self.x, -self.y, -self.z, -self.w)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
 pub struct Scale2 {
     pub x: f32,
     pub y: f32,    
 }
 
 impl Scale2 {
+    pub const IDENTITY: Scale2 = Scale2 { x: 1.0, y: 1.0 };
+
     pub fn new(x: f32, y: f32) -> Self {
         Self {
             x, y
         }
     }
+
+    /// undoes it, infinite along axes it scales to 0
+    pub fn inverse(&self) -> Self {
+        Self {
+            x: self.x.recip(),
+            y: self.y.recip(),
+        }
+    }
+}
+
+impl Mul for Scale2 {
+    type Output = Scale2;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x * rhs.x,
+            y: self.y * rhs.y,
+        }
+    }
+}
+
+impl Mul<Vector2> for Scale2 {
+    type Output = Vector2;
+
+    fn mul(self, rhs: Vector2) -> Self::Output {
+        Vector2::new(self.x * rhs.x, self.y * rhs.y)
+    }
 }
 
 impl Neg for Vector2 {
@@ -561,4 +1134,221 @@ impl Neg for Vector2 {
             y: -self.y,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn affine_inverse_undoes_transform() {
+        let mut a = Affine3::IDENTITY;
+        a.translate(&Vector3::new(1.0, -2.0, 3.0))
+            .rotate(0.7, &BiVector3::new(0.6, 0.0, 0.8))
+            .scale(&Scale3::new(2.0, 0.5, 3.0));
+        let v = Vector3::new(0.3, 4.0, -1.5);
+        let back = v.apply(&a).apply(&a.inverse());
+        assert!((back - v).norm_sqr() < 1e-8);
+
+        let identity = a.compose(&a.inverse());
+        let e = Vector3::new(5.0, 6.0, 7.0);
+        assert!((e.apply(&identity) - e).norm_sqr() < 1e-8);
+    }
+
+    #[test]
+    fn scale_inverse_undoes_scale() {
+        let scale = Scale3::new(2.0, -0.5, 4.0);
+        let v = Vector3::new(1.0, 3.0, -2.0);
+        let mut scaling = Affine3::IDENTITY;
+        assert_eq!(scale * v, v.apply(scaling.scale(&scale)));
+        assert_eq!(scale.inverse() * (scale * v), v);
+        assert_eq!(scale * scale.inverse(), Scale3::IDENTITY);
+
+        let scale = Scale2::new(4.0, 0.25);
+        assert_eq!(scale.inverse() * (scale * Vector2::new(3.0, 8.0)), Vector2::new(3.0, 8.0));
+        assert_eq!(Vector2::new(4.0, -0.5).recip(), Vector2::new(0.25, -2.0));
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        for scale in [Scale3::new(10.0, 30.0, 0.1), Scale3::new(-2.0, 0.5, 3.0)] {
+            let a = Affine3::from(scale, BiVector3::new(0.3, -0.4, 0.2).exp(), Vector3::new(1.0, 2.0, 3.0));
+            let origin = Vector3::new(0.0, 0.0, 0.0).apply(&a);
+            let linear = |v: Vector3| v.apply(&a) - origin;
+
+            let tangents = [Vector3::new(1.0, 1.0, 0.0), Vector3::new(0.0, 1.0, -2.0)];
+            let normal = tangents[0].cross(&tangents[1]).normalized().apply_normal(&a);
+            assert!((normal.length() - 1.0).abs() < 1e-5);
+            for tangent in tangents {
+                let tangent = linear(tangent).normalized();
+                assert!(normal.dot(&tangent).abs() < 1e-4, "{:?}", scale);
+            }
+            // still on the side the surface faced, also when mirrored
+            assert!(normal.dot(&linear(tangents[0].cross(&tangents[1]))) > 0.0);
+
+            // the reference, transposing the inverse
+            let inverse = a.inverse();
+            let normal_matrix = a.normal_matrix();
+            let reference = [
+                [inverse.xx, inverse.xy, inverse.xz],
+                [inverse.yx, inverse.yy, inverse.yz],
+                [inverse.zx, inverse.zy, inverse.zz],
+            ];
+            let rows = [
+                [normal_matrix.xx, normal_matrix.yx, normal_matrix.zx],
+                [normal_matrix.xy, normal_matrix.yy, normal_matrix.zy],
+                [normal_matrix.xz, normal_matrix.yz, normal_matrix.zz],
+            ];
+            for (row, reference) in rows.iter().zip(reference) {
+                for (a, b) in row.iter().zip(reference) {
+                    assert!((a - b).abs() < 1e-4, "{:?} {:?}", rows, reference);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn vector3_cross_is_perpendicular() {
+        let a = Vector3::new(1.0, 2.0, 3.0);
+        let b = Vector3::new(-2.0, 0.5, 1.0);
+        let c = a.cross(&b);
+        assert!(c.dot(&a).abs() < 1e-6 && c.dot(&b).abs() < 1e-6);
+        assert_eq!(Vector3::new(1.0, 0.0, 0.0).cross(&Vector3::new(0.0, 1.0, 0.0)), Vector3::new(0.0, 0.0, 1.0));
+
+        assert!((c.normalized().length() - 1.0).abs() < 1e-6);
+        assert_eq!(a.lerp(&b, 0.5), (a + b) * 0.5);
+        assert_eq!(a.min(&b), Vector3::new(-2.0, 0.5, 1.0));
+        assert_eq!((-a).abs(), a);
+        assert_eq!(2.0 * a, a * 2.0);
+        assert_eq!((a[0], a[1], a[2]), (1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn vector2_operations() {
+        let a = Vector2::new(3.0, 4.0);
+        let b = Vector2::new(-1.0, 2.0);
+        assert_eq!(a.length(), 5.0);
+        assert_eq!(a.dot(b), 5.0);
+        assert_eq!(a.normalized(), Vector2::new(0.6, 0.8));
+        assert_eq!(a.perp().dot(a), 0.0);
+        assert!(a.wedge(a.perp()).xy > 0.0);
+        assert_eq!(a.lerp(b, 0.5), Vector2::new(1.0, 3.0));
+        assert_eq!((a.min(b), a.max(b)), (Vector2::new(-1.0, 2.0), Vector2::new(3.0, 4.0)));
+        assert_eq!((-a).abs(), a);
+        assert_eq!(a / 2.0, 0.5 * a);
+
+        let mut c = a;
+        c *= 2.0;
+        c -= b;
+        c /= 2.0;
+        c += b;
+        assert_eq!((c[0], c[1]), (2.5, 5.0));
+        let mut d = a;
+        d.normalize();
+        assert_eq!(d, a.normalized());
+
+        assert!(Vector2::NAN.is_nan());
+        assert!(!Vector2::IDENTITY.is_nan());
+    }
+
+    #[test]
+    fn vectors_convert_between_sizes() {
+        let v = Vector3::new(1.0, 2.0, 3.0);
+        assert_eq!(v.xy(), Vector2::new(1.0, 2.0));
+        assert_eq!(v.xy().extend(3.0), v);
+        assert_eq!(v.extend(2.0).truncate(), v);
+        assert_eq!(v.extend(2.0).project(), v / 2.0);
+        assert_eq!(v.extend(1.0).dot(Vector4::new(0.0, 0.0, 1.0, -3.0)), 0.0);
+    }
+
+    #[test]
+    fn look_at_puts_the_target_ahead() {
+        let eye = Vector3::new(1.0, 2.0, 3.0);
+        let target = Vector3::new(-2.0, 0.0, 7.0);
+        let view = Affine3::look_at(&eye, &target, &Vector3::new(0.0, 1.0, 0.0));
+
+        assert!(eye.apply(&view).norm_sqr() < 1e-10);
+        let ahead = target.apply(&view);
+        assert!((ahead - Vector3::new(0.0, 0.0, (target - eye).length())).norm_sqr() < 1e-8);
+        assert!(Vector3::new(1.0, 3.0, 3.0).apply(&view).y > 0.0);
+
+        // looking along z with y up is the identity, as for the camera without any angles
+        let p = Vector3::new(0.3, 0.4, 0.5);
+        let along_z = Affine3::look_at(&Vector3::IDENTITY, &Vector3::new(0.0, 0.0, 1.0), &Vector3::new(0.0, 1.0, 0.0));
+        assert!((p.apply(&along_z) - p).norm_sqr() < 1e-12);
+    }
+
+    #[test]
+    fn quaternions_convert_to_rotors_rotating_alike() {
+        let half = std::f32::consts::FRAC_PI_4;
+        // a quarter turn about z, as glTF stores it
+        let rotor = Rotor::from_quaternion([0.0, 0.0, half.sin(), half.cos()]);
+        let turned = Vector3::new(1.0, 0.0, 0.0).apply(&Affine3::from(Scale3::new(1.0, 1.0, 1.0), rotor, Vector3::IDENTITY));
+        assert!((turned - Vector3::new(0.0, 1.0, 0.0)).norm_sqr() < 1e-10);
+        let exp = BiVector3::new(half, 0.0, 0.0).exp().to_array();
+        for (a, b) in rotor.to_array().iter().zip(exp) {
+            assert!((a - b).abs() < 1e-6);
+        }
+
+        let rotor = BiVector3::new(0.3, -0.5, 0.2).exp();
+        let back = Rotor::from_quaternion(rotor.to_quaternion());
+        assert_eq!(back.to_array(), rotor.to_array());
+        // the negated quaternion is the same rotation
+        let [x, y, z, w] = rotor.to_quaternion();
+        let negated = Rotor::from_quaternion([-x, -y, -z, -w]);
+        let p = Vector3::new(0.7, -1.1, 2.3);
+        let one = Scale3::new(1.0, 1.0, 1.0);
+        let (a, b) = (Affine3::from(one, rotor, Vector3::IDENTITY), Affine3::from(one, negated, Vector3::IDENTITY));
+        assert!((p.apply(&a) - p.apply(&b)).norm_sqr() < 1e-10);
+    }
+
+    #[test]
+    fn decompose_undoes_from() {
+        let close = |a: &Affine3, b: &Affine3| {
+            [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0), Vector3::IDENTITY]
+                .iter()
+                .all(|p| (p.apply(a) - p.apply(b)).norm_sqr() < 1e-8)
+        };
+        for bivector in [
+            BiVector3::new(0.3, -0.5, 0.2),
+            BiVector3::new(0.0, 1.5, 0.1),
+            BiVector3::new(-1.4, 0.2, 0.3),
+            BiVector3::new(0.1, 0.1, -1.5),
+            BiVector3::new(0.0, 0.0, 0.0),
+        ] {
+            let a = Affine3::from(Scale3::new(2.0, 0.5, 3.0), bivector.exp(), Vector3::new(1.0, -2.0, 4.0));
+            let (scale, rotor, translation) = a.decompose();
+            assert!(close(&Affine3::from(scale, rotor, translation), &a), "{:?}", bivector);
+            assert!((scale.x - 2.0).abs() < 1e-5 && (scale.y - 0.5).abs() < 1e-5 && (scale.z - 3.0).abs() < 1e-5);
+        }
+
+        let mirrored = Affine3::from(Scale3::new(-1.0, 2.0, 1.0), BiVector3::new(0.2, 0.4, 0.0).exp(), Vector3::IDENTITY);
+        let (scale, rotor, translation) = mirrored.decompose();
+        assert!(scale.x < 0.0);
+        assert!(close(&Affine3::from(scale, rotor, translation), &mirrored));
+    }
+
+    #[test]
+    fn orthonormalize_removes_drift() {
+        let mut a = Affine3::IDENTITY;
+        for _ in 0..10000 {
+            a.rotate(0.001, &BiVector3::new(0.6, 0.0, 0.8));
+        }
+        a.scale(&Scale3::new(1.001, 0.999, 1.0));
+        a.orthonormalize();
+        let columns = [
+            Vector3::new(a.xx, a.xy, a.xz),
+            Vector3::new(a.yx, a.yy, a.yz),
+            Vector3::new(a.zx, a.zy, a.zz),
+        ];
+        for (i, column) in columns.iter().enumerate() {
+            assert!((column.length() - 1.0).abs() < 1e-6);
+            assert!(column.dot(&columns[(i + 1) % 3]).abs() < 1e-6);
+        }
+        assert!(columns[0].cross(&columns[1]).dot(&columns[2]) > 0.0);
+
+        let mut rotor = BiVector3::new(0.3, 0.1, 0.2).exp();
+        rotor /= 0.9;
+        assert!((rotor.renormalize().norm_sqr() - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,135 @@
+use super::{Affine3, Scale2, Vector2};
+
+/// `Affine3` of the plane, applied the same way: the linear part's columns are the images of
+/// x and y, `_x` and `_y` the translation, and `compose` applies `self` first
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Affine2 {
+    pub xx: f32,
+    pub yx: f32,
+    pub _x: f32,
+
+    pub xy: f32,
+    pub yy: f32,
+    pub _y: f32,
+}
+
+impl Affine2 {
+    pub const IDENTITY: Self = Self {
+        xx: 1.0,
+        yx: 0.0,
+        _x: 0.0,
+        xy: 0.0,
+        yy: 1.0,
+        _y: 0.0,
+    };
+
+    // (A, a) * (B, b) = (A * B, a * B + b)
+    pub fn compose(&self, other: &Affine2) -> Self {
+        Self {
+            xx: self.xx * other.xx + self.xy * other.yx,
+            yx: self.yx * other.xx + self.yy * other.yx,
+            _x: self._x * other.xx + self._y * other.yx + other._x,
+
+            xy: self.xx * other.xy + self.xy * other.yy,
+            yy: self.yx * other.xy + self.yy * other.yy,
+            _y: self._x * other.xy + self._y * other.yy + other._y,
+        }
+    }
+
+    pub fn scale(&mut self, s: &Scale2) -> &mut Self {
+        self.xx *= s.x;
+        self.yx *= s.x;
+        self._x *= s.x;
+
+        self.xy *= s.y;
+        self.yy *= s.y;
+        self._y *= s.y;
+        self
+    }
+
+    pub fn translate(&mut self, v: &Vector2) -> &mut Self {
+        self._x += v.x;
+        self._y += v.y;
+        self
+    }
+
+    /// by `angle` radians, x towards y
+    pub fn rotate(&mut self, angle: f32) -> &mut Self {
+        let (sin, cos) = angle.sin_cos();
+        *self = Affine2 {
+            xx: self.xx * cos - self.xy * sin,
+            yx: self.yx * cos - self.yy * sin,
+            _x: self._x * cos - self._y * sin,
+
+            xy: self.xx * sin + self.xy * cos,
+            yy: self.yx * sin + self.yy * cos,
+            _y: self._x * sin + self._y * cos,
+        };
+        self
+    }
+
+    /// scales, then rotates by `angle` like `rotate`, then translates
+    pub fn from(scale: Scale2, angle: f32, translation: Vector2) -> Self {
+        let mut affine = Self::IDENTITY;
+        *affine.scale(&scale).rotate(angle).translate(&translation)
+    }
+
+    // self.compose(&self.inverse()) = IDENTITY, assumes non singular
+    pub fn inverse(&self) -> Self {
+        let inv_det = 1.0 / (self.xx * self.yy - self.yx * self.xy);
+
+        let xx = self.yy * inv_det;
+        let yx = -self.yx * inv_det;
+        let xy = -self.xy * inv_det;
+        let yy = self.xx * inv_det;
+
+        Self {
+            xx,
+            yx,
+            _x: -(xx * self._x + yx * self._y),
+
+            xy,
+            yy,
+            _y: -(xy * self._x + yy * self._y),
+        }
+    }
+
+    /// acting on x and y alike, keeping z
+    pub fn to_affine3(self) -> Affine3 {
+        Affine3 {
+            xx: self.xx,
+            yx: self.yx,
+            _x: self._x,
+            xy: self.xy,
+            yy: self.yy,
+            _y: self._y,
+            ..Affine3::IDENTITY
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vector3;
+
+    #[test]
+    fn affine2_acts_like_affine3_on_the_plane() {
+        let a = Affine2::from(Scale2::new(2.0, 0.5), 0.7, Vector2::new(1.0, -3.0));
+        let mut b = Affine2::IDENTITY;
+        b.translate(&Vector2::new(-2.0, 0.5)).rotate(-1.2).scale(&Scale2::new(3.0, 1.0));
+        let v = Vector2::new(0.3, 4.0);
+
+        let close = |a: Vector2, b: Vector2| (a - b).norm_sqr() < 1e-8;
+        let expected = Vector3::new(v.x, v.y, 5.0).apply(&a.to_affine3().compose(&b.to_affine3()));
+        assert!(close(v.apply(&a.compose(&b)), expected.xy()));
+        assert_eq!(expected.z, 5.0);
+        assert!(close(v.apply(&a).apply(&b), v.apply(&a.compose(&b))));
+        assert!(close(v.apply(&a).apply(&a.inverse()), v));
+
+        let mut quarter = Affine2::IDENTITY;
+        quarter.rotate(std::f32::consts::FRAC_PI_2);
+        assert!(close(Vector2::new(1.0, 0.0).apply(&quarter), Vector2::new(0.0, 1.0)));
+    }
+}
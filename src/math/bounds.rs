@@ -0,0 +1,254 @@
+use super::{Affine3, Vector3};
+
+/// axis aligned box, empty when `min` is above `max` on any axis
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb3 {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb3 {
+    /// contains nothing, the identity of `union`
+    pub const EMPTY: Self = Self {
+        min: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        max: Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+    };
+
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vector3>) -> Self {
+        points.into_iter().fold(Self::EMPTY, |aabb, point| aabb.extend(point))
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// grown to contain `point`
+    pub fn extend(&self, point: Vector3) -> Self {
+        self.union(&Self { min: point, max: point })
+    }
+
+    pub fn union(&self, other: &Aabb3) -> Self {
+        Self {
+            min: self.min.min(&other.min),
+            max: self.max.max(&other.max),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn center(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    #[allow(dead_code)]
+    pub fn half_extents(&self) -> Vector3 {
+        (self.max - self.min) * 0.5
+    }
+
+    #[allow(dead_code)]
+    pub fn contains_point(&self, point: Vector3) -> bool {
+        self.min.x <= point.x && point.x <= self.max.x
+            && self.min.y <= point.y && point.y <= self.max.y
+            && self.min.z <= point.z && point.z <= self.max.z
+    }
+
+    #[allow(dead_code)]
+    pub fn intersects(&self, other: &Aabb3) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x
+            && self.min.y <= other.max.y && other.min.y <= self.max.y
+            && self.min.z <= other.max.z && other.min.z <= self.max.z
+    }
+
+    /// the box around the transformed box, which is larger than it when `affine` rotates
+    #[allow(dead_code)]
+    pub fn transform(&self, affine: &Affine3) -> Self {
+        let center = self.center().apply(affine);
+        let h = self.half_extents();
+        // each output axis reaches as far as the absolute linear part takes the half extents
+        let extents = Vector3::new(
+            affine.xx.abs() * h.x + affine.yx.abs() * h.y + affine.zx.abs() * h.z,
+            affine.xy.abs() * h.x + affine.yy.abs() * h.y + affine.zy.abs() * h.z,
+            affine.xz.abs() * h.x + affine.yz.abs() * h.y + affine.zz.abs() * h.z,
+        );
+        Self {
+            min: center - extents,
+            max: center + extents,
+        }
+    }
+
+    /// bit 0 of the index picks max over min along x, bit 1 along y and bit 2 along z
+    #[allow(dead_code)]
+    pub fn corners(&self) -> impl Iterator<Item = Vector3> + '_ {
+        (0..8).map(|i| Vector3::new(
+            if i & 1 == 0 { self.min.x } else { self.max.x },
+            if i & 2 == 0 { self.min.y } else { self.max.y },
+            if i & 4 == 0 { self.min.z } else { self.max.z },
+        ))
+    }
+
+    /// distance along `direction` to where the ray enters the box, 0 when it starts inside
+    #[allow(dead_code)]
+    pub fn intersect_ray(&self, origin: Vector3, direction: Vector3) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+        for (origin, direction, min, max) in [
+            (origin.x, direction.x, self.min.x, self.max.x),
+            (origin.y, direction.y, self.min.y, self.max.y),
+            (origin.z, direction.z, self.min.z, self.max.z),
+        ] {
+            // infinite for axes the ray is parallel to, NaN only when starting on a slab's edge
+            let a = (min - origin) / direction;
+            let b = (max - origin) / direction;
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        (near <= far).then_some(near)
+    }
+}
+
+/// oriented box, `axes` are its half extents along perpendicular directions
+#[derive(Clone, Copy, Debug)]
+pub struct Obb3 {
+    pub center: Vector3,
+    pub axes: [Vector3; 3],
+}
+
+impl Obb3 {
+    /// the unit cube around the origin transformed by `affine`, e.g. an instance's model,
+    /// assuming it doesn't shear
+    pub fn from_affine(affine: &Affine3) -> Self {
+        Self {
+            center: Vector3::new(affine._x, affine._y, affine._z),
+            axes: [
+                Vector3::new(affine.xx, affine.xy, affine.xz) * 0.5,
+                Vector3::new(affine.yx, affine.yy, affine.yz) * 0.5,
+                Vector3::new(affine.zx, affine.zy, affine.zz) * 0.5,
+            ],
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn aabb(&self) -> Aabb3 {
+        let extents = Vector3::new(
+            self.axes.iter().map(|a| a.x.abs()).sum(),
+            self.axes.iter().map(|a| a.y.abs()).sum(),
+            self.axes.iter().map(|a| a.z.abs()).sum(),
+        );
+        Aabb3 {
+            min: self.center - extents,
+            max: self.center + extents,
+        }
+    }
+
+    /// point in the box's space, where it spans -1 to 1 along each axis
+    #[allow(dead_code)]
+    fn to_local(self, point: Vector3) -> Vector3 {
+        let d = point - self.center;
+        let [x, y, z] = self.axes.map(|axis| d.dot(&axis) / axis.norm_sqr());
+        Vector3::new(x, y, z)
+    }
+
+    #[allow(dead_code)]
+    pub fn contains_point(&self, point: Vector3) -> bool {
+        let local = self.to_local(point);
+        local.x.abs() <= 1.0 && local.y.abs() <= 1.0 && local.z.abs() <= 1.0
+    }
+
+    /// half of the box's extent projected onto `direction`, scaled by its length
+    #[allow(dead_code)]
+    fn projected_radius(&self, direction: Vector3) -> f32 {
+        self.axes.iter().map(|axis| axis.dot(&direction).abs()).sum()
+    }
+
+    /// separating axis test over the face normals of both boxes and their cross products
+    #[allow(dead_code)]
+    pub fn intersects(&self, other: &Obb3) -> bool {
+        let d = other.center - self.center;
+        let separates = |axis: Vector3| {
+            d.dot(&axis).abs() > self.projected_radius(axis) + other.projected_radius(axis)
+        };
+
+        let face_axes = self.axes.iter().chain(&other.axes).copied();
+        let edge_axes = self.axes
+            .iter()
+            .flat_map(|a| other.axes.iter().map(move |b| a.cross(b)))
+            // parallel edges give no axis, and are covered by the face axes
+            .filter(|axis| axis.norm_sqr() > 1e-12);
+        !face_axes.chain(edge_axes).any(separates)
+    }
+
+    #[allow(dead_code)]
+    pub fn intersects_aabb(&self, aabb: &Aabb3) -> bool {
+        let h = aabb.half_extents();
+        self.intersects(&Obb3 {
+            center: aabb.center(),
+            axes: [
+                Vector3::new(h.x, 0.0, 0.0),
+                Vector3::new(0.0, h.y, 0.0),
+                Vector3::new(0.0, 0.0, h.z),
+            ],
+        })
+    }
+
+    /// distance along `direction` to where the ray enters the box, 0 when it starts inside
+    #[allow(dead_code)]
+    pub fn intersect_ray(&self, origin: Vector3, direction: Vector3) -> Option<f32> {
+        // a unit cube in the box's space, the same parameter along the ray in both spaces
+        let local_origin = self.to_local(origin);
+        let [x, y, z] = self.axes.map(|axis| direction.dot(&axis) / axis.norm_sqr());
+        let cube = Aabb3::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0));
+        cube.intersect_ray(local_origin, Vector3::new(x, y, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{BiVector3, Scale3};
+
+    #[test]
+    fn transformed_aabb_contains_transformed_corners() {
+        let aabb = Aabb3::from_points([Vector3::new(1.0, 2.0, 3.0), Vector3::new(-1.0, 0.0, 4.0)]);
+        assert_eq!(aabb, Aabb3::new(Vector3::new(-1.0, 0.0, 3.0), Vector3::new(1.0, 2.0, 4.0)));
+
+        let affine = Affine3::from(
+            Scale3::new(2.0, 1.0, 0.5),
+            BiVector3::new(0.3, -0.4, 0.2).exp(),
+            Vector3::new(5.0, 0.0, -1.0),
+        );
+        let transformed = aabb.transform(&affine);
+        for corner in aabb.corners() {
+            let corner = corner.apply(&affine);
+            let inside = transformed.union(&Aabb3::new(corner, corner));
+            assert!((inside.min - transformed.min).norm_sqr() < 1e-10);
+            assert!((inside.max - transformed.max).norm_sqr() < 1e-10);
+        }
+        assert!(Aabb3::EMPTY.is_empty());
+        assert!(!aabb.union(&Aabb3::EMPTY).is_empty());
+    }
+
+    #[test]
+    fn obb_separating_axes() {
+        let unit = Obb3::from_affine(&Affine3::IDENTITY);
+        assert!(unit.contains_point(Vector3::new(0.4, -0.4, 0.4)));
+        assert!(!unit.contains_point(Vector3::new(0.6, 0.0, 0.0)));
+
+        // turned 45 degrees about y, its corner reaches x = 0.5 * sqrt 2 ~ 0.707
+        let turned = |x: f32| Obb3::from_affine(&Affine3::from(
+            Scale3::new(1.0, 1.0, 1.0),
+            BiVector3::new(0.0, 0.0, std::f32::consts::FRAC_PI_8).exp(),
+            Vector3::new(x, 0.0, 0.0),
+        ));
+        assert!(unit.intersects(&turned(1.15)));
+        assert!(!unit.intersects(&turned(1.25)));
+        assert!(turned(1.15).intersects_aabb(&unit.aabb()));
+
+        let hit = unit.intersect_ray(Vector3::new(-3.0, 0.1, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        assert!((hit.unwrap() - 2.5).abs() < 1e-6);
+        assert!(unit.intersect_ray(Vector3::new(-3.0, 2.0, 0.0), Vector3::new(1.0, 0.0, 0.0)).is_none());
+    }
+}
@@ -0,0 +1,180 @@
+//! colors are kept linear, as the shaders light and blend them. srgb is only how they're written
+//! down and picked, the srgb surface and textures encode and decode them on the gpu
+
+use super::damp::Interpolate;
+
+/// linear rgb with straight alpha
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+/// decodes one srgb channel in 0..1
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// encodes one linear channel in 0..1
+#[allow(dead_code)]
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl Color {
+    #[allow(dead_code)]
+    pub const BLACK: Self = Self::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Self = Self::rgb(1.0, 1.0, 1.0);
+    #[allow(dead_code)]
+    pub const TRANSPARENT: Self = Self::new(0.0, 0.0, 0.0, 0.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// opaque
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::new(r, g, b, 1.0)
+    }
+
+    /// opaque, from srgb channels in 0..1 as a color picker shows them
+    pub fn from_srgb(r: f32, g: f32, b: f32) -> Self {
+        Self::rgb(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b))
+    }
+
+    /// opaque, from 0..255 srgb channels as in `#rrggbb`
+    #[allow(dead_code)]
+    pub fn from_srgb8(r: u8, g: u8, b: u8) -> Self {
+        Self::from_srgb(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+    }
+
+    /// opaque, hue in degrees and saturation and value in 0..1. like color pickers,
+    /// hsv describes the srgb encoded color
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = value - chroma;
+        Self::from_srgb(r + m, g + m, b + m)
+    }
+
+    /// hue in degrees, saturation and value of the srgb encoded color
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let [r, g, b, _] = self.to_srgb();
+        let max = r.max(g).max(b);
+        let chroma = max - r.min(g).min(b);
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+        (hue, saturation, max)
+    }
+
+    #[allow(dead_code)]
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    /// srgb encoded channels, alpha stays linear
+    #[allow(dead_code)]
+    pub fn to_srgb(self) -> [f32; 4] {
+        [
+            linear_to_srgb(self.r.clamp(0.0, 1.0)),
+            linear_to_srgb(self.g.clamp(0.0, 1.0)),
+            linear_to_srgb(self.b.clamp(0.0, 1.0)),
+            self.a,
+        ]
+    }
+
+    /// for uniforms, which take linear colors
+    pub fn to_rgb_array(self) -> [f32; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// for clearing render targets, which take linear colors whatever their format
+    pub fn to_wgpu(self) -> wgpu::Color {
+        wgpu::Color {
+            r: self.r as f64,
+            g: self.g as f64,
+            b: self.b as f64,
+            a: self.a as f64,
+        }
+    }
+
+    /// the rgb scaled, as by a light's intensity
+    pub fn scaled(self, s: f32) -> Self {
+        Self::new(self.r * s, self.g * s, self.b * s, self.a)
+    }
+}
+
+impl From<[f32; 3]> for Color {
+    fn from([r, g, b]: [f32; 3]) -> Self {
+        Self::rgb(r, g, b)
+    }
+}
+
+/// blends in linear space
+impl Interpolate for Color {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        let [a, b] = [self.to_array(), other.to_array()];
+        let [r, g, b, a] = [0, 1, 2, 3].map(|i| a[i] + (b[i] - a[i]) * t);
+        Self::new(r, g, b, a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trips() {
+        for i in 0..=255 {
+            let c = i as f32 / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(c)) - c).abs() < 1e-5);
+        }
+        // mid gray in srgb is about a fifth of the light
+        assert!((Color::from_srgb8(128, 128, 128).r - 0.2158605).abs() < 1e-5);
+        assert_eq!(Color::from_srgb(1.0, 0.0, 1.0), Color::rgb(1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn hsv_round_trips() {
+        assert_eq!(Color::from_hsv(0.0, 1.0, 1.0), Color::rgb(1.0, 0.0, 0.0));
+        assert_eq!(Color::from_hsv(120.0, 1.0, 1.0), Color::rgb(0.0, 1.0, 0.0));
+        assert_eq!(Color::from_hsv(-120.0, 1.0, 1.0), Color::rgb(0.0, 0.0, 1.0));
+        assert_eq!(Color::from_hsv(42.0, 0.0, 1.0), Color::WHITE);
+        for (h, s, v) in [(30.0, 0.5, 0.8), (200.0, 0.9, 0.3), (330.0, 0.2, 1.0)] {
+            let (hue, saturation, value) = Color::from_hsv(h, s, v).to_hsv();
+            assert!((hue - h).abs() < 1e-2 && (saturation - s).abs() < 1e-4 && (value - v).abs() < 1e-4);
+        }
+    }
+}
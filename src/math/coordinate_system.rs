@@ -0,0 +1,209 @@
+//! the engine's world is y up and left handed, in meters: looking along +z with y up, +x is to the
+//! right, as the camera without any angles sees it. the math itself doesn't care, the cross product
+//! and the rotors are the usual algebraic ones, only what's drawn where on screen does.
+//! content made under other conventions is converted once, when it's imported, and everything after
+//! that is in the engine's
+
+use super::{Affine3, Rotor, Scale3, Vector3};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(dead_code)]
+pub enum Up {
+    Y,
+    Z,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(dead_code)]
+pub enum Handedness {
+    Left,
+    Right,
+}
+
+/// where a source's axes point. +x is to the right in all of them, so a y up right handed source
+/// looks along -z and a z up right handed one along +y
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(dead_code)]
+pub struct CoordinateSystem {
+    pub up: Up,
+    pub handedness: Handedness,
+    /// the length of a source unit, 0.01 for centimeters
+    pub meters_per_unit: f32,
+}
+
+impl CoordinateSystem {
+    #[allow(dead_code)]
+    pub const ENGINE: Self = Self::new(Up::Y, Handedness::Left, 1.0);
+    /// as the gltf spec fixes it. obj has no convention, though it's mostly written like this
+    #[allow(dead_code)]
+    pub const GLTF: Self = Self::new(Up::Y, Handedness::Right, 1.0);
+    #[allow(dead_code)]
+    pub const BLENDER: Self = Self::new(Up::Z, Handedness::Right, 1.0);
+
+    #[allow(dead_code)]
+    pub const fn new(up: Up, handedness: Handedness, meters_per_unit: f32) -> Self {
+        Self {
+            up,
+            handedness,
+            meters_per_unit,
+        }
+    }
+
+    /// the right of a view along `forward` with `up`, perpendicular to both
+    #[allow(dead_code)]
+    pub fn right(self, forward: &Vector3, up: &Vector3) -> Vector3 {
+        match self.handedness {
+            Handedness::Left => up.cross(forward),
+            Handedness::Right => forward.cross(up),
+        }
+    }
+
+    /// for each engine axis, the source axis it comes from and its sign
+    #[allow(dead_code)]
+    fn axes(self) -> [(usize, f32); 3] {
+        match (self.up, self.handedness) {
+            (Up::Y, Handedness::Left) => [(0, 1.0), (1, 1.0), (2, 1.0)],
+            (Up::Y, Handedness::Right) => [(0, 1.0), (1, 1.0), (2, -1.0)],
+            (Up::Z, Handedness::Left) => [(0, 1.0), (2, 1.0), (1, -1.0)],
+            (Up::Z, Handedness::Right) => [(0, 1.0), (2, 1.0), (1, 1.0)],
+        }
+    }
+
+    /// whether converting mirrors. triangles keep their winding, counter clockwise from the front in
+    /// the source's handedness is counter clockwise in the engine's, but tangent frames flip, such as
+    /// the sign of a normal map's bitangent
+    #[allow(dead_code)]
+    pub fn mirrors(self) -> bool {
+        self.handedness != Self::ENGINE.handedness
+    }
+
+    /// a source direction or normal in the engine's axes, keeping its length
+    #[allow(dead_code)]
+    pub fn convert_direction(self, v: Vector3) -> Vector3 {
+        let [x, y, z] = self.axes().map(|(axis, sign)| v[axis] * sign);
+        Vector3::new(x, y, z)
+    }
+
+    /// a source position in engine meters
+    #[allow(dead_code)]
+    pub fn convert_point(self, p: Vector3) -> Vector3 {
+        self.convert_direction(p) * self.meters_per_unit
+    }
+
+    /// a source scale along the engine's axes, unitless so only swapped around
+    #[allow(dead_code)]
+    pub fn convert_scale(self, s: Scale3) -> Scale3 {
+        let [x, y, z] = self.axes().map(|(axis, _)| [s.x, s.y, s.z][axis]);
+        Scale3::new(x, y, z)
+    }
+
+    /// a source rotation turning the engine's axes as it turned the source's, which in a mirrored
+    /// source is the other way around
+    #[allow(dead_code)]
+    pub fn convert_rotor(self, r: Rotor) -> Rotor {
+        let rotation = Affine3::from(Scale3::IDENTITY, r, Vector3::IDENTITY);
+        let [x, y, z] = self.axes().map(|(axis, sign)| {
+            let mut source_axis = Vector3::IDENTITY;
+            source_axis[axis] = sign;
+            self.convert_direction(source_axis.apply(&rotation))
+        });
+        Rotor::from_rotated_axes(&x, &y, &z)
+    }
+
+    /// source to engine space, scaling to meters
+    #[allow(dead_code)]
+    pub fn to_engine(self) -> Affine3 {
+        let [x, y, z] = [0, 1, 2].map(|axis| {
+            let mut source_axis = Vector3::IDENTITY;
+            source_axis[axis] = 1.0;
+            self.convert_point(source_axis)
+        });
+        Affine3 {
+            xx: x.x,
+            xy: x.y,
+            xz: x.z,
+            yx: y.x,
+            yy: y.y,
+            yz: y.z,
+            zx: z.x,
+            zy: z.y,
+            zz: z.z,
+            ..Affine3::IDENTITY
+        }
+    }
+
+    /// a source transform, such as a node's, acting on engine space instead
+    #[allow(dead_code)]
+    pub fn convert_affine(self, a: &Affine3) -> Affine3 {
+        self.to_engine().inverse().compose(a).compose(&self.to_engine())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{BiVector3, Plane};
+
+    fn close(a: Vector3, b: Vector3) -> bool {
+        (a - b).norm_sqr() < 1e-8
+    }
+
+    #[test]
+    fn the_engine_camera_sees_x_to_the_right() {
+        let eye = Vector3::new(1.0, 2.0, 3.0);
+        let forward = Vector3::new(0.6, 0.0, 0.8);
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let view = Affine3::look_at(&eye, &(eye + forward), &up);
+        let right = CoordinateSystem::ENGINE.right(&forward, &up);
+        assert!(close((eye + right).apply(&view), Vector3::new(1.0, 0.0, 0.0)));
+
+        // each source's own view along its forward sees its right where the engine's does
+        for (system, forward, up) in [
+            (CoordinateSystem::GLTF, Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 1.0, 0.0)),
+            (CoordinateSystem::BLENDER, Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)),
+            (CoordinateSystem::new(Up::Z, Handedness::Left, 1.0), Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)),
+        ] {
+            assert!(close(system.convert_direction(forward), Vector3::new(0.0, 0.0, 1.0)));
+            assert!(close(system.convert_direction(up), Vector3::new(0.0, 1.0, 0.0)));
+            assert!(close(system.convert_direction(system.right(&forward, &up)), Vector3::new(1.0, 0.0, 0.0)));
+        }
+    }
+
+    #[test]
+    fn converted_content_keeps_its_shape_and_facing() {
+        // the outward normal of a front face, wound counter clockwise seen from outside in its own
+        // handedness, see `Plane::from_points`
+        let front = |handedness, [a, b, c]: [Vector3; 3]| {
+            let normal = Plane::from_points(a, b, c).normal;
+            if handedness == Handedness::Right { normal } else { -normal }
+        };
+
+        let centimeters = CoordinateSystem::new(Up::Z, Handedness::Right, 0.01);
+        for system in [CoordinateSystem::ENGINE, CoordinateSystem::GLTF, CoordinateSystem::BLENDER, centimeters] {
+            let positions = [Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 2.0, 1.0)];
+            let converted = positions.map(|p| system.convert_point(p));
+            assert!(close(
+                front(Handedness::Left, converted),
+                system.convert_direction(front(system.handedness, positions)),
+            ));
+
+            let scale = Scale3::new(1.0, 2.0, 3.0);
+            let rotor = (BiVector3::new(0.3, -0.5, 0.2) * 1.1).exp();
+            let translation = Vector3::new(4.0, 5.0, 6.0);
+            let transform = Affine3::from(scale, rotor, translation);
+            let p = Vector3::new(0.7, -0.2, 0.4);
+            let expected = system.convert_point(p.apply(&transform));
+            assert!(close(system.convert_point(p).apply(&system.convert_affine(&transform)), expected));
+
+            // the signs of a mirror cancel out of the scale, which stays along the axes, and out of
+            // the rotation, which stays proper, so a node's parts can be converted one by one
+            let converted = Affine3::from(
+                system.convert_scale(scale),
+                system.convert_rotor(rotor),
+                system.convert_point(translation),
+            );
+            assert!(close(system.convert_point(p).apply(&converted), expected));
+        }
+        assert!(close(centimeters.convert_point(Vector3::new(0.0, 0.0, 150.0)), Vector3::new(0.0, 1.5, 0.0)));
+    }
+}
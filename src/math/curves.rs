@@ -0,0 +1,258 @@
+//! piecewise cubic curves through control points, evaluated from the parameter `u`,
+//! which runs from 0 to the number of segments, one per segment
+
+use super::{BiVector3, Rotor, Vector3};
+
+/// how each segment's 4 controls shape it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Basis {
+    /// through the first and last controls, the middle two pull it along.
+    /// the points are the segments' controls, sharing their ends, 3n + 1 of them
+    Bezier,
+    /// through every point, each segment from the second to the third of its controls
+    CatmullRom,
+    /// near every point without passing through them, smoother than catmull rom
+    BSpline,
+}
+
+impl Basis {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "bezier" => Some(Basis::Bezier),
+            "catmull-rom" => Some(Basis::CatmullRom),
+            "bspline" => Some(Basis::BSpline),
+            _ => None,
+        }
+    }
+
+    /// of the 4 controls at `t` along a segment, summing to 1
+    pub fn weights(self, t: f32) -> [f32; 4] {
+        let s = 1.0 - t;
+        let (t2, t3) = (t * t, t * t * t);
+        match self {
+            Basis::Bezier => [s * s * s, 3.0 * s * s * t, 3.0 * s * t2, t3],
+            Basis::CatmullRom => [
+                0.5 * (-t3 + 2.0 * t2 - t),
+                0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+                0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+                0.5 * (t3 - t2),
+            ],
+            Basis::BSpline => [
+                s * s * s / 6.0,
+                (3.0 * t3 - 6.0 * t2 + 4.0) / 6.0,
+                (-3.0 * t3 + 3.0 * t2 + 3.0 * t + 1.0) / 6.0,
+                t3 / 6.0,
+            ],
+        }
+    }
+
+    /// derivatives of `weights` by `t`
+    #[allow(dead_code)]
+    pub fn derivative_weights(self, t: f32) -> [f32; 4] {
+        let s = 1.0 - t;
+        let t2 = t * t;
+        match self {
+            Basis::Bezier => [-3.0 * s * s, 3.0 * s * s - 6.0 * s * t, 6.0 * s * t - 3.0 * t2, 3.0 * t2],
+            Basis::CatmullRom => [
+                0.5 * (-3.0 * t2 + 4.0 * t - 1.0),
+                0.5 * (9.0 * t2 - 10.0 * t),
+                0.5 * (-9.0 * t2 + 8.0 * t + 1.0),
+                0.5 * (3.0 * t2 - 2.0 * t),
+            ],
+            Basis::BSpline => [
+                -0.5 * s * s,
+                (9.0 * t2 - 12.0 * t) / 6.0,
+                (-9.0 * t2 + 6.0 * t + 3.0) / 6.0,
+                0.5 * t2,
+            ],
+        }
+    }
+}
+
+fn weighted(controls: [Vector3; 4], weights: [f32; 4]) -> Vector3 {
+    controls
+        .iter()
+        .zip(weights)
+        .fold(Vector3::IDENTITY, |sum, (control, weight)| sum + *control * weight)
+}
+
+/// a curve through or near `points`, which must not be empty
+#[derive(Clone, Debug)]
+pub struct Spline<T> {
+    pub basis: Basis,
+    pub points: Vec<T>,
+}
+
+impl<T: Copy> Spline<T> {
+    pub fn new(basis: Basis) -> Self {
+        Self { basis, points: Vec::new() }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        match self.basis {
+            Basis::Bezier => self.points.len().saturating_sub(1) / 3,
+            Basis::CatmullRom | Basis::BSpline => self.points.len().saturating_sub(1),
+        }
+    }
+
+    /// the controls of the segment at `u` and how far along it `u` is.
+    /// the ends repeat the first and last points
+    fn controls(&self, u: f32) -> ([T; 4], f32) {
+        assert!(!self.points.is_empty());
+        let count = self.segment_count();
+        if count == 0 {
+            return ([self.points[0]; 4], 0.0);
+        }
+        let u = u.clamp(0.0, count as f32);
+        let i = (u as usize).min(count - 1);
+        let t = u - i as f32;
+        let controls = match self.basis {
+            Basis::Bezier => [0, 1, 2, 3].map(|j| self.points[3 * i + j]),
+            Basis::CatmullRom | Basis::BSpline => {
+                let last = self.points.len() - 1;
+                [i.saturating_sub(1), i, i + 1, (i + 2).min(last)].map(|j| self.points[j])
+            }
+        };
+        (controls, t)
+    }
+}
+
+impl Spline<Vector3> {
+    pub fn evaluate(&self, u: f32) -> Vector3 {
+        let (controls, t) = self.controls(u);
+        weighted(controls, self.basis.weights(t))
+    }
+
+    /// derivative by `u`, not normalized
+    #[allow(dead_code)]
+    pub fn tangent(&self, u: f32) -> Vector3 {
+        let (controls, t) = self.controls(u);
+        weighted(controls, self.basis.derivative_weights(t))
+    }
+}
+
+impl Spline<Rotor> {
+    /// the controls are weighted as bivectors, logs of their rotations from the segment's second,
+    /// so unit rotors stay unit and rotations through it are smooth
+    pub fn evaluate(&self, u: f32) -> Rotor {
+        let (controls, t) = self.controls(u);
+        let base = controls[1];
+        let logs = controls.map(|control| {
+            let log = (control * base.reverse()).log();
+            Vector3::new(log.xy, log.yz, log.zx)
+        });
+        let log = weighted(logs, self.basis.weights(t));
+        BiVector3::new(log.x, log.y, log.z).exp() * base
+    }
+}
+
+/// distances along a spline, mapping distance to `u` so it can be traveled at a constant speed
+#[derive(Clone, Debug)]
+pub struct ArcLength {
+    /// from the start to each sample, evenly spaced in `u`
+    distances: Vec<f32>,
+    step: f32,
+}
+
+impl ArcLength {
+    /// sums the chords between `samples_per_segment` samples of each segment
+    pub fn new(spline: &Spline<Vector3>, samples_per_segment: usize) -> Self {
+        let count = spline.segment_count() * samples_per_segment.max(1);
+        let step = 1.0 / samples_per_segment.max(1) as f32;
+        let mut distances = Vec::with_capacity(count + 1);
+        let mut distance = 0.0;
+        let mut previous = spline.evaluate(0.0);
+        distances.push(0.0);
+        for i in 1..=count {
+            let point = spline.evaluate(i as f32 * step);
+            distance += (point - previous).length();
+            distances.push(distance);
+            previous = point;
+        }
+        Self { distances, step }
+    }
+
+    pub fn length(&self) -> f32 {
+        *self.distances.last().unwrap()
+    }
+
+    /// `u` at `distance` from the start, clamped to the spline
+    pub fn parameter(&self, distance: f32) -> f32 {
+        let distance = distance.clamp(0.0, self.length());
+        let i = self.distances.partition_point(|d| *d < distance);
+        if i == 0 {
+            return 0.0;
+        }
+        let (before, after) = (self.distances[i - 1], self.distances[i]);
+        let t = if after > before { (distance - before) / (after - before) } else { 0.0 };
+        (i as f32 - 1.0 + t) * self.step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splines_interpolate_their_points() {
+        let points = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 2.0, 0.0),
+            Vector3::new(3.0, 2.0, 1.0),
+            Vector3::new(4.0, 0.0, 1.0),
+        ];
+        let catmull_rom = Spline { basis: Basis::CatmullRom, points: points.clone() };
+        for (i, point) in points.iter().enumerate() {
+            assert!((catmull_rom.evaluate(i as f32) - *point).length() < 1e-5);
+        }
+        let bezier = Spline { basis: Basis::Bezier, points: points.clone() };
+        assert!((bezier.evaluate(0.0) - points[0]).length() < 1e-5);
+        assert!((bezier.evaluate(1.0) - points[3]).length() < 1e-5);
+        assert!((bezier.tangent(0.0) - (points[1] - points[0]) * 3.0).length() < 1e-5);
+
+        for basis in [Basis::Bezier, Basis::CatmullRom, Basis::BSpline] {
+            assert!((basis.weights(0.3).iter().sum::<f32>() - 1.0).abs() < 1e-6);
+            let spline = Spline { basis, points: points.clone() };
+            let (u, h) = (0.4, 1e-2);
+            let difference = (spline.evaluate(u + h) - spline.evaluate(u - h)) / (2.0 * h);
+            assert!((difference - spline.tangent(u)).length() < 1e-2, "{:?}", basis);
+        }
+        assert_eq!(Basis::parse("catmull-rom"), Some(Basis::CatmullRom));
+        assert_eq!(Basis::parse("hermite"), None);
+    }
+
+    #[test]
+    fn arc_length_travels_at_constant_speed() {
+        let line = Spline {
+            basis: Basis::Bezier,
+            points: vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(0.1, 0.0, 0.0),
+                Vector3::new(0.2, 0.0, 0.0),
+                Vector3::new(6.0, 0.0, 0.0),
+            ],
+        };
+        let arc_length = ArcLength::new(&line, 256);
+        assert!((arc_length.length() - 6.0).abs() < 1e-3);
+        for distance in [0.0, 1.0, 3.0, 5.5, 6.0] {
+            let point = line.evaluate(arc_length.parameter(distance));
+            assert!((point.x - distance).abs() < 1e-2, "{} {:?}", distance, point);
+        }
+    }
+
+    #[test]
+    fn rotor_spline_passes_through_its_rotations() {
+        let rotations: Vec<_> = [0.0, 0.4, 1.1, 0.9]
+            .iter()
+            .map(|angle| (BiVector3::new(0.6, 0.0, 0.8) * *angle).exp())
+            .collect();
+        let spline = Spline { basis: Basis::CatmullRom, points: rotations.clone() };
+        for (i, rotation) in rotations.iter().enumerate() {
+            let [a, b] = [spline.evaluate(i as f32).to_array(), rotation.to_array()];
+            let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+            assert!(1.0 - dot.abs() < 1e-5);
+        }
+        let half = spline.evaluate(1.5).norm_sqr();
+        assert!((half - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,100 @@
+use super::{Motor, Rotor, Vector2, Vector3};
+
+/// values moving from one to another along a path parametrized from 0 to 1
+pub trait Interpolate: Copy {
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Vector2 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(*other, t)
+    }
+}
+
+impl Interpolate for Vector3 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(other, t)
+    }
+}
+
+impl Interpolate for Rotor {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Rotor::interpolate(self, other, t)
+    }
+}
+
+impl Interpolate for Motor {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Motor::interpolate(self, other, t)
+    }
+}
+
+/// each moving along its own path
+impl<A: Interpolate, B: Interpolate> Interpolate for (A, B) {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        (self.0.interpolate(&other.0, t), self.1.interpolate(&other.1, t))
+    }
+}
+
+/// moves `current` towards `target`, closing the fraction `1 - e^(-lambda * dt)` of the way.
+/// two steps of `dt / 2` end where one of `dt` does, so it's the same at any frame rate.
+/// `lambda` is in 1/seconds, about a third of the way is left after `1 / lambda` seconds
+pub fn damp<T: Interpolate>(current: T, target: T, lambda: f32, dt: f32) -> T {
+    current.interpolate(&target, 1.0 - (-lambda * dt).exp())
+}
+
+/// `damp` for angles in radians, turning the shorter way around
+pub fn damp_angle(current: f32, target: f32, lambda: f32, dt: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+    let difference = (target - current + PI).rem_euclid(TAU) - PI;
+    damp(current, current + difference, lambda, dt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::BiVector3;
+
+    #[test]
+    fn damping_is_frame_rate_independent() {
+        let once = damp(0.0, 10.0, 4.0, 0.1);
+        let twice = damp(damp(0.0, 10.0, 4.0, 0.05), 10.0, 4.0, 0.05);
+        assert!((once - twice).abs() < 1e-5);
+        assert_eq!(damp(3.0, 10.0, 4.0, 0.0), 3.0);
+        assert!((damp(3.0, 10.0, 4.0, 100.0) - 10.0).abs() < 1e-5);
+
+        let a = Vector3::new(1.0, 2.0, 3.0);
+        let b = Vector3::new(-4.0, 0.0, 8.0);
+        let once = damp(a, b, 2.0, 0.3);
+        let thrice = (0..3).fold(a, |v, _| damp(v, b, 2.0, 0.1));
+        assert!((once - thrice).length() < 1e-5);
+
+        assert!((damp_angle(0.1, std::f32::consts::TAU - 0.1, 1.0, 100.0) + 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rotor_damping_follows_the_shortest_arc() {
+        let from = (BiVector3::new(1.0, 0.0, 0.0) * 0.3).exp();
+        let to = (BiVector3::new(0.0, 0.6, 0.8) * 1.2).exp();
+        let close = |a: Rotor, b: Rotor| {
+            let [a, b] = [a.to_array(), b.to_array()];
+            let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+            1.0 - dot.abs() < 1e-5
+        };
+        assert!(close(from.interpolate(&to, 0.0), from));
+        assert!(close(from.interpolate(&to, 1.0), to));
+
+        let once = damp(from, to, 3.0, 0.2);
+        let twice = damp(damp(from, to, 3.0, 0.1), to, 3.0, 0.1);
+        assert!(close(once, twice));
+
+        let log = (BiVector3::new(0.0, 0.6, 0.8) * 1.2).exp().log();
+        assert!((log.yz - 0.72).abs() < 1e-5 && (log.zx - 0.96).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,268 @@
+use std::ops::*;
+
+use super::{Affine3, Vector3};
+
+/// double precision `Vector3`, for reference computations on the cpu
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DVector3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl DVector3 {
+    pub const IDENTITY: DVector3 = DVector3::new(0.0, 0.0, 0.0);
+
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    #[allow(dead_code)]
+    pub fn norm_sqr(&self) -> f64 {
+        self.dot(self)
+    }
+
+    #[allow(dead_code)]
+    pub fn length(&self) -> f64 {
+        self.norm_sqr().sqrt()
+    }
+
+    #[allow(dead_code)]
+    pub fn dot(&self, rhs: &DVector3) -> f64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    #[allow(dead_code)]
+    pub fn cross(&self, rhs: &DVector3) -> DVector3 {
+        DVector3::new(
+            self.y * rhs.z - self.z * rhs.y,
+            self.z * rhs.x - self.x * rhs.z,
+            self.x * rhs.y - self.y * rhs.x,
+        )
+    }
+
+    pub fn apply(&self, a: &DAffine3) -> Self {
+        Self {
+            x: self.x * a.xx + self.y * a.yx + self.z * a.zx + a._x,
+            y: self.x * a.xy + self.y * a.yy + self.z * a.zy + a._y,
+            z: self.x * a.xz + self.y * a.yz + self.z * a.zz + a._z,
+        }
+    }
+
+    /// rounded to single precision
+    pub fn to_f32(self) -> Vector3 {
+        Vector3::new(self.x as f32, self.y as f32, self.z as f32)
+    }
+}
+
+impl From<Vector3> for DVector3 {
+    fn from(v: Vector3) -> Self {
+        DVector3::new(v.x as f64, v.y as f64, v.z as f64)
+    }
+}
+
+impl Add for DVector3 {
+    type Output = DVector3;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        DVector3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub for DVector3 {
+    type Output = DVector3;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        DVector3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Mul<f64> for DVector3 {
+    type Output = DVector3;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        DVector3::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Div<f64> for DVector3 {
+    type Output = DVector3;
+
+    fn div(self, rhs: f64) -> Self::Output {
+        DVector3::new(self.x / rhs, self.y / rhs, self.z / rhs)
+    }
+}
+
+impl Neg for DVector3 {
+    type Output = DVector3;
+
+    fn neg(self) -> Self::Output {
+        DVector3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/// double precision `Affine3`, same layout and conventions
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DAffine3 {
+    pub xx: f64,
+    pub yx: f64,
+    pub zx: f64,
+    pub _x: f64,
+
+    pub xy: f64,
+    pub yy: f64,
+    pub zy: f64,
+    pub _y: f64,
+
+    pub xz: f64,
+    pub yz: f64,
+    pub zz: f64,
+    pub _z: f64,
+}
+
+impl DAffine3 {
+    #[allow(dead_code)]
+    pub const IDENTITY: Self = Self {
+        xx: 1.0,
+        yx: 0.0,
+        zx: 0.0,
+        _x: 0.0,
+        xy: 0.0,
+        yy: 1.0,
+        zy: 0.0,
+        _y: 0.0,
+        xz: 0.0,
+        yz: 0.0,
+        zz: 1.0,
+        _z: 0.0,
+    };
+
+    /// see `Affine3::compose`
+    #[allow(dead_code)]
+    pub fn compose(&self, other: &DAffine3) -> Self {
+        Self {
+            xx: self.xx * other.xx + self.xy * other.yx + self.xz * other.zx,
+            yx: self.yx * other.xx + self.yy * other.yx + self.yz * other.zx,
+            zx: self.zx * other.xx + self.zy * other.yx + self.zz * other.zx,
+            _x: self._x * other.xx + self._y * other.yx + self._z * other.zx + other._x,
+
+            xy: self.xx * other.xy + self.xy * other.yy + self.xz * other.zy,
+            yy: self.yx * other.xy + self.yy * other.yy + self.yz * other.zy,
+            zy: self.zx * other.xy + self.zy * other.yy + self.zz * other.zy,
+            _y: self._x * other.xy + self._y * other.yy + self._z * other.zy + other._y,
+
+            xz: self.xx * other.xz + self.xy * other.yz + self.xz * other.zz,
+            yz: self.yx * other.xz + self.yy * other.yz + self.yz * other.zz,
+            zz: self.zx * other.xz + self.zy * other.yz + self.zz * other.zz,
+            _z: self._x * other.xz + self._y * other.yz + self._z * other.zz + other._z,
+        }
+    }
+
+    /// see `Affine3::inverse`
+    #[allow(dead_code)]
+    pub fn inverse(&self) -> Self {
+        let c_xx = self.yy * self.zz - self.zy * self.yz;
+        let c_yx = self.zy * self.xz - self.xy * self.zz;
+        let c_zx = self.xy * self.yz - self.yy * self.xz;
+
+        let det = self.xx * c_xx + self.yx * c_yx + self.zx * c_zx;
+        let inv_det = 1.0 / det;
+
+        let xx = c_xx * inv_det;
+        let yx = (self.zx * self.yz - self.yx * self.zz) * inv_det;
+        let zx = (self.yx * self.zy - self.zx * self.yy) * inv_det;
+
+        let xy = c_yx * inv_det;
+        let yy = (self.xx * self.zz - self.zx * self.xz) * inv_det;
+        let zy = (self.zx * self.xy - self.xx * self.zy) * inv_det;
+
+        let xz = c_zx * inv_det;
+        let yz = (self.yx * self.xz - self.xx * self.yz) * inv_det;
+        let zz = (self.xx * self.yy - self.yx * self.xy) * inv_det;
+
+        Self {
+            xx,
+            yx,
+            zx,
+            _x: -(xx * self._x + yx * self._y + zx * self._z),
+
+            xy,
+            yy,
+            zy,
+            _y: -(xy * self._x + yy * self._y + zy * self._z),
+
+            xz,
+            yz,
+            zz,
+            _z: -(xz * self._x + yz * self._y + zz * self._z),
+        }
+    }
+
+    /// rounded to single precision
+    #[allow(dead_code)]
+    pub fn to_f32(self) -> Affine3 {
+        Affine3 {
+            xx: self.xx as f32,
+            yx: self.yx as f32,
+            zx: self.zx as f32,
+            _x: self._x as f32,
+
+            xy: self.xy as f32,
+            yy: self.yy as f32,
+            zy: self.zy as f32,
+            _y: self._y as f32,
+
+            xz: self.xz as f32,
+            yz: self.yz as f32,
+            zz: self.zz as f32,
+            _z: self._z as f32,
+        }
+    }
+}
+
+impl From<Affine3> for DAffine3 {
+    fn from(a: Affine3) -> Self {
+        Self {
+            xx: a.xx as f64,
+            yx: a.yx as f64,
+            zx: a.zx as f64,
+            _x: a._x as f64,
+
+            xy: a.xy as f64,
+            yy: a.yy as f64,
+            zy: a.zy as f64,
+            _y: a._y as f64,
+
+            xz: a.xz as f64,
+            yz: a.yz as f64,
+            zz: a.zz as f64,
+            _z: a._z as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{BiVector3, Scale3};
+
+    #[test]
+    fn double_precision_matches_single_precision() {
+        // a light far from the origin, like the shadow fit's
+        let mut view = Affine3::IDENTITY;
+        view.translate(&Vector3::new(0.0, 0.0, 100.0))
+            .rotate(0.3, &BiVector3::new(0.0, 0.6, 0.8))
+            .scale(&Scale3::new(0.02, 0.02, 1.0));
+        let p = Vector3::new(3.0, -7.0, 40.0);
+
+        let single = p.apply(&view.inverse()).apply(&view);
+        let double_view = DAffine3::from(view);
+        let double = DVector3::from(p).apply(&double_view.inverse()).apply(&double_view);
+
+        let single_error = (DVector3::from(single) - DVector3::from(p)).length();
+        let double_error = (double - DVector3::from(p)).length();
+        assert!(single_error < 1e-3, "{}", single_error);
+        assert!(double_error < 1e-9, "{}", double_error);
+        assert!((double.to_f32() - p).norm_sqr() < 1e-10);
+    }
+}
@@ -0,0 +1,166 @@
+//! easing curves, mapping the fraction of a transition's time passed to the fraction of the way gone
+
+use super::damp::Interpolate;
+
+/// shape of the start of a transition
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ease {
+    Linear,
+    Quad,
+    Cubic,
+    Expo,
+    /// pulls back before going
+    Back,
+    /// springs into going
+    Elastic,
+}
+
+impl Ease {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "linear" => Some(Ease::Linear),
+            "quad" => Some(Ease::Quad),
+            "cubic" => Some(Ease::Cubic),
+            "expo" => Some(Ease::Expo),
+            "back" => Some(Ease::Back),
+            "elastic" => Some(Ease::Elastic),
+            _ => None,
+        }
+    }
+}
+
+/// which ends of a transition are eased
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EaseDirection {
+    In,
+    Out,
+    InOut,
+}
+
+impl EaseDirection {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "in" => Some(EaseDirection::In),
+            "out" => Some(EaseDirection::Out),
+            "in-out" => Some(EaseDirection::InOut),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Easing {
+    pub ease: Ease,
+    pub direction: EaseDirection,
+}
+
+impl Easing {
+    #[allow(dead_code)]
+    pub const LINEAR: Self = Self::new(Ease::Linear, EaseDirection::In);
+
+    pub const fn new(ease: Ease, direction: EaseDirection) -> Self {
+        Self { ease, direction }
+    }
+
+    /// an ease and a direction, e.g. `cubic in-out`
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut words = s.split_whitespace();
+        let easing = Self::new(Ease::parse(words.next()?)?, EaseDirection::parse(words.next()?)?);
+        words.next().is_none().then_some(easing)
+    }
+
+    fn ease_in(ease: Ease, t: f32) -> f32 {
+        use std::f32::consts::TAU;
+        const BACK: f32 = 1.70158;
+        match ease {
+            Ease::Linear => t,
+            Ease::Quad => t * t,
+            Ease::Cubic => t * t * t,
+            Ease::Expo if t <= 0.0 => 0.0,
+            Ease::Expo => 2f32.powf(10.0 * (t - 1.0)),
+            Ease::Back => t * t * ((BACK + 1.0) * t - BACK),
+            Ease::Elastic if t <= 0.0 || t >= 1.0 => t,
+            Ease::Elastic => -(2f32.powf(10.0 * (t - 1.0))) * ((t - 1.075) * TAU / 0.3).sin(),
+        }
+    }
+
+    /// 0 at 0 and 1 at 1, `t` is clamped to between them
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self.direction {
+            EaseDirection::In => Self::ease_in(self.ease, t),
+            EaseDirection::Out => 1.0 - Self::ease_in(self.ease, 1.0 - t),
+            EaseDirection::InOut if t < 0.5 => Self::ease_in(self.ease, 2.0 * t) / 2.0,
+            EaseDirection::InOut => 1.0 - Self::ease_in(self.ease, 2.0 - 2.0 * t) / 2.0,
+        }
+    }
+}
+
+/// a value moving from `from` to `to` over `duration` seconds
+#[derive(Clone, Debug)]
+pub struct Tween<T> {
+    pub from: T,
+    pub to: T,
+    pub duration: f32,
+    pub easing: Easing,
+    elapsed: f32,
+}
+
+impl<T: Interpolate> Tween<T> {
+    pub fn new(from: T, to: T, duration: f32, easing: Easing) -> Self {
+        Self { from, to, duration, easing, elapsed: 0.0 }
+    }
+
+    /// the value after `dt` more seconds
+    pub fn advance(&mut self, dt: f32) -> T {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        self.value()
+    }
+
+    pub fn value(&self) -> T {
+        let t = if self.duration > 0.0 { self.elapsed / self.duration } else { 1.0 };
+        self.from.interpolate(&self.to, self.easing.apply(t))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vector3;
+
+    #[test]
+    fn easings_start_at_0_and_end_at_1() {
+        for ease in [Ease::Linear, Ease::Quad, Ease::Cubic, Ease::Expo, Ease::Back, Ease::Elastic] {
+            for direction in [EaseDirection::In, EaseDirection::Out, EaseDirection::InOut] {
+                let easing = Easing::new(ease, direction);
+                assert!(easing.apply(0.0).abs() < 1e-3, "{:?}", easing);
+                assert!((easing.apply(1.0) - 1.0).abs() < 1e-3, "{:?}", easing);
+                if direction == EaseDirection::InOut {
+                    assert!((easing.apply(0.5) - 0.5).abs() < 1e-3, "{:?}", easing);
+                }
+            }
+        }
+        assert!(Easing::new(Ease::Back, EaseDirection::In).apply(0.2) < 0.0);
+        assert_eq!(Easing::new(Ease::Quad, EaseDirection::Out).apply(0.5), 0.75);
+        assert_eq!(Easing::parse("back in-out"), Some(Easing::new(Ease::Back, EaseDirection::InOut)));
+        assert_eq!(Easing::parse("back"), None);
+    }
+
+    #[test]
+    fn tween_reaches_its_end() {
+        let mut tween = Tween::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(2.0, 4.0, 0.0),
+            1.0,
+            Easing::new(Ease::Cubic, EaseDirection::InOut),
+        );
+        assert_eq!(tween.advance(0.5), Vector3::new(1.0, 2.0, 0.0));
+        assert!(!tween.is_finished());
+        assert_eq!(tween.advance(2.0), Vector3::new(2.0, 4.0, 0.0));
+        assert!(tween.is_finished());
+    }
+}
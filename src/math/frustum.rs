@@ -0,0 +1,130 @@
+use super::{Aabb3, Affine3, Plane, Vector3};
+
+/// the volume seen through a view as uploaded in the camera buffer, where visible points
+/// have |x| and |y| below z, and z beyond the near plane.
+/// the projection is infinite, so the far plane is optional.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    /// left, right, bottom, top, near, then far if there is one.
+    /// world space, unit normals pointing inwards
+    planes: [Plane; 6],
+    plane_count: usize,
+    to_world: Affine3,
+    near_z: f32,
+    far_z: Option<f32>,
+}
+
+impl Frustum {
+    /// `view` is world to view space, scaled so the sides are at |x| = z and |y| = z
+    pub fn new(view: &Affine3, near_z: f32, far_z: Option<f32>) -> Self {
+        // rows of the view, each a view space coordinate as a function of world space
+        let x = Plane::new(Vector3::new(view.xx, view.yx, view.zx), view._x);
+        let y = Plane::new(Vector3::new(view.xy, view.yy, view.zy), view._y);
+        let z = Plane::new(Vector3::new(view.xz, view.yz, view.zz), view._z);
+
+        let add = |a: Plane, b: Plane| Plane::new(a.normal + b.normal, a.distance + b.distance);
+        let sub = |a: Plane, b: Plane| Plane::new(a.normal - b.normal, a.distance - b.distance);
+        let planes = [
+            add(z, x),
+            sub(z, x),
+            add(z, y),
+            sub(z, y),
+            Plane::new(z.normal, z.distance - near_z),
+            far_z.map_or(Plane::new(Vector3::IDENTITY, 0.0), |far_z| Plane::new(-z.normal, far_z - z.distance)),
+        ].map(|plane| plane.normalized());
+
+        Self {
+            planes,
+            plane_count: if far_z.is_some() { 6 } else { 5 },
+            to_world: view.inverse(),
+            near_z,
+            far_z,
+        }
+    }
+
+    /// see `Frustum::planes`
+    pub fn planes(&self) -> &[Plane] {
+        &self.planes[..self.plane_count]
+    }
+
+    #[allow(dead_code)]
+    pub fn contains_point(&self, point: Vector3) -> bool {
+        self.planes().iter().all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    /// conservative, may be true for spheres just outside of the corners
+    pub fn intersects_sphere(&self, center: Vector3, radius: f32) -> bool {
+        self.planes().iter().all(|plane| plane.signed_distance(center) >= -radius)
+    }
+
+    /// conservative, false only when the box is entirely outside of one of the planes
+    pub fn intersects_aabb(&self, aabb: &Aabb3) -> bool {
+        let (min, max) = (aabb.min, aabb.max);
+        self.planes().iter().all(|plane| {
+            let normal = plane.normal;
+            // the corner furthest along the normal
+            let corner = Vector3::new(
+                if normal.x > 0.0 { max.x } else { min.x },
+                if normal.y > 0.0 { max.y } else { min.y },
+                if normal.z > 0.0 { max.z } else { min.z },
+            );
+            plane.signed_distance(corner) >= 0.0
+        })
+    }
+
+    /// world space, bit 0 of the index picks right over left, bit 1 top over bottom,
+    /// and bit 2 far over near, so corners one bit apart share an edge.
+    /// None without a far plane
+    pub fn corners(&self) -> Option<[Vector3; 8]> {
+        let far_z = self.far_z?;
+        Some(std::array::from_fn(|i| {
+            let z = if i & 4 == 0 { self.near_z } else { far_z };
+            Vector3::new(
+                if i & 1 == 0 { -z } else { z },
+                if i & 2 == 0 { -z } else { z },
+                z,
+            ).apply(&self.to_world)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{BiVector3, Scale3};
+
+    #[test]
+    fn planes_bound_the_view() {
+        let mut view = Affine3::IDENTITY;
+        view.translate(&Vector3::new(1.0, 0.0, -2.0))
+            .rotate(0.4, &BiVector3::new(0.0, 0.0, 1.0))
+            .scale(&Scale3::new(2.0, 3.0, 1.0));
+        let frustum = Frustum::new(&view, 0.5, Some(10.0));
+        let to_world = view.inverse();
+
+        assert!(frustum.contains_point(Vector3::new(0.0, 0.0, 5.0).apply(&to_world)));
+        assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, 0.2).apply(&to_world)));
+        assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, 11.0).apply(&to_world)));
+        assert!(!frustum.contains_point(Vector3::new(3.0, 0.0, 2.0).apply(&to_world)));
+
+        for corner in frustum.corners().unwrap() {
+            let in_view = corner.apply(&view);
+            assert!((in_view.x.abs() - in_view.z).abs() < 1e-4, "{:?}", in_view);
+            assert!((in_view.y.abs() - in_view.z).abs() < 1e-4, "{:?}", in_view);
+        }
+
+        // a sphere outside of the left plane, by less than its radius
+        let center = Vector3::new(-6.0, 0.0, 5.0).apply(&to_world);
+        assert!(!frustum.contains_point(center));
+        assert!(frustum.intersects_sphere(center, 1.0));
+        assert!(!frustum.intersects_sphere(center, 0.1));
+    }
+
+    #[test]
+    fn box_behind_the_view_is_culled() {
+        let frustum = Frustum::new(&Affine3::IDENTITY, 0.1, None);
+        assert!(frustum.corners().is_none());
+        assert!(frustum.intersects_aabb(&Aabb3::new(Vector3::new(-1.0, -1.0, 5.0), Vector3::new(1.0, 1.0, 6.0))));
+        assert!(!frustum.intersects_aabb(&Aabb3::new(Vector3::new(-1.0, -1.0, -6.0), Vector3::new(1.0, 1.0, -5.0))));
+    }
+}
@@ -0,0 +1,179 @@
+#[cfg(feature = "glam")]
+mod glam_conversions {
+    use crate::math::{Affine3, Rotor, Vector2, Vector3};
+
+    impl From<glam::Vec2> for Vector2 {
+        fn from(v: glam::Vec2) -> Self {
+            Vector2::new(v.x, v.y)
+        }
+    }
+
+    impl From<Vector2> for glam::Vec2 {
+        fn from(v: Vector2) -> Self {
+            glam::Vec2::new(v.x, v.y)
+        }
+    }
+
+    impl From<glam::Vec3> for Vector3 {
+        fn from(v: glam::Vec3) -> Self {
+            Vector3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<Vector3> for glam::Vec3 {
+        fn from(v: Vector3) -> Self {
+            glam::Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    /// see `Rotor::from_quaternion`
+    impl From<glam::Quat> for Rotor {
+        fn from(q: glam::Quat) -> Self {
+            Rotor::from_quaternion(q.to_array())
+        }
+    }
+
+    impl From<Rotor> for glam::Quat {
+        fn from(r: Rotor) -> Self {
+            glam::Quat::from_array(r.to_quaternion())
+        }
+    }
+
+    impl From<glam::Affine3A> for Affine3 {
+        fn from(a: glam::Affine3A) -> Self {
+            let (x, y, z, w) = (a.matrix3.x_axis, a.matrix3.y_axis, a.matrix3.z_axis, a.translation);
+            Affine3 {
+                xx: x.x,
+                yx: y.x,
+                zx: z.x,
+                _x: w.x,
+
+                xy: x.y,
+                yy: y.y,
+                zy: z.y,
+                _y: w.y,
+
+                xz: x.z,
+                yz: y.z,
+                zz: z.z,
+                _z: w.z,
+            }
+        }
+    }
+
+    impl From<Affine3> for glam::Affine3A {
+        fn from(a: Affine3) -> Self {
+            glam::Affine3A::from_cols(
+                glam::Vec3A::new(a.xx, a.xy, a.xz),
+                glam::Vec3A::new(a.yx, a.yy, a.yz),
+                glam::Vec3A::new(a.zx, a.zy, a.zz),
+                glam::Vec3A::new(a._x, a._y, a._z),
+            )
+        }
+    }
+
+    /// the last row of `m` is assumed to be 0, 0, 0, 1
+    impl From<glam::Mat4> for Affine3 {
+        fn from(m: glam::Mat4) -> Self {
+            glam::Affine3A::from_mat4(m).into()
+        }
+    }
+}
+
+#[cfg(feature = "mint")]
+mod mint_conversions {
+    use crate::math::{Affine3, Rotor, Vector2, Vector3};
+
+    impl From<mint::Vector2<f32>> for Vector2 {
+        fn from(v: mint::Vector2<f32>) -> Self {
+            Vector2::new(v.x, v.y)
+        }
+    }
+
+    impl From<Vector2> for mint::Vector2<f32> {
+        fn from(v: Vector2) -> Self {
+            mint::Vector2 { x: v.x, y: v.y }
+        }
+    }
+
+    impl From<mint::Vector3<f32>> for Vector3 {
+        fn from(v: mint::Vector3<f32>) -> Self {
+            Vector3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<Vector3> for mint::Vector3<f32> {
+        fn from(v: Vector3) -> Self {
+            mint::Vector3 { x: v.x, y: v.y, z: v.z }
+        }
+    }
+
+    /// see `Rotor::from_quaternion`
+    impl From<mint::Quaternion<f32>> for Rotor {
+        fn from(q: mint::Quaternion<f32>) -> Self {
+            Rotor::from_quaternion([q.v.x, q.v.y, q.v.z, q.s])
+        }
+    }
+
+    impl From<Rotor> for mint::Quaternion<f32> {
+        fn from(r: Rotor) -> Self {
+            let [x, y, z, s] = r.to_quaternion();
+            mint::Quaternion { v: mint::Vector3 { x, y, z }, s }
+        }
+    }
+
+    /// columns are the images of x, y, z and the translation
+    impl From<mint::ColumnMatrix3x4<f32>> for Affine3 {
+        fn from(m: mint::ColumnMatrix3x4<f32>) -> Self {
+            Affine3 {
+                xx: m.x.x,
+                yx: m.y.x,
+                zx: m.z.x,
+                _x: m.w.x,
+
+                xy: m.x.y,
+                yy: m.y.y,
+                zy: m.z.y,
+                _y: m.w.y,
+
+                xz: m.x.z,
+                yz: m.y.z,
+                zz: m.z.z,
+                _z: m.w.z,
+            }
+        }
+    }
+
+    impl From<Affine3> for mint::ColumnMatrix3x4<f32> {
+        fn from(a: Affine3) -> Self {
+            mint::ColumnMatrix3x4 {
+                x: mint::Vector3 { x: a.xx, y: a.xy, z: a.xz },
+                y: mint::Vector3 { x: a.yx, y: a.yy, z: a.yz },
+                z: mint::Vector3 { x: a.zx, y: a.zy, z: a.zz },
+                w: mint::Vector3 { x: a._x, y: a._y, z: a._z },
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "glam"))]
+mod tests {
+    use crate::math::{Affine3, BiVector3, Scale3, Vector3};
+
+    #[test]
+    fn glam_rotation_matches_rotor() {
+        let rotor = BiVector3::new(0.2, -0.5, 0.3).exp();
+        let affine = Affine3::from(Scale3::new(1.0, 2.0, 3.0), rotor, Vector3::new(4.0, 5.0, 6.0));
+        let glam_affine = glam::Affine3A::from_scale_rotation_translation(
+            glam::Vec3::new(1.0, 2.0, 3.0),
+            rotor.into(),
+            glam::Vec3::new(4.0, 5.0, 6.0),
+        );
+
+        let p = Vector3::new(0.7, -1.1, 2.3);
+        let expected = p.apply(&affine);
+        let actual = Vector3::from(glam_affine.transform_point3(p.into()));
+        assert!((expected - actual).norm_sqr() < 1e-8, "{:?} {:?}", expected, actual);
+        assert!((p.apply(&glam_affine.into()) - expected).norm_sqr() < 1e-8);
+    }
+}
@@ -0,0 +1,270 @@
+use std::ops::*;
+
+use super::{Affine3, BiVector3, Rotor, Scale3, Vector3};
+
+/// a rotor written as a scalar and the dual of its bivector, yz as x, zx as y and xy as z,
+/// so products are quaternion products. rotates like `Affine3::from` with the rotor
+#[derive(Clone, Copy, Debug)]
+struct Quaternion {
+    w: f32,
+    v: Vector3,
+}
+
+impl Quaternion {
+    #[allow(dead_code)]
+    const ZERO: Quaternion = Quaternion { w: 0.0, v: Vector3::IDENTITY };
+
+    fn from_rotor(r: Rotor) -> Self {
+        Self { w: r._1, v: Vector3::new(r.yz, r.zx, r.xy) }
+    }
+
+    fn to_rotor(self) -> Rotor {
+        Rotor { _1: self.w, xy: self.v.z, yz: self.v.x, zx: self.v.y }
+    }
+
+    fn pure(v: Vector3) -> Self {
+        Self { w: 0.0, v }
+    }
+
+    fn conjugate(self) -> Self {
+        Self { w: self.w, v: -self.v }
+    }
+
+    #[allow(dead_code)]
+    fn dot(self, rhs: Quaternion) -> f32 {
+        self.w * rhs.w + self.v.dot(&rhs.v)
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Quaternion {
+            w: self.w * rhs.w - self.v.dot(&rhs.v),
+            v: rhs.v * self.w + self.v * rhs.w + self.v.cross(&rhs.v),
+        }
+    }
+}
+
+impl Add for Quaternion {
+    type Output = Quaternion;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Quaternion { w: self.w + rhs.w, v: self.v + rhs.v }
+    }
+}
+
+impl Mul<f32> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Quaternion { w: self.w * rhs, v: self.v * rhs }
+    }
+}
+
+/// sin(x) / x, 1 at 0
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-4 { 1.0 - x * x / 6.0 } else { x.sin() / x }
+}
+
+/// a rigid transform as a single versor, the rotor `real` and the translation folded into
+/// `dual`, so that `real + e0 dual` squared by its reverse is 1. like a rotor it has a
+/// logarithm, a `MotorBiVector`, and scaling that interpolates along a screw motion:
+/// turning about a line while moving along it.
+#[derive(Clone, Copy, Debug)]
+pub struct Motor {
+    real: Quaternion,
+    dual: Quaternion,
+}
+
+/// the logarithm of a motor, `real` is half the rotation as for `BiVector3::exp`,
+/// `dual` holds the translation, both in the bivector basis
+#[derive(Clone, Copy, Debug)]
+pub struct MotorBiVector {
+    pub real: BiVector3,
+    pub dual: BiVector3,
+}
+
+impl Motor {
+    #[allow(dead_code)]
+    pub const IDENTITY: Motor = Motor {
+        real: Quaternion { w: 1.0, v: Vector3::IDENTITY },
+        dual: Quaternion::ZERO,
+    };
+
+    /// rotates by `rotor` then translates by `translation`, like `Affine3::from`
+    pub fn new(rotor: Rotor, translation: Vector3) -> Self {
+        let real = Quaternion::from_rotor(rotor);
+        Self {
+            real,
+            dual: Quaternion::pure(translation) * real * 0.5,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn from_translation(translation: Vector3) -> Self {
+        Self::new(Rotor::IDENTITY, translation)
+    }
+
+    pub fn rotor(&self) -> Rotor {
+        self.real.to_rotor()
+    }
+
+    pub fn translation(&self) -> Vector3 {
+        (self.dual * self.real.conjugate()).v * 2.0
+    }
+
+    /// applies `self` first, then `other`, like `Affine3::compose`
+    pub fn compose(&self, other: &Motor) -> Self {
+        Self {
+            real: other.real * self.real,
+            dual: other.real * self.dual + other.dual * self.real,
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            real: self.real.conjugate(),
+            dual: self.dual.conjugate(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn apply(&self, point: Vector3) -> Vector3 {
+        let (w, u) = (self.real.w, self.real.v);
+        let t = u.cross(&point) * 2.0;
+        point + t * w + u.cross(&t) + self.translation()
+    }
+
+    #[allow(dead_code)]
+    pub fn to_affine(self) -> Affine3 {
+        Affine3::from(Scale3::IDENTITY, self.rotor(), self.translation())
+    }
+
+    /// the same transform with the real part's scalar positive, so its logarithm turns the
+    /// shorter way around
+    fn shortest(&self) -> Self {
+        if self.real.w < 0.0 {
+            Self { real: self.real * -1.0, dual: self.dual * -1.0 }
+        } else {
+            *self
+        }
+    }
+
+    pub fn log(&self) -> MotorBiVector {
+        let Motor { real, dual } = self.shortest();
+        let sin = real.v.length();
+        let angle = sin.atan2(real.w);
+        let r = if sin < 1e-6 { real.v } else { real.v * (angle / sin) };
+        let sinc = sinc(angle);
+        // inverts the dual part of `MotorBiVector::exp`, the derivative of the real part's
+        let r_dot_d = -dual.w / sinc;
+        let d = if angle.abs() < 1e-4 {
+            dual.v
+        } else {
+            (dual.v - r * (r_dot_d / (angle * angle) * (angle.cos() - sinc))) / sinc
+        };
+
+        let bivector = |v: Vector3| BiVector3::new(v.z, v.x, v.y);
+        MotorBiVector {
+            real: bivector(r),
+            dual: bivector(d),
+        }
+    }
+
+    /// follows the screw motion from `self` at 0 to `other` at 1
+    pub fn interpolate(&self, other: &Motor, t: f32) -> Self {
+        let delta = self.inverse().compose(other);
+        self.compose(&(delta.log() * t).exp())
+    }
+
+    /// both transform points the same, a motor and its negation are the same transform
+    #[allow(dead_code)]
+    pub fn is_close(&self, other: &Motor, epsilon: f32) -> bool {
+        let sign = self.real.dot(other.real).signum();
+        let difference = |a: Quaternion, b: Quaternion| {
+            let d = a + b * -sign;
+            d.dot(d)
+        };
+        difference(self.real, other.real) + difference(self.dual, other.dual) < epsilon * epsilon
+    }
+}
+
+impl MotorBiVector {
+    pub fn exp(self) -> Motor {
+        let vector = |b: BiVector3| Vector3::new(b.yz, b.zx, b.xy);
+        let (r, d) = (vector(self.real), vector(self.dual));
+        let angle = r.length();
+        let sinc = sinc(angle);
+        let r_dot_d = r.dot(&d);
+        // the real part's derivative along `d`, dual numbers carry it as the dual part
+        let dual_v = if angle < 1e-4 {
+            d
+        } else {
+            d * sinc + r * (r_dot_d / (angle * angle) * (angle.cos() - sinc))
+        };
+        Motor {
+            real: Quaternion { w: angle.cos(), v: r * sinc },
+            dual: Quaternion { w: -r_dot_d * sinc, v: dual_v },
+        }
+    }
+}
+
+impl Mul<f32> for MotorBiVector {
+    type Output = MotorBiVector;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        MotorBiVector {
+            real: self.real * rhs,
+            dual: self.dual * rhs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3, b: Vector3) -> bool {
+        (a - b).norm_sqr() < 1e-8
+    }
+
+    #[test]
+    fn motor_matches_affine() {
+        let a = Motor::new(BiVector3::new(0.3, -0.2, 0.5).exp(), Vector3::new(1.0, 2.0, 3.0));
+        let b = Motor::new(BiVector3::new(-0.4, 0.1, 0.2).exp(), Vector3::new(-2.0, 0.5, 1.0));
+        let p = Vector3::new(0.5, -1.5, 2.0);
+
+        assert!(close(a.apply(p), p.apply(&a.to_affine())));
+        let composed = a.to_affine().compose(&b.to_affine());
+        assert!(close(a.compose(&b).apply(p), p.apply(&composed)));
+        assert!(close(a.inverse().apply(a.apply(p)), p));
+        assert!(close(a.translation(), Vector3::new(1.0, 2.0, 3.0)));
+
+        assert!(a.log().exp().is_close(&a, 1e-5));
+        assert!(Motor::IDENTITY.log().exp().is_close(&Motor::IDENTITY, 1e-6));
+    }
+
+    #[test]
+    fn interpolation_is_a_screw_motion() {
+        let from = Motor::from_translation(Vector3::new(1.0, 0.0, 0.0));
+        // a quarter turn in the zx plane about the y axis, and up along it
+        let turn = Motor::new(
+            BiVector3::new(0.0, 0.0, std::f32::consts::FRAC_PI_4).exp(),
+            Vector3::new(0.0, 2.0, 0.0),
+        );
+        let to = from.compose(&turn);
+
+        assert!(from.interpolate(&to, 0.0).is_close(&from, 1e-5));
+        assert!(from.interpolate(&to, 1.0).is_close(&to, 1e-5));
+
+        // halfway, the point on the y axis has only moved halfway up it
+        let halfway = from.interpolate(&to, 0.5);
+        let on_axis = from.inverse().apply(Vector3::IDENTITY);
+        assert!(close(halfway.apply(on_axis), Vector3::new(0.0, 1.0, 0.0)));
+        // and a point off the axis stays as far from it
+        let off_axis = halfway.apply(Vector3::IDENTITY);
+        assert!(((off_axis.x * off_axis.x + off_axis.z * off_axis.z) - 1.0).abs() < 1e-5);
+    }
+}
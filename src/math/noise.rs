@@ -0,0 +1,264 @@
+//! gradient noise hashed from the lattice coordinates, without permutation tables,
+//! mirrored by `noise.wgsl` so the gpu can evaluate the same fields
+
+use super::{Vector2, Vector3};
+
+/// `noise.wgsl`, to be prepended to shaders sampling noise
+pub const NOISE_WGSL: &str = include_str!("../noise.wgsl");
+
+/// pcg hash
+pub fn hash(v: u32) -> u32 {
+    let state = v.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+fn hash2(x: i32, y: i32) -> u32 {
+    hash((x as u32).wrapping_add(hash(y as u32)))
+}
+
+#[allow(dead_code)]
+fn hash3(x: i32, y: i32, z: i32) -> u32 {
+    hash((x as u32).wrapping_add(hash((y as u32).wrapping_add(hash(z as u32)))))
+}
+
+/// quintic, with zero first and second derivatives at 0 and 1
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// one of 8 unit directions
+fn gradient2(hash: u32, x: f32, y: f32) -> f32 {
+    const DIAGONAL: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    let (gx, gy) = match hash & 7 {
+        0 => (1.0, 0.0),
+        1 => (-1.0, 0.0),
+        2 => (0.0, 1.0),
+        3 => (0.0, -1.0),
+        4 => (DIAGONAL, DIAGONAL),
+        5 => (-DIAGONAL, DIAGONAL),
+        6 => (DIAGONAL, -DIAGONAL),
+        _ => (-DIAGONAL, -DIAGONAL),
+    };
+    gx * x + gy * y
+}
+
+/// one of the 12 directions to the edges of a cube, as in improved perlin noise
+#[allow(dead_code)]
+fn gradient3(hash: u32, x: f32, y: f32, z: f32) -> f32 {
+    match hash % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+/// perlin noise, about -1 to 1, 0 on integer coordinates
+pub fn noise2(p: Vector2) -> f32 {
+    let (x0, y0) = (p.x.floor(), p.y.floor());
+    let (ix, iy) = (x0 as i32, y0 as i32);
+    let (fx, fy) = (p.x - x0, p.y - y0);
+
+    let n00 = gradient2(hash2(ix, iy), fx, fy);
+    let n10 = gradient2(hash2(ix + 1, iy), fx - 1.0, fy);
+    let n01 = gradient2(hash2(ix, iy + 1), fx, fy - 1.0);
+    let n11 = gradient2(hash2(ix + 1, iy + 1), fx - 1.0, fy - 1.0);
+
+    let (u, v) = (fade(fx), fade(fy));
+    // unit gradients reach at most sqrt(1/2)
+    lerp(lerp(n00, n10, u), lerp(n01, n11, u), v) * std::f32::consts::SQRT_2
+}
+
+/// perlin noise, about -1 to 1, 0 on integer coordinates
+#[allow(dead_code)]
+pub fn noise3(p: Vector3) -> f32 {
+    let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+    let (fx, fy, fz) = (p.x - x0, p.y - y0, p.z - z0);
+
+    let corner = |dx: i32, dy: i32, dz: i32| gradient3(
+        hash3(ix + dx, iy + dy, iz + dz),
+        fx - dx as f32,
+        fy - dy as f32,
+        fz - dz as f32,
+    );
+
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    lerp(
+        lerp(lerp(corner(0, 0, 0), corner(1, 0, 0), u), lerp(corner(0, 1, 0), corner(1, 1, 0), u), v),
+        lerp(lerp(corner(0, 0, 1), corner(1, 0, 1), u), lerp(corner(0, 1, 1), corner(1, 1, 1), u), v),
+        w,
+    )
+}
+
+/// octaves of noise, each `lacunarity` times the frequency and `gain` times the amplitude
+/// of the previous one, normalized to about -1 to 1
+#[derive(Clone, Copy, Debug)]
+pub struct Fbm {
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl Default for Fbm {
+    fn default() -> Self {
+        Self {
+            octaves: 5,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+impl Fbm {
+    fn sum(&self, mut octave: impl FnMut(f32) -> f32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut total_amplitude = 0.0;
+        let mut frequency = 1.0;
+        for _ in 0..self.octaves {
+            sum += amplitude * octave(frequency);
+            total_amplitude += amplitude;
+            amplitude *= self.gain;
+            frequency *= self.lacunarity;
+        }
+        sum / total_amplitude
+    }
+
+    pub fn sample2(&self, p: Vector2) -> f32 {
+        // each octave is shifted so lattice points don't line up at 0
+        self.sum(|frequency| noise2(p * frequency + Vector2::new(frequency * 17.3, frequency * 9.1)))
+    }
+
+    #[allow(dead_code)]
+    pub fn sample3(&self, p: Vector3) -> f32 {
+        self.sum(|frequency| noise3(
+            p * frequency + Vector3::new(frequency * 17.3, frequency * 9.1, frequency * 5.7),
+        ))
+    }
+
+    /// samples at `p` displaced by two other fbm fields times `strength`,
+    /// which turns the rounded blobs of plain fbm into folded, eroded looking shapes
+    pub fn warped2(&self, p: Vector2, strength: f32) -> f32 {
+        let warp = Vector2::new(
+            self.sample2(p + Vector2::new(3.7, 1.9)),
+            self.sample2(p + Vector2::new(8.3, 2.8)),
+        );
+        self.sample2(p + warp * strength)
+    }
+}
+
+/// a `size` by `size` tile of blue noise, the rank of each texel in 0 to 1. texels of close
+/// ranks are spread apart, so thresholding it leaves no clumps or low frequencies.
+/// void and cluster without the initial pattern: the lower half of the ranks each go to the
+/// largest void, the texel least covered by gaussians around those ranked before, and the upper
+/// half from the top down to the tightest cluster of the texels left, which are the minority
+/// then. distances wrap around so the tile repeats seamlessly
+pub fn blue_noise(size: u32) -> Vec<f32> {
+    const SIGMA: f32 = 1.5;
+    let n = (size * size) as usize;
+    let wrapped = |d: u32| d.min(size - d) as f32;
+    // energy a texel adds at each offset from it
+    let kernel: Vec<f32> = (0..size * size)
+        .map(|i| {
+            let (dx, dy) = (wrapped(i % size), wrapped(i / size));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+    let spread = |energy: &mut [f32], from: usize, sign: f32| {
+        let (from_x, from_y) = (from as u32 % size, from as u32 / size);
+        for (i, energy) in energy.iter_mut().enumerate() {
+            let (x, y) = (i as u32 % size, i as u32 / size);
+            let offset = (x + size - from_x) % size + size * ((y + size - from_y) % size);
+            *energy += sign * kernel[offset as usize];
+        }
+    };
+
+    // a little white noise breaks the ties of the regular pattern the first ranks would form
+    let mut energy: Vec<f32> = (0..n as u32).map(|i| 1e-3 * hash(i) as f32 / u32::MAX as f32).collect();
+    let mut ranks = vec![None; n];
+    for rank in 0..n / 2 {
+        let void = (0..n)
+            .filter(|&i| ranks[i].is_none())
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap();
+        ranks[void] = Some(rank as f32 / n as f32);
+        spread(&mut energy, void, 1.0);
+    }
+
+    let mut energy = vec![0.0; n];
+    for i in (0..n).filter(|&i| ranks[i].is_none()) {
+        spread(&mut energy, i, 1.0);
+    }
+    for rank in (n / 2..n).rev() {
+        let cluster = (0..n)
+            .filter(|&i| ranks[i].is_none())
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap();
+        ranks[cluster] = Some(rank as f32 / n as f32);
+        spread(&mut energy, cluster, -1.0);
+    }
+    ranks.into_iter().map(Option::unwrap).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_is_zero_on_lattice_and_bounded() {
+        for i in -3..3 {
+            assert_eq!(noise2(Vector2::new(i as f32, 2.0 * i as f32)), 0.0);
+            assert_eq!(noise3(Vector3::new(i as f32, 1.0, -i as f32)), 0.0);
+        }
+
+        let fbm = Fbm::default();
+        let mut any_nonzero = false;
+        for i in 0..1000 {
+            let p = Vector2::new(i as f32 * 0.137, i as f32 * 0.071);
+            let n = noise2(p);
+            assert!(n.abs() <= 1.0001, "{}", n);
+            assert!(noise3(Vector3::new(p.x, p.y, i as f32 * 0.053)).abs() <= 1.0001);
+            assert!(fbm.warped2(p, 2.0).abs() <= 1.0001);
+            any_nonzero |= n.abs() > 0.1;
+        }
+        assert!(any_nonzero);
+
+        // continuous across cell borders
+        let a = noise2(Vector2::new(0.9999, 0.5));
+        let b = noise2(Vector2::new(1.0001, 0.5));
+        assert!((a - b).abs() < 1e-3);
+    }
+
+    #[test]
+    fn blue_noise_has_no_clumps() {
+        let size = 16;
+        let noise = blue_noise(size);
+        let mut sorted = noise.clone();
+        sorted.sort_by(f32::total_cmp);
+        assert!(sorted.iter().enumerate().all(|(i, &rank)| rank == i as f32 / 256.0));
+
+        // a quarter of the texels, white noise would have 44% of them next to one another
+        let texel = |x: u32, y: u32| noise[((x % size) + size * (y % size)) as usize];
+        for quarter in [|rank: f32| rank < 0.25, |rank: f32| rank >= 0.75] {
+            let clumped = (0..size * size)
+                .map(|i| (i % size, i / size))
+                .filter(|&(x, y)| quarter(texel(x, y)) && (quarter(texel(x + 1, y)) || quarter(texel(x, y + 1))))
+                .count();
+            assert!(clumped < 64 / 4, "{}", clumped);
+        }
+    }
+}
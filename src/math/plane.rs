@@ -0,0 +1,106 @@
+use super::{Vector3, Vector4};
+
+/// the points p where normal.p + distance is 0, in front of it where that's positive.
+/// the same four numbers as a clip plane in the camera buffer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Plane {
+    /// unit, unless built unnormalized with `new`
+    pub normal: Vector3,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub const fn new(normal: Vector3, distance: f32) -> Self {
+        Self { normal, distance }
+    }
+
+    /// `normal` is unit
+    pub fn from_point_normal(point: Vector3, normal: Vector3) -> Self {
+        Self {
+            normal,
+            distance: -normal.dot(&point),
+        }
+    }
+
+    /// facing the side from which `a`, `b`, `c` wind counter clockwise in a right handed
+    /// space, so the side they are back facing from in the camera's space
+    pub fn from_points(a: Vector3, b: Vector3, c: Vector3) -> Self {
+        Self::from_point_normal(a, (b - a).cross(&(c - a)).normalized())
+    }
+
+    /// scaled so the normal is unit, left as is when it's zero
+    pub fn normalized(&self) -> Self {
+        let length = self.normal.length();
+        if length > 0.0 {
+            Self {
+                normal: self.normal / length,
+                distance: self.distance / length,
+            }
+        } else {
+            *self
+        }
+    }
+
+    /// scaled by the length of the normal
+    pub fn signed_distance(&self, point: Vector3) -> f32 {
+        self.normal.dot(&point) + self.distance
+    }
+
+    /// the closest point on the plane
+    #[allow(dead_code)]
+    pub fn project_point(&self, point: Vector3) -> Vector3 {
+        point - self.normal * (self.signed_distance(point) / self.normal.norm_sqr())
+    }
+
+    /// where the segment from `a` to `b` crosses the plane, None when both are on one side
+    pub fn intersect_segment(&self, a: Vector3, b: Vector3) -> Option<Vector3> {
+        let (d_a, d_b) = (self.signed_distance(a), self.signed_distance(b));
+        if (d_a < 0.0) == (d_b < 0.0) && d_a != 0.0 && d_b != 0.0 {
+            return None;
+        }
+        let t = d_a / (d_a - d_b);
+        Some(a + (b - a) * t)
+    }
+
+    /// distance along `direction` to where the ray crosses the plane, from either side
+    #[allow(dead_code)]
+    pub fn intersect_ray(&self, origin: Vector3, direction: Vector3) -> Option<f32> {
+        let t = -self.signed_distance(origin) / self.normal.dot(&direction);
+        // NaN or infinite when parallel
+        (t >= 0.0 && t.is_finite()).then_some(t)
+    }
+
+    /// dotted with a point extended by a w of 1, the signed distance
+    pub fn to_vector4(self) -> Vector4 {
+        self.normal.extend(self.distance)
+    }
+
+    /// as uploaded in the camera buffer
+    pub fn to_array(self) -> [f32; 4] {
+        self.to_vector4().to_array()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plane_through_three_points() {
+        let plane = Plane::from_points(
+            Vector3::new(0.0, 2.0, 0.0),
+            Vector3::new(0.0, 2.0, 1.0),
+            Vector3::new(1.0, 2.0, 0.0),
+        );
+        assert_eq!(plane, Plane::new(Vector3::new(0.0, 1.0, 0.0), -2.0));
+        assert_eq!(plane.signed_distance(Vector3::new(5.0, 5.0, 5.0)), 3.0);
+        assert_eq!(plane.project_point(Vector3::new(5.0, 5.0, 5.0)), Vector3::new(5.0, 2.0, 5.0));
+
+        let crossing = plane.intersect_segment(Vector3::new(1.0, 0.0, 0.0), Vector3::new(1.0, 4.0, 2.0));
+        assert_eq!(crossing, Some(Vector3::new(1.0, 2.0, 1.0)));
+        assert!(plane.intersect_segment(Vector3::new(1.0, 3.0, 0.0), Vector3::new(1.0, 4.0, 2.0)).is_none());
+
+        assert_eq!(plane.intersect_ray(Vector3::new(0.0, 5.0, 0.0), Vector3::new(0.0, -2.0, 0.0)), Some(1.5));
+        assert!(plane.intersect_ray(Vector3::new(0.0, 5.0, 0.0), Vector3::new(1.0, 0.0, 0.0)).is_none());
+    }
+}
@@ -0,0 +1,77 @@
+use wgpu::util::DeviceExt;
+use wgpu::*;
+
+/// the smallest index format able to address `vertex_count` vertices
+pub fn index_format_for(vertex_count: usize) -> IndexFormat {
+    if vertex_count <= u16::MAX as usize + 1 {
+        IndexFormat::Uint16
+    } else {
+        IndexFormat::Uint32
+    }
+}
+
+/// indexed triangles in vertex slot 0, indices are stored as `u16` whenever they fit
+pub struct Mesh {
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    pub index_format: IndexFormat,
+    pub index_count: u32,
+}
+
+impl Mesh {
+    pub fn new<V: bytemuck::Pod>(
+        device: &Device,
+        label: &str,
+        vertices: &[V],
+        indices: &[u32],
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&util::BufferInitDescriptor {
+            label: Some(&format!("{label} vertex buffer")),
+            contents: bytemuck::cast_slice(vertices),
+            usage: BufferUsages::VERTEX,
+        });
+
+        let index_format = index_format_for(vertices.len());
+        let index_label = format!("{label} index buffer");
+        let index_buffer = match index_format {
+            IndexFormat::Uint16 => {
+                let indices: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+                device.create_buffer_init(&util::BufferInitDescriptor {
+                    label: Some(&index_label),
+                    contents: bytemuck::cast_slice(&indices),
+                    usage: BufferUsages::INDEX,
+                })
+            }
+            IndexFormat::Uint32 => device.create_buffer_init(&util::BufferInitDescriptor {
+                label: Some(&index_label),
+                contents: bytemuck::cast_slice(indices),
+                usage: BufferUsages::INDEX,
+            }),
+        };
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_format,
+            index_count: indices.len() as u32,
+        }
+    }
+
+    /// binds the vertices to slot 0 and the indices, ready for `draw_indexed(0..index_count, ..)`
+    pub fn bind<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), self.index_format);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_format_fits_vertex_count() {
+        assert_eq!(index_format_for(8), IndexFormat::Uint16);
+        assert_eq!(index_format_for(65536), IndexFormat::Uint16);
+        assert_eq!(index_format_for(65537), IndexFormat::Uint32);
+    }
+}
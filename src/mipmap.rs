@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use wgpu::*;
+
+use crate::post::{self, FullscreenPass};
+
+/// mip levels in a full chain down to 1x1
+pub fn level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).leading_zeros()
+}
+
+/// fills in the mip levels of a texture from its first one, drawing each level as a bilinear
+/// sample of the one above, which is a 2x2 box filter for even sizes.
+/// srgb levels are averaged in linear space, since sampling and rendering convert them.
+pub struct MipmapGenerator {
+    /// one per texture format
+    passes: HashMap<TextureFormat, FullscreenPass>,
+    sampler: Sampler,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &Device) -> Self {
+        Self {
+            passes: HashMap::new(),
+            sampler: post::create_sampler(device),
+        }
+    }
+
+    /// `texture` needs `TEXTURE_BINDING` and `RENDER_ATTACHMENT` usages and a renderable format
+    pub fn generate(&mut self, device: &Device, encoder: &mut CommandEncoder, texture: &Texture) {
+        let format = texture.format();
+        let pass = self.passes.entry(format).or_insert_with(|| FullscreenPass::new(
+            device,
+            "copy.wgsl",
+            include_str!("copy.wgsl"),
+            "fs_main",
+            format,
+            post::input_layout("mipmap bind group layout"),
+        ));
+
+        let level_view = |level| texture.create_view(&TextureViewDescriptor {
+            label: Some("mipmap level"),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            ..Default::default()
+        });
+        for level in 1..texture.mip_level_count() {
+            let source = level_view(level - 1);
+            let bind_group = pass.binding.create_bind_group(device, "mipmap bind group", &[
+                BindingResource::TextureView(&source),
+                BindingResource::Sampler(&self.sampler),
+            ]);
+            pass.draw(encoder, "mipmap pass", &level_view(level), &bind_group);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_chain_ends_at_one_texel() {
+        assert_eq!(level_count(1, 1), 1);
+        assert_eq!(level_count(256, 256), 9);
+        assert_eq!(level_count(300, 20), 9);
+    }
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use wgpu::*;
+
+use crate::binding::{self, BindGroupLayoutBuilder};
+use crate::buffer::GpuBuffer;
+use crate::layout::{self, PadVec3};
+use crate::math::{Affine3, Color, Plane, Vector3};
+use crate::pipeline_cache::{DepthKey, PipelineCache, PipelineKey, StencilKey};
+
+/// marked in the stencil where the mirror is visible
+pub const STENCIL_REFERENCE: u32 = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MirrorRaw {
+    center: PadVec3,
+    right: PadVec3,
+    up: PadVec3,
+    tint: [f32; 4],
+}
+layout::assert_uniform_size!(MirrorRaw, 64);
+
+/// a rectangular planar mirror drawn with the stencil buffer: `mark` writes
+/// `STENCIL_REFERENCE` where the mirror is, the scene is then drawn reflected by `reflection`
+/// with a stencil equal test, and `draw_surface` blends the tint over the reflection
+/// writing the mirror's depth, so the scene drawn afterwards is hidden behind it.
+pub struct Mirror {
+    pub center: Vector3,
+    /// unit, the side the mirror reflects
+    pub normal: Vector3,
+    /// half extents along the mirror's plane
+    pub right: Vector3,
+    pub up: Vector3,
+    /// alpha blended over the reflection
+    pub tint: Color,
+    mark_pipeline: Arc<RenderPipeline>,
+    surface_pipeline: Arc<RenderPipeline>,
+    bind_group: BindGroup,
+    params_buffer: GpuBuffer<MirrorRaw>,
+}
+
+impl Mirror {
+    /// `camera` is the camera uniform buffer of the light pass,
+    /// `depth_format` needs a stencil aspect
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &Device,
+        cache: &mut PipelineCache,
+        camera: BindingResource,
+        color_format: TextureFormat,
+        depth_format: TextureFormat,
+        center: Vector3,
+        normal: Vector3,
+        right: Vector3,
+        up: Vector3,
+    ) -> Self {
+        assert!(depth_format.has_stencil_aspect(), "{:?} has no stencil", depth_format);
+
+        let source = include_str!("mirror.wgsl");
+        let layout = BindGroupLayoutBuilder::new("mirror bind group layout")
+            .uniform(ShaderStages::VERTEX) // camera
+            .uniform(ShaderStages::VERTEX | ShaderStages::FRAGMENT); // mirror
+        layout.validate(0, &binding::reflect("mirror.wgsl", source)).unwrap();
+        let binding = layout.build(device);
+        let params_buffer = GpuBuffer::new_uniform(device, "mirror params buffer");
+        let bind_group = binding.create_bind_group(device, "mirror bind group", &[
+            camera,
+            params_buffer.as_entire_binding(),
+        ]);
+
+        cache.register(device, "mirror.wgsl", source, &[&binding.layout], &[]);
+        // over a freshly cleared depth, the scene drawn later hides what's covered
+        let mark_pipeline = cache.get(device, PipelineKey {
+            topology: PrimitiveTopology::TriangleStrip,
+            depth: Some(DepthKey {
+                format: depth_format,
+                write: false,
+                compare: CompareFunction::Always,
+                stencil: Some(StencilKey::replace()),
+            }),
+            ..PipelineKey::new("mirror.wgsl", None)
+        });
+        let surface_pipeline = cache.get(device, PipelineKey {
+            topology: PrimitiveTopology::TriangleStrip,
+            blend: Some(BlendState::ALPHA_BLENDING),
+            depth: Some(DepthKey {
+                format: depth_format,
+                write: true,
+                compare: CompareFunction::Always,
+                stencil: Some(StencilKey::equal()),
+            }),
+            ..PipelineKey::new("mirror.wgsl", Some(color_format))
+        });
+
+        Self {
+            center,
+            normal,
+            right,
+            up,
+            tint: Color::new(0.6, 0.7, 0.8, 0.15),
+            mark_pipeline,
+            surface_pipeline,
+            bind_group,
+            params_buffer,
+        }
+    }
+
+    /// in front of it where the mirror reflects
+    pub fn plane(&self) -> Plane {
+        Plane::from_point_normal(self.center, self.normal)
+    }
+
+    /// world to world, mirroring across the plane. composed before a view, it is the view
+    /// of the camera's reflection, which flips the winding of front faces
+    pub fn reflection(&self) -> Affine3 {
+        reflection(&self.plane())
+    }
+
+    /// plane as in the camera buffer, positive in front of the mirror, so the reflected pass
+    /// drops what's behind it
+    pub fn clip_plane(&self) -> [f32; 4] {
+        self.plane().to_array()
+    }
+
+    /// only the front of the mirror reflects
+    pub fn is_facing(&self, eye: Vector3) -> bool {
+        self.plane().signed_distance(eye) > 0.0
+    }
+
+    pub fn upload(&mut self, queue: &Queue) {
+        self.params_buffer.write(queue, &[MirrorRaw {
+            center: self.center.into(),
+            right: self.right.into(),
+            up: self.up.into(),
+            tint: self.tint.to_array(),
+        }]);
+    }
+
+    /// in a depth stencil only pass
+    pub fn mark<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        pass.set_pipeline(&self.mark_pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_stencil_reference(STENCIL_REFERENCE);
+        pass.draw(0..4, 0..1);
+    }
+
+    /// after the reflected scene, in a pass with only the color target
+    pub fn draw_surface<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        pass.set_pipeline(&self.surface_pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_stencil_reference(STENCIL_REFERENCE);
+        pass.draw(0..4, 0..1);
+    }
+}
+
+/// across `plane` with a unit normal, p - 2 (n.p + d) n
+fn reflection(plane: &Plane) -> Affine3 {
+    let n = plane.normal;
+    let d = -2.0 * plane.distance;
+    Affine3 {
+        xx: 1.0 - 2.0 * n.x * n.x,
+        yx: -2.0 * n.y * n.x,
+        zx: -2.0 * n.z * n.x,
+        _x: d * n.x,
+
+        xy: -2.0 * n.x * n.y,
+        yy: 1.0 - 2.0 * n.y * n.y,
+        zy: -2.0 * n.z * n.y,
+        _y: d * n.y,
+
+        xz: -2.0 * n.x * n.z,
+        yz: -2.0 * n.y * n.z,
+        zz: 1.0 - 2.0 * n.z * n.z,
+        _z: d * n.z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflection_mirrors_across_the_plane() {
+        let s = 0.5f32.sqrt();
+        let reflection = reflection(&Plane::new(Vector3::new(0.0, s, -s), -2.0));
+        // on the plane
+        let on_plane = Vector3::new(3.0, 0.0, -2.0 / s);
+        assert!((on_plane.apply(&reflection) - on_plane).norm_sqr() < 1e-8);
+
+        let p = Vector3::new(1.0, 2.0, 3.0);
+        let reflected = p.apply(&reflection);
+        assert!((reflected.apply(&reflection) - p).norm_sqr() < 1e-8);
+        // the midpoint is on the plane
+        let midpoint = (p + reflected) * 0.5;
+        assert!((Vector3::new(0.0, s, -s).dot(&midpoint) - 2.0).abs() < 1e-5);
+    }
+}
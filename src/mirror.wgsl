@@ -0,0 +1,37 @@
+struct Camera {
+    view_0: vec4<f32>,
+    view_1: vec4<f32>,
+    view_2: vec4<f32>,
+    near_z: f32,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// mirror::MirrorRaw
+struct Mirror {
+    center: vec3<f32>,
+    right: vec3<f32>,
+    up: vec3<f32>,
+    tint: vec4<f32>,
+};
+@group(0) @binding(1)
+var<uniform> mirror: Mirror;
+
+// the mirror's rectangle as a 4 vertex strip
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let corner = vec2<f32>(f32(vertex_index & 1u), f32(vertex_index >> 1u)) * 2.0 - 1.0;
+    let position = mirror.center + mirror.right * corner.x + mirror.up * corner.y;
+    let view = vec3<f32>(
+        dot(camera.view_0.xyz, position) + camera.view_0.w,
+        dot(camera.view_1.xyz, position) + camera.view_1.w,
+        dot(camera.view_2.xyz, position) + camera.view_2.w,
+    );
+    // infinite reversed z, as in light.wgsl
+    return vec4<f32>(view.xy, camera.near_z, view.z);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return mirror.tint;
+}
@@ -0,0 +1,90 @@
+use wgpu::*;
+
+use crate::buffer::GpuBuffer;
+use crate::math::Affine3;
+use crate::post::{self, FullscreenPass, PostInput, PostNode, HDR_FORMAT};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MotionBlurParams {
+    reprojection: Affine3,
+    near_z: f32,
+    shutter: f32,
+    _padding: [u32; 2],
+}
+
+/// screen space motion of the instances themselves, in uv from where the frame before's camera
+/// saw them then to where it would see them now, written by the light pass
+pub const MOTION_FORMAT: TextureFormat = TextureFormat::Rg16Float;
+
+/// motion blur, the camera's velocity is reconstructed per pixel from the depth buffer and the
+/// current and previous camera views, and the instances' own is added from `MOTION_FORMAT`'s target
+pub struct MotionBlur {
+    /// fraction of the frame the shutter is open for, scales the blur length
+    pub shutter: f32,
+    params: MotionBlurParams,
+    params_buffer: GpuBuffer<MotionBlurParams>,
+    pass: FullscreenPass,
+    bind_group: Option<BindGroup>,
+}
+
+impl MotionBlur {
+    pub fn new(device: &Device) -> Self {
+        let pass = FullscreenPass::new(
+            device,
+            "motion_blur.wgsl",
+            include_str!("motion_blur.wgsl"),
+            "fs_main",
+            HDR_FORMAT,
+            post::input_layout("motion blur bind group layout")
+                .uniform(ShaderStages::FRAGMENT)
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Depth)
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: false }),
+        );
+
+        Self {
+            shutter: 0.5,
+            params: MotionBlurParams {
+                reprojection: Affine3::IDENTITY,
+                near_z: 1.0,
+                shutter: 0.0,
+                _padding: Default::default(),
+            },
+            params_buffer: GpuBuffer::new_uniform(device, "motion blur params buffer"),
+            pass,
+            bind_group: None,
+        }
+    }
+
+    /// views as uploaded in the camera buffer, the previous one being last frame's
+    pub fn set_views(&mut self, previous_view: &Affine3, view: &Affine3, near_z: f32) {
+        self.params.reprojection = view.inverse().compose(previous_view);
+        self.params.near_z = near_z;
+    }
+}
+
+impl PostNode for MotionBlur {
+    fn name(&self) -> &'static str {
+        "motion blur"
+    }
+
+    fn inputs(&self) -> &'static [PostInput] {
+        &[PostInput::Previous, PostInput::SceneDepth, PostInput::ObjectMotion]
+    }
+
+    fn bind(&mut self, device: &Device, sampler: &Sampler, inputs: &[&TextureView]) {
+        self.bind_group = Some(self.pass.binding.create_bind_group(device, "motion blur bind group", &[
+            BindingResource::TextureView(inputs[0]),
+            BindingResource::Sampler(sampler),
+            self.params_buffer.as_entire_binding(),
+            BindingResource::TextureView(inputs[1]),
+            BindingResource::TextureView(inputs[2]),
+        ]));
+    }
+
+    fn run(&mut self, queue: &Queue, encoder: &mut CommandEncoder, output: &TextureView) {
+        self.params.shutter = self.shutter;
+        self.params_buffer.write(queue, &[self.params]);
+        self.pass.draw(encoder, "motion blur pass", output, self.bind_group.as_ref().unwrap());
+    }
+}
@@ -0,0 +1,58 @@
+struct MotionBlurParams {
+    // current camera view to previous camera view
+    reprojection_0: vec4<f32>,
+    reprojection_1: vec4<f32>,
+    reprojection_2: vec4<f32>,
+    near_z: f32,
+    // fraction of the frame the shutter is open for
+    shutter: f32,
+}
+
+@group(0) @binding(2) var<uniform> params: MotionBlurParams;
+@group(0) @binding(3) var depth: texture_depth_2d;
+// motion_blur::MOTION_FORMAT, zero where nothing moved by itself
+@group(0) @binding(4) var object_motion: texture_2d<f32>;
+
+const SAMPLES: i32 = 12;
+// longest blur in uv units, so fast turns don't smear the whole screen
+const MAX_VELOCITY: f32 = 0.1;
+
+@fragment
+fn fs_main(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(depth));
+    let coords = clamp(vec2<i32>(in.uv * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    let d = textureLoad(depth, coords, 0);
+
+    // view position divided by its z, which keeps infinitely far points finite:
+    // (ndc.x, ndc.y, 1) with the translation weighted by 1 / z = d / near_z
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let p = vec3<f32>(ndc, 1.0);
+    let w = d / params.near_z;
+    let previous = vec3<f32>(
+        dot(params.reprojection_0.xyz, p) + params.reprojection_0.w * w,
+        dot(params.reprojection_1.xyz, p) + params.reprojection_1.w * w,
+        dot(params.reprojection_2.xyz, p) + params.reprojection_2.w * w,
+    );
+
+    let color = textureSampleLevel(input, input_sampler, in.uv, 0.0);
+    // behind the previous camera
+    if previous.z <= 0.0 {
+        return color;
+    }
+
+    let ndc_velocity = ndc - previous.xy / previous.z;
+    let camera_velocity = vec2<f32>(ndc_velocity.x, -ndc_velocity.y) * 0.5;
+    var velocity = (camera_velocity + textureLoad(object_motion, coords, 0).xy) * params.shutter;
+    let speed = length(velocity);
+    if speed > MAX_VELOCITY {
+        velocity *= MAX_VELOCITY / speed;
+    }
+
+    // centered on the pixel, along where it came from and where it goes
+    var sum = vec3<f32>(0.0);
+    for (var i = 0; i < SAMPLES; i++) {
+        let t = f32(i) / f32(SAMPLES - 1) - 0.5;
+        sum += textureSampleLevel(input, input_sampler, in.uv - velocity * t, 0.0).rgb;
+    }
+    return vec4<f32>(sum / f32(SAMPLES), color.a);
+}
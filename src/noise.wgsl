@@ -0,0 +1,129 @@
+// mirrors math::noise, prepend math::noise::NOISE_WGSL to use it
+
+// pcg hash
+fn noise_hash(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn noise_hash2(p: vec2<i32>) -> u32 {
+    return noise_hash(u32(p.x) + noise_hash(u32(p.y)));
+}
+
+fn noise_hash3(p: vec3<i32>) -> u32 {
+    return noise_hash(u32(p.x) + noise_hash(u32(p.y) + noise_hash(u32(p.z))));
+}
+
+fn noise_fade(t: vec3<f32>) -> vec3<f32> {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}
+
+fn noise_gradient2(hash: u32, p: vec2<f32>) -> f32 {
+    let diagonal = 0.70710678;
+    var g: vec2<f32>;
+    switch hash & 7u {
+        case 0u: { g = vec2<f32>(1.0, 0.0); }
+        case 1u: { g = vec2<f32>(-1.0, 0.0); }
+        case 2u: { g = vec2<f32>(0.0, 1.0); }
+        case 3u: { g = vec2<f32>(0.0, -1.0); }
+        case 4u: { g = vec2<f32>(diagonal, diagonal); }
+        case 5u: { g = vec2<f32>(-diagonal, diagonal); }
+        case 6u: { g = vec2<f32>(diagonal, -diagonal); }
+        default: { g = vec2<f32>(-diagonal, -diagonal); }
+    }
+    return dot(g, p);
+}
+
+fn noise_gradient3(hash: u32, p: vec3<f32>) -> f32 {
+    switch hash % 12u {
+        case 0u: { return p.x + p.y; }
+        case 1u: { return -p.x + p.y; }
+        case 2u: { return p.x - p.y; }
+        case 3u: { return -p.x - p.y; }
+        case 4u: { return p.x + p.z; }
+        case 5u: { return -p.x + p.z; }
+        case 6u: { return p.x - p.z; }
+        case 7u: { return -p.x - p.z; }
+        case 8u: { return p.y + p.z; }
+        case 9u: { return -p.y + p.z; }
+        case 10u: { return p.y - p.z; }
+        default: { return -p.y - p.z; }
+    }
+}
+
+// perlin noise, about -1 to 1, 0 on integer coordinates
+fn noise2(p: vec2<f32>) -> f32 {
+    let cell = floor(p);
+    let i = vec2<i32>(cell);
+    let f = p - cell;
+
+    let n00 = noise_gradient2(noise_hash2(i), f);
+    let n10 = noise_gradient2(noise_hash2(i + vec2<i32>(1, 0)), f - vec2<f32>(1.0, 0.0));
+    let n01 = noise_gradient2(noise_hash2(i + vec2<i32>(0, 1)), f - vec2<f32>(0.0, 1.0));
+    let n11 = noise_gradient2(noise_hash2(i + vec2<i32>(1, 1)), f - vec2<f32>(1.0, 1.0));
+
+    let u = noise_fade(vec3<f32>(f, 0.0)).xy;
+    return mix(mix(n00, n10, u.x), mix(n01, n11, u.x), u.y) * 1.41421356;
+}
+
+// perlin noise, about -1 to 1, 0 on integer coordinates
+fn noise3(p: vec3<f32>) -> f32 {
+    let cell = floor(p);
+    let i = vec3<i32>(cell);
+    let f = p - cell;
+
+    let n000 = noise_gradient3(noise_hash3(i), f);
+    let n100 = noise_gradient3(noise_hash3(i + vec3<i32>(1, 0, 0)), f - vec3<f32>(1.0, 0.0, 0.0));
+    let n010 = noise_gradient3(noise_hash3(i + vec3<i32>(0, 1, 0)), f - vec3<f32>(0.0, 1.0, 0.0));
+    let n110 = noise_gradient3(noise_hash3(i + vec3<i32>(1, 1, 0)), f - vec3<f32>(1.0, 1.0, 0.0));
+    let n001 = noise_gradient3(noise_hash3(i + vec3<i32>(0, 0, 1)), f - vec3<f32>(0.0, 0.0, 1.0));
+    let n101 = noise_gradient3(noise_hash3(i + vec3<i32>(1, 0, 1)), f - vec3<f32>(1.0, 0.0, 1.0));
+    let n011 = noise_gradient3(noise_hash3(i + vec3<i32>(0, 1, 1)), f - vec3<f32>(0.0, 1.0, 1.0));
+    let n111 = noise_gradient3(noise_hash3(i + vec3<i32>(1, 1, 1)), f - vec3<f32>(1.0, 1.0, 1.0));
+
+    let u = noise_fade(f);
+    return mix(
+        mix(mix(n000, n100, u.x), mix(n010, n110, u.x), u.y),
+        mix(mix(n001, n101, u.x), mix(n011, n111, u.x), u.y),
+        u.z,
+    );
+}
+
+// math::noise::Fbm, normalized to about -1 to 1
+fn fbm2(p: vec2<f32>, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+    var sum = 0.0;
+    var amplitude = 1.0;
+    var total_amplitude = 0.0;
+    var frequency = 1.0;
+    for (var i = 0u; i < octaves; i++) {
+        sum += amplitude * noise2(p * frequency + vec2<f32>(17.3, 9.1) * frequency);
+        total_amplitude += amplitude;
+        amplitude *= gain;
+        frequency *= lacunarity;
+    }
+    return sum / total_amplitude;
+}
+
+fn fbm3(p: vec3<f32>, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+    var sum = 0.0;
+    var amplitude = 1.0;
+    var total_amplitude = 0.0;
+    var frequency = 1.0;
+    for (var i = 0u; i < octaves; i++) {
+        sum += amplitude * noise3(p * frequency + vec3<f32>(17.3, 9.1, 5.7) * frequency);
+        total_amplitude += amplitude;
+        amplitude *= gain;
+        frequency *= lacunarity;
+    }
+    return sum / total_amplitude;
+}
+
+// math::noise::Fbm::warped2
+fn fbm2_warped(p: vec2<f32>, strength: f32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+    let warp = vec2<f32>(
+        fbm2(p + vec2<f32>(3.7, 1.9), octaves, lacunarity, gain),
+        fbm2(p + vec2<f32>(8.3, 2.8), octaves, lacunarity, gain),
+    );
+    return fbm2(p + warp * strength, octaves, lacunarity, gain);
+}
@@ -0,0 +1,85 @@
+use wgpu::*;
+
+use crate::buffer::GpuBuffer;
+use crate::math::Color;
+use crate::post::{self, FullscreenPass, PostInput, PostNode, HDR_FORMAT};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineParams {
+    color: [f32; 4],
+    id: u32,
+    thickness: f32,
+    _padding: [u32; 2],
+}
+
+/// outlines the selected instance by finding the edges of its pixels in the object-id target,
+/// so it shows through whatever covers it only where its own pixels are visible
+pub struct Outline {
+    /// alpha blends it over the scene
+    pub color: Color,
+    /// in logical pixels
+    pub thickness: f32,
+    /// physical pixels per logical pixel of the window
+    pub scale_factor: f32,
+    /// instance index as picked, None draws no outline
+    pub selected: Option<u32>,
+    params_buffer: GpuBuffer<OutlineParams>,
+    pass: FullscreenPass,
+    bind_group: Option<BindGroup>,
+}
+
+impl Outline {
+    pub fn new(device: &Device) -> Self {
+        let pass = FullscreenPass::new(
+            device,
+            "outline.wgsl",
+            include_str!("outline.wgsl"),
+            "fs_main",
+            HDR_FORMAT,
+            post::input_layout("outline bind group layout")
+                .uniform(ShaderStages::FRAGMENT)
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Uint),
+        );
+
+        Self {
+            color: Color::rgb(1.0, 0.6, 0.1),
+            thickness: 2.0,
+            scale_factor: 1.0,
+            selected: None,
+            params_buffer: GpuBuffer::new_uniform(device, "outline params buffer"),
+            pass,
+            bind_group: None,
+        }
+    }
+}
+
+impl PostNode for Outline {
+    fn name(&self) -> &'static str {
+        "outline"
+    }
+
+    fn inputs(&self) -> &'static [PostInput] {
+        &[PostInput::Previous, PostInput::ObjectId]
+    }
+
+    fn bind(&mut self, device: &Device, sampler: &Sampler, inputs: &[&TextureView]) {
+        self.bind_group = Some(self.pass.binding.create_bind_group(device, "outline bind group", &[
+            BindingResource::TextureView(inputs[0]),
+            BindingResource::Sampler(sampler),
+            self.params_buffer.as_entire_binding(),
+            BindingResource::TextureView(inputs[1]),
+        ]));
+    }
+
+    fn run(&mut self, queue: &Queue, encoder: &mut CommandEncoder, output: &TextureView) {
+        self.params_buffer.write(queue, &[OutlineParams {
+            color: self.color.to_array(),
+            // ids are offset by one, 0 is the background and matches no instance here
+            id: self.selected.map_or(u32::MAX, |index| index + 1),
+            thickness: self.thickness * self.scale_factor,
+            _padding: [0; 2],
+        }]);
+        self.pass.draw(encoder, "outline pass", output, self.bind_group.as_ref().unwrap());
+    }
+}
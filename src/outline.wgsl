@@ -0,0 +1,37 @@
+struct OutlineParams {
+    color: vec4<f32>,
+    // object id of the outlined instance, never 0
+    id: u32,
+    // in pixels
+    thickness: f32,
+}
+
+@group(0) @binding(2) var<uniform> params: OutlineParams;
+@group(0) @binding(3) var object_id: texture_2d<u32>;
+
+@fragment
+fn fs_main(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(input, input_sampler, in.uv, 0.0);
+
+    let size = vec2<i32>(textureDimensions(object_id));
+    let coords = clamp(vec2<i32>(in.uv * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    if textureLoad(object_id, coords, 0).r == params.id {
+        return color;
+    }
+
+    // covered by how far within the thickness the nearest pixel of the instance is,
+    // antialiasing the outer edge
+    let radius = i32(ceil(params.thickness));
+    var coverage = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let neighbour = clamp(coords + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            if textureLoad(object_id, neighbour, 0).r == params.id {
+                let distance = length(vec2<f32>(f32(x), f32(y)));
+                coverage = max(coverage, clamp(params.thickness + 0.5 - distance, 0.0, 1.0));
+            }
+        }
+    }
+
+    return vec4<f32>(mix(color.rgb, params.color.rgb, coverage * params.color.a), color.a);
+}
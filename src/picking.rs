@@ -0,0 +1,79 @@
+use wgpu::*;
+
+use crate::readback::Readback;
+
+pub const ID_FORMAT: TextureFormat = TextureFormat::R32Uint;
+
+/// reads back the object-id render target written by the light pass, where 0 is the background
+/// and `n` is the instance drawn at index `n - 1`.
+/// the texel under the cursor is read back asynchronously when a pick is requested.
+pub struct Picking {
+    width: u32,
+    height: u32,
+    requested: Option<(u32, u32)>,
+    recorded: Option<Readback<u32>>,
+    pending: Option<Readback<u32>>,
+}
+
+impl Picking {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            requested: None,
+            recorded: None,
+            pending: None,
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// picks the pixel at `x`, `y` of the next rendered frame
+    pub fn request(&mut self, x: f32, y: f32) {
+        if x >= 0.0 && y >= 0.0 && (x as u32) < self.width && (y as u32) < self.height {
+            self.requested = Some((x as u32, y as u32));
+        }
+    }
+
+    /// records the copy of the requested texel from `texture`, after the light pass wrote the ids
+    pub fn record(&mut self, device: &Device, encoder: &mut CommandEncoder, texture: &Texture) {
+        if self.pending.is_some() {
+            return;
+        }
+        if let Some((x, y)) = self.requested.take() {
+            self.recorded = Some(Readback::texture(
+                device,
+                encoder,
+                texture,
+                TextureAspect::All,
+                Origin3d { x, y, z: 0 },
+                1,
+                1,
+            ));
+        }
+    }
+
+    /// call with the submission of the encoder passed to `record`
+    pub fn submitted(&mut self, submission: &SubmissionIndex) {
+        if let Some(mut readback) = self.recorded.take() {
+            readback.map(submission.clone());
+            self.pending = Some(readback);
+        }
+    }
+
+    /// whether a pick is yet to be recorded or read back
+    pub fn is_busy(&self) -> bool {
+        self.requested.is_some() || self.recorded.is_some() || self.pending.is_some()
+    }
+
+    /// returns the result of the last pick once available:
+    /// the picked instance index, or None when the background was picked
+    pub fn poll(&mut self, device: &Device) -> Option<Option<u32>> {
+        let id = self.pending.as_mut()?.try_read(device, |ids| ids[0])?;
+        self.pending = None;
+        Some(id.checked_sub(1))
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use wgpu::*;
+
+/// stencil test and operations of a cached pipeline, the same for both faces
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct StencilKey {
+    pub face: StencilFaceState,
+    pub read_mask: u32,
+    pub write_mask: u32,
+}
+
+impl StencilKey {
+    /// passes where the stencil equals the reference, leaving it as is
+    pub fn equal() -> Self {
+        Self {
+            face: StencilFaceState {
+                compare: CompareFunction::Equal,
+                ..StencilFaceState::IGNORE
+            },
+            read_mask: !0,
+            write_mask: 0,
+        }
+    }
+
+    /// writes the reference where the depth test passes
+    pub fn replace() -> Self {
+        Self {
+            face: StencilFaceState {
+                compare: CompareFunction::Always,
+                pass_op: StencilOperation::Replace,
+                ..StencilFaceState::IGNORE
+            },
+            read_mask: !0,
+            write_mask: !0,
+        }
+    }
+}
+
+/// which faces are culled, per material so thin geometry can be seen from both sides
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Cull {
+    Back,
+    Front,
+    /// two sided
+    None,
+}
+
+impl Cull {
+    pub fn face(self) -> Option<Face> {
+        match self {
+            Cull::Back => Some(Face::Back),
+            Cull::Front => Some(Face::Front),
+            Cull::None => None,
+        }
+    }
+
+    /// as seen in a mirror, whose reflection reverses the winding
+    pub fn mirrored(self) -> Self {
+        match self {
+            Cull::Back => Cull::Front,
+            Cull::Front => Cull::Back,
+            Cull::None => Cull::None,
+        }
+    }
+}
+
+/// depth state of a cached pipeline, `DepthStencilState` itself can't be hashed
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct DepthKey {
+    pub format: TextureFormat,
+    pub write: bool,
+    pub compare: CompareFunction,
+    /// None leaves the stencil alone, set the reference with `RenderPass::set_stencil_reference`
+    pub stencil: Option<StencilKey>,
+}
+
+/// everything a cached pipeline varies in besides its shader's layout and vertex buffers
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PipelineKey {
+    /// as passed to `PipelineCache::register`
+    pub shader: &'static str,
+    pub topology: PrimitiveTopology,
+    /// needed for indexed strips
+    pub strip_index_format: Option<IndexFormat>,
+    pub cull: Cull,
+    pub blend: Option<BlendState>,
+    pub depth: Option<DepthKey>,
+    /// None for depth only pipelines
+    pub color_format: Option<TextureFormat>,
+}
+
+impl PipelineKey {
+    /// triangles without culling, blending or depth
+    pub fn new(shader: &'static str, color_format: Option<TextureFormat>) -> Self {
+        Self {
+            shader,
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            cull: Cull::None,
+            blend: None,
+            depth: None,
+            color_format,
+        }
+    }
+}
+
+struct Shader {
+    module: ShaderModule,
+    layout: PipelineLayout,
+    buffers: Vec<VertexBufferLayout<'static>>,
+}
+
+/// render pipelines created on first use for each `PipelineKey`, so another topology or
+/// target format of a registered shader is a different key rather than a hand-rolled pipeline.
+/// shaders are expected to have `vs_main` and `fs_main` entry points.
+pub struct PipelineCache {
+    shaders: HashMap<&'static str, Shader>,
+    pipelines: HashMap<PipelineKey, Arc<RenderPipeline>>,
+}
+
+impl PipelineCache {
+    pub fn new() -> Self {
+        Self {
+            shaders: HashMap::new(),
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// makes `source` available to keys with `shader: name`, the same name can't be registered twice
+    pub fn register(
+        &mut self,
+        device: &Device,
+        name: &'static str,
+        source: &str,
+        bind_group_layouts: &[&BindGroupLayout],
+        buffers: &[VertexBufferLayout<'static>],
+    ) {
+        assert!(!self.shaders.contains_key(name), "shader `{}` is already registered", name);
+
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(name),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some(name),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        self.shaders.insert(name, Shader {
+            module,
+            layout,
+            buffers: buffers.to_vec(),
+        });
+    }
+
+    pub fn get(&mut self, device: &Device, key: PipelineKey) -> Arc<RenderPipeline> {
+        if let Some(pipeline) = self.pipelines.get(&key) {
+            return pipeline.clone();
+        }
+
+        let shader = self.shaders
+            .get(key.shader)
+            .unwrap_or_else(|| panic!("shader `{}` isn't registered", key.shader));
+        let targets = [key.color_format.map(|format| ColorTargetState {
+            format,
+            blend: key.blend,
+            write_mask: ColorWrites::ALL,
+        })];
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(key.shader),
+            layout: Some(&shader.layout),
+            vertex: VertexState {
+                module: &shader.module,
+                entry_point: "vs_main",
+                buffers: &shader.buffers,
+            },
+            fragment: key.color_format.map(|_| FragmentState {
+                module: &shader.module,
+                entry_point: "fs_main",
+                targets: &targets,
+            }),
+            primitive: PrimitiveState {
+                topology: key.topology,
+                strip_index_format: key.strip_index_format,
+                cull_mode: key.cull.face(),
+                ..Default::default()
+            },
+            depth_stencil: key.depth.map(|depth| DepthStencilState {
+                format: depth.format,
+                depth_write_enabled: depth.write,
+                depth_compare: depth.compare,
+                stencil: depth.stencil.map_or_else(StencilState::default, |stencil| StencilState {
+                    front: stencil.face,
+                    back: stencil.face,
+                    read_mask: stencil.read_mask,
+                    write_mask: stencil.write_mask,
+                }),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        let pipeline = Arc::new(pipeline);
+        self.pipelines.insert(key, pipeline.clone());
+        pipeline
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+}
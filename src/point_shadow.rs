@@ -0,0 +1,366 @@
+use std::num::NonZeroU32;
+
+use wgpu::*;
+
+use crate::binding::{self, BindGroupLayoutBuilder};
+use crate::buffer::GpuBuffer;
+use crate::math::{Color, Vector3};
+use crate::preprocess::{Defines, Preprocessor};
+
+/// how a point light's shadows are rendered, `light.wgsl` matches on it.
+/// there's no cube map path to pick instead: it would cost six passes over the casters
+/// where dual paraboloids take two, or one with multiview, at the price of bent shadows
+/// from large triangles and less resolution towards the hemispheres' edges.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PointShadowMode {
+    None = 0,
+    DualParaboloid = 1,
+}
+
+impl PointShadowMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "none" => Some(PointShadowMode::None),
+            "paraboloid" => Some(PointShadowMode::DualParaboloid),
+            _ => None,
+        }
+    }
+}
+
+/// a light shining from `position` in every direction, added to the light pass's,
+/// the instances it reaches are lit by it like by the light, the terrain also by its normals
+pub struct PointLight {
+    /// off, it neither lights nor renders its shadow
+    pub enabled: bool,
+    pub position: Vector3,
+    /// casters closer than this don't cast shadows
+    pub near_z: f32,
+    pub color: Color,
+    pub intensity: f32,
+    /// falls off with the square of the distance, down to nothing at this one
+    pub range: f32,
+    pub shadow: PointShadowMode,
+}
+
+/// as `light.wgsl` reads it
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointLightRaw {
+    position: [f32; 3],
+    near_z: f32,
+    color: [f32; 3],
+    intensity: f32,
+    range: f32,
+    shadow: u32,
+    _padding: [u32; 2],
+}
+crate::layout::assert_uniform_size!(PointLightRaw, 48);
+
+impl PointLight {
+    pub fn to_raw(&self) -> PointLightRaw {
+        PointLightRaw {
+            position: [self.position.x, self.position.y, self.position.z],
+            near_z: self.near_z,
+            color: self.color.to_rgb_array(),
+            intensity: if self.enabled { self.intensity } else { 0.0 },
+            range: self.range,
+            shadow: self.shadow as u32,
+            _padding: Default::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hemisphere {
+    /// facing +z, layer 0 of the shadow texture
+    Front,
+    /// facing -z, layer 1
+    Back,
+}
+
+impl Hemisphere {
+    pub const ALL: [Hemisphere; 2] = [Self::Front, Self::Back];
+
+    fn direction(self) -> f32 {
+        match self {
+            Self::Front => 1.0,
+            Self::Back => -1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct HemisphereRaw {
+    light_position: [f32; 3],
+    near_z: f32,
+    direction: f32,
+    _padding: [u32; 3],
+}
+
+/// `point_shadow.wgsl` rendering both layers in one pass, with `Features::MULTIVIEW`,
+/// or one per pass
+pub fn source(multiview: bool) -> Result<String, String> {
+    let mut preprocessor = Preprocessor::new();
+    preprocessor.add("point_shadow.wgsl", include_str!("point_shadow.wgsl"));
+    preprocessor.process("point_shadow.wgsl", &Defines::new().with_if("MULTIVIEW", multiview))
+}
+
+/// a pipeline drawing the casters into one or both layers, with the bind groups of its passes
+struct HemispherePipeline {
+    pipeline: RenderPipeline,
+    /// one per pass
+    bind_groups: Vec<BindGroup>,
+}
+
+/// dual paraboloid shadow map of a point light, one texture layer per hemisphere,
+/// sampled by `light.wgsl`. with `Features::MULTIVIEW` both are rendered in a single pass,
+/// one view per layer
+pub struct PointShadow {
+    /// both layers, for sampling, and for rendering to with multiview
+    pub view: TextureView,
+    layer_views: [TextureView; 2],
+    /// whether `multiview` renders the layers when there is one, rather than `layered`,
+    /// to compare the single pass with the one per layer
+    pub use_multiview: bool,
+    /// both layers in one pass, with `Features::MULTIVIEW`
+    multiview: Option<HemispherePipeline>,
+    /// a pass per layer
+    layered: HemispherePipeline,
+    /// by layer
+    hemispheres: GpuBuffer<HemisphereRaw>,
+    /// the layer of each of `layered`'s passes
+    #[allow(dead_code)]
+    layers: [GpuBuffer<u32>; 2],
+}
+
+impl PointShadow {
+    /// `buffers` are the vertex and instance layouts of the shadow casters,
+    /// laid out like in `shadow.wgsl`
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        format: TextureFormat,
+        size: u32,
+        buffers: &[VertexBufferLayout],
+    ) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("point shadow texture"),
+            size: Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 2,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = [0, 1].map(|layer| texture.create_view(&TextureViewDescriptor {
+            label: Some("point shadow layer view"),
+            dimension: Some(TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        }));
+
+        let hemispheres = GpuBuffer::new_storage(device, "point shadow hemisphere buffer", Hemisphere::ALL.len());
+        let mut layers = Hemisphere::ALL.map(|_| GpuBuffer::new_uniform(device, "point shadow layer buffer"));
+        for (layer, hemisphere) in layers.iter_mut().zip(Hemisphere::ALL) {
+            layer.write(queue, &[hemisphere as u32]);
+        }
+
+        let create_pipeline = |multiview: bool| {
+            let source = source(multiview).unwrap();
+            let mut layout = BindGroupLayoutBuilder::new("point shadow bind group layout")
+                .storage(ShaderStages::VERTEX, true);
+            if !multiview {
+                layout = layout.uniform(ShaderStages::VERTEX);
+            }
+            let module = binding::reflect("point_shadow.wgsl", &source);
+            layout.validate(0, &module).unwrap();
+            crate::layout::validate_vertex_input(&module, "vs_main", buffers).unwrap();
+            let binding = layout.build(device);
+
+            let bind_groups = match multiview {
+                true => vec![binding.create_bind_group(
+                    device,
+                    "point shadow bind group",
+                    &[hemispheres.as_entire_binding()],
+                )],
+                false => layers.iter().map(|layer| binding.create_bind_group(
+                    device,
+                    "point shadow bind group",
+                    &[hemispheres.as_entire_binding(), layer.as_entire_binding()],
+                )).collect(),
+            };
+
+            let shader = device.create_shader_module(ShaderModuleDescriptor {
+                label: Some("Point shadow shader"),
+                source: ShaderSource::Wgsl(source.into()),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Point shadow pipeline layout"),
+                bind_group_layouts: &[&binding.layout],
+                push_constant_ranges: &[],
+            });
+            let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("Point shadow pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers,
+                },
+                fragment: None,
+                primitive: PrimitiveState {
+                    cull_mode: Some(Face::Back),
+                    ..Default::default()
+                },
+                depth_stencil: Some(DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: CompareFunction::Greater,
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState::default(),
+                multiview: multiview.then(|| NonZeroU32::new(Hemisphere::ALL.len() as u32).unwrap()),
+            });
+            HemispherePipeline {
+                pipeline,
+                bind_groups,
+            }
+        };
+        let multiview = device.features().contains(Features::MULTIVIEW).then(|| create_pipeline(true));
+        let layered = create_pipeline(false);
+
+        Self {
+            view,
+            layer_views,
+            use_multiview: true,
+            multiview,
+            layered,
+            hemispheres,
+            layers,
+        }
+    }
+
+    /// whether the device has `Features::MULTIVIEW`
+    pub fn supports_multiview(&self) -> bool {
+        self.multiview.is_some()
+    }
+
+    pub fn set_light(&mut self, queue: &Queue, light: &PointLight) {
+        self.hemispheres.write(queue, &Hemisphere::ALL.map(|hemisphere| HemisphereRaw {
+            light_position: [light.position.x, light.position.y, light.position.z],
+            near_z: light.near_z,
+            direction: hemisphere.direction(),
+            _padding: Default::default(),
+        }));
+    }
+
+    fn multiview(&self) -> Option<&HemispherePipeline> {
+        self.multiview.as_ref().filter(|_| self.use_multiview)
+    }
+
+    /// the passes to draw the casters in, each a hemisphere's,
+    /// or a single None rendering both with multiview
+    pub fn passes(&self) -> &'static [Option<Hemisphere>] {
+        match self.multiview() {
+            Some(_) => &[None],
+            None => &[Some(Hemisphere::Front), Some(Hemisphere::Back)],
+        }
+    }
+
+    /// clears the layer of `hemisphere`, or both layers for None, and returns a pass ready to
+    /// draw the casters into it, with the pipeline and bind group 0 set.
+    /// `hemisphere` is one of `passes`
+    pub fn begin_pass<'a>(
+        &'a self,
+        encoder: &'a mut CommandEncoder,
+        hemisphere: Option<Hemisphere>,
+    ) -> RenderPass<'a> {
+        let (view, pipeline, bind_group) = match (hemisphere, self.multiview()) {
+            (Some(hemisphere), None) => (
+                &self.layer_views[hemisphere as usize],
+                &self.layered.pipeline,
+                &self.layered.bind_groups[hemisphere as usize],
+            ),
+            (None, Some(multiview)) => (&self.view, &multiview.pipeline, &multiview.bind_groups[0]),
+            _ => panic!("not one of the point shadow's passes"),
+        };
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("point shadow pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(0.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vector2;
+
+    /// the hemisphere and shadow map uv `to_point` falls on, as `light.wgsl` samples it,
+    /// +y is down in the uv like in the other shadow maps
+    fn paraboloid_uv(to_point: Vector3) -> (Hemisphere, Vector2) {
+        let hemisphere = if to_point.z >= 0.0 { Hemisphere::Front } else { Hemisphere::Back };
+        let direction = hemisphere.direction();
+        let normal = to_point.normalized();
+        let x = normal.x * direction / (1.0 + normal.z * direction);
+        let y = normal.y / (1.0 + normal.z * direction);
+        (hemisphere, Vector2::new(x * 0.5 + 0.5, 0.5 - y * 0.5))
+    }
+
+    #[test]
+    fn paraboloid_uv_covers_each_hemisphere() {
+        let (hemisphere, uv) = paraboloid_uv(Vector3::new(0.0, 0.0, 2.0));
+        assert_eq!(hemisphere, Hemisphere::Front);
+        assert!((uv.x - 0.5).abs() < 1e-6 && (uv.y - 0.5).abs() < 1e-6);
+
+        let (hemisphere, uv) = paraboloid_uv(Vector3::new(0.0, 0.0, -2.0));
+        assert_eq!(hemisphere, Hemisphere::Back);
+        assert!((uv.x - 0.5).abs() < 1e-6 && (uv.y - 0.5).abs() < 1e-6);
+
+        // the rim of the hemisphere is the edge of the map, mirrored in x for the back one
+        let (_, front) = paraboloid_uv(Vector3::new(1.0, 0.0, 1e-6));
+        let (_, back) = paraboloid_uv(Vector3::new(1.0, 0.0, -1e-6));
+        assert!((front.x - 1.0).abs() < 1e-4 && back.x.abs() < 1e-4);
+    }
+
+    #[test]
+    fn point_light_matches_light_wgsl() {
+        use std::mem::{offset_of, size_of};
+
+        let source = crate::shader_preprocessor(crate::draw_params::DrawParamsMode::Uniform { group: 1 })
+            .process("light.wgsl", &crate::preprocess::ShaderVariant::all().defines())
+            .unwrap();
+        let shader = binding::reflect("light.wgsl", &source);
+        crate::layout::validate_struct(&shader, "PointLight", size_of::<PointLightRaw>(), &[
+            ("position", offset_of!(PointLightRaw, position)),
+            ("near_z", offset_of!(PointLightRaw, near_z)),
+            ("color", offset_of!(PointLightRaw, color)),
+            ("intensity", offset_of!(PointLightRaw, intensity)),
+            ("range", offset_of!(PointLightRaw, range)),
+            ("shadow", offset_of!(PointLightRaw, shadow)),
+        ]).unwrap();
+    }
+}
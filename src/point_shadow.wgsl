@@ -0,0 +1,93 @@
+// point_shadow::HemisphereRaw
+struct Hemisphere {
+    light_position: vec3<f32>,
+    near_z: f32,
+    // 1 for the hemisphere facing +z, -1 for the one facing -z
+    direction: f32,
+};
+// by the layer of the shadow texture rendered to
+@group(0) @binding(0) var<storage, read> hemispheres: array<Hemisphere, 2>;
+#ifndef MULTIVIEW
+// one pass per layer, each with its own bind group
+@group(0) @binding(1) var<uniform> layer: u32;
+#endif
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) flags: u32,
+}
+
+// scene::CASTS_SHADOW
+const CASTS_SHADOW: u32 = 1u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+@vertex fn vs_main(
+#ifdef MULTIVIEW
+    // both layers in a single pass, one view each
+    @builtin(view_index) view: i32,
+#endif
+    vertex: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+#ifdef MULTIVIEW
+    let hemisphere = hemispheres[view];
+#else
+    let hemisphere = hemispheres[layer];
+#endif
+    var out: VertexOutput;
+    if (instance.flags & CASTS_SHADOW) == 0u {
+        out.clip_position = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+        return out;
+    }
+
+    let position = apply_affine(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        vertex.position,
+    );
+    let projected = paraboloid_project(hemisphere, position);
+
+    // the projection isn't linear, so only the vertices are exactly on the paraboloid:
+    // large triangles need to be tessellated to not cast bent shadows
+    out.clip_position = vec4<f32>(projected.xy, projected.z, 1.0);
+    return out;
+}
+
+// xy in -1 to 1 on the hemisphere's paraboloid, z is reversed depth like the other shadow maps,
+// negative behind the hemisphere so it gets clipped
+fn paraboloid_project(hemisphere: Hemisphere, position: vec3<f32>) -> vec3<f32> {
+    var to_point = position - hemisphere.light_position;
+    // mirrored around y for the back hemisphere, keeping the winding
+    to_point.x *= hemisphere.direction;
+    to_point.z *= hemisphere.direction;
+
+    let distance = length(to_point);
+    let normal = to_point / distance;
+    if normal.z < 0.0 {
+        return vec3<f32>(0.0, 0.0, -1.0);
+    }
+    return vec3<f32>(normal.xy / (1.0 + normal.z), hemisphere.near_z / distance);
+}
+
+fn apply_affine(
+    _0: vec4<f32>,
+    _1: vec4<f32>,
+    _2: vec4<f32>,
+    pos: vec3<f32>
+) -> vec3<f32> {
+    return vec3<f32>(
+        dot(_0.xyz, pos) + _0.w,
+        dot(_1.xyz, pos) + _1.w,
+        dot(_2.xyz, pos) + _2.w,
+    );
+}
@@ -1,12 +1,11 @@
-use std::cmp::{min, max};
-
-use crate::math::{self, Vector3};
-use math::Vector2;
+use crate::math;
+use math::{Affine2, Scale2, Vector2};
 
 /// `points`: points to be wrapped by convex polygon; points also get sorted to avoid unintended allocation.
 /// `prev_indices`: contains an index to the `points` slice if corresponding point is on convex hull.
 /// (within graham_scan `indices_on_convex` used for each point on convex to refer to previous point on convex)
 /// returns the number of points on convex hull, or effective len of prev_indices.
+#[allow(dead_code)]
 pub fn graham_scan(
     points: &mut [Vector2],
     indices_on_hull: &mut [usize],
@@ -18,9 +17,8 @@ pub fn graham_scan(
         }
     }
 
-    let point_0 = points[min_i];
-    points[min_i] = points[0];
-    points[0] = point_0;
+    points.swap(0, min_i);
+    let point_0 = points[0];
 
     points[1..].sort_unstable_by(|a, b| {
         // (b.x - point_0.x) / (b.y - point_0.y) < (a.x - point_0.x) / (a.y - point_0.y)
@@ -78,6 +76,7 @@ pub fn graham_scan(
 /// returns a Vec of the intersection of 2 polygons which is also a polygon
 /// allocations: the Vec
 /// algorithm used: https://www.cs.jhu.edu/~misha/Spring16/ORourke82.pdf
+#[allow(dead_code)]
 pub fn convex_intersect_alloc(
     convex_p: &[Vector2],
     convex_q: &[Vector2],
@@ -106,9 +105,9 @@ pub fn convex_intersect_alloc(
         let t = dq.wedge(dold).xy / dq_dp;
         let s = dp.wedge(dold).xy / dq_dp;
 
-        if t >= 0.0 && t <= 1.0 && s >= 0.0 && s <= 1.0 {
+        if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&s) {
             let r = *old_p + dp * t;
-            if convex_r.len() != 0 && convex_r[0] == r {
+            if !convex_r.is_empty() && convex_r[0] == r {
                 break;
             } else {
                 convex_r.push(r);
@@ -145,13 +144,14 @@ pub fn convex_intersect_alloc(
         }
     }
 
-    return convex_r;
+    convex_r
 } 
 
 /// writes to the slice provided, it writes the intersection of 2 polygons which is also a polygon
 /// returns the size of the resulting polygon
 /// no allocations
 /// algorithm used: https://www.cs.jhu.edu/~misha/Spring16/ORourke82.pdf
+#[allow(dead_code)]
 pub fn convex_intersect_no_alloc(
     convex_p: &[Vector2],
     convex_q: &[Vector2],
@@ -182,7 +182,7 @@ pub fn convex_intersect_no_alloc(
         let t = dq.wedge(dold).xy / dq_dp;
         let s = dp.wedge(dold).xy / dq_dp;
 
-        if t >= 0.0 && t <= 1.0 && s >= 0.0 && s <= 1.0 {
+        if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&s) {
             let r = *old_p + dp * t;
             if convex_r_len != 0 && convex_r[0] == r {
                 break;
@@ -224,9 +224,244 @@ pub fn convex_intersect_no_alloc(
         }
     }
 
-    return convex_r_len;
+    convex_r_len
 } 
 
+/// twice the area of the polygon, positive when it's counter clockwise
+#[allow(dead_code)]
+fn signed_area_2(points: &[Vector2]) -> f32 {
+    (0..points.len())
+        .map(|i| points[i].wedge(points[(i + 1) % points.len()]).xy)
+        .sum()
+}
+
+/// triangles covering a simple polygon, indices into `points` wound like it.
+/// a convex polygon is fanned out from its first point, others have their ears clipped one
+/// by one, in O(n^2) at worst. a polygon that isn't simple gets the triangles clipped before
+/// no ear was left
+#[allow(dead_code)]
+pub fn triangulate(points: &[Vector2]) -> Vec<[u16; 3]> {
+    assert!(points.len() <= u16::MAX as usize + 1, "{} points can't be indexed by u16", points.len());
+    let n = points.len();
+    if n < 3 {
+        return vec![];
+    }
+    // turns are measured as if the polygon were counter clockwise
+    let orientation = signed_area_2(points).signum();
+    let turn = |a: u16, b: u16, c: u16| {
+        let (a, b, c) = (points[a as usize], points[b as usize], points[c as usize]);
+        (b - a).wedge(c - b).xy * orientation
+    };
+    let in_or_on = |p: Vector2, triangle: [u16; 3]| {
+        (0..3).all(|i| {
+            let (from, to) = (points[triangle[i] as usize], points[triangle[(i + 1) % 3] as usize]);
+            (to - from).wedge(p - from).xy * orientation >= 0.0
+        })
+    };
+
+    let indices: Vec<u16> = (0..n as u16).collect();
+    if (0..n).all(|i| turn(indices[i], indices[(i + 1) % n], indices[(i + 2) % n]) >= 0.0) {
+        return (1..n as u16 - 1).map(|i| [0, i, i + 1]).collect();
+    }
+
+    let mut remaining = indices;
+    let mut triangles = Vec::with_capacity(n - 2);
+    let mut i = 0;
+    // corners looked at since the last ear, once it's all of them there are no ears left
+    let mut since_ear = 0;
+    while remaining.len() > 3 && since_ear < remaining.len() {
+        let len = remaining.len();
+        let [a, b, c] = [remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]];
+        // convex, with no other corner in or on it
+        let is_ear = turn(a, b, c) > 0.0 && !remaining.iter().any(|&d| {
+            let p = points[d as usize];
+            [a, b, c].iter().all(|&corner| points[corner as usize] != p) && in_or_on(p, [a, b, c])
+        });
+        if is_ear {
+            triangles.push([a, b, c]);
+            remaining.remove(i);
+            i %= remaining.len();
+            since_ear = 0;
+        } else {
+            i = (i + 1) % len;
+            since_ear += 1;
+        }
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+/// whether `point` is in or on a convex polygon of either winding, by which side of each
+/// edge it's on
+#[allow(dead_code)]
+pub fn convex_contains_point(points: &[Vector2], point: Vector2) -> bool {
+    let sides = (0..points.len()).map(|i| {
+        let (from, to) = (points[i], points[(i + 1) % points.len()]);
+        (to - from).wedge(point - from).xy
+    });
+    let (mut left, mut right) = (false, false);
+    for side in sides {
+        left |= side > 0.0;
+        right |= side < 0.0;
+    }
+    let on_both_sides = left && right;
+    !points.is_empty() && !on_both_sides
+}
+
+/// whether `point` is inside a simple polygon of either winding, by its winding number.
+/// points on an edge may go either way
+#[allow(dead_code)]
+pub fn contains_point(points: &[Vector2], point: Vector2) -> bool {
+    let mut winding = 0;
+    for i in 0..points.len() {
+        let (from, to) = (points[i], points[(i + 1) % points.len()]);
+        let side = (to - from).wedge(point - from).xy;
+        // edges crossing the horizontal line through the point, upwards on its right or
+        // downwards on its left
+        if from.y <= point.y && point.y < to.y && side > 0.0 {
+            winding += 1;
+        } else if to.y <= point.y && point.y < from.y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding != 0
+}
+
+/// slab test of the segment from `a` to `b` against the rect, edges included
+#[allow(dead_code)]
+fn segment_intersects_rect(a: Vector2, b: Vector2, rect: &Rect) -> bool {
+    let direction = b - a;
+    let (mut near, mut far) = (0.0f32, 1.0f32);
+    for axis in 0..2 {
+        if direction[axis] == 0.0 {
+            if a[axis] < rect.min[axis] || rect.max[axis] < a[axis] {
+                return false;
+            }
+            continue;
+        }
+        let inverse = 1.0 / direction[axis];
+        let t0 = (rect.min[axis] - a[axis]) * inverse;
+        let t1 = (rect.max[axis] - a[axis]) * inverse;
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    near <= far
+}
+
+/// whether a simple polygon and `rect` overlap or touch: one holds a corner of the other,
+/// or an edge of the polygon crosses the rect
+#[allow(dead_code)]
+pub fn polygon_intersects_rect(points: &[Vector2], rect: &Rect) -> bool {
+    points.iter().any(|&point| rect.contains_point(point))
+        || rect.corners().iter().any(|&corner| contains_point(points, corner))
+        || (0..points.len()).any(|i| segment_intersects_rect(points[i], points[(i + 1) % points.len()], rect))
+}
+
+/// at most `N` points kept inline, e.g. a convex polygon's corners counter clockwise,
+/// so polygons can be built every frame without allocating
+#[derive(Clone, Copy, Debug)]
+pub struct Polygon<const N: usize> {
+    points: [Vector2; N],
+    len: usize,
+}
+
+impl<const N: usize> Polygon<N> {
+    pub const fn new() -> Self {
+        Self {
+            points: [Vector2::IDENTITY; N],
+            len: 0,
+        }
+    }
+
+    /// panics when there are more than `N`
+    #[allow(dead_code)]
+    pub fn from_points(points: &[Vector2]) -> Self {
+        let mut polygon = Self::new();
+        for &point in points {
+            polygon.push(point);
+        }
+        polygon
+    }
+
+    /// panics when it's full
+    pub fn push(&mut self, point: Vector2) {
+        assert!(self.len < N, "a polygon of at most {} points is full", N);
+        self.points[self.len] = point;
+        self.len += 1;
+    }
+
+    #[allow(dead_code)]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[allow(dead_code)]
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn points(&self) -> &[Vector2] {
+        &self.points[..self.len]
+    }
+
+    /// see `contains_point`
+    #[allow(dead_code)]
+    pub fn contains_point(&self, point: Vector2) -> bool {
+        contains_point(self.points(), point)
+    }
+
+    /// see `polygon_intersects_rect`
+    #[allow(dead_code)]
+    pub fn intersects_rect(&self, rect: &Rect) -> bool {
+        polygon_intersects_rect(self.points(), rect)
+    }
+
+    /// counter clockwise from the lowest point, see `graham_scan`
+    #[allow(dead_code)]
+    pub fn convex_hull(points: &[Vector2]) -> Self {
+        let mut polygon = Self::from_points(points);
+        if polygon.len < 3 {
+            return polygon;
+        }
+        let mut indices_on_hull = [0; N];
+        let hull_len = graham_scan(&mut polygon.points[..polygon.len], &mut indices_on_hull);
+        let sorted = polygon.points;
+        polygon.clear();
+        for &i in &indices_on_hull[..hull_len] {
+            polygon.push(sorted[i]);
+        }
+        polygon
+    }
+
+    /// of two convex polygons, both counter clockwise, see `convex_intersect_no_alloc`.
+    /// it has at most as many corners as both together, which has to fit in `N`
+    #[allow(dead_code)]
+    pub fn convex_intersect<const P: usize, const Q: usize>(p: &Polygon<P>, q: &Polygon<Q>) -> Self {
+        assert!(p.len + q.len <= N, "the intersection of {} and {} corners may not fit in {}", p.len, q.len, N);
+        let mut polygon = Self::new();
+        polygon.len = convex_intersect_no_alloc(p.points(), q.points(), &mut polygon.points);
+        polygon
+    }
+}
+
+impl<const N: usize> Default for Polygon<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Rect {
     pub max: Vector2,
     pub min: Vector2,
@@ -261,18 +496,80 @@ impl Rect {
             None
         } else {
             Some(Rect{
-                max: Vector2::new(
-                    f32::min(self.max.x, other.max.x),
-                    f32::min(self.max.y, other.max.y),
-                ),
-                min: Vector2::new(
-                    f32::max(self.min.x, other.min.x),
-                    f32::max(self.min.y, other.min.y),
-                ),
+                max: self.max.min(other.max),
+                min: self.min.max(other.min),
             })
         }
     }
 
+    /// the smallest rect containing both
+    #[allow(dead_code)]
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect {
+            max: self.max.max(other.max),
+            min: self.min.min(other.min),
+        }
+    }
+
+    /// edges included
+    pub fn contains_point(&self, point: Vector2) -> bool {
+        self.min.x <= point.x && point.x <= self.max.x
+        && self.min.y <= point.y && point.y <= self.max.y
+    }
+
+    /// moves every edge out by `margin`, or in when negative
+    #[allow(dead_code)]
+    pub fn expand(&self, margin: f32) -> Rect {
+        let margin = Vector2::new(margin, margin);
+        Rect {
+            max: self.max + margin,
+            min: self.min - margin,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn area(&self) -> f32 {
+        self.width() * self.height()
+    }
+
+    #[allow(dead_code)]
+    pub fn center(&self) -> Vector2 {
+        (self.min + self.max) * 0.5
+    }
+
+    #[allow(dead_code)]
+    pub fn translate(&self, by: Vector2) -> Rect {
+        Rect {
+            max: self.max + by,
+            min: self.min + by,
+        }
+    }
+
+    /// about the origin, a negative scale flips the rect without turning it inside out
+    #[allow(dead_code)]
+    pub fn scale(&self, scale: Scale2) -> Rect {
+        let (a, b) = (scale * self.min, scale * self.max);
+        Rect {
+            max: a.max(b),
+            min: a.min(b),
+        }
+    }
+
+    /// the smallest rect containing the transformed corners
+    pub fn transform(&self, a: &Affine2) -> Rect {
+        Rect::from_points(&self.corners().map(|corner| corner.apply(a)))
+    }
+
+    /// counter clockwise from `min`
+    pub fn corners(&self) -> [Vector2; 4] {
+        [
+            self.min,
+            Vector2::new(self.max.x, self.min.y),
+            self.max,
+            Vector2::new(self.min.x, self.max.y),
+        ]
+    }
+
     #[inline(always)]
     pub fn width(&self) -> f32 {
         self.max.x - self.min.x
@@ -282,4 +579,110 @@ impl Rect {
     pub fn height(&self) -> f32 {
         self.max.y - self.min.y
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polygons_stay_within_their_capacity() {
+        let square = |min: f32, max: f32| Polygon::<4>::from_points(&Rect {
+            max: Vector2::new(max, max),
+            min: Vector2::new(min, min),
+        }.corners());
+
+        // the inner points are dropped
+        let mut points = square(0.0, 2.0).points().to_vec();
+        points.extend([Vector2::new(1.0, 1.0), Vector2::new(0.5, 1.5)]);
+        let hull = Polygon::<8>::convex_hull(&points);
+        assert_eq!(hull.len(), 4);
+        assert!(hull.points().iter().all(|corner| square(0.0, 2.0).points().contains(corner)));
+
+        let overlap = Polygon::<8>::convex_intersect(&square(0.0, 2.0), &square(1.0, 3.0));
+        let bounds = Rect::from_points(overlap.points());
+        assert_eq!(bounds, Rect { max: Vector2::new(2.0, 2.0), min: Vector2::new(1.0, 1.0) });
+
+        let mut polygon = Polygon::<2>::new();
+        polygon.push(Vector2::IDENTITY);
+        polygon.push(Vector2::IDENTITY);
+        assert!(polygon.is_full());
+        polygon.clear();
+        assert!(polygon.is_empty());
+    }
+
+    #[test]
+    fn triangulations_cover_the_polygon() {
+        // an l shape, its inner corner at (1, 1)
+        let l = [(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)]
+            .map(|(x, y)| Vector2::new(x, y));
+        let mut clockwise = l;
+        clockwise.reverse();
+        let square = Rect { max: Vector2::new(1.0, 1.0), min: Vector2::IDENTITY }.corners();
+
+        for (points, area) in [(&l[..], 3.0), (&clockwise[..], -3.0), (&square[..], 1.0)] {
+            let triangles = triangulate(points);
+            assert_eq!(triangles.len(), points.len() - 2);
+            let areas: Vec<f32> = triangles
+                .iter()
+                .map(|triangle| signed_area_2(&triangle.map(|i| points[i as usize])) / 2.0)
+                .collect();
+            // wound like the polygon, none overlapping
+            assert!(areas.iter().all(|a| a * area > 0.0), "{:?}", areas);
+            assert!((areas.iter().sum::<f32>() - area).abs() < 1e-5);
+        }
+        assert!(triangulate(&l[..2]).is_empty());
+    }
+
+    #[test]
+    fn points_in_polygons() {
+        let l = [(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)]
+            .map(|(x, y)| Vector2::new(x, y));
+        let mut clockwise = l;
+        clockwise.reverse();
+        for points in [&l, &clockwise] {
+            assert!(contains_point(points, Vector2::new(0.5, 1.5)));
+            assert!(contains_point(points, Vector2::new(1.5, 0.5)));
+            // the notch
+            assert!(!contains_point(points, Vector2::new(1.5, 1.5)));
+            assert!(!contains_point(points, Vector2::new(-0.5, 0.5)));
+        }
+
+        let square = Rect { max: Vector2::new(1.0, 1.0), min: Vector2::IDENTITY }.corners();
+        assert!(convex_contains_point(&square, Vector2::new(0.5, 0.5)));
+        assert!(convex_contains_point(&square, Vector2::new(1.0, 0.5)));
+        assert!(!convex_contains_point(&square, Vector2::new(1.5, 0.5)));
+
+        let rect = |x: f32, y: f32| Rect { max: Vector2::new(x + 0.2, y + 0.2), min: Vector2::new(x, y) };
+        // inside, holding the polygon, crossed by an edge, in the notch
+        assert!(polygon_intersects_rect(&l, &rect(0.4, 0.4)));
+        assert!(polygon_intersects_rect(&l, &rect(0.0, 0.0).expand(5.0)));
+        assert!(polygon_intersects_rect(&l, &rect(1.9, 0.5)));
+        assert!(!polygon_intersects_rect(&l, &rect(1.5, 1.5)));
+        assert!(Polygon::<6>::from_points(&l).intersects_rect(&rect(-0.1, 1.9)));
+    }
+
+    #[test]
+    fn rect_helpers_agree() {
+        let a = Rect {
+            max: Vector2::new(2.0, 1.0),
+            min: Vector2::new(-1.0, 0.0),
+        };
+        let b = a.translate(Vector2::new(2.0, 2.0));
+        assert_eq!(b.center(), Vector2::new(2.5, 2.5));
+        assert_eq!(a.union(&b), Rect { max: b.max, min: a.min });
+        assert!(a.intersect(&b).is_none());
+
+        assert_eq!(a.area(), 3.0);
+        assert_eq!(a.expand(0.5).area(), 4.0 * 2.0);
+        assert!(a.contains_point(a.max) && !a.contains_point(b.max));
+        assert!(a.corners().iter().all(|&corner| a.contains_point(corner)));
+
+        let flipped = a.scale(Scale2::new(-1.0, 2.0));
+        assert_eq!(flipped, Rect { max: Vector2::new(1.0, 2.0), min: Vector2::new(-2.0, 0.0) });
+        let mut quarter = Affine2::IDENTITY;
+        quarter.rotate(std::f32::consts::FRAC_PI_2);
+        let turned = a.transform(&quarter);
+        assert!((turned.width() - a.height()).abs() < 1e-6 && (turned.height() - a.width()).abs() < 1e-6);
+    }
+}
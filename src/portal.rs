@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use wgpu::*;
+
+use crate::binding::{self, BindGroupLayoutBuilder, BindingLayout};
+use crate::buffer::GpuBuffer;
+use crate::layout::{self, PadVec3};
+use crate::math::{Affine3, Plane, Vector3};
+use crate::pipeline_cache::{DepthKey, PipelineCache, PipelineKey};
+
+/// most levels of portals seen through portals, each one a screen sized texture
+pub const MAX_DEPTH: usize = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PortalRaw {
+    center: PadVec3,
+    right: PadVec3,
+    up: PadVec3,
+}
+layout::assert_uniform_size!(PortalRaw, 48);
+
+/// a rectangle in the world, seen from the side `normal` points to
+#[derive(Clone, Copy, Debug)]
+pub struct PortalFrame {
+    pub center: Vector3,
+    /// unit
+    pub normal: Vector3,
+    /// half extents along the frame's plane, perpendicular to `normal` and each other.
+    /// `right`, `up` and `normal` have the same handedness in both frames of a portal
+    pub right: Vector3,
+    pub up: Vector3,
+}
+
+impl PortalFrame {
+    /// from a space with x along `right`, y along `up` and z along `normal`, all unit,
+    /// and the origin at the center
+    fn to_world(self) -> Affine3 {
+        let x = self.right.normalized();
+        let y = self.up.normalized();
+        let z = self.normal;
+        Affine3 {
+            xx: x.x,
+            yx: y.x,
+            zx: z.x,
+            _x: self.center.x,
+
+            xy: x.y,
+            yy: y.y,
+            zy: z.y,
+            _y: self.center.y,
+
+            xz: x.z,
+            yz: y.z,
+            zz: z.z,
+            _z: self.center.z,
+        }
+    }
+
+    pub fn plane(&self) -> Plane {
+        Plane::from_point_normal(self.center, self.normal)
+    }
+
+    pub fn is_facing(&self, eye: Vector3) -> bool {
+        self.plane().signed_distance(eye) > 0.0
+    }
+}
+
+/// a one way portal: looking into the entrance shows what's in front of the exit, as seen by
+/// the camera carried over by `transform`. each level is the scene rendered from the camera
+/// carried over once more into its own screen sized texture, drawn on the entrance of the
+/// level before it by pixel position, since every level shares the camera's projection.
+/// levels are rendered deepest first, the deepest one shows no portal.
+pub struct Portal {
+    pub entrance: PortalFrame,
+    pub exit: PortalFrame,
+    /// levels rendered at most, up to `MAX_DEPTH`, 0 disables the portal
+    pub max_depth: usize,
+    binding: BindingLayout,
+    pipeline: Arc<RenderPipeline>,
+    params_buffer: GpuBuffer<PortalRaw>,
+}
+
+impl Portal {
+    /// the bind groups of `draw` take a camera ring slot, in the layout of the light pass
+    pub fn new(
+        device: &Device,
+        cache: &mut PipelineCache,
+        color_format: TextureFormat,
+        depth_format: TextureFormat,
+        entrance: PortalFrame,
+        exit: PortalFrame,
+    ) -> Self {
+        let source = include_str!("portal.wgsl");
+        let layout = BindGroupLayoutBuilder::new("portal bind group layout")
+            .uniform_dynamic(ShaderStages::VERTEX | ShaderStages::FRAGMENT) // camera
+            .uniform(ShaderStages::VERTEX) // portal
+            .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: false }); // next level
+        layout.validate(0, &binding::reflect("portal.wgsl", source)).unwrap();
+        let binding = layout.build(device);
+
+        cache.register(device, "portal.wgsl", source, &[&binding.layout], &[]);
+        let pipeline = cache.get(device, PipelineKey {
+            topology: PrimitiveTopology::TriangleStrip,
+            depth: Some(DepthKey {
+                format: depth_format,
+                write: true,
+                compare: CompareFunction::GreaterEqual,
+                stencil: None,
+            }),
+            ..PipelineKey::new("portal.wgsl", Some(color_format))
+        });
+
+        Self {
+            entrance,
+            exit,
+            max_depth: 2,
+            binding,
+            pipeline,
+            params_buffer: GpuBuffer::new_uniform(device, "portal params buffer"),
+        }
+    }
+
+    /// carries what's in front of the entrance to behind the exit, turned around
+    pub fn transform(&self) -> Affine3 {
+        carry(&self.entrance, &self.exit)
+    }
+
+    /// plane as in the camera buffer, positive in front of the exit, so the levels drop
+    /// what's between their carried over camera and the exit
+    pub fn clip_plane(&self) -> [f32; 4] {
+        self.exit.plane().to_array()
+    }
+
+    /// views of the levels, world to view like `view`, from the first one seen through the
+    /// entrance in `view` for as long as the entrance faces the previous level's camera
+    pub fn level_views(&self, view: &Affine3) -> Vec<Affine3> {
+        let inverse = self.transform().inverse();
+        let mut views = Vec::new();
+        let mut view = *view;
+        while views.len() < self.max_depth.min(MAX_DEPTH) {
+            let eye = Vector3::IDENTITY.apply(&view.inverse());
+            if !self.entrance.is_facing(eye) {
+                break;
+            }
+            view = inverse.compose(&view);
+            views.push(view);
+        }
+        views
+    }
+
+    pub fn upload(&mut self, queue: &Queue) {
+        self.params_buffer.write(queue, &[PortalRaw {
+            center: self.entrance.center.into(),
+            right: self.entrance.right.into(),
+            up: self.entrance.up.into(),
+        }]);
+    }
+
+    /// `cameras` is the camera ring's binding, `level` the texture shown on the entrance
+    pub fn bind_group(&self, device: &Device, cameras: BindingResource, level: &TextureView) -> BindGroup {
+        self.binding.create_bind_group(device, "portal bind group", &[
+            cameras,
+            self.params_buffer.as_entire_binding(),
+            BindingResource::TextureView(level),
+        ])
+    }
+
+    /// the entrance, over the scene of the view whose camera is at `camera` in the ring
+    pub fn draw<'a>(&'a self, pass: &mut RenderPass<'a>, bind_group: &'a BindGroup, camera: u32) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[camera]);
+        pass.draw(0..4, 0..1);
+    }
+}
+
+fn carry(entrance: &PortalFrame, exit: &PortalFrame) -> Affine3 {
+    let turned = PortalFrame {
+        normal: -exit.normal,
+        right: -exit.right,
+        ..*exit
+    };
+    entrance.to_world().inverse().compose(&turned.to_world())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carry_turns_the_entrance_into_the_exit() {
+        let entrance = PortalFrame {
+            center: Vector3::new(0.0, 0.0, 10.0),
+            normal: Vector3::new(0.0, 0.0, -1.0),
+            right: Vector3::new(1.0, 0.0, 0.0),
+            up: Vector3::new(0.0, 2.0, 0.0),
+        };
+        // facing along +x
+        let exit = PortalFrame {
+            center: Vector3::new(5.0, 1.0, 0.0),
+            normal: Vector3::new(1.0, 0.0, 0.0),
+            right: Vector3::new(0.0, 0.0, 1.0),
+            up: Vector3::new(0.0, 2.0, 0.0),
+        };
+        let transform = carry(&entrance, &exit);
+        let close = |a: Vector3, b: Vector3| (a - b).norm_sqr() < 1e-8;
+
+        assert!(close(entrance.center.apply(&transform), exit.center));
+        // 3 in front of the entrance ends up 3 behind the exit
+        let eye = Vector3::new(0.0, 0.0, 7.0);
+        assert!(close(eye.apply(&transform), Vector3::new(2.0, 1.0, 0.0)));
+        // a point 3 along the entrance's right is 3 along the exit's left
+        assert!(close(Vector3::new(3.0, 0.0, 10.0).apply(&transform), Vector3::new(5.0, 1.0, -3.0)));
+    }
+}
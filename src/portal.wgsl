@@ -0,0 +1,53 @@
+struct Camera {
+    view_0: vec4<f32>,
+    view_1: vec4<f32>,
+    view_2: vec4<f32>,
+    near_z: f32,
+    clip_plane: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// portal::PortalRaw, the entrance
+struct Portal {
+    center: vec3<f32>,
+    right: vec3<f32>,
+    up: vec3<f32>,
+};
+@group(0) @binding(1)
+var<uniform> portal: Portal;
+// the next level, rendered with the same projection and size as this view
+@group(0) @binding(2)
+var level: texture_2d<f32>;
+
+struct VertexOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+}
+
+// the entrance as a 4 vertex strip
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOut {
+    let corner = vec2<f32>(f32(vertex_index & 1u), f32(vertex_index >> 1u)) * 2.0 - 1.0;
+    let position = portal.center + portal.right * corner.x + portal.up * corner.y;
+    let view = vec3<f32>(
+        dot(camera.view_0.xyz, position) + camera.view_0.w,
+        dot(camera.view_1.xyz, position) + camera.view_1.w,
+        dot(camera.view_2.xyz, position) + camera.view_2.w,
+    );
+
+    var out: VertexOut;
+    // infinite reversed z, as in light.wgsl
+    out.clip_position = vec4<f32>(view.xy, camera.near_z, view.z);
+    out.world_position = position;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    // levels are clipped at the exit like the rest of their scene
+    if dot(camera.clip_plane, vec4<f32>(in.world_position, 1.0)) < 0.0 {
+        discard;
+    }
+    return textureLoad(level, vec2<i32>(in.clip_position.xy), 0);
+}
@@ -0,0 +1,554 @@
+use std::any::Any;
+
+use wgpu::*;
+
+use crate::binding::{self, BindGroupLayoutBuilder, BindingLayout};
+use crate::buffer::GpuBuffer;
+
+/// format the scene is rendered in before post processing
+pub const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// a texture rendered to by one pass and sampled by the next
+pub struct RenderTarget {
+    #[allow(dead_code)]
+    pub texture: Texture,
+    pub view: TextureView,
+    #[allow(dead_code)]
+    pub format: TextureFormat,
+}
+
+impl RenderTarget {
+    pub fn new(device: &Device, label: &str, format: TextureFormat, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            format,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            view,
+            format,
+        }
+    }
+}
+
+/// layout of every fullscreen pass: the input texture and its sampler, more bindings can be chained
+pub fn input_layout(label: &'static str) -> BindGroupLayoutBuilder {
+    BindGroupLayoutBuilder::new(label)
+        .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: true })
+        .sampler(ShaderStages::FRAGMENT, SamplerBindingType::Filtering)
+}
+
+pub fn create_sampler(device: &Device) -> Sampler {
+    device.create_sampler(&SamplerDescriptor {
+        label: Some("post process sampler"),
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..Default::default()
+    })
+}
+
+/// `fragment` appended to `fullscreen.wgsl`, as drawn by `FullscreenPass`
+pub fn fullscreen_source(fragment: &str) -> String {
+    include_str!("fullscreen.wgsl").to_string() + fragment
+}
+
+/// `fragment` after the srgb, dither and tonemap functions it may use, as in the final resolve
+pub fn resolved(fragment: &str) -> String {
+    [include_str!("srgb.wgsl"), include_str!("dither.wgsl"), include_str!("tonemap.wgsl"), fragment].concat()
+}
+
+/// draws a single triangle covering `target`, with `fragment` appended to `fullscreen.wgsl`
+/// and `entry_point` as its fragment shader
+pub struct FullscreenPass {
+    pipeline: RenderPipeline,
+    pub binding: BindingLayout,
+}
+
+impl FullscreenPass {
+    pub fn new(
+        device: &Device,
+        label: &'static str,
+        fragment: &str,
+        entry_point: &str,
+        format: TextureFormat,
+        layout: BindGroupLayoutBuilder,
+    ) -> Self {
+        let source = fullscreen_source(fragment);
+        layout.validate(0, &binding::reflect(label, &source)).unwrap();
+        let binding = layout.build(device);
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(label),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[&binding.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point,
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            binding,
+        }
+    }
+
+    pub fn draw(&self, encoder: &mut CommandEncoder, label: &str, target: &TextureView, bind_group: &BindGroup) {
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AntiAliasing {
+    Off,
+    /// not available yet: the object-id target can't be resolved
+    Msaa,
+    Fxaa,
+    /// not available yet: needs a history target to accumulate into
+    Taa,
+}
+
+impl AntiAliasing {
+    const ALL: [AntiAliasing; 4] = [Self::Off, Self::Msaa, Self::Fxaa, Self::Taa];
+
+    pub fn is_supported(self) -> bool {
+        matches!(self, Self::Off | Self::Fxaa)
+    }
+
+    /// the next supported mode, wrapping around
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&m| m == self).unwrap();
+        (1..=Self::ALL.len())
+            .map(|offset| Self::ALL[(i + offset) % Self::ALL.len()])
+            .find(|m| m.is_supported())
+            .unwrap()
+    }
+}
+
+/// an image the chain resolves for a node, in the order of `PostNode::inputs`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PostInput {
+    /// output of the previous enabled node, the scene color for the first one
+    Previous,
+    #[allow(dead_code)]
+    SceneColor,
+    SceneDepth,
+    /// `picking::ID_FORMAT`, only loadable
+    ObjectId,
+    /// `motion_blur::MOTION_FORMAT`, only loadable
+    ObjectMotion,
+}
+
+/// an effect in a `PostChain`, rendering into the view it is given in `run`
+pub trait PostNode: Any + Send {
+    fn name(&self) -> &'static str;
+
+    fn inputs(&self) -> &'static [PostInput];
+
+    /// recreate size dependent resources, `bind` is called afterwards
+    fn resize(&mut self, _device: &Device, _width: u32, _height: u32) {}
+
+    /// recreate bind groups on the resolved `inputs`,
+    /// called whenever the chain is resized or its enabled nodes change
+    fn bind(&mut self, device: &Device, sampler: &Sampler, inputs: &[&TextureView]);
+
+    fn run(&mut self, queue: &Queue, encoder: &mut CommandEncoder, output: &TextureView);
+}
+
+struct PostEffect {
+    node: Box<dyn PostNode>,
+    enabled: bool,
+}
+
+/// runs the enabled effects in order over the scene targets, ping-ponging between
+/// two intermediate targets, then resolves the result into the surface with `resolve`
+pub struct PostChain {
+    sampler: Sampler,
+    intermediates: [RenderTarget; 2],
+    effects: Vec<PostEffect>,
+    pub resolve: Resolve,
+    /// set when bind groups are stale
+    dirty: bool,
+}
+
+impl PostChain {
+    pub fn new(device: &Device, output_format: TextureFormat, width: u32, height: u32) -> Self {
+        Self {
+            sampler: create_sampler(device),
+            intermediates: create_intermediates(device, width, height),
+            effects: Vec::new(),
+            resolve: Resolve::new(device, output_format),
+            dirty: true,
+        }
+    }
+
+    /// appends an effect, taking `HDR_FORMAT` in and out
+    pub fn push(&mut self, node: impl PostNode, enabled: bool) {
+        self.effects.push(PostEffect {
+            node: Box::new(node),
+            enabled,
+        });
+        self.dirty = true;
+    }
+
+    pub fn get_mut<T: PostNode>(&mut self) -> Option<&mut T> {
+        self.effects
+            .iter_mut()
+            .find_map(|effect| (effect.node.as_mut() as &mut dyn Any).downcast_mut::<T>())
+    }
+
+    pub fn is_enabled<T: PostNode>(&self) -> bool {
+        self.effects
+            .iter()
+            .any(|effect| effect.enabled && (effect.node.as_ref() as &dyn Any).is::<T>())
+    }
+
+    pub fn set_enabled<T: PostNode>(&mut self, enabled: bool) {
+        for effect in &mut self.effects {
+            if (effect.node.as_ref() as &dyn Any).is::<T>() && effect.enabled != enabled {
+                effect.enabled = enabled;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// returns whether it is now enabled
+    pub fn toggle<T: PostNode>(&mut self) -> bool {
+        let enabled = !self.is_enabled::<T>();
+        self.set_enabled::<T>(enabled);
+        enabled
+    }
+
+    /// moves the effect at `from` so it runs at position `to`
+    pub fn move_effect(&mut self, from: usize, to: usize) {
+        let effect = self.effects.remove(from);
+        self.effects.insert(to, effect);
+        self.dirty = true;
+    }
+
+    /// names of the effects in order, with whether they are enabled
+    pub fn effects(&self) -> impl Iterator<Item = (&'static str, bool)> + '_ {
+        self.effects.iter().map(|effect| (effect.node.name(), effect.enabled))
+    }
+
+    /// the scene targets passed to `run` changed
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        self.intermediates = create_intermediates(device, width, height);
+        for effect in &mut self.effects {
+            effect.node.resize(device, width, height);
+        }
+        self.dirty = true;
+    }
+
+    fn bind(
+        &mut self,
+        device: &Device,
+        scene_color: &TextureView,
+        scene_depth: &TextureView,
+        object_id: &TextureView,
+        object_motion: &TextureView,
+    ) {
+        let mut previous = scene_color;
+        let mut next = 0;
+        let nodes = self.effects
+            .iter_mut()
+            .filter(|effect| effect.enabled)
+            .map(|effect| effect.node.as_mut() as &mut dyn PostNode)
+            .chain([&mut self.resolve as &mut dyn PostNode]);
+        for node in nodes {
+            let inputs: Vec<_> = node.inputs()
+                .iter()
+                .map(|input| match input {
+                    PostInput::Previous => previous,
+                    PostInput::SceneColor => scene_color,
+                    PostInput::SceneDepth => scene_depth,
+                    PostInput::ObjectId => object_id,
+                    PostInput::ObjectMotion => object_motion,
+                })
+                .collect();
+            node.bind(device, &self.sampler, &inputs);
+
+            previous = &self.intermediates[next].view;
+            next = 1 - next;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn run(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        scene_color: &TextureView,
+        scene_depth: &TextureView,
+        object_id: &TextureView,
+        object_motion: &TextureView,
+        output: &TextureView,
+    ) {
+        if self.dirty {
+            self.bind(device, scene_color, scene_depth, object_id, object_motion);
+            self.dirty = false;
+        }
+
+        let mut next = 0;
+        for effect in self.effects.iter_mut().filter(|effect| effect.enabled) {
+            effect.node.run(queue, encoder, &self.intermediates[next].view);
+            next = 1 - next;
+        }
+        self.resolve.run(queue, encoder, output);
+    }
+}
+
+fn create_intermediates(device: &Device, width: u32, height: u32) -> [RenderTarget; 2] {
+    [
+        RenderTarget::new(device, "post intermediate 0", HDR_FORMAT, width, height),
+        RenderTarget::new(device, "post intermediate 1", HDR_FORMAT, width, height),
+    ]
+}
+
+/// the curve the scene's linear color is mapped into the output's 0 to 1 by,
+/// ahead of the anti aliasing so it blends the colors as they're displayed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tonemap {
+    /// clamps, the highlights clip
+    Off = 0,
+    Reinhard = 1,
+    /// a fit of the aces filmic curve, with a toe and a softer shoulder
+    Aces = 2,
+}
+
+impl Tonemap {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(Tonemap::Off),
+            "reinhard" => Some(Tonemap::Reinhard),
+            "aces" => Some(Tonemap::Aces),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dither {
+    Off = 0,
+    /// 8 by 8 bayer matrix, a regular cross hatch
+    Ordered = 1,
+    /// a tile of `noise::blue_noise`, fine grain without a pattern
+    BlueNoise = 2,
+}
+
+impl Dither {
+    const ALL: [Dither; 3] = [Self::Off, Self::Ordered, Self::BlueNoise];
+
+    /// wraps around
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&d| d == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ResolveParams {
+    tonemap: u32,
+    exposure: f32,
+    dither: u32,
+    dither_strength: f32,
+    srgb_output: u32,
+    _padding: [u32; 3],
+}
+crate::layout::assert_uniform_size!(ResolveParams, 32);
+
+/// side of the blue noise tile
+const BLUE_NOISE_SIZE: u32 = 32;
+
+/// last node of every chain, tonemaps into the surface with the selected anti aliasing,
+/// dithered to hide the banding of dark gradients in 8 bit outputs
+pub struct Resolve {
+    pub tonemap: Tonemap,
+    /// scales the scene color before it's tonemapped
+    pub exposure: f32,
+    pub anti_aliasing: AntiAliasing,
+    pub dither: Dither,
+    /// amplitude of the dither in steps of the 8 bit output, 1 spans exactly one step
+    pub dither_strength: f32,
+    srgb_output: bool,
+    params_buffer: GpuBuffer<ResolveParams>,
+    blue_noise: Texture,
+    blue_noise_view: TextureView,
+    /// written on the first run, which has the queue
+    blue_noise_written: bool,
+    copy: FullscreenPass,
+    fxaa: FullscreenPass,
+    copy_bind_group: Option<BindGroup>,
+    fxaa_bind_group: Option<BindGroup>,
+}
+
+impl Resolve {
+    fn new(device: &Device, output_format: TextureFormat) -> Self {
+        let layout = |label| input_layout(label)
+            .uniform(ShaderStages::FRAGMENT)
+            .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: false });
+        let blue_noise = device.create_texture(&TextureDescriptor {
+            label: Some("blue noise"),
+            size: Extent3d {
+                width: BLUE_NOISE_SIZE,
+                height: BLUE_NOISE_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        Self {
+            tonemap: Tonemap::Aces,
+            exposure: 1.0,
+            anti_aliasing: AntiAliasing::Off,
+            dither: Dither::BlueNoise,
+            dither_strength: 1.0,
+            srgb_output: output_format.is_srgb(),
+            params_buffer: GpuBuffer::new_uniform(device, "resolve params buffer"),
+            blue_noise_view: blue_noise.create_view(&TextureViewDescriptor::default()),
+            blue_noise,
+            blue_noise_written: false,
+            copy: FullscreenPass::new(
+                device,
+                "dither.wgsl",
+                &resolved(""),
+                "fs_copy",
+                output_format,
+                layout("copy bind group layout"),
+            ),
+            fxaa: FullscreenPass::new(
+                device,
+                "fxaa.wgsl",
+                &resolved(include_str!("fxaa.wgsl")),
+                "fs_main",
+                output_format,
+                layout("fxaa bind group layout"),
+            ),
+            copy_bind_group: None,
+            fxaa_bind_group: None,
+        }
+    }
+}
+
+impl PostNode for Resolve {
+    fn name(&self) -> &'static str {
+        "resolve"
+    }
+
+    fn inputs(&self) -> &'static [PostInput] {
+        &[PostInput::Previous]
+    }
+
+    fn bind(&mut self, device: &Device, sampler: &Sampler, inputs: &[&TextureView]) {
+        let bind_group = |pass: &FullscreenPass| pass.binding.create_bind_group(device, "resolve bind group", &[
+            BindingResource::TextureView(inputs[0]),
+            BindingResource::Sampler(sampler),
+            self.params_buffer.as_entire_binding(),
+            BindingResource::TextureView(&self.blue_noise_view),
+        ]);
+        self.copy_bind_group = Some(bind_group(&self.copy));
+        self.fxaa_bind_group = Some(bind_group(&self.fxaa));
+    }
+
+    fn run(&mut self, queue: &Queue, encoder: &mut CommandEncoder, output: &TextureView) {
+        if !self.blue_noise_written {
+            let noise = crate::math::noise::blue_noise(BLUE_NOISE_SIZE);
+            queue.write_texture(
+                ImageCopyTexture {
+                    texture: &self.blue_noise,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                bytemuck::cast_slice(&noise),
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * BLUE_NOISE_SIZE),
+                    rows_per_image: None,
+                },
+                self.blue_noise.size(),
+            );
+            self.blue_noise_written = true;
+        }
+
+        self.params_buffer.write(queue, &[ResolveParams {
+            tonemap: self.tonemap as u32,
+            exposure: self.exposure,
+            dither: self.dither as u32,
+            dither_strength: self.dither_strength,
+            srgb_output: self.srgb_output as u32,
+            _padding: [0; 3],
+        }]);
+        match self.anti_aliasing {
+            AntiAliasing::Fxaa => self.fxaa.draw(encoder, "fxaa pass", output, self.fxaa_bind_group.as_ref().unwrap()),
+            _ => self.copy.draw(encoder, "copy pass", output, self.copy_bind_group.as_ref().unwrap()),
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn create_input_bind_group(device: &Device, pass: &FullscreenPass, input: &TextureView, sampler: &Sampler) -> BindGroup {
+    pass.binding.create_bind_group(device, "post input bind group", &[
+        BindingResource::TextureView(input),
+        BindingResource::Sampler(sampler),
+    ])
+}
@@ -0,0 +1,248 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use wgpu::*;
+
+/// names defined for a shader, ordered so equal sets hash the same.
+/// a name with a value is replaced by it wherever it appears as a whole identifier
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Defines(BTreeMap<String, String>);
+
+impl Defines {
+    pub fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// defines `name` without a value, for `#ifdef` switches
+    pub fn with(mut self, name: &str) -> Self {
+        self.0.insert(name.to_string(), String::new());
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_value(mut self, name: &str, value: impl ToString) -> Self {
+        self.0.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// defines `name` only if `enabled`
+    pub fn with_if(self, name: &str, enabled: bool) -> Self {
+        if enabled { self.with(name) } else { self }
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+}
+
+bitflags::bitflags! {
+    /// features a shader is specialized for, each defining the flag's name when set,
+    /// so that toggling one swaps to another variant rather than branching in the shader
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+    pub struct ShaderVariant: u32 {
+        /// looks up the shadow map, otherwise everything the light reaches is lit
+        const SHADOWS = 1 << 0;
+        /// the soft shadow filters, otherwise every filter is hard
+        const PCF = 1 << 1;
+        /// looks up the traced shadow mask instead of the shadow map, in the view it was traced from
+        const TRACED_SHADOWS = 1 << 2;
+    }
+}
+
+impl ShaderVariant {
+    pub fn defines(self) -> Defines {
+        self.iter_names().fold(Defines::new(), |defines, (name, _)| defines.with(name))
+    }
+}
+
+/// expands the directives of wgsl sources, each on its own line:
+/// `#include "name"` pastes another added source, once per shader however often it's included,
+/// `#define NAME value` defines for the rest of the shader,
+/// `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` keep or drop the lines between them
+pub struct Preprocessor {
+    sources: HashMap<&'static str, String>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self {
+            sources: HashMap::new(),
+        }
+    }
+
+    /// makes `source` available as `name` to `process` and `#include`
+    pub fn add(&mut self, name: &'static str, source: impl Into<String>) -> &mut Self {
+        self.sources.insert(name, source.into());
+        self
+    }
+
+    /// the source of `name` with its directives expanded, or the first error as `file:line: message`
+    pub fn process(&self, name: &str, defines: &Defines) -> Result<String, String> {
+        let mut defines = defines.clone();
+        let mut included = HashSet::new();
+        let mut out = String::new();
+        self.expand(name, &mut defines, &mut included, &mut out)?;
+        Ok(out)
+    }
+
+    fn expand<'a>(
+        &'a self,
+        name: &str,
+        defines: &mut Defines,
+        included: &mut HashSet<&'a str>,
+        out: &mut String,
+    ) -> Result<(), String> {
+        let (&name, source) = self.sources
+            .get_key_value(name)
+            .ok_or_else(|| format!("no shader source `{}`", name))?;
+        if !included.insert(name) {
+            return Ok(());
+        }
+
+        // whether the lines of each enclosing `#ifdef` are kept, and whether its `#else` was seen
+        let mut conditions: Vec<(bool, bool)> = vec![];
+        let active = |conditions: &[(bool, bool)]| conditions.iter().all(|&(keep, _)| keep);
+
+        for (i, line) in source.lines().enumerate() {
+            let error = |message: String| format!("{}:{}: {}", name, i + 1, message);
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active(&conditions) {
+                    substitute(line, defines, out);
+                    out.push('\n');
+                }
+                continue;
+            };
+
+            let mut words = directive.split_whitespace();
+            let keyword = words.next().unwrap_or("");
+            let argument = words.next();
+            match (keyword, argument) {
+                ("ifdef", Some(define)) => conditions.push((defines.is_defined(define), false)),
+                ("ifndef", Some(define)) => conditions.push((!defines.is_defined(define), false)),
+                ("else", None) => match conditions.last_mut() {
+                    Some((keep, seen_else @ false)) => {
+                        *keep = !*keep;
+                        *seen_else = true;
+                    }
+                    _ => return Err(error("`#else` without `#ifdef`".to_string())),
+                },
+                ("endif", None) => {
+                    conditions.pop().ok_or_else(|| error("`#endif` without `#ifdef`".to_string()))?;
+                }
+                _ if !active(&conditions) => {}
+                ("include", Some(file)) => {
+                    let file = file
+                        .strip_prefix('"')
+                        .and_then(|file| file.strip_suffix('"'))
+                        .ok_or_else(|| error(format!("expected a quoted file name, not `{}`", file)))?;
+                    self.expand(file, defines, included, out).map_err(error)?;
+                }
+                ("define", Some(define)) => {
+                    let value = words.collect::<Vec<_>>().join(" ");
+                    defines.0.insert(define.to_string(), value);
+                }
+                _ => return Err(error(format!("unknown directive `#{}`", directive.trim()))),
+            }
+        }
+
+        if conditions.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{}: `#ifdef` without `#endif`", name))
+        }
+    }
+}
+
+/// pushes `line` to `out` with defined identifiers replaced by their values
+fn substitute(line: &str, defines: &Defines, out: &mut String) {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut rest = line;
+    while let Some(start) = rest.find(is_identifier) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_identifier(c)).unwrap_or(rest.len());
+        let word = &rest[..end];
+        match defines.0.get(word) {
+            Some(value) if !value.is_empty() => out.push_str(value),
+            _ => out.push_str(word),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+}
+
+/// shader modules of preprocessed sources, created once for each name and set of defines
+pub struct ShaderCache {
+    preprocessor: Preprocessor,
+    modules: HashMap<(&'static str, Defines), Arc<ShaderModule>>,
+}
+
+impl ShaderCache {
+    pub fn new(preprocessor: Preprocessor) -> Self {
+        Self {
+            preprocessor,
+            modules: HashMap::new(),
+        }
+    }
+
+    /// the expanded source of `name`, panicking on preprocessor errors
+    pub fn source(&self, name: &str, defines: &Defines) -> String {
+        self.preprocessor.process(name, defines).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn get(&mut self, device: &Device, name: &'static str, defines: &Defines) -> Arc<ShaderModule> {
+        if let Some(module) = self.modules.get(&(name, defines.clone())) {
+            return module.clone();
+        }
+
+        let module = Arc::new(device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(name),
+            source: ShaderSource::Wgsl(self.source(name, defines).into()),
+        }));
+        self.modules.insert((name, defines.clone()), module.clone());
+        module
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directives_are_expanded() {
+        let mut preprocessor = Preprocessor::new();
+        preprocessor
+            .add("common.wgsl", "const ONE: f32 = 1.0;")
+            .add("a.wgsl", "#include \"common.wgsl\"\n#include \"common.wgsl\"\n#define SIZE 4u\n\
+                #ifdef FAST\nlet x = SIZE;\n#else\nlet x = SIZE * LEVEL;\n#endif");
+
+        let fast = preprocessor.process("a.wgsl", &Defines::new().with("FAST")).unwrap();
+        assert_eq!(fast, "const ONE: f32 = 1.0;\nlet x = 4u;\n");
+        let slow = preprocessor.process("a.wgsl", &Defines::new().with_value("LEVEL", 2)).unwrap();
+        assert_eq!(slow, "const ONE: f32 = 1.0;\nlet x = 4u * 2;\n");
+
+        preprocessor
+            .add("unclosed.wgsl", "#ifdef FAST")
+            .add("missing.wgsl", "\n#include \"nowhere.wgsl\"");
+        assert!(preprocessor.process("unclosed.wgsl", &Defines::new()).is_err());
+        let error = preprocessor.process("missing.wgsl", &Defines::new()).unwrap_err();
+        assert!(error.starts_with("missing.wgsl:2: "), "{}", error);
+    }
+
+    #[test]
+    fn variants_define_their_flags() {
+        let defines = (ShaderVariant::SHADOWS | ShaderVariant::PCF).defines();
+        assert_eq!(defines, Defines::new().with("PCF").with("SHADOWS"));
+        assert_eq!(ShaderVariant::empty().defines(), Defines::new());
+    }
+}
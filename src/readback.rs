@@ -0,0 +1,155 @@
+use std::{marker::PhantomData, mem::size_of, sync::{Arc, Mutex}};
+
+use bytemuck::Pod;
+use wgpu::*;
+
+/// copies gpu data into a map-readable staging buffer and reads it back without blocking:
+/// record the copy with `buffer` or `texture`, submit the encoder, call `map` with the submission,
+/// then call `try_read` once per frame until it returns the data.
+pub struct Readback<T: Pod> {
+    staging: Buffer,
+    len: usize,
+    /// (padded, unpadded) bytes per row for texture copies
+    rows: Option<(usize, usize)>,
+    mapped: Arc<Mutex<Option<Result<(), BufferAsyncError>>>>,
+    /// the submission holding the copy, once mapped
+    submission: Option<SubmissionIndex>,
+    state: ReadbackState,
+    _marker: PhantomData<T>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReadbackState {
+    Recorded,
+    Mapping,
+    Read,
+}
+
+impl<T: Pod> Readback<T> {
+    /// records a copy of `len` elements of `source` starting at byte `offset`
+    pub fn buffer(
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        source: &Buffer,
+        offset: BufferAddress,
+        len: usize,
+    ) -> Self {
+        let size = (len * size_of::<T>()) as BufferAddress;
+        let staging = create_staging(device, size);
+        encoder.copy_buffer_to_buffer(source, offset, &staging, 0, size);
+        Self::new(staging, len, None)
+    }
+
+    /// records a copy of a `width` x `height` region of mip 0 of `texture`,
+    /// where `T` is one texel of the copied `aspect`
+    pub fn texture(
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        texture: &Texture,
+        aspect: TextureAspect,
+        origin: Origin3d,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let unpadded = width as usize * size_of::<T>();
+        let align = COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+        let padded = unpadded.div_ceil(align) * align;
+
+        let staging = create_staging(device, (padded * height as usize) as BufferAddress);
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin,
+                aspect,
+            },
+            ImageCopyBuffer {
+                buffer: &staging,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded as u32),
+                    rows_per_image: Some(height),
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        Self::new(staging, (width * height) as usize, Some((padded, unpadded)))
+    }
+
+    fn new(staging: Buffer, len: usize, rows: Option<(usize, usize)>) -> Self {
+        Self {
+            staging,
+            len,
+            rows,
+            mapped: Arc::new(Mutex::new(None)),
+            submission: None,
+            state: ReadbackState::Recorded,
+            _marker: PhantomData,
+        }
+    }
+
+    /// starts mapping the staging buffer once `submission`, holding the copy, is done
+    pub fn map(&mut self, submission: SubmissionIndex) {
+        assert!(self.state == ReadbackState::Recorded, "readback already mapped");
+        self.state = ReadbackState::Mapping;
+        self.submission = Some(submission);
+
+        let mapped = self.mapped.clone();
+        self.staging.slice(..).map_async(MapMode::Read, move |result| {
+            *mapped.lock().unwrap() = Some(result);
+        });
+    }
+
+    /// polls the device without waiting, and once the map completed hands `f` the data.
+    /// returns Some exactly once.
+    pub fn try_read<R>(&mut self, device: &Device, f: impl FnOnce(&[T]) -> R) -> Option<R> {
+        if self.state != ReadbackState::Mapping {
+            return None;
+        }
+
+        device.poll(Maintain::Poll);
+        match self.mapped.lock().unwrap().take() {
+            None => return None,
+            Some(result) => result.expect("readback staging buffer failed to map"),
+        }
+        self.state = ReadbackState::Read;
+
+        let result = {
+            let view = self.staging.slice(..).get_mapped_range();
+            match self.rows {
+                Some((padded, unpadded)) if padded != unpadded => {
+                    let mut data = Vec::with_capacity(self.len);
+                    for row in view.chunks(padded) {
+                        data.extend_from_slice(bytemuck::cast_slice::<u8, T>(&row[..unpadded]));
+                    }
+                    f(&data)
+                }
+                _ => f(&bytemuck::cast_slice(&view)[..self.len]),
+            }
+        };
+        self.staging.unmap();
+        Some(result)
+    }
+
+    /// blocks until the submission of the copy is done and the data is available,
+    /// for tools and tests rather than the frame loop
+    #[allow(dead_code)]
+    pub fn read_blocking<R>(&mut self, device: &Device, f: impl FnOnce(&[T]) -> R) -> R {
+        let submission = self.submission.clone().expect("readback wasn't mapped");
+        device.poll(Maintain::WaitForSubmissionIndex(submission));
+        self.try_read(device, f).expect("readback was already read")
+    }
+}
+
+fn create_staging(device: &Device, size: BufferAddress) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("Readback staging buffer"),
+        size,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
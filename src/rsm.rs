@@ -0,0 +1,192 @@
+use wgpu::*;
+
+use crate::buffer::GpuBuffer;
+use crate::math::Affine3;
+use crate::post::{self, FullscreenPass, PostInput, PostNode, HDR_FORMAT};
+
+/// coarser than the shadow map, the gather blurs it over many texels anyway
+pub const SIZE: u32 = 256;
+pub const FLUX_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+pub const NORMAL_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// reflective shadow map: the surfaces the light sees, like the shadow map, along with the flux
+/// they reflect and their normals. rendered every frame by `shadow.wgsl`'s `fs_rsm` with every
+/// instance, not only the casters, since the static shadow cache only keeps depth
+pub struct ReflectiveShadowMap {
+    flux: Texture,
+    pub flux_view: TextureView,
+    normal: Texture,
+    pub normal_view: TextureView,
+    depth: Texture,
+    pub depth_view: TextureView,
+}
+
+impl ReflectiveShadowMap {
+    pub fn new(device: &Device, depth_format: TextureFormat) -> Self {
+        let create = |label, format| {
+            let texture = device.create_texture(&TextureDescriptor {
+                label: Some(label),
+                size: Extent3d {
+                    width: SIZE,
+                    height: SIZE,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        };
+        let (flux, flux_view) = create("Rsm flux texture", FLUX_FORMAT);
+        let (normal, normal_view) = create("Rsm normal texture", NORMAL_FORMAT);
+        let (depth, depth_view) = create("Rsm depth texture", depth_format);
+        Self {
+            flux,
+            flux_view,
+            normal,
+            normal_view,
+            depth,
+            depth_view,
+        }
+    }
+
+    /// of the pipelines drawing into it
+    pub fn targets() -> [Option<ColorTargetState>; 2] {
+        [FLUX_FORMAT, NORMAL_FORMAT].map(|format| Some(ColorTargetState {
+            format,
+            blend: None,
+            write_mask: ColorWrites::ALL,
+        }))
+    }
+
+    /// clears it for the light's view, reversed z like the shadow map
+    pub fn begin_pass<'a>(&'a self, encoder: &'a mut CommandEncoder) -> RenderPass<'a> {
+        let clear = |view| Some(RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: Operations {
+                load: LoadOp::Clear(Color::TRANSPARENT),
+                store: true,
+            },
+        });
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("rsm pass"),
+            color_attachments: &[clear(&self.flux_view), clear(&self.normal_view)],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(0.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct IndirectParams {
+    inverse_view: Affine3,
+    near_z: f32,
+    light_near_z: f32,
+    samples: u32,
+    radius: f32,
+    light_view: Affine3,
+    inverse_light_view: Affine3,
+    intensity: f32,
+    _padding: [u32; 3],
+}
+crate::layout::assert_uniform_size!(IndirectParams, 176);
+
+/// one bounce of indirect light: gathers the light reflected by the rsm's texels around where
+/// each pixel's surface is in it, adding it on top of the scene as if every surface were white
+pub struct IndirectLight {
+    /// rsm texels gathered per pixel
+    pub samples: u32,
+    /// of the disk they're taken from, in rsm uv
+    pub radius: f32,
+    pub intensity: f32,
+    params: IndirectParams,
+    params_buffer: GpuBuffer<IndirectParams>,
+    pass: FullscreenPass,
+    rsm: [TextureView; 3],
+    bind_group: Option<BindGroup>,
+}
+
+impl IndirectLight {
+    pub fn new(device: &Device, rsm: &ReflectiveShadowMap) -> Self {
+        let pass = FullscreenPass::new(
+            device,
+            "rsm.wgsl",
+            include_str!("rsm.wgsl"),
+            "fs_main",
+            HDR_FORMAT,
+            post::input_layout("indirect light bind group layout")
+                .uniform(ShaderStages::FRAGMENT)
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Depth)
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: false })
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: false })
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Depth),
+        );
+
+        Self {
+            samples: 64,
+            radius: 0.1,
+            intensity: 1.0,
+            params: bytemuck::Zeroable::zeroed(),
+            params_buffer: GpuBuffer::new_uniform(device, "indirect light params buffer"),
+            pass,
+            rsm: [&rsm.flux, &rsm.normal, &rsm.depth].map(|texture| texture.create_view(&TextureViewDescriptor::default())),
+            bind_group: None,
+        }
+    }
+
+    /// `view` as uploaded in the camera buffer
+    pub fn set_view(&mut self, view: &Affine3, near_z: f32) {
+        self.params.inverse_view = view.inverse();
+        self.params.near_z = near_z;
+    }
+
+    /// `view` as uploaded in the light buffer, which the rsm was rendered with
+    pub fn set_light(&mut self, view: &Affine3, near_z: f32) {
+        self.params.light_view = *view;
+        self.params.inverse_light_view = view.inverse();
+        self.params.light_near_z = near_z;
+    }
+}
+
+impl PostNode for IndirectLight {
+    fn name(&self) -> &'static str {
+        "indirect light"
+    }
+
+    fn inputs(&self) -> &'static [PostInput] {
+        &[PostInput::Previous, PostInput::SceneDepth]
+    }
+
+    fn bind(&mut self, device: &Device, sampler: &Sampler, inputs: &[&TextureView]) {
+        let [flux, normal, depth] = &self.rsm;
+        self.bind_group = Some(self.pass.binding.create_bind_group(device, "indirect light bind group", &[
+            BindingResource::TextureView(inputs[0]),
+            BindingResource::Sampler(sampler),
+            self.params_buffer.as_entire_binding(),
+            BindingResource::TextureView(inputs[1]),
+            BindingResource::TextureView(flux),
+            BindingResource::TextureView(normal),
+            BindingResource::TextureView(depth),
+        ]));
+    }
+
+    fn run(&mut self, queue: &Queue, encoder: &mut CommandEncoder, output: &TextureView) {
+        self.params.samples = self.samples.max(1);
+        self.params.radius = self.radius;
+        self.params.intensity = self.intensity;
+        self.params_buffer.write(queue, &[self.params]);
+        self.pass.draw(encoder, "indirect light pass", output, self.bind_group.as_ref().unwrap());
+    }
+}
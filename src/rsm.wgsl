@@ -0,0 +1,124 @@
+// rsm::IndirectParams
+struct IndirectParams {
+    // camera view to world
+    inverse_view_0: vec4<f32>,
+    inverse_view_1: vec4<f32>,
+    inverse_view_2: vec4<f32>,
+    near_z: f32,
+    light_near_z: f32,
+    samples: u32,
+    // of the disk the samples are taken from, in rsm uv
+    radius: f32,
+    // world to light view, as in the light uniform the rsm was rendered with
+    light_view_0: vec4<f32>,
+    light_view_1: vec4<f32>,
+    light_view_2: vec4<f32>,
+    inverse_light_view_0: vec4<f32>,
+    inverse_light_view_1: vec4<f32>,
+    inverse_light_view_2: vec4<f32>,
+    intensity: f32,
+}
+
+@group(0) @binding(2) var<uniform> params: IndirectParams;
+@group(0) @binding(3) var depth: texture_depth_2d;
+@group(0) @binding(4) var rsm_flux: texture_2d<f32>;
+@group(0) @binding(5) var rsm_normal: texture_2d<f32>;
+@group(0) @binding(6) var rsm_depth: texture_depth_2d;
+
+const PI: f32 = 3.14159265;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+fn to_world(v: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(params.inverse_view_0.xyz, v) + params.inverse_view_0.w,
+        dot(params.inverse_view_1.xyz, v) + params.inverse_view_1.w,
+        dot(params.inverse_view_2.xyz, v) + params.inverse_view_2.w,
+    );
+}
+
+fn to_light_view(position: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(params.light_view_0.xyz, position) + params.light_view_0.w,
+        dot(params.light_view_1.xyz, position) + params.light_view_1.w,
+        dot(params.light_view_2.xyz, position) + params.light_view_2.w,
+    );
+}
+
+fn from_light_view(v: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(params.inverse_light_view_0.xyz, v) + params.inverse_light_view_0.w,
+        dot(params.inverse_light_view_1.xyz, v) + params.inverse_light_view_1.w,
+        dot(params.inverse_light_view_2.xyz, v) + params.inverse_light_view_2.w,
+    );
+}
+
+// interleaved gradient noise, rotates the samples per pixel to trade banding for noise
+fn jitter(pixel: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+@fragment
+fn fs_main(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(input, input_sampler, in.uv, 0.0);
+
+    let size = vec2<i32>(textureDimensions(depth));
+    let coords = clamp(vec2<i32>(in.uv * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    let d = textureLoad(depth, coords, 0);
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let view_z = params.near_z / max(d, 1e-12);
+    let position = to_world(vec3<f32>(ndc * view_z, view_z));
+    // derivatives before anything diverges, the normal faces the camera whichever way they turn
+    var normal = normalize(cross(dpdx(position), dpdy(position)));
+    if dot(normal, to_world(vec3<f32>(0.0)) - position) < 0.0 {
+        normal = -normal;
+    }
+    // the background
+    if d <= 0.0 {
+        return color;
+    }
+
+    let from_light = to_light_view(position);
+    if from_light.z <= params.light_near_z {
+        return color;
+    }
+    // light.wgsl's to_light, without the divide
+    let center = vec2<f32>(from_light.x + from_light.z, -from_light.y + from_light.z) * 0.5 / from_light.z;
+
+    let rsm_size = vec2<f32>(textureDimensions(rsm_flux));
+    // the light view's projection scale, texels further from the light cover more of the world
+    let scale = vec2<f32>(length(params.light_view_0.xyz), length(params.light_view_1.xyz));
+    let rotation = jitter(in.position.xy) * 2.0 * PI;
+
+    var indirect = vec3<f32>(0.0);
+    for (var i = 0u; i < params.samples; i++) {
+        // denser towards the center, where the texels contribute the most, the weight makes up for it
+        let xi = (f32(i) + 0.5) / f32(params.samples);
+        let angle = f32(i) * GOLDEN_ANGLE + rotation;
+        let uv = center + params.radius * xi * vec2<f32>(cos(angle), sin(angle));
+        if any(uv <= vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0)) {
+            continue;
+        }
+        let texel = vec2<i32>(uv * rsm_size);
+        let texel_depth = textureLoad(rsm_depth, texel, 0);
+        if texel_depth <= 0.0 {
+            continue;
+        }
+
+        let z = params.light_near_z / texel_depth;
+        let sample_position = from_light_view(vec3<f32>((uv.x * 2.0 - 1.0) * z, (1.0 - uv.y * 2.0) * z, z));
+        let flux = textureLoad(rsm_flux, texel, 0).rgb;
+        let sample_normal = textureLoad(rsm_normal, texel, 0).xyz;
+        let area = 4.0 * z * z / (scale.x * scale.y * rsm_size.x * rsm_size.y);
+
+        let to_surface = position - sample_position;
+        let distance_squared = max(dot(to_surface, to_surface), 1e-4);
+        let emitted = max(dot(sample_normal, to_surface), 0.0);
+        let received = max(dot(normal, -to_surface), 0.0);
+        indirect += flux * area / PI * emitted * received / (distance_squared * distance_squared) * xi;
+    }
+    // the disk's area in texels, spread over the samples along the radius
+    let texels = params.radius * params.radius * rsm_size.x * rsm_size.y;
+    indirect *= 2.0 * PI * texels / f32(params.samples);
+
+    return vec4<f32>(color.rgb + indirect * params.intensity, color.a);
+}
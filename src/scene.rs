@@ -0,0 +1,465 @@
+use std::ops::Range;
+
+use wgpu::{Device, Queue};
+
+use crate::buffer::GpuBuffer;
+use crate::lightmap;
+use crate::math::{self, Vector3};
+
+/// which of the renderer's meshes an instance is drawn with, its index into `meshes` in `run`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct MeshId(pub u32);
+
+impl MeshId {
+    pub const CUBE: Self = Self(0);
+    /// the cube cut along a diagonal, sloping down from its top back edge
+    pub const WEDGE: Self = Self(1);
+}
+
+/// instances drawn together in one call, with the same mesh
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DrawItem {
+    pub mesh: MeshId,
+    /// in upload order
+    pub instances: Range<u32>,
+    pub is_static: bool,
+}
+
+pub struct Instance {
+    pub mesh: MeshId,
+    pub translation: Vector3,
+    pub rotation: math::Rotor,
+    pub scale: math::Scale3,
+    /// drawn into the shadow maps
+    pub casts_shadow: bool,
+    /// darkened by the shadow maps, lit throughout the light's frustum otherwise
+    pub receives_shadow: bool,
+    /// lit by its baked lighting instead of the light, see `lightmap::bake`
+    pub lightmap: Option<lightmap::Tile>,
+}
+
+/// bits of `InstanceRaw::flags`, mirrored in the shaders drawing instances
+pub const CASTS_SHADOW: u32 = 1;
+pub const RECEIVES_SHADOW: u32 = 2;
+pub const LIGHTMAPPED: u32 = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct InstanceRaw {
+    pub affine: math::Affine3,
+    pub flags: u32,
+    /// `lightmap::Tile::to_array`, zero when not lightmapped
+    pub lightmap: [f32; 3],
+}
+crate::layout::assert_uniform_size!(InstanceRaw, 64);
+
+/// the components of an `Instance` as laid out in `transform.wgsl`,
+/// turned into an `InstanceRaw` on the gpu
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct InstanceComponents {
+    pub rotation: [f32; 4],
+    pub translation: [f32; 3],
+    pub flags: u32,
+    pub scale: [f32; 3],
+    pub _padding1: f32,
+    pub lightmap: [f32; 3],
+    pub _padding2: f32,
+}
+crate::layout::assert_uniform_size!(InstanceComponents, 64);
+
+impl Instance {
+    #[allow(dead_code)]
+    pub fn to_raw(&self) -> InstanceRaw {
+        InstanceRaw {
+            affine: math::Affine3::from(self.scale, self.rotation, self.translation),
+            flags: self.flags(),
+            lightmap: self.lightmap.map_or([0.0; 3], |tile| tile.to_array()),
+        }
+    }
+
+    /// from e.g. an imported node's matrix, dropping any shear
+    #[allow(dead_code)]
+    pub fn set_affine(&mut self, affine: &math::Affine3) {
+        (self.scale, self.rotation, self.translation) = affine.decompose();
+    }
+
+    /// world space box around the instance's cube
+    pub fn bounds(&self) -> math::Obb3 {
+        math::Obb3::from_affine(&math::Affine3::from(self.scale, self.rotation, self.translation))
+    }
+
+    pub fn flags(&self) -> u32 {
+        let mut flags = 0;
+        if self.casts_shadow {
+            flags |= CASTS_SHADOW;
+        }
+        if self.receives_shadow {
+            flags |= RECEIVES_SHADOW;
+        }
+        if self.lightmap.is_some() {
+            flags |= LIGHTMAPPED;
+        }
+        flags
+    }
+
+    pub fn to_components(&self) -> InstanceComponents {
+        InstanceComponents {
+            rotation: self.rotation.to_array(),
+            translation: [self.translation.x, self.translation.y, self.translation.z],
+            flags: self.flags(),
+            scale: [self.scale.x, self.scale.y, self.scale.z],
+            _padding1: 0.0,
+            lightmap: self.lightmap.map_or([0.0; 3], |tile| tile.to_array()),
+            _padding2: 0.0,
+        }
+    }
+}
+
+/// refers to a spawned instance, stays invalid after despawning even when the slot is reused
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+struct Slot {
+    instance: Option<Instance>,
+    generation: u32,
+    /// static instances are expected to rarely change, e.g. their shadows are cached
+    is_static: bool,
+}
+
+/// owns the instances of the scene, despawned slots are kept in a free-list for reuse.
+/// instances are uploaded densely, static ones first then dynamic ones, each grouped by mesh and
+/// then in slot order, so an instance's position in the gpu buffer can change when instances
+/// before it are despawned or change mesh.
+pub struct Scene {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    len: usize,
+    static_len: usize,
+    /// bumped whenever a static instance may have changed
+    static_generation: u64,
+    components: Vec<InstanceComponents>,
+    /// the instances of the last upload, in upload order
+    uploaded: Vec<Handle>,
+    /// whether the last upload laid out other instances than the one before
+    layout_changed: bool,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self {
+            slots: vec![],
+            free: vec![],
+            len: 0,
+            static_len: 0,
+            static_generation: 0,
+            components: vec![],
+            uploaded: vec![],
+            layout_changed: false,
+        }
+    }
+
+    /// spawns a dynamic instance
+    pub fn spawn(&mut self, instance: Instance) -> Handle {
+        self.spawn_with(instance, false)
+    }
+
+    pub fn spawn_static(&mut self, instance: Instance) -> Handle {
+        self.spawn_with(instance, true)
+    }
+
+    fn spawn_with(&mut self, instance: Instance, is_static: bool) -> Handle {
+        self.len += 1;
+        if is_static {
+            self.static_len += 1;
+            self.static_generation += 1;
+        }
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.instance = Some(instance);
+            slot.is_static = is_static;
+            Handle { index, generation: slot.generation }
+        } else {
+            self.slots.push(Slot { instance: Some(instance), generation: 0, is_static });
+            Handle { index: self.slots.len() as u32 - 1, generation: 0 }
+        }
+    }
+
+    /// returns the despawned instance, or None if `handle` was already despawned
+    pub fn despawn(&mut self, handle: Handle) -> Option<Instance> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+
+        let instance = slot.instance.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        if slot.is_static {
+            self.static_len -= 1;
+            self.static_generation += 1;
+        }
+        self.free.push(handle.index);
+        self.len -= 1;
+        Some(instance)
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&Instance> {
+        let slot = self.slots.get(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.instance.as_ref()
+    }
+
+    /// invalidates what is cached about static instances when `handle` is static
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut Instance> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        if slot.is_static && slot.instance.is_some() {
+            self.static_generation += 1;
+        }
+        slot.instance.as_mut()
+    }
+
+    /// moves every instance by `by`, e.g. when the floating origin is rebased
+    pub fn translate_all(&mut self, by: Vector3) {
+        for instance in self.slots.iter_mut().filter_map(|slot| slot.instance.as_mut()) {
+            instance.translation += by;
+        }
+        if self.static_len > 0 {
+            self.static_generation += 1;
+        }
+    }
+
+    /// indices of the live slots in upload order
+    fn upload_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.slots.len())
+            .filter(|&i| self.slots[i].instance.is_some())
+            .collect();
+        // stable, so slot order is kept within each group
+        order.sort_by_key(|&i| {
+            let slot = &self.slots[i];
+            (!slot.is_static, slot.instance.as_ref().unwrap().mesh)
+        });
+        order
+    }
+
+    /// live slots with their index in upload order
+    fn ordered_slots(&self) -> impl Iterator<Item = (usize, &Slot)> {
+        self.upload_order().into_iter().map(|i| (i, &self.slots[i]))
+    }
+
+    /// one item per run of instances with the same mesh, static runs first,
+    /// as laid out by `upload`
+    pub fn draw_items(&self) -> Vec<DrawItem> {
+        let mut items: Vec<DrawItem> = vec![];
+        for (i, (_, slot)) in self.ordered_slots().enumerate() {
+            let mesh = slot.instance.as_ref().unwrap().mesh;
+            match items.last_mut() {
+                Some(item) if item.mesh == mesh && item.is_static == slot.is_static => item.instances.end += 1,
+                _ => items.push(DrawItem {
+                    mesh,
+                    instances: i as u32..i as u32 + 1,
+                    is_static: slot.is_static,
+                }),
+            }
+        }
+        items
+    }
+
+    /// live instances in upload order
+    pub fn iter(&self) -> impl Iterator<Item = &Instance> {
+        self.ordered_slots().map(|(_, slot)| slot.instance.as_ref().unwrap())
+    }
+
+    /// handles of the static instances in upload order
+    pub fn static_handles(&self) -> Vec<Handle> {
+        self.ordered_slots()
+            .take(self.static_len)
+            .map(|(i, slot)| Handle { index: i as u32, generation: slot.generation })
+            .collect()
+    }
+
+    /// handle of the instance at `index` in upload order, e.g. as written to the object-id target
+    pub fn handle_at(&self, index: usize) -> Option<Handle> {
+        self.ordered_slots()
+            .nth(index)
+            .map(|(i, slot)| Handle { index: i as u32, generation: slot.generation })
+    }
+
+    /// index of `handle` in upload order, None if it was despawned
+    pub fn index_of(&self, handle: Handle) -> Option<usize> {
+        self.ordered_slots()
+            .position(|(i, slot)| i == handle.index as usize && slot.generation == handle.generation)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// static instances are uploaded at `0..static_len()`, dynamic ones after
+    pub fn static_len(&self) -> usize {
+        self.static_len
+    }
+
+    pub fn static_generation(&self) -> u64 {
+        self.static_generation
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// whether the last `upload` put other instances at some index than the upload before it did,
+    /// after which what the gpu kept of the frame before isn't at the same indices
+    pub fn layout_changed(&self) -> bool {
+        self.layout_changed
+    }
+
+    /// writes the components of all live instances to `buffer`, growing it if needed.
+    /// returns true if the buffer was recreated and bind groups using it need rebuilding.
+    pub fn upload(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        buffer: &mut GpuBuffer<InstanceComponents>,
+    ) -> bool {
+        use rayon::prelude::*;
+
+        let order = self.upload_order();
+        let uploaded: Vec<Handle> = order
+            .iter()
+            .map(|&i| Handle { index: i as u32, generation: self.slots[i].generation })
+            .collect();
+        self.layout_changed = uploaded != self.uploaded;
+        self.uploaded = uploaded;
+
+        let slots = &self.slots;
+        self.components.clear();
+        self.components.par_extend(
+            order.par_iter().map(|&i| slots[i].instance.as_ref().unwrap().to_components()),
+        );
+
+        let recreated = buffer.reserve(device, self.components.len());
+        buffer.write(queue, &self.components);
+        recreated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(x: f32) -> Instance {
+        Instance {
+            mesh: MeshId::CUBE,
+            translation: Vector3::new(x, 0.0, 0.0),
+            rotation: math::Rotor::IDENTITY,
+            scale: math::Scale3::new(1.0, 1.0, 1.0),
+            casts_shadow: true,
+            receives_shadow: true,
+            lightmap: None,
+        }
+    }
+
+    #[test]
+    fn despawned_slots_are_reused_with_new_generation() {
+        let mut scene = Scene::new();
+        let a = scene.spawn(instance(0.0));
+        let b = scene.spawn(instance(1.0));
+        assert_eq!(scene.len(), 2);
+
+        assert!(scene.despawn(a).is_some());
+        assert!(scene.despawn(a).is_none());
+        assert!(scene.get(a).is_none());
+        assert_eq!(scene.len(), 1);
+
+        let c = scene.spawn(instance(2.0));
+        assert_ne!(a, c);
+        assert!(scene.get(a).is_none());
+        assert_eq!(scene.get(c).unwrap().translation.x, 2.0);
+        assert_eq!(scene.get(b).unwrap().translation.x, 1.0);
+
+        let xs: Vec<_> = scene.iter().map(|i| i.translation.x).collect();
+        assert_eq!(xs, [2.0, 1.0]);
+    }
+
+    #[test]
+    fn static_instances_come_first_and_track_changes() {
+        let mut scene = Scene::new();
+        let dynamic = scene.spawn(instance(0.0));
+        let a = scene.spawn_static(instance(1.0));
+        scene.spawn_static(instance(2.0));
+        assert_eq!(scene.static_len(), 2);
+
+        let xs: Vec<_> = scene.iter().map(|i| i.translation.x).collect();
+        assert_eq!(xs, [1.0, 2.0, 0.0]);
+        assert_eq!(scene.handle_at(0), Some(a));
+        assert_eq!(scene.static_handles(), [a, scene.handle_at(1).unwrap()]);
+        assert_eq!(scene.handle_at(2), Some(dynamic));
+        assert_eq!(scene.index_of(dynamic), Some(2));
+
+        let generation = scene.static_generation();
+        scene.get_mut(dynamic).unwrap().translation.x = 3.0;
+        assert_eq!(scene.static_generation(), generation);
+        scene.get_mut(a).unwrap().translation.x = 4.0;
+        assert_ne!(scene.static_generation(), generation);
+
+        let generation = scene.static_generation();
+        scene.despawn(a);
+        assert_eq!(scene.static_len(), 1);
+        assert_eq!(scene.index_of(a), None);
+        assert_eq!(scene.index_of(dynamic), Some(1));
+        assert_ne!(scene.static_generation(), generation);
+    }
+
+    #[test]
+    fn instances_are_grouped_by_mesh_into_draw_items() {
+        let mut scene = Scene::new();
+        let wedge = |x| Instance { mesh: MeshId::WEDGE, ..instance(x) };
+        scene.spawn(wedge(0.0));
+        scene.spawn(instance(1.0));
+        let a = scene.spawn_static(wedge(2.0));
+        scene.spawn(wedge(3.0));
+        scene.spawn_static(instance(4.0));
+
+        let xs: Vec<_> = scene.iter().map(|i| i.translation.x).collect();
+        assert_eq!(xs, [4.0, 2.0, 1.0, 0.0, 3.0]);
+        assert_eq!(scene.draw_items(), [
+            DrawItem { mesh: MeshId::CUBE, instances: 0..1, is_static: true },
+            DrawItem { mesh: MeshId::WEDGE, instances: 1..2, is_static: true },
+            DrawItem { mesh: MeshId::CUBE, instances: 2..3, is_static: false },
+            DrawItem { mesh: MeshId::WEDGE, instances: 3..5, is_static: false },
+        ]);
+        assert_eq!(scene.index_of(a), Some(1));
+
+        scene.get_mut(a).unwrap().mesh = MeshId::CUBE;
+        assert_eq!(scene.draw_items()[0].instances, 0..2);
+        assert!(Scene::new().draw_items().is_empty());
+    }
+
+    #[test]
+    fn instance_structs_match_transform_wgsl() {
+        use std::mem::{offset_of, size_of};
+
+        let shader = crate::binding::reflect("transform.wgsl", include_str!("transform.wgsl"));
+        crate::layout::validate_struct(&shader, "Affine", size_of::<InstanceRaw>(), &[
+            ("_0", offset_of!(InstanceRaw, affine)),
+            ("flags", offset_of!(InstanceRaw, flags)),
+            ("lightmap", offset_of!(InstanceRaw, lightmap)),
+        ]).unwrap();
+        crate::layout::validate_struct(&shader, "Components", size_of::<InstanceComponents>(), &[
+            ("rotation", offset_of!(InstanceComponents, rotation)),
+            ("translation", offset_of!(InstanceComponents, translation)),
+            ("flags", offset_of!(InstanceComponents, flags)),
+            ("scale", offset_of!(InstanceComponents, scale)),
+            ("lightmap", offset_of!(InstanceComponents, lightmap)),
+        ]).unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+//! every shader as its owner composes it, validated up front so a typo fails at startup with its
+//! file and line, rather than as a panic wherever its pipeline happens to be created
+
+use crate::draw_params::DrawParamsMode;
+use crate::preprocess::{Defines, ShaderVariant};
+use crate::{binding, point_shadow, post, shader_preprocessor, vegetation};
+
+/// (file name, source or the preprocessor's error) of every shader, in every variant
+pub fn sources() -> Vec<(&'static str, Result<String, String>)> {
+    let mut sources: Vec<_> = [
+        ("debug_draw.wgsl", include_str!("debug_draw.wgsl")),
+        ("depth_range.wgsl", include_str!("depth_range.wgsl")),
+        ("mirror.wgsl", include_str!("mirror.wgsl")),
+        ("portal.wgsl", include_str!("portal.wgsl")),
+        ("traced_shadow.wgsl", include_str!("traced_shadow.wgsl")),
+        ("transform.wgsl", include_str!("transform.wgsl")),
+    ]
+    .into_iter()
+    .map(|(name, source)| (name, Ok(source.to_string())))
+    .collect();
+
+    let fullscreen = [
+        ("copy.wgsl", include_str!("copy.wgsl").to_string()),
+        ("dither.wgsl", post::resolved("")),
+        ("dof.wgsl", include_str!("dof.wgsl").to_string()),
+        ("fog.wgsl", include_str!("fog.wgsl").to_string()),
+        ("fxaa.wgsl", post::resolved(include_str!("fxaa.wgsl"))),
+        ("lut.wgsl", include_str!("srgb.wgsl").to_string() + include_str!("lut.wgsl")),
+        ("motion_blur.wgsl", include_str!("motion_blur.wgsl").to_string()),
+        ("outline.wgsl", include_str!("outline.wgsl").to_string()),
+        ("rsm.wgsl", include_str!("rsm.wgsl").to_string()),
+        ("volumetric.wgsl", include_str!("volumetric.wgsl").to_string()),
+    ];
+    for (name, fragment) in fullscreen {
+        sources.push((name, Ok(post::fullscreen_source(&fragment))));
+    }
+
+    sources.push(("vegetation.wgsl", Ok(vegetation::source())));
+
+    for multiview in [false, true] {
+        sources.push(("point_shadow.wgsl", point_shadow::source(multiview)));
+    }
+
+    for mode in [DrawParamsMode::PushConstants, DrawParamsMode::Uniform { group: 1 }] {
+        let preprocessor = shader_preprocessor(mode);
+        for bits in 0..=ShaderVariant::all().bits() {
+            let defines = ShaderVariant::from_bits(bits).unwrap().defines();
+            sources.push(("light.wgsl", preprocessor.process("light.wgsl", &defines)));
+        }
+        for defines in [Defines::new(), Defines::new().with("PANCAKE_IN_SHADER")] {
+            sources.push(("shadow.wgsl", preprocessor.process("shadow.wgsl", &defines)));
+        }
+    }
+    sources
+}
+
+/// every problem found in `sources`, each shader's first one
+pub fn validate_all() -> Result<(), String> {
+    let mut errors: Vec<String> = sources()
+        .into_iter()
+        .filter_map(|(name, source)| {
+            let source = match source {
+                Ok(source) => source,
+                Err(e) => return Some(format!("{}: {}", name, e)),
+            };
+            binding::validate_wgsl(name, &source).err()
+        })
+        .collect();
+    // a mistake in a shared source shows up in each variant
+    errors.dedup();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_shader_validates() {
+        if let Err(e) = validate_all() {
+            panic!("{}", e);
+        }
+        assert!(binding::validate_wgsl("typo.wgsl", "fn f() -> f32 { return 1.0 }").is_err());
+        assert!(binding::validate_wgsl("types.wgsl", "fn f() -> f32 { return 1u; }").is_err());
+    }
+}
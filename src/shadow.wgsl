@@ -1,13 +1,20 @@
+#include "common.wgsl"
+
+// the beginning of LightRaw
 struct Camera {
     view_0: vec4<f32>,
     view_1: vec4<f32>,
     view_2: vec4<f32>,
     near_z: f32,
+    color: vec3<f32>,
+    intensity: f32,
 };
 @group(0) @binding(0) var<uniform> light: Camera;
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,  
+    @location(0) world_position: vec3<f32>,
+    @location(1) @interpolate(flat) terrain: u32,
 }
 
 struct VertexInput {
@@ -18,43 +25,87 @@ struct InstanceInput {
     @location(5) model_0: vec4<f32>,
     @location(6) model_1: vec4<f32>,
     @location(7) model_2: vec4<f32>,
+    @location(8) flags: u32,
 }
 
 @vertex fn vs_main(
     vertex: VertexInput,
     instance: InstanceInput,
 ) -> VertexOutput {
-    var out: VertexOutput;
+    if (instance.flags & CASTS_SHADOW) == 0u {
+        var out: VertexOutput;
+        // outside the clip volume, so the whole instance is clipped away
+        out.clip_position = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+        return out;
+    }
+    return to_light(apply_affine(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        vertex.position,
+    ), 0u);
+}
+
+// every instance reflects light into the rsm, casting shadows or not
+@vertex fn vs_rsm(
+    vertex: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    return to_light(apply_affine(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        vertex.position,
+    ), 0u);
+}
 
+// terrain::TerrainVertex, already in world space
+@vertex fn vs_terrain(vertex: VertexInput) -> VertexOutput {
+    return to_light(vertex.position, 1u);
+}
+
+fn to_light(world_position: vec3<f32>, terrain: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.world_position = world_position;
+    out.terrain = terrain;
     out.clip_position = vec4<f32>(apply_affine(
         light.view_0,
         light.view_1,
         light.view_2,
-        apply_affine(
-            instance.model_0,
-            instance.model_1,
-            instance.model_2,
-            vertex.position,
-        ),
+        world_position,
     ), 1.0);
 
     out.clip_position.w = out.clip_position.z;
     out.clip_position.z = light.near_z;
+    // without unclipped depth, casters between the light and its near plane are
+    // flattened onto the near plane per vertex, rather than being clipped away
+#ifdef PANCAKE_IN_SHADER
+    out.clip_position.z = min(light.near_z, out.clip_position.w);
+#endif
+    return out;
+}
+
+// rsm::ReflectiveShadowMap's targets
+struct RsmOut {
+    // reflected per unit of area across the light, the texel's area is multiplied in by the gather
+    @location(0) flux: vec4<f32>,
+    // world space, facing the light
+    @location(1) normal: vec4<f32>,
+}
 
-    // textured is stored such that +y is down, so we need to invert y
+@fragment fn fs_rsm(in: VertexOutput) -> RsmOut {
+    var normal = normalize(cross(dpdx(in.world_position), dpdy(in.world_position)));
+    if dot(normal, light.view_2.xyz) > 0.0 {
+        normal = -normal;
+    }
+    // instances are drawn white
+    var albedo = vec3<f32>(1.0);
+    if in.terrain != 0u {
+        albedo = terrain_albedo(in.world_position.y, normal);
+    }
 
+    var out: RsmOut;
+    out.flux = vec4<f32>(albedo * light.color * light.intensity, 1.0);
+    out.normal = vec4<f32>(normal, 0.0);
     return out;
 }
-
-fn apply_affine(
-    _0: vec4<f32>,
-    _1: vec4<f32>,
-    _2: vec4<f32>,
-    pos: vec3<f32>
-) -> vec3<f32> {
-    return vec3<f32>(
-        dot(_0.xyz, pos) + _0.w,
-        dot(_1.xyz, pos) + _1.w,
-        dot(_2.xyz, pos) + _2.w,
-    );
-}
\ No newline at end of file
@@ -0,0 +1,75 @@
+use wgpu::*;
+
+use crate::math::Affine3;
+
+/// depth of the static shadow casters, rendered only when the light view or a static
+/// instance changed, and copied into the shadow map before the dynamic casters each frame
+pub struct ShadowCache {
+    texture: Texture,
+    pub view: TextureView,
+    width: u32,
+    height: u32,
+    /// what the cache was rendered with, None when never rendered
+    rendered: Option<(Affine3, u64)>,
+}
+
+impl ShadowCache {
+    pub fn new(device: &Device, format: TextureFormat, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("static shadow texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            view,
+            width,
+            height,
+            rendered: None,
+        }
+    }
+
+    /// whether the static casters have to be rendered again for `light_view` and
+    /// the scene's `static_generation`, assumes they are rendered when true is returned
+    pub fn update(&mut self, light_view: &Affine3, static_generation: u64) -> bool {
+        let stale = match &self.rendered {
+            Some((view, generation)) => {
+                *generation != static_generation || bytemuck::bytes_of(view) != bytemuck::bytes_of(light_view)
+            }
+            None => true,
+        };
+        if stale {
+            self.rendered = Some((*light_view, static_generation));
+        }
+        stale
+    }
+
+    /// forces the static casters to be rendered next frame
+    pub fn invalidate(&mut self) {
+        self.rendered = None;
+    }
+
+    /// copies the cached depth into `shadow_map`, which must be the same size and format
+    pub fn copy_to(&self, encoder: &mut CommandEncoder, shadow_map: &Texture) {
+        encoder.copy_texture_to_texture(
+            self.texture.as_image_copy(),
+            shadow_map.as_image_copy(),
+            Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
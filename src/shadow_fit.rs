@@ -0,0 +1,74 @@
+use crate::math::Vector2;
+use crate::polygon::Rect;
+
+/// steadies the rect of the light plane the shadow map is fitted to: the camera's rect is
+/// padded by `margin` and the padded rect kept while the camera's stays inside it and the
+/// padded one stays within `hysteresis` of it, so small camera motions don't move the shadow
+/// map's texels every frame
+pub struct StableFit {
+    /// of the camera's rect's size, added on every side
+    pub margin: f32,
+    /// of the kept rect's size, how far its edges may be from the padded rect's
+    pub hysteresis: f32,
+    kept: Option<Rect>,
+}
+
+impl StableFit {
+    pub fn new() -> Self {
+        Self {
+            margin: 0.05,
+            hysteresis: 0.05,
+            kept: None,
+        }
+    }
+
+    /// the rect to fit to for the camera's `ideal` one, both within `bounds`
+    pub fn update(&mut self, ideal: &Rect, bounds: &Rect) -> Rect {
+        let margin = Vector2::new(ideal.width(), ideal.height()) * self.margin;
+        let padded = Rect {
+            max: ideal.max + margin,
+            min: ideal.min - margin,
+        };
+        let padded = padded.intersect(bounds).unwrap_or(*ideal);
+
+        let keep = self.kept.is_some_and(|kept| {
+            let tolerance = Vector2::new(kept.width(), kept.height()) * self.hysteresis;
+            let off = (kept.min - padded.min).abs().max((kept.max - padded.max).abs());
+            kept.contains_point(ideal.min)
+                && kept.contains_point(ideal.max)
+                && bounds.contains_point(kept.min)
+                && bounds.contains_point(kept.max)
+                && off.x <= tolerance.x
+                && off.y <= tolerance.y
+        });
+        if !keep {
+            self.kept = Some(padded);
+        }
+        self.kept.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_motions_keep_the_rect() {
+        let bounds = Rect { max: Vector2::new(10.0, 10.0), min: Vector2::new(-10.0, -10.0) };
+        let rect = |x: f32| Rect { max: Vector2::new(x + 2.0, 2.0), min: Vector2::new(x, 0.0) };
+        let mut fit = StableFit::new();
+
+        let first = fit.update(&rect(0.0), &bounds);
+        assert_eq!(first, Rect { max: Vector2::new(2.1, 2.1), min: Vector2::new(-0.1, -0.1) });
+        assert_eq!(fit.update(&rect(0.05), &bounds), first);
+        // out of the kept rect
+        assert_ne!(fit.update(&rect(0.2), &bounds), first);
+
+        // clamped to the bounds
+        assert_eq!(fit.update(&rect(8.0), &bounds).max.x, 10.0);
+
+        fit.margin = 0.0;
+        fit.hysteresis = 0.0;
+        assert_eq!(fit.update(&rect(1.0), &bounds), rect(1.0));
+    }
+}
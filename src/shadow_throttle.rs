@@ -0,0 +1,132 @@
+use crate::math::{Affine3, Obb3};
+use crate::scene::Scene;
+
+/// what the shadow map was last rendered with
+struct Rendered {
+    light_view: Affine3,
+    static_generation: u64,
+    scissor: Option<(u32, u32, u32, u32)>,
+    /// bounds of the dynamic casters in upload order
+    casters: Vec<Obb3>,
+}
+
+/// skips the shadow pass on frames where neither the fitted light view nor any caster moved by
+/// more than `threshold`, the shadow map keeping what it was last rendered with.
+/// the swaying grass isn't tracked, its shadows hold still on skipped frames
+pub struct ShadowThrottle {
+    pub enabled: bool,
+    /// world units a caster's corners may move by before the shadow map is rendered again
+    pub threshold: f32,
+    rendered: Option<Rendered>,
+}
+
+impl ShadowThrottle {
+    /// differences in the light view's entries below it don't count as it moving
+    const LIGHT_VIEW_TOLERANCE: f32 = 1e-5;
+
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            threshold: 0.01,
+            rendered: None,
+        }
+    }
+
+    /// whether the shadow map has to be rendered this frame, assumes it is when true is returned
+    pub fn update(
+        &mut self,
+        light_view: &Affine3,
+        scissor: Option<(u32, u32, u32, u32)>,
+        scene: &Scene,
+    ) -> bool {
+        let casters: Vec<Obb3> = scene
+            .iter()
+            .skip(scene.static_len())
+            .filter(|instance| instance.casts_shadow)
+            .map(|instance| instance.bounds())
+            .collect();
+
+        let stale = !self.enabled || match &self.rendered {
+            Some(rendered) => {
+                rendered.static_generation != scene.static_generation()
+                    || rendered.scissor != scissor
+                    || !light_view_close(&rendered.light_view, light_view)
+                    || rendered.casters.len() != casters.len()
+                    || rendered.casters.iter().zip(&casters).any(|(a, b)| moved_by(a, b) > self.threshold)
+            }
+            None => true,
+        };
+        if stale {
+            self.rendered = Some(Rendered {
+                light_view: *light_view,
+                static_generation: scene.static_generation(),
+                scissor,
+                casters,
+            });
+        }
+        stale
+    }
+
+    /// forces the shadow map to be rendered next frame
+    pub fn invalidate(&mut self) {
+        self.rendered = None;
+    }
+}
+
+fn light_view_close(a: &Affine3, b: &Affine3) -> bool {
+    let (a, b): (&[f32; 12], &[f32; 12]) = (bytemuck::cast_ref(a), bytemuck::cast_ref(b));
+    a.iter().zip(b).all(|(a, b)| (a - b).abs() <= ShadowThrottle::LIGHT_VIEW_TOLERANCE)
+}
+
+/// the most any corner moved by, or a bit more
+fn moved_by(a: &Obb3, b: &Obb3) -> f32 {
+    (a.center - b.center).length() + a.axes.iter().zip(&b.axes).map(|(a, b)| (*a - *b).length()).sum::<f32>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Rotor, Scale3, Vector3};
+    use crate::scene::{Instance, MeshId};
+
+    fn caster(x: f32) -> Instance {
+        Instance {
+            mesh: MeshId::CUBE,
+            translation: Vector3::new(x, 0.0, 0.0),
+            rotation: Rotor::IDENTITY,
+            scale: Scale3::new(1.0, 1.0, 1.0),
+            casts_shadow: true,
+            receives_shadow: true,
+            lightmap: None,
+        }
+    }
+
+    #[test]
+    fn only_moves_beyond_the_threshold_render() {
+        let mut throttle = ShadowThrottle::new();
+        let mut scene = Scene::new();
+        let handle = scene.spawn(caster(0.0));
+        let view = Affine3::IDENTITY;
+        assert!(throttle.update(&view, None, &scene));
+        assert!(!throttle.update(&view, None, &scene));
+
+        scene.get_mut(handle).unwrap().translation.x = throttle.threshold * 0.5;
+        assert!(!throttle.update(&view, None, &scene));
+        scene.get_mut(handle).unwrap().translation.x = throttle.threshold * 2.0;
+        assert!(throttle.update(&view, None, &scene));
+
+        let mut moved = Affine3::IDENTITY;
+        moved.translate(&Vector3::new(0.1, 0.0, 0.0));
+        assert!(throttle.update(&moved, None, &scene));
+        assert!(throttle.update(&moved, Some((0, 0, 8, 8)), &scene));
+
+        // shadowless instances come and go unnoticed, casters don't
+        scene.spawn(Instance { casts_shadow: false, ..caster(5.0) });
+        assert!(!throttle.update(&moved, Some((0, 0, 8, 8)), &scene));
+        scene.spawn_static(caster(5.0));
+        assert!(throttle.update(&moved, Some((0, 0, 8, 8)), &scene));
+
+        throttle.enabled = false;
+        assert!(throttle.update(&moved, Some((0, 0, 8, 8)), &scene));
+    }
+}
@@ -0,0 +1,15 @@
+fn to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    return select(
+        1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055,
+        12.92 * linear,
+        linear <= vec3<f32>(0.0031308),
+    );
+}
+
+fn from_srgb(srgb: vec3<f32>) -> vec3<f32> {
+    return select(
+        pow((srgb + 0.055) / 1.055, vec3<f32>(2.4)),
+        srgb / 12.92,
+        srgb <= vec3<f32>(0.04045),
+    );
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+/// accumulates cpu timings of the frame stages and reports their averages once per interval
+pub struct FrameStats {
+    pub enabled: bool,
+    frames: u32,
+    elapsed: f32,
+    /// instance component preparation and upload
+    pub upload: Duration,
+    /// command recording of all passes
+    pub encode: Duration,
+    /// in the scene as of the last frame
+    pub instances: usize,
+    /// frames whose shadow pass was skipped by `shadow_throttle::ShadowThrottle`
+    pub shadows_skipped: u32,
+}
+
+impl FrameStats {
+    const REPORT_INTERVAL: f32 = 1.0;
+
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            frames: 0,
+            elapsed: 0.0,
+            upload: Duration::ZERO,
+            encode: Duration::ZERO,
+            instances: 0,
+            shadows_skipped: 0,
+        }
+    }
+
+    /// returns the report once per interval while enabled
+    pub fn end_frame(&mut self, delta_frame_time: f32) -> Option<String> {
+        self.frames += 1;
+        self.elapsed += delta_frame_time;
+        if self.elapsed < Self::REPORT_INTERVAL {
+            return None;
+        }
+
+        let frames = self.frames;
+        let report = format!(
+            "fps: {:.0}, instances: {}, upload: {:.3}ms, encode: {:.3}ms, shadows skipped: {:.0}%",
+            frames as f32 / self.elapsed,
+            self.instances,
+            self.upload.as_secs_f64() * 1000.0 / frames as f64,
+            self.encode.as_secs_f64() * 1000.0 / frames as f64,
+            self.shadows_skipped as f32 * 100.0 / frames as f32,
+        );
+
+        self.frames = 0;
+        self.elapsed = 0.0;
+        self.upload = Duration::ZERO;
+        self.encode = Duration::ZERO;
+        self.shadows_skipped = 0;
+
+        self.enabled.then_some(report)
+    }
+}
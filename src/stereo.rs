@@ -0,0 +1,70 @@
+use crate::math::Vector3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Eye {
+    /// drawn into the left half of the frame
+    Left,
+    Right,
+}
+
+impl Eye {
+    pub const ALL: [Eye; 2] = [Self::Left, Self::Right];
+
+    fn side(self) -> f32 {
+        match self {
+            Self::Left => -1.0,
+            Self::Right => 1.0,
+        }
+    }
+
+    /// x, y, width and height of the eye's half of a `width` by `height` frame
+    pub fn viewport(self, width: u32, height: u32) -> (f32, f32, f32, f32) {
+        let half = (width / 2) as f32;
+        match self {
+            Self::Left => (0.0, 0.0, half, height as f32),
+            Self::Right => (half, 0.0, width as f32 - half, height as f32),
+        }
+    }
+}
+
+/// experimental side by side stereo without a headset: the light pass draws the scene once per
+/// eye into its half of the frame, each eye a view of its own in the camera ring, offset from
+/// the camera along its right and seeing half as wide.
+/// the mirror, the portal and the grass are left out, and the post processes still see the frame
+/// as the camera's
+pub struct Stereo {
+    pub enabled: bool,
+    /// world units between the eyes
+    pub separation: f32,
+}
+
+impl Stereo {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            separation: 0.064,
+        }
+    }
+
+    /// from the camera to `eye`, `right` being the camera's unit right
+    pub fn eye_offset(&self, eye: Eye, right: Vector3) -> Vector3 {
+        right * (eye.side() * self.separation / 2.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eyes_split_the_frame_and_the_separation() {
+        assert_eq!(Eye::Left.viewport(801, 600), (0.0, 0.0, 400.0, 600.0));
+        assert_eq!(Eye::Right.viewport(801, 600), (400.0, 0.0, 401.0, 600.0));
+
+        let stereo = Stereo::new();
+        let right = Vector3::new(0.0, 0.0, 1.0);
+        let between = stereo.eye_offset(Eye::Right, right) - stereo.eye_offset(Eye::Left, right);
+        assert!((between.length() - stereo.separation).abs() < 1e-6);
+        assert!(stereo.eye_offset(Eye::Left, right).z < 0.0);
+    }
+}
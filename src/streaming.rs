@@ -0,0 +1,204 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+
+use wgpu::Device;
+
+use crate::math::Vector3;
+use crate::scene::{Handle, Instance, Scene};
+use crate::terrain::{Chunk, ChunkGeometry};
+
+/// square of the world's xz plane, `Streamer::chunk_size` wide, counted from the absolute origin
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ChunkCoord {
+    pub x: i32,
+    pub z: i32,
+}
+
+/// what a chunk holds, built by the loader on a worker thread. positioned relative to the chunk's
+/// corner at the lowest x and z, so it's as precise far from the absolute origin as near it
+pub struct ChunkData {
+    /// spawned as static instances
+    pub instances: Vec<Instance>,
+    pub terrain: Option<ChunkGeometry>,
+}
+
+pub type ChunkLoader = dyn Fn(ChunkCoord, f32) -> ChunkData + Send + Sync;
+
+struct LoadedChunk {
+    handles: Vec<Handle>,
+    /// kept to rebuild `terrain` when the origin moves
+    geometry: Option<ChunkGeometry>,
+    terrain: Option<Chunk>,
+}
+
+/// keeps the chunks around the camera loaded, building their data on the rayon pool
+/// and creating their buffers and instances on the calling thread as they finish.
+/// chunks are unloaded one chunk further out than they're loaded, so moving back and forth
+/// over a border doesn't reload them.
+pub struct Streamer {
+    pub chunk_size: f32,
+    /// chunks at most this many chunks away from the camera's one are loaded
+    pub radius: i32,
+    /// finished chunks turned into buffers per update, to spread the work over frames
+    pub uploads_per_update: usize,
+    loader: Arc<ChunkLoader>,
+    /// chunk of the local origin the positions are relative to, moved by `rebase`
+    origin: ChunkCoord,
+    loaded: HashMap<ChunkCoord, LoadedChunk>,
+    pending: HashSet<ChunkCoord>,
+    sender: Sender<(ChunkCoord, ChunkData)>,
+    receiver: Receiver<(ChunkCoord, ChunkData)>,
+}
+
+impl Streamer {
+    /// `loader` is called with the chunk and `chunk_size`
+    pub fn new(
+        chunk_size: f32,
+        radius: i32,
+        loader: impl Fn(ChunkCoord, f32) -> ChunkData + Send + Sync + 'static,
+    ) -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            chunk_size,
+            radius,
+            uploads_per_update: 2,
+            loader: Arc::new(loader),
+            origin: ChunkCoord { x: 0, z: 0 },
+            loaded: HashMap::new(),
+            pending: HashSet::new(),
+            sender,
+            receiver,
+        }
+    }
+
+    /// of the chunk `position` in local space is in
+    pub fn coord_at(&self, position: Vector3) -> ChunkCoord {
+        ChunkCoord {
+            x: self.origin.x + (position.x / self.chunk_size).floor() as i32,
+            z: self.origin.z + (position.z / self.chunk_size).floor() as i32,
+        }
+    }
+
+    /// local position of the chunk's corner at the lowest x and z
+    fn corner(&self, coord: ChunkCoord) -> Vector3 {
+        Vector3::new(
+            (coord.x - self.origin.x) as f32 * self.chunk_size,
+            0.0,
+            (coord.z - self.origin.z) as f32 * self.chunk_size,
+        )
+    }
+
+    fn distance(a: ChunkCoord, b: ChunkCoord) -> i32 {
+        (a.x - b.x).abs().max((a.z - b.z).abs())
+    }
+
+    /// returns true when chunks were loaded or unloaded, e.g. so cached shadows can be redrawn
+    #[profiling::function]
+    pub fn update(&mut self, device: &Device, scene: &mut Scene, camera_position: Vector3) -> bool {
+        let center = self.coord_at(camera_position);
+        let mut changed = false;
+
+        let far: Vec<_> = self.loaded
+            .keys()
+            .copied()
+            .filter(|&coord| Self::distance(coord, center) > self.radius + 1)
+            .collect();
+        for coord in far {
+            let chunk = self.loaded.remove(&coord).unwrap();
+            for handle in chunk.handles {
+                scene.despawn(handle);
+            }
+            changed = true;
+        }
+
+        for (coord, data) in self.receiver.try_iter().take(self.uploads_per_update) {
+            self.pending.remove(&coord);
+            // the camera may have moved away while it was loading
+            if Self::distance(coord, center) > self.radius + 1 {
+                continue;
+            }
+            let corner = self.corner(coord);
+            let handles = data.instances
+                .into_iter()
+                .map(|instance| scene.spawn_static(Instance {
+                    translation: instance.translation + corner,
+                    ..instance
+                }))
+                .collect();
+            let geometry = data.terrain.map(|mut geometry| {
+                geometry.translate(corner);
+                geometry
+            });
+            let terrain = geometry.as_ref().map(|geometry| Chunk::new(device, geometry));
+            self.loaded.insert(coord, LoadedChunk { handles, geometry, terrain });
+            changed = true;
+        }
+
+        for z in center.z - self.radius..=center.z + self.radius {
+            for x in center.x - self.radius..=center.x + self.radius {
+                let coord = ChunkCoord { x, z };
+                if self.loaded.contains_key(&coord) || !self.pending.insert(coord) {
+                    continue;
+                }
+                let loader = self.loader.clone();
+                let sender = self.sender.clone();
+                let chunk_size = self.chunk_size;
+                rayon::spawn(move || {
+                    // fails only once the streamer is gone, when the chunk isn't wanted anymore
+                    let _ = sender.send((coord, loader(coord, chunk_size)));
+                });
+            }
+        }
+
+        changed
+    }
+
+    /// follows the floating origin moving by `shift`, a multiple of `chunk_size` along x and z.
+    /// the chunks' instances are left to be moved with the rest of the scene
+    pub fn rebase(&mut self, device: &Device, shift: Vector3) {
+        let chunks = |x: f32| {
+            let chunks = x / self.chunk_size;
+            assert_eq!(chunks, chunks.round(), "{:?} isn't a multiple of the chunk size", shift);
+            chunks as i32
+        };
+        self.origin.x += chunks(shift.x);
+        self.origin.z += chunks(shift.z);
+        for chunk in self.loaded.values_mut() {
+            if let Some(geometry) = &mut chunk.geometry {
+                geometry.translate(-shift);
+                chunk.terrain = Some(Chunk::new(device, geometry));
+            }
+        }
+    }
+
+    /// despawns the instances of all loaded chunks, chunks still loading are dropped
+    pub fn unload_all(self, scene: &mut Scene) {
+        for chunk in self.loaded.into_values() {
+            for handle in chunk.handles {
+                scene.despawn(handle);
+            }
+        }
+    }
+
+    pub fn terrain_chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.loaded.values().filter_map(|chunk| chunk.terrain.as_ref())
+    }
+
+    #[allow(dead_code)]
+    pub fn loaded_len(&self) -> usize {
+        self.loaded.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coords_floor_towards_negative() {
+        let streamer = Streamer::new(16.0, 1, |_, _| ChunkData { instances: vec![], terrain: None });
+        assert_eq!(streamer.coord_at(Vector3::new(1.0, 5.0, 15.9)), ChunkCoord { x: 0, z: 0 });
+        assert_eq!(streamer.coord_at(Vector3::new(-0.1, 0.0, 16.0)), ChunkCoord { x: -1, z: 1 });
+    }
+}
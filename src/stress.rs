@@ -0,0 +1,178 @@
+use crate::math::{self, BiVector3, Scale3, Vector2, Vector3};
+use crate::scene::{Handle, Instance, MeshId, Scene};
+
+/// how the stress test's instances are placed on the xz plane
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Layout {
+    Grid,
+    /// no two closer than the spacing, without the grid's regularity
+    Poisson,
+}
+
+impl Layout {
+    /// cycles off, grid, poisson
+    pub fn next(layout: Option<Self>) -> Option<Self> {
+        match layout {
+            None => Some(Layout::Grid),
+            Some(Layout::Grid) => Some(Layout::Poisson),
+            Some(Layout::Poisson) => None,
+        }
+    }
+}
+
+/// uniform in 0..1, hashed from `seed` and `i`
+fn random(seed: u32, i: u32) -> f32 {
+    math::noise::hash(seed.wrapping_add(math::noise::hash(i))) as f32 / u32::MAX as f32
+}
+
+/// `count` points `spacing` apart in a square centered on the origin
+pub fn grid(count: usize, spacing: f32) -> Vec<Vector2> {
+    let side = (count as f32).sqrt().ceil() as usize;
+    let offset = (side as f32 - 1.0) * spacing / 2.0;
+    (0..count)
+        .map(|i| Vector2::new((i % side) as f32 * spacing - offset, (i / side) as f32 * spacing - offset))
+        .collect()
+}
+
+/// up to `count` points no closer than `spacing` in a square centered on the origin, by bridson's
+/// algorithm. the square is sized so it's usually filled before running out of room
+pub fn poisson_disk(count: usize, spacing: f32, seed: u32) -> Vec<Vector2> {
+    const ATTEMPTS: u32 = 30;
+    if count == 0 {
+        return vec![];
+    }
+    // a filled square holds about 0.7 points per spacing squared
+    let side = spacing * (count as f32 / 0.5).sqrt();
+    // cells small enough to hold at most one point
+    let cell = spacing / std::f32::consts::SQRT_2;
+    let cells = (side / cell).ceil() as usize;
+    let mut grid = vec![u32::MAX; cells * cells];
+    let cell_of = |p: Vector2| ((p.x / cell) as usize).min(cells - 1) + ((p.y / cell) as usize).min(cells - 1) * cells;
+
+    let mut points = vec![Vector2::new(side / 2.0, side / 2.0)];
+    grid[cell_of(points[0])] = 0;
+    let mut active = vec![0];
+    let mut draws = 0;
+    while let Some(&last) = active.last() {
+        if points.len() == count {
+            break;
+        }
+        let center = points[last];
+        let candidate = (0..ATTEMPTS).find_map(|_| {
+            draws += 2;
+            let angle = random(seed, draws) * std::f32::consts::TAU;
+            let radius = spacing * (1.0 + random(seed, draws + 1));
+            let p = center + Vector2::new(angle.cos(), angle.sin()) * radius;
+            if p.x < 0.0 || p.y < 0.0 || p.x >= side || p.y >= side {
+                return None;
+            }
+            let (cx, cy) = ((p.x / cell) as usize, (p.y / cell) as usize);
+            let too_close = (cy.saturating_sub(2)..(cy + 3).min(cells))
+                .flat_map(|y| (cx.saturating_sub(2)..(cx + 3).min(cells)).map(move |x| x + y * cells))
+                .any(|i| grid[i] != u32::MAX && (points[grid[i] as usize] - p).length() < spacing);
+            (!too_close).then_some(p)
+        });
+        match candidate {
+            Some(p) => {
+                grid[cell_of(p)] = points.len() as u32;
+                active.push(points.len());
+                points.push(p);
+            }
+            None => {
+                active.pop();
+            }
+        }
+    }
+
+    let center = Vector2::new(side / 2.0, side / 2.0);
+    points.into_iter().map(|p| p - center).collect()
+}
+
+/// fills the scene with many small dynamic instances, to stress the instance upload and the passes
+/// drawing them
+pub struct StressTest {
+    pub layout: Option<Layout>,
+    pub count: usize,
+    pub spacing: f32,
+    /// where the layout is centered on the ground
+    pub center: Vector3,
+    handles: Vec<Handle>,
+}
+
+impl StressTest {
+    pub fn new(count: usize) -> Self {
+        Self {
+            layout: None,
+            count,
+            spacing: 1.5,
+            center: Vector3::IDENTITY,
+            handles: vec![],
+        }
+    }
+
+    /// replaces the instances spawned before with `count` ones in `layout`, or none when it's off
+    pub fn respawn(&mut self, scene: &mut Scene) {
+        for handle in self.handles.drain(..) {
+            scene.despawn(handle);
+        }
+        let positions = match self.layout {
+            None => return,
+            Some(Layout::Grid) => grid(self.count, self.spacing),
+            Some(Layout::Poisson) => poisson_disk(self.count, self.spacing, 0),
+        };
+        self.handles = positions
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let s = 0.3 + 0.4 * random(1, i as u32);
+                scene.spawn(Instance {
+                    mesh: MeshId::CUBE,
+                    translation: self.center + Vector3::new(p.x, s, p.y),
+                    rotation: BiVector3::new(0.0, 0.0, 3.0 * random(2, i as u32)).exp(),
+                    scale: Scale3::new(s, s, s),
+                    casts_shadow: true,
+                    receives_shadow: true,
+                    lightmap: None,
+                })
+            })
+            .collect();
+    }
+
+    pub fn spawned(&self) -> usize {
+        self.handles.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poisson_disk_points_keep_their_distance() {
+        let points = poisson_disk(500, 1.0, 7);
+        assert_eq!(points.len(), 500);
+        for (i, a) in points.iter().enumerate() {
+            for b in &points[i + 1..] {
+                assert!((*a - *b).length() >= 1.0 - 1e-4);
+            }
+        }
+        assert_eq!(grid(10, 2.0).len(), 10);
+        assert!(poisson_disk(0, 1.0, 7).is_empty());
+    }
+
+    #[test]
+    fn respawning_replaces_the_instances() {
+        let mut scene = Scene::new();
+        let mut stress = StressTest::new(100);
+        stress.layout = Some(Layout::Grid);
+        stress.respawn(&mut scene);
+        assert_eq!(scene.len(), 100);
+        stress.count = 40;
+        stress.layout = Some(Layout::Poisson);
+        stress.respawn(&mut scene);
+        assert_eq!((scene.len(), stress.spawned()), (40, 40));
+        stress.layout = None;
+        stress.respawn(&mut scene);
+        assert!(scene.is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use std::f32::consts::PI;
+
+use crate::math::{Color, Vector3};
+
+/// sun moving over the sky with the time of day, rising along +x and setting along -x
+pub struct Sun {
+    /// hours, 0 to 24
+    pub time_of_day: f32,
+    /// real seconds per simulated day
+    pub day_length: f32,
+    pub paused: bool,
+    /// angle of the sun's path from passing overhead, leaning towards +z
+    pub tilt: f32,
+}
+
+impl Sun {
+    pub fn new() -> Self {
+        Self {
+            time_of_day: 9.0,
+            day_length: 120.0,
+            paused: false,
+            tilt: 0.4,
+        }
+    }
+
+    pub fn advance(&mut self, delta_time: f32) {
+        if !self.paused {
+            self.scrub(24.0 * delta_time / self.day_length);
+        }
+    }
+
+    pub fn scrub(&mut self, hours: f32) {
+        self.time_of_day = (self.time_of_day + hours).rem_euclid(24.0);
+    }
+
+    /// unit vector from the scene towards the sun
+    pub fn to_sun(&self) -> Vector3 {
+        // 0 at sunrise, pi / 2 at noon
+        let angle = (self.time_of_day - 6.0) / 24.0 * 2.0 * PI;
+        let up = angle.sin();
+        Vector3::new(angle.cos(), up * self.tilt.cos(), -up * self.tilt.sin())
+    }
+
+    /// direction the light travels in
+    pub fn direction(&self) -> Vector3 {
+        -self.to_sun()
+    }
+
+    /// sine of the angle above the horizon
+    pub fn elevation(&self) -> f32 {
+        self.to_sun().y
+    }
+
+    /// kelvin, warm at the horizon and neutral once high enough
+    pub fn temperature(&self) -> f32 {
+        let t = (self.elevation() / 0.5).clamp(0.0, 1.0);
+        1900.0 + (6500.0 - 1900.0) * t
+    }
+
+    /// fades out around sunset, 0 at night
+    pub fn intensity(&self) -> f32 {
+        let t = ((self.elevation() + 0.05) / 0.2).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    /// linear light color, without the intensity
+    pub fn color(&self) -> Color {
+        kelvin_to_color(self.temperature())
+    }
+}
+
+/// linear color of a black body at `kelvin`, normalized so the brightest channel is about 1,
+/// after Tanner Helland's fit
+pub fn kelvin_to_color(kelvin: f32) -> Color {
+    let t = kelvin / 100.0;
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.69873 * (t - 60.0).powf(-0.13320476)
+    };
+    let g = if t <= 66.0 {
+        99.4708 * t.ln() - 161.11957
+    } else {
+        288.12217 * (t - 60.0).powf(-0.07551485)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (t - 10.0).ln() - 305.0448
+    };
+
+    // the fit is in srgb
+    let [r, g, b] = [r, g, b].map(|c| (c / 255.0).clamp(0.0, 1.0));
+    Color::from_srgb(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_is_up_at_noon_and_down_at_midnight() {
+        let mut sun = Sun::new();
+        sun.time_of_day = 12.0;
+        assert!(sun.elevation() > 0.9);
+        assert!(sun.intensity() > 0.99);
+
+        sun.scrub(12.0);
+        assert!(sun.time_of_day.abs() < 1e-5);
+        assert!(sun.elevation() < -0.9);
+        assert_eq!(sun.intensity(), 0.0);
+
+        // reddest near the horizon
+        sun.time_of_day = 6.5;
+        let color = sun.color();
+        assert!(color.r > color.b);
+    }
+}
@@ -0,0 +1,330 @@
+use wgpu::*;
+
+use crate::math::noise::{self, Fbm};
+use crate::math::{Aabb3, Vector2, Vector3};
+use crate::mesh::Mesh;
+use crate::texture::{TexelFormat, TextureData};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TerrainVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
+pub const TERRAIN_VERTEX_LAYOUT: VertexBufferLayout = crate::layout::vertex_layout!(TerrainVertex, Vertex, 0, [
+    position => Float32x3,
+    normal => Float32x3,
+]);
+
+/// heights on a regular grid in the xz plane, y is up
+pub struct Heightmap {
+    /// samples along x
+    pub width: usize,
+    /// samples along z
+    pub depth: usize,
+    /// distance between neighbouring samples
+    pub spacing: f32,
+    /// row major, x fastest
+    pub heights: Vec<f32>,
+}
+
+impl Heightmap {
+    /// hills of domain warped fbm, about `amplitude` high at most
+    pub fn generate(width: usize, depth: usize, spacing: f32, amplitude: f32) -> Self {
+        let fbm = Fbm::default();
+        // hills about this far apart
+        let feature_size = 24.0;
+        let mut heights = Vec::with_capacity(width * depth);
+        for z in 0..depth {
+            for x in 0..width {
+                let p = Vector2::new(x as f32, z as f32) * (spacing / feature_size);
+                heights.push(fbm.warped2(p, 0.8) * amplitude);
+            }
+        }
+        Self { width, depth, spacing, heights }
+    }
+
+    /// a binary pgm (P5) image, 8 or 16 bit, black at 0 and white at `amplitude`
+    pub fn from_pgm(bytes: &[u8], spacing: f32, amplitude: f32) -> Result<Self, String> {
+        // header fields are whitespace separated, with # comments up to the end of the line
+        let mut fields = vec![];
+        let mut i = 0;
+        while fields.len() < 4 {
+            match bytes.get(i) {
+                None => return Err("pgm header is truncated".to_string()),
+                Some(b'#') => {
+                    while bytes.get(i).is_some_and(|&b| b != b'\n') {
+                        i += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => i += 1,
+                Some(_) => {
+                    let start = i;
+                    while bytes.get(i).is_some_and(|b| !b.is_ascii_whitespace()) {
+                        i += 1;
+                    }
+                    fields.push(String::from_utf8_lossy(&bytes[start..i]).into_owned());
+                }
+            }
+        }
+        // a single whitespace byte separates the header from the samples
+        let samples = &bytes[i + 1..];
+
+        if fields[0] != "P5" {
+            return Err(format!("expected a binary pgm (P5), found `{}`", fields[0]));
+        }
+        let parse = |field: &str| field
+            .parse::<usize>()
+            .map_err(|e| format!("invalid pgm header field `{}`: {}", field, e));
+        let width = parse(&fields[1])?;
+        let depth = parse(&fields[2])?;
+        let max_value = parse(&fields[3])?;
+        let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+        if samples.len() < width * depth * bytes_per_sample {
+            return Err(format!("pgm has too few samples for {}x{}", width, depth));
+        }
+
+        let heights = (0..width * depth)
+            .map(|i| {
+                let value = if bytes_per_sample == 1 {
+                    samples[i] as f32
+                } else {
+                    // 16 bit samples are big endian
+                    u16::from_be_bytes([samples[2 * i], samples[2 * i + 1]]) as f32
+                };
+                value / max_value as f32 * amplitude
+            })
+            .collect();
+        Ok(Self { width, depth, spacing, heights })
+    }
+
+    /// clamped to the edges
+    pub fn height(&self, x: isize, z: isize) -> f32 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let z = z.clamp(0, self.depth as isize - 1) as usize;
+        self.heights[z * self.width + x]
+    }
+
+    /// bilinear between the samples, `x` and `z` in world units from the first sample,
+    /// clamped to the edges
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        let (x, z) = (x / self.spacing, z / self.spacing);
+        let (x0, z0) = (x.floor(), z.floor());
+        let (tx, tz) = (x - x0, z - z0);
+        let (x0, z0) = (x0 as isize, z0 as isize);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        lerp(
+            lerp(self.height(x0, z0), self.height(x0 + 1, z0), tx),
+            lerp(self.height(x0, z0 + 1), self.height(x0 + 1, z0 + 1), tx),
+            tz,
+        )
+    }
+
+    /// from central differences
+    pub fn normal(&self, x: isize, z: isize) -> Vector3 {
+        let dx = (self.height(x + 1, z) - self.height(x - 1, z)) / (2.0 * self.spacing);
+        let dz = (self.height(x, z + 1) - self.height(x, z - 1)) / (2.0 * self.spacing);
+        let normal = Vector3::new(-dx, 1.0, -dz);
+        normal.normalized()
+    }
+
+    /// world space triangles of the whole grid with its first sample at `origin`, for the cpu
+    pub fn triangles(&self, origin: Vector3) -> Vec<[Vector3; 3]> {
+        let point = |x: usize, z: usize| origin + Vector3::new(
+            x as f32 * self.spacing,
+            self.height(x as isize, z as isize),
+            z as f32 * self.spacing,
+        );
+        (0..self.depth - 1)
+            .flat_map(|z| (0..self.width - 1).map(move |x| (x, z)))
+            .flat_map(|(x, z)| [
+                // wound like `ChunkGeometry::grid`'s
+                [point(x, z), point(x + 1, z), point(x, z + 1)],
+                [point(x + 1, z), point(x + 1, z + 1), point(x, z + 1)],
+            ])
+            .collect()
+    }
+}
+
+/// cpu side of a chunk, so it can be built away from the device
+pub struct ChunkGeometry {
+    pub vertices: Vec<TerrainVertex>,
+    pub indices: Vec<u32>,
+    /// world space
+    pub bounds: Aabb3,
+}
+
+impl ChunkGeometry {
+    /// grid of `quads_x` by `quads_z` cells, `sample` gives the world position and normal
+    /// of the grid point at x, z, which range from 0 to `quads_x` and `quads_z` inclusive
+    pub fn grid(
+        quads_x: usize,
+        quads_z: usize,
+        sample: impl Fn(usize, usize) -> (Vector3, Vector3),
+    ) -> Self {
+        let mut vertices = Vec::with_capacity((quads_x + 1) * (quads_z + 1));
+        let mut bounds = Aabb3::EMPTY;
+        for z in 0..=quads_z {
+            for x in 0..=quads_x {
+                let (position, normal) = sample(x, z);
+                bounds = bounds.extend(position);
+                vertices.push(TerrainVertex {
+                    position: [position.x, position.y, position.z],
+                    normal: [normal.x, normal.y, normal.z],
+                });
+            }
+        }
+
+        let row = quads_x as u32 + 1;
+        let mut indices = Vec::with_capacity(quads_x * quads_z * 6);
+        for z in 0..quads_z as u32 {
+            for x in 0..quads_x as u32 {
+                let i = z * row + x;
+                // wound like the cube's top face, so it's front facing from above
+                indices.extend_from_slice(&[i, i + 1, i + row, i + 1, i + row + 1, i + row]);
+            }
+        }
+
+        Self {
+            vertices,
+            indices,
+            bounds,
+        }
+    }
+
+    pub fn translate(&mut self, by: Vector3) {
+        for vertex in &mut self.vertices {
+            let [x, y, z] = &mut vertex.position;
+            (*x, *y, *z) = (*x + by.x, *y + by.y, *z + by.z);
+        }
+        self.bounds = Aabb3::new(self.bounds.min + by, self.bounds.max + by);
+    }
+}
+
+pub struct Chunk {
+    pub mesh: Mesh,
+    /// world space
+    pub bounds: Aabb3,
+}
+
+impl Chunk {
+    pub fn new(device: &Device, geometry: &ChunkGeometry) -> Self {
+        Self {
+            mesh: Mesh::new(device, "Terrain chunk", &geometry.vertices, &geometry.indices),
+            bounds: geometry.bounds,
+        }
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        self.mesh.bind(pass);
+        pass.draw_indexed(0..self.mesh.index_count, 0, 0..1);
+    }
+}
+
+/// a heightmap split into square chunks of grid meshes, each culled on its own.
+/// drawn with `vs_terrain` of the light and shadow shaders, with no instance buffer.
+pub struct Terrain {
+    pub chunks: Vec<Chunk>,
+    /// world position of the heightmap's first sample
+    pub origin: Vector3,
+    chunk_quads: usize,
+}
+
+impl Terrain {
+    /// `chunk_quads` is the number of grid cells along each side of a chunk
+    pub fn new(device: &Device, heightmap: &Heightmap, origin: Vector3, chunk_quads: usize) -> Self {
+        let mut chunks = vec![];
+        for chunk_z in (0..heightmap.depth - 1).step_by(chunk_quads) {
+            for chunk_x in (0..heightmap.width - 1).step_by(chunk_quads) {
+                let quads_x = chunk_quads.min(heightmap.width - 1 - chunk_x);
+                let quads_z = chunk_quads.min(heightmap.depth - 1 - chunk_z);
+
+                let geometry = ChunkGeometry::grid(quads_x, quads_z, |x, z| {
+                    let (x, z) = ((chunk_x + x) as isize, (chunk_z + z) as isize);
+                    let position = origin + Vector3::new(
+                        x as f32 * heightmap.spacing,
+                        heightmap.height(x, z),
+                        z as f32 * heightmap.spacing,
+                    );
+                    (position, heightmap.normal(x, z))
+                });
+                chunks.push(Chunk::new(device, &geometry));
+            }
+        }
+
+        Self { chunks, origin, chunk_quads }
+    }
+
+    /// rebuilds the chunks moved by `by`, `heightmap` being the one it was built from
+    pub fn translate(&mut self, device: &Device, heightmap: &Heightmap, by: Vector3) {
+        *self = Self::new(device, heightmap, self.origin + by, self.chunk_quads);
+    }
+
+    /// draws the chunks for which `visible` is true, with the pipeline already set
+    pub fn draw<'a>(&'a self, pass: &mut RenderPass<'a>, visible: impl Fn(&Chunk) -> bool) {
+        for chunk in self.chunks.iter().filter(|chunk| visible(chunk)) {
+            chunk.draw(pass);
+        }
+    }
+}
+
+/// grey detail multiplied into the terrain's albedo, 0.5 on average: 4 by 4 tiles of
+/// speckled stone with dark seams, fine enough to show how it's filtered at grazing angles.
+/// repeats seamlessly, the texel noise doesn't depend on its neighbours.
+pub fn detail_texture(size: u32) -> TextureData {
+    let tile_size = size / 4;
+    let mut texels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let random = |v: u32| noise::hash(v) as f32 / u32::MAX as f32;
+            let tile = random(x / tile_size + 4 * (y / tile_size) + 0x9E37);
+            let speckle = random(x + size * y);
+            let seam = x % tile_size < 2 || y % tile_size < 2;
+
+            let value = if seam { 0.25 } else { 0.5 + 0.2 * (tile - 0.5) + 0.15 * (speckle - 0.5) };
+            let value = (value * 255.0) as u8;
+            texels.extend_from_slice(&[value, value, value, 255]);
+        }
+    }
+
+    TextureData {
+        width: size,
+        height: size,
+        format: TexelFormat::Rgba8,
+        srgb: false,
+        levels: vec![texels],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pgm_heightmap_is_parsed() {
+        let mut bytes = b"P5\n# comment\n3 2\n255\n".to_vec();
+        bytes.extend_from_slice(&[0, 51, 255, 255, 0, 102]);
+        let heightmap = Heightmap::from_pgm(&bytes, 1.0, 10.0).unwrap();
+        assert_eq!((heightmap.width, heightmap.depth), (3, 2));
+        assert_eq!(heightmap.height(1, 0), 2.0);
+        assert_eq!(heightmap.height(2, 1), 4.0);
+        // clamped outside
+        assert_eq!(heightmap.height(5, -1), 10.0);
+        assert_eq!(heightmap.height_at(1.5, 0.5), 0.25 * (2.0 + 10.0 + 0.0 + 4.0));
+
+        assert!(Heightmap::from_pgm(b"P2\n1 1\n255\n0", 1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn chunk_behind_camera_is_culled() {
+        let grid = |z: f32| ChunkGeometry::grid(2, 2, |x, y| {
+            (Vector3::new(x as f32 - 1.0, y as f32 - 1.0, z), Vector3::new(0.0, 1.0, 0.0))
+        });
+        let frustum = crate::math::Frustum::new(&crate::math::Affine3::IDENTITY, 0.1, None);
+        let ahead = grid(5.0).bounds;
+        assert_eq!(ahead, Aabb3::new(Vector3::new(-1.0, -1.0, 5.0), Vector3::new(1.0, 1.0, 5.0)));
+        assert!(frustum.intersects_aabb(&ahead));
+        assert!(!frustum.intersects_aabb(&grid(-5.0).bounds));
+    }
+}
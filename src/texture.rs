@@ -0,0 +1,531 @@
+use wgpu::*;
+
+use crate::mipmap::{self, MipmapGenerator};
+
+pub mod bc;
+
+/// how the texels of `TextureData` are stored
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TexelFormat {
+    Rgba8,
+    /// rgb with 1 bit alpha
+    Bc1,
+    /// rgba
+    Bc3,
+    /// rg, for normal maps
+    Bc5,
+    /// rgba, best quality of the same size as bc3
+    Bc7,
+}
+
+impl TexelFormat {
+    /// 4 for the block compressed formats
+    pub fn block_size(self) -> u32 {
+        match self {
+            TexelFormat::Rgba8 => 1,
+            _ => 4,
+        }
+    }
+
+    pub fn block_bytes(self) -> u32 {
+        match self {
+            TexelFormat::Rgba8 => 4,
+            TexelFormat::Bc1 => 8,
+            TexelFormat::Bc3 | TexelFormat::Bc5 | TexelFormat::Bc7 => 16,
+        }
+    }
+
+    /// bc5 has no srgb variant, it's never color data
+    pub fn wgpu_format(self, srgb: bool) -> TextureFormat {
+        match (self, srgb) {
+            (TexelFormat::Rgba8, false) => TextureFormat::Rgba8Unorm,
+            (TexelFormat::Rgba8, true) => TextureFormat::Rgba8UnormSrgb,
+            (TexelFormat::Bc1, false) => TextureFormat::Bc1RgbaUnorm,
+            (TexelFormat::Bc1, true) => TextureFormat::Bc1RgbaUnormSrgb,
+            (TexelFormat::Bc3, false) => TextureFormat::Bc3RgbaUnorm,
+            (TexelFormat::Bc3, true) => TextureFormat::Bc3RgbaUnormSrgb,
+            (TexelFormat::Bc5, _) => TextureFormat::Bc5RgUnorm,
+            (TexelFormat::Bc7, false) => TextureFormat::Bc7RgbaUnorm,
+            (TexelFormat::Bc7, true) => TextureFormat::Bc7RgbaUnormSrgb,
+        }
+    }
+
+    fn decode_block(self, block: &[u8]) -> bc::Block {
+        match self {
+            TexelFormat::Rgba8 => unreachable!("rgba8 isn't block compressed"),
+            TexelFormat::Bc1 => bc::bc1(block),
+            TexelFormat::Bc3 => bc::bc3(block),
+            TexelFormat::Bc5 => bc::bc5(block),
+            TexelFormat::Bc7 => bc::bc7(block),
+        }
+    }
+}
+
+/// which formats the device can sample, queried once so every upload doesn't need the adapter
+pub struct FormatSupport {
+    supported: Vec<TextureFormat>,
+    /// without it wgpu clamps the anisotropy of samplers to 1
+    pub anisotropic_filtering: bool,
+}
+
+impl FormatSupport {
+    /// a format is supported when the device has its features and the adapter can sample it
+    pub fn new(adapter: &Adapter, device: &Device) -> Self {
+        let formats = [
+            TexelFormat::Rgba8,
+            TexelFormat::Bc1,
+            TexelFormat::Bc3,
+            TexelFormat::Bc5,
+            TexelFormat::Bc7,
+        ];
+        let supported = formats
+            .iter()
+            .flat_map(|format| [format.wgpu_format(false), format.wgpu_format(true)])
+            .filter(|format| {
+                let usages = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
+                device.features().contains(format.required_features())
+                    && adapter.get_texture_format_features(*format).allowed_usages.contains(usages)
+            })
+            .collect();
+        let anisotropic_filtering = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::ANISOTROPIC_FILTERING);
+        Self {
+            supported,
+            anisotropic_filtering,
+        }
+    }
+
+    pub fn supports(&self, format: TextureFormat) -> bool {
+        self.supported.contains(&format)
+    }
+}
+
+/// a 2d texture as stored in a file, with all of its mip levels
+#[derive(Clone, Debug)]
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub format: TexelFormat,
+    pub srgb: bool,
+    /// from the largest, each tightly packed rows of blocks
+    pub levels: Vec<Vec<u8>>,
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "header is truncated".to_string())
+}
+
+/// more levels than a mip chain of a u32 sized texture has are a malformed header
+const MAX_LEVELS: u32 = 32;
+
+fn u64_at(bytes: &[u8], offset: usize) -> Result<u64, String> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "header is truncated".to_string())
+}
+
+impl TextureData {
+    /// ktx2 or dds, told apart by their magic
+    pub fn load(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.starts_with(b"DDS ") {
+            Self::from_dds(bytes)
+        } else {
+            Self::from_ktx2(bytes)
+        }
+    }
+
+    /// a 2d ktx2 texture without supercompression
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self, String> {
+        const IDENTIFIER: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+        if !bytes.starts_with(&IDENTIFIER) {
+            return Err("not a ktx2 file".to_string());
+        }
+
+        let (format, srgb) = match u32_at(bytes, 12)? {
+            37 => (TexelFormat::Rgba8, false),
+            43 => (TexelFormat::Rgba8, true),
+            131 | 133 => (TexelFormat::Bc1, false),
+            132 | 134 => (TexelFormat::Bc1, true),
+            137 => (TexelFormat::Bc3, false),
+            138 => (TexelFormat::Bc3, true),
+            141 => (TexelFormat::Bc5, false),
+            145 => (TexelFormat::Bc7, false),
+            146 => (TexelFormat::Bc7, true),
+            vk_format => return Err(format!("unsupported ktx2 vkFormat {}", vk_format)),
+        };
+        let width = u32_at(bytes, 20)?;
+        let height = u32_at(bytes, 24)?;
+        if u32_at(bytes, 28)? > 0 || u32_at(bytes, 32)? > 1 || u32_at(bytes, 36)? != 1 {
+            return Err("only 2d ktx2 textures are supported, not 3d, arrays or cubemaps".to_string());
+        }
+        let level_count = u32_at(bytes, 40)?.max(1);
+        if level_count > MAX_LEVELS {
+            return Err(format!("ktx2 texture has {} levels, at most {} are possible", level_count, MAX_LEVELS));
+        }
+        if u32_at(bytes, 44)? != 0 {
+            return Err("supercompressed ktx2 textures aren't supported".to_string());
+        }
+
+        let mut texture = Self {
+            width,
+            height,
+            format,
+            srgb,
+            levels: vec![],
+        };
+        // the level index follows the 80 byte header, with offsets from the start of the file
+        for level in 0..level_count {
+            let entry = 80 + 24 * level as usize;
+            let offset = u64_at(bytes, entry)?;
+            let length = u64_at(bytes, entry + 8)?;
+            let expected = texture.level_bytes(level)?;
+            if length != expected as u64 {
+                return Err(format!("ktx2 level {} has {} bytes, expected {}", level, length, expected));
+            }
+            let data = usize::try_from(offset)
+                .ok()
+                .and_then(|offset| bytes.get(offset..offset.checked_add(expected)?))
+                .ok_or_else(|| format!("ktx2 level {} is truncated", level))?;
+            texture.levels.push(data.to_vec());
+        }
+        Ok(texture)
+    }
+
+    /// a 2d dds texture, bc1, bc3 and bc5 by four cc or any format here through the dx10 header
+    pub fn from_dds(bytes: &[u8]) -> Result<Self, String> {
+        if !bytes.starts_with(b"DDS ") {
+            return Err("not a dds file".to_string());
+        }
+
+        const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+        const DDSCAPS2_CUBEMAP: u32 = 0x200;
+        let height = u32_at(bytes, 12)?;
+        let width = u32_at(bytes, 16)?;
+        let level_count = if u32_at(bytes, 8)? & DDSD_MIPMAPCOUNT != 0 {
+            u32_at(bytes, 28)?.max(1)
+        } else {
+            1
+        };
+        if level_count > MAX_LEVELS {
+            return Err(format!("dds texture has {} levels, at most {} are possible", level_count, MAX_LEVELS));
+        }
+        if u32_at(bytes, 112)? & DDSCAPS2_CUBEMAP != 0 {
+            return Err("dds cubemaps aren't supported".to_string());
+        }
+
+        let (format, srgb, data_offset) = match &bytes[84..88] {
+            b"DXT1" => (TexelFormat::Bc1, false, 128),
+            b"DXT5" => (TexelFormat::Bc3, false, 128),
+            b"ATI2" | b"BC5U" => (TexelFormat::Bc5, false, 128),
+            b"DX10" => {
+                let (format, srgb) = match u32_at(bytes, 128)? {
+                    28 => (TexelFormat::Rgba8, false),
+                    29 => (TexelFormat::Rgba8, true),
+                    71 => (TexelFormat::Bc1, false),
+                    72 => (TexelFormat::Bc1, true),
+                    77 => (TexelFormat::Bc3, false),
+                    78 => (TexelFormat::Bc3, true),
+                    83 => (TexelFormat::Bc5, false),
+                    98 => (TexelFormat::Bc7, false),
+                    99 => (TexelFormat::Bc7, true),
+                    dxgi_format => return Err(format!("unsupported dds dxgi format {}", dxgi_format)),
+                };
+                if u32_at(bytes, 140)? > 1 {
+                    return Err("dds texture arrays aren't supported".to_string());
+                }
+                (format, srgb, 148)
+            }
+            four_cc => return Err(format!("unsupported dds four cc `{}`", String::from_utf8_lossy(four_cc))),
+        };
+
+        let mut texture = Self {
+            width,
+            height,
+            format,
+            srgb,
+            levels: vec![],
+        };
+        // levels are stored one after another
+        let mut offset: usize = data_offset;
+        for level in 0..level_count {
+            let length = texture.level_bytes(level)?;
+            let data = offset
+                .checked_add(length)
+                .and_then(|end| bytes.get(offset..end))
+                .ok_or_else(|| format!("dds level {} is truncated", level))?;
+            texture.levels.push(data.to_vec());
+            offset += length;
+        }
+        Ok(texture)
+    }
+
+    pub fn level_size(&self, level: u32) -> (u32, u32) {
+        let halve = |size: u32| size.checked_shr(level).unwrap_or(0).max(1);
+        (halve(self.width), halve(self.height))
+    }
+
+    /// blocks along x and y, partial blocks at the edges count as whole ones
+    fn level_blocks(&self, level: u32) -> (u32, u32) {
+        let (width, height) = self.level_size(level);
+        let block_size = self.format.block_size();
+        (width.div_ceil(block_size), height.div_ceil(block_size))
+    }
+
+    /// fails when the size in the header doesn't fit in memory
+    fn level_bytes(&self, level: u32) -> Result<usize, String> {
+        let (blocks_x, blocks_y) = self.level_blocks(level);
+        (blocks_x as u64)
+            .checked_mul(blocks_y as u64)
+            .and_then(|blocks| blocks.checked_mul(self.format.block_bytes() as u64))
+            .and_then(|bytes| usize::try_from(bytes).ok())
+            .ok_or_else(|| format!("level {} of a {} by {} texture is too large", level, self.width, self.height))
+    }
+
+    /// every level decoded to rgba8
+    pub fn decompress(&self) -> Self {
+        if self.format == TexelFormat::Rgba8 {
+            return self.clone();
+        }
+
+        let levels = (0..self.levels.len() as u32)
+            .map(|level| {
+                let (width, height) = self.level_size(level);
+                let (blocks_x, _) = self.level_blocks(level);
+                let mut rgba = vec![0; width as usize * height as usize * 4];
+                let block_bytes = self.format.block_bytes() as usize;
+                for (i, block) in self.levels[level as usize].chunks_exact(block_bytes).enumerate() {
+                    let (block_x, block_y) = (i as u32 % blocks_x * 4, i as u32 / blocks_x * 4);
+                    let texels = self.format.decode_block(block);
+                    for (j, texel) in texels.iter().enumerate() {
+                        let (x, y) = (block_x + j as u32 % 4, block_y + j as u32 / 4);
+                        // edge blocks of textures that aren't a multiple of 4 hang over
+                        if x < width && y < height {
+                            let offset = (y as usize * width as usize + x as usize) * 4;
+                            rgba[offset..offset + 4].copy_from_slice(texel);
+                        }
+                    }
+                }
+                rgba
+            })
+            .collect();
+
+        Self {
+            format: TexelFormat::Rgba8,
+            levels,
+            ..*self
+        }
+    }
+
+    /// uploads the texture as stored when `support` allows it, and decompressed otherwise,
+    /// which also covers block compressed textures whose size isn't a multiple of 4.
+    /// rgba8 textures with a single level get a full mip chain from `mipmaps`,
+    /// block compressed ones can't be rendered to and keep the levels they were stored with.
+    pub fn upload(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        support: &FormatSupport,
+        mipmaps: &mut MipmapGenerator,
+        label: &str,
+    ) -> TextureAsset {
+        let block_size = self.format.block_size();
+        let fits_blocks = self.width.is_multiple_of(block_size) && self.height.is_multiple_of(block_size);
+        if !support.supports(self.format.wgpu_format(self.srgb)) || !fits_blocks {
+            assert_ne!(self.format, TexelFormat::Rgba8, "rgba8 textures are always supported");
+            let mut asset = self.decompress().upload(device, queue, support, mipmaps, label);
+            asset.decompressed = true;
+            return asset;
+        }
+
+        let format = self.format.wgpu_format(self.srgb);
+        let generate_mipmaps = self.format == TexelFormat::Rgba8 && self.levels.len() == 1;
+        let (mip_level_count, usage) = if generate_mipmaps {
+            (
+                mipmap::level_count(self.width, self.height),
+                TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT,
+            )
+        } else {
+            (self.levels.len() as u32, TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST)
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        for (level, data) in self.levels.iter().enumerate() {
+            let (blocks_x, blocks_y) = self.level_blocks(level as u32);
+            queue.write_texture(
+                ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                data,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(blocks_x * self.format.block_bytes()),
+                    rows_per_image: Some(blocks_y),
+                },
+                // levels smaller than a block are still copied as whole blocks
+                Extent3d {
+                    width: blocks_x * block_size,
+                    height: blocks_y * block_size,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        if generate_mipmaps {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("mipmap encoder"),
+            });
+            mipmaps.generate(device, &mut encoder, &texture);
+            queue.submit([encoder.finish()]);
+        }
+
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        TextureAsset {
+            texture,
+            view,
+            format,
+            decompressed: false,
+        }
+    }
+}
+
+/// how textures are filtered when sampled
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Filtering {
+    /// within the nearest mip level
+    Bilinear,
+    /// also blends between the two nearest mip levels
+    Trilinear,
+    /// trilinear with up to this many samples along the direction the texture is stretched in,
+    /// from 1 to 16
+    Anisotropic(u16),
+}
+
+impl Filtering {
+    const ALL: [Filtering; 6] = [
+        Self::Bilinear,
+        Self::Trilinear,
+        Self::Anisotropic(2),
+        Self::Anisotropic(4),
+        Self::Anisotropic(8),
+        Self::Anisotropic(16),
+    ];
+
+    /// from bilinear up through the anisotropy levels, wrapping around
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&f| f == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    /// repeating samplers, anisotropy is ignored when `support` doesn't allow it
+    pub fn create_sampler(self, device: &Device, support: &FormatSupport, label: &str) -> Sampler {
+        let mipmap_filter = match self {
+            Filtering::Bilinear => FilterMode::Nearest,
+            _ => FilterMode::Linear,
+        };
+        let anisotropy_clamp = match self {
+            Filtering::Anisotropic(samples) if support.anisotropic_filtering => samples.clamp(1, 16),
+            _ => 1,
+        };
+        device.create_sampler(&SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter,
+            anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+}
+
+/// a texture uploaded from `TextureData`
+pub struct TextureAsset {
+    #[allow(dead_code)]
+    pub texture: Texture,
+    pub view: TextureView,
+    #[allow(dead_code)]
+    pub format: TextureFormat,
+    /// whether it had to be decompressed for the device
+    pub decompressed: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dds_bc1_is_parsed_and_decompressed() {
+        let mut bytes = vec![0; 128];
+        bytes[..4].copy_from_slice(b"DDS ");
+        bytes[12..16].copy_from_slice(&6u32.to_le_bytes());
+        bytes[16..20].copy_from_slice(&5u32.to_le_bytes());
+        bytes[84..88].copy_from_slice(b"DXT1");
+        // 2x2 blocks of pure red, every texel on the first endpoint
+        for _ in 0..4 {
+            bytes.extend_from_slice(&[0x00, 0xF8, 0x00, 0x00, 0, 0, 0, 0]);
+        }
+
+        let texture = TextureData::load(&bytes).unwrap();
+        assert_eq!((texture.width, texture.height, texture.format), (5, 6, TexelFormat::Bc1));
+        let rgba = texture.decompress();
+        assert_eq!(rgba.levels[0].len(), 5 * 6 * 4);
+        assert!(rgba.levels[0].chunks(4).all(|texel| texel == [255, 0, 0, 255]));
+
+        bytes.truncate(140);
+        assert!(TextureData::from_dds(&bytes).is_err());
+        assert!(TextureData::load(b"not a texture").is_err());
+    }
+
+    #[test]
+    fn malformed_headers_are_errors() {
+        let mut bytes = vec![0; 128];
+        bytes[..4].copy_from_slice(b"DDS ");
+        bytes[8..12].copy_from_slice(&0x20000u32.to_le_bytes());
+        bytes[12..16].copy_from_slice(&4u32.to_le_bytes());
+        bytes[16..20].copy_from_slice(&4u32.to_le_bytes());
+        bytes[28..32].copy_from_slice(&40u32.to_le_bytes());
+        bytes[84..88].copy_from_slice(b"DXT1");
+        assert!(TextureData::from_dds(&bytes).unwrap_err().contains("levels"));
+
+        // a single level larger than memory
+        bytes[12..20].copy_from_slice(&[u32::MAX.to_le_bytes(), u32::MAX.to_le_bytes()].concat());
+        bytes[28..32].copy_from_slice(&1u32.to_le_bytes());
+        bytes[84..88].copy_from_slice(b"DX10");
+        bytes.extend_from_slice(&[0; 20]);
+        bytes[128..132].copy_from_slice(&28u32.to_le_bytes());
+        assert!(TextureData::from_dds(&bytes).is_err());
+
+        let mut ktx2 = vec![0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+        ktx2.resize(80, 0);
+        for (offset, value) in [(12, 37u32), (20, 4), (24, 4), (36, 1), (40, 40)] {
+            ktx2[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        assert!(TextureData::from_ktx2(&ktx2).unwrap_err().contains("levels"));
+
+        let data = TextureData { width: 1, height: 1, format: TexelFormat::Rgba8, srgb: false, levels: vec![] };
+        assert_eq!(data.level_size(40), (1, 1));
+    }
+}
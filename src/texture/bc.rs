@@ -0,0 +1,259 @@
+//! software decoders of 4x4 BCn blocks, for devices that can't sample them
+
+/// texels of a block in rows, rgba
+pub type Block = [[u8; 4]; 16];
+
+fn expand_565(color: u16) -> [u8; 3] {
+    let r = (color >> 11) as u8 & 31;
+    let g = (color >> 5) as u8 & 63;
+    let b = color as u8 & 31;
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+/// `opaque` is set for the color half of bc3, which is always in 4 color mode
+fn bc1_colors(block: &[u8], opaque: bool) -> Block {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (expand_565(c0), expand_565(c1));
+
+    let mix = |w0: u32, w1: u32, divisor: u32| {
+        let mut color = [255; 4];
+        for c in 0..3 {
+            color[c] = ((w0 * e0[c] as u32 + w1 * e1[c] as u32) / divisor) as u8;
+        }
+        color
+    };
+    let palette = if c0 > c1 || opaque {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(2, 1, 3), mix(1, 2, 3)]
+    } else {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(1, 1, 2), [0; 4]]
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[(indices >> (2 * i)) as usize & 3])
+}
+
+/// a single channel
+fn bc4(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for (i, value) in palette.iter_mut().enumerate().skip(2) {
+            *value = ((8 - i as u32) * a0 + (i as u32 - 1) * a1) / 7;
+        }
+    } else {
+        for (i, value) in palette.iter_mut().enumerate().take(6).skip(2) {
+            *value = ((6 - i as u32) * a0 + (i as u32 - 1) * a1) / 5;
+        }
+    }
+
+    let mut indices = [0; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+    std::array::from_fn(|i| palette[(indices >> (3 * i)) as usize & 7] as u8)
+}
+
+pub fn bc1(block: &[u8]) -> Block {
+    bc1_colors(block, false)
+}
+
+/// bc4 alpha followed by opaque bc1 colors
+pub fn bc3(block: &[u8]) -> Block {
+    let alpha = bc4(&block[..8]);
+    let mut texels = bc1_colors(&block[8..], true);
+    for (texel, alpha) in texels.iter_mut().zip(alpha) {
+        texel[3] = alpha;
+    }
+    texels
+}
+
+/// two bc4 channels, sampled as red and green with blue 0 like `Bc5RgUnorm`
+pub fn bc5(block: &[u8]) -> Block {
+    let (red, green) = (bc4(&block[..8]), bc4(&block[8..]));
+    std::array::from_fn(|i| [red[i], green[i], 0, 255])
+}
+
+/// subset of each texel of the 2 subset partitions, 2 bits per texel
+const PARTITIONS_2: [u32; 64] = [
+    0x50505050, 0x40404040, 0x54545454, 0x54505040, 0x50404000, 0x55545450, 0x55545040, 0x54504000,
+    0x50400000, 0x55555450, 0x55544000, 0x54400000, 0x55555440, 0x55550000, 0x55555500, 0x55000000,
+    0x55150100, 0x00004054, 0x15010000, 0x00405054, 0x00004050, 0x15050100, 0x05010000, 0x40505054,
+    0x00404050, 0x05010100, 0x14141414, 0x05141450, 0x01155440, 0x00555500, 0x15014054, 0x05414150,
+    0x44444444, 0x55005500, 0x11441144, 0x05055050, 0x05500550, 0x11114444, 0x41144114, 0x44111144,
+    0x15055054, 0x01055040, 0x05041050, 0x05455150, 0x14414114, 0x50050550, 0x41411414, 0x00141400,
+    0x00041504, 0x00105410, 0x10541000, 0x04150400, 0x50410514, 0x41051450, 0x05415014, 0x14054150,
+    0x41050514, 0x41505014, 0x40011554, 0x54150140, 0x50505500, 0x00555050, 0x15151010, 0x54540404,
+];
+
+/// texel whose index of the second subset has its top bit implied, the first subset's is texel 0
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+const PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+const ANCHORS_3: [[u8; 2]; 64] = [
+    [3, 15], [3, 8], [15, 8], [15, 3], [8, 15], [3, 15], [15, 3], [15, 8],
+    [8, 15], [8, 15], [6, 15], [6, 15], [6, 15], [5, 15], [3, 15], [3, 8],
+    [3, 15], [3, 8], [8, 15], [15, 3], [3, 15], [3, 8], [6, 15], [10, 8],
+    [5, 3], [8, 15], [8, 6], [6, 10], [8, 15], [5, 15], [15, 10], [15, 8],
+    [8, 15], [15, 3], [3, 15], [5, 10], [6, 10], [10, 8], [8, 9], [15, 10],
+    [15, 6], [3, 15], [15, 8], [5, 15], [15, 3], [15, 6], [15, 6], [15, 8],
+    [3, 15], [15, 3], [5, 15], [5, 15], [5, 15], [8, 15], [5, 15], [10, 15],
+    [5, 15], [10, 15], [8, 15], [13, 15], [15, 3], [12, 15], [3, 15], [3, 8],
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// per mode: subsets, partition bits, rotation bits, index selection bits, color bits,
+/// alpha bits, p bits per endpoint, p bits per subset, index bits, second index bits
+const MODES: [[u32; 10]; 8] = [
+    [3, 4, 0, 0, 4, 0, 1, 0, 3, 0],
+    [2, 6, 0, 0, 6, 0, 0, 1, 3, 0],
+    [3, 6, 0, 0, 5, 0, 0, 0, 2, 0],
+    [2, 6, 0, 0, 7, 0, 1, 0, 2, 0],
+    [1, 0, 2, 1, 5, 6, 0, 0, 2, 3],
+    [1, 0, 2, 0, 7, 8, 0, 0, 2, 2],
+    [1, 0, 0, 0, 7, 7, 1, 0, 4, 0],
+    [2, 6, 0, 0, 5, 5, 1, 0, 2, 0],
+];
+
+/// reads a block's bits from the least significant
+struct Bits(u128);
+
+impl Bits {
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.0 & ((1 << count) - 1)) as u32;
+        self.0 >>= count;
+        value
+    }
+}
+
+pub fn bc7(block: &[u8]) -> Block {
+    let mut bits = Bits(u128::from_le_bytes(block.try_into().unwrap()));
+    // the mode is the number of zeros before the first set bit
+    let mode = bits.0.trailing_zeros() as usize;
+    if mode >= 8 {
+        return [[0; 4]; 16];
+    }
+    bits.read(mode as u32 + 1);
+
+    let [
+        subsets,
+        partition_bits,
+        rotation_bits,
+        selection_bits,
+        color_bits,
+        alpha_bits,
+        endpoint_p_bits,
+        subset_p_bits,
+        index_bits,
+        index_bits2,
+    ] = MODES[mode];
+    let partition = bits.read(partition_bits) as usize;
+    let rotation = bits.read(rotation_bits);
+    let selection = bits.read(selection_bits);
+
+    let endpoint_count = 2 * subsets as usize;
+    let mut endpoints = [[0u32; 4]; 6];
+    for c in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[c] = bits.read(color_bits);
+        }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        endpoint[3] = bits.read(alpha_bits);
+    }
+
+    let mut p_bits = [0; 6];
+    if endpoint_p_bits != 0 {
+        for p in &mut p_bits[..endpoint_count] {
+            *p = bits.read(1);
+        }
+    } else if subset_p_bits != 0 {
+        for pair in p_bits[..endpoint_count].chunks_mut(2) {
+            pair.fill(bits.read(1));
+        }
+    }
+    let has_p_bits = endpoint_p_bits + subset_p_bits != 0;
+
+    // to 8 bits, with the top bits repeated in the new low bits
+    for (endpoint, p) in endpoints[..endpoint_count].iter_mut().zip(p_bits) {
+        for (c, value) in endpoint.iter_mut().enumerate() {
+            let mut precision = if c < 3 { color_bits } else { alpha_bits };
+            if precision == 0 {
+                *value = 255;
+                continue;
+            }
+            if has_p_bits {
+                *value = *value << 1 | p;
+                precision += 1;
+            }
+            *value <<= 8 - precision;
+            *value |= *value >> precision;
+        }
+    }
+
+    let subset = |i: usize| match subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition] >> (2 * i)) as usize & 3,
+        _ => (PARTITIONS_3[partition] >> (2 * i)) as usize & 3,
+    };
+    // the top bit of each subset's first index is implied to be 0
+    let is_anchor = |i: usize| i == 0 || match subsets {
+        1 => false,
+        2 => i == ANCHORS_2[partition] as usize,
+        _ => ANCHORS_3[partition].contains(&(i as u8)),
+    };
+
+    let indices: [u32; 16] = std::array::from_fn(|i| bits.read(index_bits - is_anchor(i) as u32));
+    let indices2: [u32; 16] = if index_bits2 == 0 {
+        indices
+    } else {
+        std::array::from_fn(|i| bits.read(index_bits2 - (i == 0) as u32))
+    };
+
+    let weights = |index_bits: u32| match index_bits {
+        2 => &WEIGHTS_2[..],
+        3 => &WEIGHTS_3[..],
+        _ => &WEIGHTS_4[..],
+    };
+    let (color_weights, alpha_weights, color_indices, alpha_indices) = match (index_bits2, selection) {
+        (0, _) => (weights(index_bits), weights(index_bits), indices, indices),
+        (_, 0) => (weights(index_bits), weights(index_bits2), indices, indices2),
+        _ => (weights(index_bits2), weights(index_bits), indices2, indices),
+    };
+
+    std::array::from_fn(|i| {
+        let s = subset(i);
+        let (e0, e1) = (endpoints[2 * s], endpoints[2 * s + 1]);
+        let interpolate = |c: usize, weight: u32| ((64 - weight) * e0[c] + weight * e1[c] + 32) >> 6;
+
+        let color_weight = color_weights[color_indices[i] as usize];
+        let alpha_weight = alpha_weights[alpha_indices[i] as usize];
+        let mut texel = [
+            interpolate(0, color_weight) as u8,
+            interpolate(1, color_weight) as u8,
+            interpolate(2, color_weight) as u8,
+            interpolate(3, alpha_weight) as u8,
+        ];
+        if rotation != 0 {
+            texel.swap(3, rotation as usize - 1);
+        }
+        texel
+    })
+}
@@ -0,0 +1,203 @@
+use std::time::{Duration, Instant};
+
+use winit::event_loop::ControlFlow;
+
+/// what rendering does while the window is unfocused
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Background {
+    /// renders as if focused
+    Full,
+    /// renders a few frames per second
+    Throttled,
+    /// renders nothing until focused again, still handling events
+    Suspended,
+}
+
+impl Background {
+    pub fn next(self) -> Self {
+        match self {
+            Background::Full => Background::Throttled,
+            Background::Throttled => Background::Suspended,
+            Background::Suspended => Background::Full,
+        }
+    }
+}
+
+/// when to render while the window is focused
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RenderMode {
+    /// every time the event loop is cleared
+    Continuous,
+    /// only after input or while something moves, waiting for events otherwise
+    Reactive,
+}
+
+impl RenderMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "continuous" => Some(RenderMode::Continuous),
+            "reactive" => Some(RenderMode::Reactive),
+            _ => None,
+        }
+    }
+}
+
+/// decides whether to render each time the event loop is cleared, waiting in between otherwise.
+/// a minimized or occluded window never renders
+pub struct Throttle {
+    pub background: Background,
+    pub mode: RenderMode,
+    /// whether something changed since the last render, in reactive mode
+    redraw_requested: bool,
+    focused: bool,
+    minimized: bool,
+    occluded: bool,
+    last_render: Option<Instant>,
+    /// whether the previous `should_render` was suspended
+    paused: bool,
+    /// set when rendering continues after a pause, until taken
+    resumed: bool,
+}
+
+impl Throttle {
+    pub const THROTTLED_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub fn new(background: Background, mode: RenderMode) -> Self {
+        Self {
+            background,
+            mode,
+            redraw_requested: true,
+            focused: true,
+            minimized: false,
+            occluded: false,
+            last_render: None,
+            paused: false,
+            resumed: false,
+        }
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// from the window's size, zero while minimized
+    pub fn set_minimized(&mut self, minimized: bool) {
+        self.minimized = minimized;
+    }
+
+    pub fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded;
+    }
+
+    /// asks reactive mode for another frame, after input or while something's moving
+    pub fn request_redraw(&mut self) {
+        self.redraw_requested = true;
+    }
+
+    /// whether to update and render now, otherwise `control_flow` waits for the next event
+    /// or until the next throttled frame
+    pub fn should_render(&mut self, now: Instant, control_flow: &mut ControlFlow) -> bool {
+        let background = if self.minimized || self.occluded {
+            Background::Suspended
+        } else if self.focused {
+            Background::Full
+        } else {
+            self.background
+        };
+
+        // nothing to show until asked, so the wait is a pause like a suspension
+        let idle = self.mode == RenderMode::Reactive && !self.redraw_requested;
+        let render = match background {
+            Background::Suspended => {
+                *control_flow = ControlFlow::Wait;
+                false
+            }
+            _ if idle => {
+                *control_flow = ControlFlow::Wait;
+                false
+            }
+            Background::Full => true,
+            Background::Throttled => match self.last_render {
+                Some(last) if now < last + Self::THROTTLED_INTERVAL => {
+                    *control_flow = ControlFlow::WaitUntil(last + Self::THROTTLED_INTERVAL);
+                    false
+                }
+                _ => true,
+            },
+        };
+
+        if render {
+            *control_flow = ControlFlow::Poll;
+            self.last_render = Some(now);
+            self.resumed |= self.paused;
+            self.redraw_requested = false;
+        }
+        // waiting between throttled frames isn't a pause, their time steps just get longer
+        self.paused = background == Background::Suspended || idle;
+        render
+    }
+
+    /// whether rendering continued after a pause since the last call,
+    /// so the time of the pause should be left out of the next time step
+    pub fn take_resumed(&mut self) -> bool {
+        std::mem::take(&mut self.resumed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unfocused_window_is_throttled_or_suspended() {
+        let start = Instant::now();
+        let mut control_flow = ControlFlow::Poll;
+        let mut throttle = Throttle::new(Background::Throttled, RenderMode::Continuous);
+        throttle.set_focused(false);
+        assert!(throttle.should_render(start, &mut control_flow));
+        assert!(!throttle.should_render(start + Duration::from_millis(10), &mut control_flow));
+        assert_eq!(control_flow, ControlFlow::WaitUntil(start + Throttle::THROTTLED_INTERVAL));
+        assert!(throttle.should_render(start + Throttle::THROTTLED_INTERVAL, &mut control_flow));
+        assert!(!throttle.take_resumed());
+
+        throttle.background = Background::Suspended;
+        assert!(!throttle.should_render(start + Duration::from_secs(1), &mut control_flow));
+        assert_eq!(control_flow, ControlFlow::Wait);
+        throttle.set_focused(true);
+        assert!(throttle.should_render(start + Duration::from_secs(5), &mut control_flow));
+        assert_eq!(control_flow, ControlFlow::Poll);
+        assert!(throttle.take_resumed());
+        assert!(!throttle.take_resumed());
+    }
+
+    #[test]
+    fn reactive_mode_renders_only_when_asked() {
+        let start = Instant::now();
+        let mut control_flow = ControlFlow::Poll;
+        let mut throttle = Throttle::new(Background::Throttled, RenderMode::Reactive);
+        // the first frame is always drawn
+        assert!(throttle.should_render(start, &mut control_flow));
+        assert!(!throttle.should_render(start + Duration::from_millis(10), &mut control_flow));
+        assert_eq!(control_flow, ControlFlow::Wait);
+
+        throttle.request_redraw();
+        assert!(throttle.should_render(start + Duration::from_secs(3), &mut control_flow));
+        assert_eq!(control_flow, ControlFlow::Poll);
+        // the time spent waiting is left out
+        assert!(throttle.take_resumed());
+        assert!(!throttle.should_render(start + Duration::from_secs(4), &mut control_flow));
+
+        // unfocused, a requested frame still waits for the throttled interval
+        throttle.set_focused(false);
+        throttle.request_redraw();
+        let last = start + Duration::from_secs(5);
+        assert!(throttle.should_render(last, &mut control_flow));
+        throttle.request_redraw();
+        assert!(!throttle.should_render(last + Duration::from_millis(10), &mut control_flow));
+        assert_eq!(control_flow, ControlFlow::WaitUntil(last + Throttle::THROTTLED_INTERVAL));
+        assert!(throttle.should_render(last + Throttle::THROTTLED_INTERVAL, &mut control_flow));
+
+        throttle.mode = RenderMode::Continuous;
+        assert!(throttle.should_render(last + Duration::from_secs(1), &mut control_flow));
+    }
+}
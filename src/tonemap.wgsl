@@ -0,0 +1,21 @@
+// post::Tonemap, maps the scene's unbounded linear color into 0 to 1 before the resolve
+// anti aliases and quantizes it
+const TONEMAP_OFF: u32 = 0u;
+const TONEMAP_REINHARD: u32 = 1u;
+const TONEMAP_ACES: u32 = 2u;
+
+// Krzysztof Narkowicz's fit of the aces filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    return (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+}
+
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    let exposed = max(color * resolve_params.exposure, vec3<f32>(0.0));
+    var mapped = exposed;
+    if resolve_params.tonemap == TONEMAP_REINHARD {
+        mapped = exposed / (1.0 + exposed);
+    } else if resolve_params.tonemap == TONEMAP_ACES {
+        mapped = aces(exposed);
+    }
+    return clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+}
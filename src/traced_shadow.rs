@@ -0,0 +1,228 @@
+use wgpu::*;
+
+use crate::binding::{self, BindGroupLayoutBuilder, BindingLayout};
+use crate::buffer::GpuBuffer;
+use crate::bvh::{Bvh, BvhNode, TriangleRaw};
+use crate::math::{Affine3, Vector3};
+use crate::scene::{Instance, Scene};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct TracedShadowParams {
+    inverse_view: Affine3,
+    light_position: [f32; 3],
+    near_z: f32,
+    size: [u32; 2],
+    static_nodes: u32,
+    dynamic_nodes: u32,
+}
+crate::layout::assert_uniform_size!(TracedShadowParams, 80);
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// the triangles of indexed positions
+pub fn triangles(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[Vector3; 3]> {
+    indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            [0, 1, 2].map(|corner| {
+                let [x, y, z] = positions[triangle[corner] as usize];
+                Vector3::new(x, y, z)
+            })
+        })
+        .collect()
+}
+
+/// an experimental ground truth for the shadow map: traces the segment from every pixel's surface
+/// to the light against the shadow casters' triangles, writing whether it's clear to `mask`, which
+/// `light.wgsl` reads instead of the shadow map in its `TRACED_SHADOWS` variant.
+/// the pixels are those of the depth of the frame before, so the mask trails a moving camera by a
+/// frame. the static casters' tree is rebuilt when they change and the dynamic ones' every frame,
+/// the grass and the streamed terrain cast no shadows here
+pub struct TracedShadows {
+    pipeline: ComputePipeline,
+    binding: BindingLayout,
+    params: GpuBuffer<TracedShadowParams>,
+    /// the static tree's nodes and triangles, then the dynamic one's
+    nodes: GpuBuffer<BvhNode>,
+    triangles: GpuBuffer<TriangleRaw>,
+    /// local space triangles of each `scene::MeshId`
+    meshes: Vec<Vec<[Vector3; 3]>>,
+    /// world space triangles of the fixed terrain, part of the static tree while it's drawn
+    terrain: Vec<[Vector3; 3]>,
+    static_bvh: Bvh,
+    /// what the static tree was built from, the scene's static generation and whether it has the terrain
+    static_key: Option<(u64, bool)>,
+    size: [u32; 2],
+    mask: Texture,
+    pub mask_view: TextureView,
+}
+
+impl TracedShadows {
+    pub const MASK_FORMAT: TextureFormat = TextureFormat::R32Float;
+
+    pub fn new(
+        device: &Device,
+        width: u32,
+        height: u32,
+        meshes: Vec<Vec<[Vector3; 3]>>,
+        terrain: Vec<[Vector3; 3]>,
+    ) -> Self {
+        let source = include_str!("traced_shadow.wgsl");
+        let layout = BindGroupLayoutBuilder::new("traced shadow bind group layout")
+            .uniform(ShaderStages::COMPUTE)
+            .texture(ShaderStages::COMPUTE, TextureSampleType::Depth)
+            .storage(ShaderStages::COMPUTE, true)
+            .storage(ShaderStages::COMPUTE, true)
+            .storage_texture(ShaderStages::COMPUTE, Self::MASK_FORMAT);
+        layout.validate(0, &binding::reflect("traced_shadow.wgsl", source)).unwrap();
+        let binding = layout.build(device);
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Traced shadow shader"),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Traced shadow pipeline layout"),
+            bind_group_layouts: &[&binding.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Traced shadow pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        let (mask, mask_view) = create_mask(device, width, height);
+        Self {
+            pipeline,
+            binding,
+            params: GpuBuffer::new_uniform(device, "Traced shadow params"),
+            nodes: GpuBuffer::new_storage(device, "Bvh node buffer", 1),
+            triangles: GpuBuffer::new_storage(device, "Bvh triangle buffer", 1),
+            meshes,
+            terrain,
+            static_bvh: Bvh::build(&[]),
+            static_key: None,
+            size: [width, height],
+            mask,
+            mask_view,
+        }
+    }
+
+    /// recreates the mask, so bind groups on `mask_view` have to be recreated too
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        (self.mask, self.mask_view) = create_mask(device, width, height);
+        self.size = [width, height];
+    }
+
+    /// replaces the fixed terrain's triangles, e.g. moved with the floating origin
+    pub fn set_terrain(&mut self, terrain: Vec<[Vector3; 3]>) {
+        self.terrain = terrain;
+        self.static_key = None;
+    }
+
+    /// world space triangles of the `instances` casting shadows
+    fn instance_triangles<'a>(
+        &'a self,
+        instances: impl Iterator<Item = &'a Instance> + 'a,
+    ) -> impl Iterator<Item = [Vector3; 3]> + 'a {
+        instances.filter(|instance| instance.casts_shadow).flat_map(|instance| {
+            let model = Affine3::from(instance.scale, instance.rotation, instance.translation);
+            self.meshes[instance.mesh.0 as usize]
+                .iter()
+                .map(move |triangle| triangle.map(|corner| corner.apply(&model)))
+        })
+    }
+
+    /// builds the trees of the scene's casters and uploads them, with the camera the next traced
+    /// depth is rendered from
+    #[allow(clippy::too_many_arguments)]
+    #[profiling::function]
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        scene: &Scene,
+        with_terrain: bool,
+        camera_view: &Affine3,
+        near_z: f32,
+        light_position: Vector3,
+    ) {
+        let static_key = (scene.static_generation(), with_terrain);
+        let static_changed = self.static_key != Some(static_key);
+        if static_changed {
+            let terrain = if with_terrain { &self.terrain[..] } else { &[] };
+            let triangles: Vec<_> = terrain
+                .iter()
+                .copied()
+                .chain(self.instance_triangles(scene.iter().take(scene.static_len())))
+                .collect();
+            self.static_bvh = Bvh::build(&triangles);
+            self.static_key = Some(static_key);
+        }
+        let dynamic_triangles: Vec<_> = self.instance_triangles(scene.iter().skip(scene.static_len())).collect();
+        let dynamic_bvh = Bvh::build(&dynamic_triangles);
+
+        let (static_nodes, static_triangles) = (self.static_bvh.nodes.len(), self.static_bvh.triangles.len());
+        let mut recreated = self.nodes.reserve(device, static_nodes + dynamic_bvh.nodes.len());
+        recreated |= self.triangles.reserve(device, static_triangles + dynamic_bvh.triangles.len());
+        if static_changed || recreated {
+            self.nodes.write_at(queue, 0, &self.static_bvh.nodes);
+            self.triangles.write_at(queue, 0, &self.static_bvh.triangles);
+        }
+        self.nodes.write_at(queue, static_nodes, &dynamic_bvh.rebased_nodes(static_nodes as u32, static_triangles as u32));
+        self.triangles.write_at(queue, static_triangles, &dynamic_bvh.triangles);
+
+        self.params.write(queue, &[TracedShadowParams {
+            inverse_view: camera_view.inverse(),
+            light_position: [light_position.x, light_position.y, light_position.z],
+            near_z,
+            size: self.size,
+            static_nodes: static_nodes as u32,
+            dynamic_nodes: dynamic_bvh.nodes.len() as u32,
+        }]);
+    }
+
+    /// traces the mask from `depth_view`, which is as large as the mask
+    pub fn dispatch(&self, device: &Device, encoder: &mut CommandEncoder, depth_view: &TextureView) {
+        let bind_group = self.binding.create_bind_group(
+            device,
+            "traced shadow bind group",
+            &[
+                self.params.as_entire_binding(),
+                BindingResource::TextureView(depth_view),
+                self.nodes.as_entire_binding(),
+                self.triangles.as_entire_binding(),
+                BindingResource::TextureView(&self.mask_view),
+            ],
+        );
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("traced shadow pass"),
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        let [width, height] = self.size;
+        pass.dispatch_workgroups(width.div_ceil(WORKGROUP_SIZE), height.div_ceil(WORKGROUP_SIZE), 1);
+    }
+}
+
+fn create_mask(device: &Device, width: u32, height: u32) -> (Texture, TextureView) {
+    let mask = device.create_texture(&TextureDescriptor {
+        label: Some("Traced shadow mask"),
+        size: Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TracedShadows::MASK_FORMAT,
+        usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = mask.create_view(&TextureViewDescriptor::default());
+    (mask, view)
+}
@@ -0,0 +1,137 @@
+// traced_shadow::TracedShadowParams
+struct Params {
+    // view to world of the camera the depth was rendered from
+    inverse_view_0: vec4<f32>,
+    inverse_view_1: vec4<f32>,
+    inverse_view_2: vec4<f32>,
+    light_position: vec3<f32>,
+    near_z: f32,
+    size: vec2<u32>,
+    // the static tree is rooted at node 0, the dynamic one right after its nodes
+    static_nodes: u32,
+    dynamic_nodes: u32,
+}
+
+// bvh::BvhNode
+struct Node {
+    min: vec3<f32>,
+    // a leaf's first triangle, or an inner node's first child, the second following it
+    first: u32,
+    max: vec3<f32>,
+    // triangles in a leaf, 0 for inner nodes
+    count: u32,
+}
+
+// bvh::TriangleRaw
+struct Triangle {
+    a: vec3<f32>,
+    b: vec3<f32>,
+    c: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var depth_texture: texture_depth_2d;
+@group(0) @binding(2) var<storage, read> nodes: array<Node>;
+@group(0) @binding(3) var<storage, read> triangles: array<Triangle>;
+@group(0) @binding(4) var mask: texture_storage_2d<r32float, write>;
+
+// deeper than a median split tree of any triangle count that fits in memory
+const STACK_SIZE: u32 = 32u;
+
+// slab test of the segment from `origin` to `origin + direction` against the box
+fn hits_box(node: Node, origin: vec3<f32>, inverse_direction: vec3<f32>) -> bool {
+    let t0 = (node.min - origin) * inverse_direction;
+    let t1 = (node.max - origin) * inverse_direction;
+    let near = max(max(max(min(t0.x, t1.x), min(t0.y, t1.y)), min(t0.z, t1.z)), 0.0);
+    let far = min(min(min(max(t0.x, t1.x), max(t0.y, t1.y)), max(t0.z, t1.z)), 1.0);
+    return near <= far;
+}
+
+// bvh::hits_triangle, möller-trumbore for either face
+fn hits_triangle(triangle: Triangle, origin: vec3<f32>, direction: vec3<f32>) -> bool {
+    let ab = triangle.b - triangle.a;
+    let ac = triangle.c - triangle.a;
+    let p = cross(direction, ac);
+    let determinant = dot(ab, p);
+    if abs(determinant) < 1e-12 {
+        return false;
+    }
+    let inverse = 1.0 / determinant;
+    let s = origin - triangle.a;
+    let u = dot(s, p) * inverse;
+    let q = cross(s, ab);
+    let v = dot(direction, q) * inverse;
+    let t = dot(ac, q) * inverse;
+    return u >= 0.0 && u <= 1.0 && v >= 0.0 && u + v <= 1.0 && 1e-4 < t && t < 1.0;
+}
+
+// bvh::Bvh::occluded for the tree of `node_count` nodes rooted at `root`
+fn occluded(origin: vec3<f32>, direction: vec3<f32>, root: u32, node_count: u32) -> bool {
+    if node_count == 0u {
+        return false;
+    }
+    // axis aligned segments would divide by zero
+    let safe_direction = select(direction, vec3<f32>(1e-12), abs(direction) < vec3<f32>(1e-12));
+    let inverse_direction = 1.0 / safe_direction;
+
+    var stack: array<u32, STACK_SIZE>;
+    stack[0] = root;
+    var top = 1u;
+    while top > 0u {
+        top -= 1u;
+        let node = nodes[stack[top]];
+        if !hits_box(node, origin, inverse_direction) {
+            continue;
+        }
+        if node.count > 0u {
+            for (var i = node.first; i < node.first + node.count; i++) {
+                if hits_triangle(triangles[i], origin, direction) {
+                    return true;
+                }
+            }
+        } else if top + 2u <= STACK_SIZE {
+            stack[top] = node.first;
+            stack[top + 1u] = node.first + 1u;
+            top += 2u;
+        }
+    }
+    return false;
+}
+
+fn to_world(view: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(params.inverse_view_0.xyz, view) + params.inverse_view_0.w,
+        dot(params.inverse_view_1.xyz, view) + params.inverse_view_1.w,
+        dot(params.inverse_view_2.xyz, view) + params.inverse_view_2.w,
+    );
+}
+
+// 1 where the segment from the surface in the pixel to the light is clear, 0 where it's blocked
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.size.x || id.y >= params.size.y {
+        return;
+    }
+    let pixel = vec2<i32>(id.xy);
+    let depth = textureLoad(depth_texture, pixel, 0);
+    // the background, cleared to the infinitely far reversed z
+    if depth <= 0.0 {
+        textureStore(mask, pixel, vec4<f32>(1.0));
+        return;
+    }
+
+    // undoes the infinite reversed z projection of light.wgsl's to_clip
+    let ndc = vec2<f32>(
+        (f32(id.x) + 0.5) / f32(params.size.x) * 2.0 - 1.0,
+        1.0 - (f32(id.y) + 0.5) / f32(params.size.y) * 2.0,
+    );
+    let view_z = params.near_z / depth;
+    let position = to_world(vec3<f32>(ndc * view_z, view_z));
+    // pulled towards the camera, off the surface the depth is only so precise about
+    let origin = mix(position, to_world(vec3<f32>(0.0)), 1e-3);
+    let direction = params.light_position - origin;
+
+    let blocked = occluded(origin, direction, 0u, params.static_nodes)
+        || occluded(origin, direction, params.static_nodes, params.dynamic_nodes);
+    textureStore(mask, pixel, vec4<f32>(select(1.0, 0.0, blocked)));
+}
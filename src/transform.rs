@@ -0,0 +1,159 @@
+use wgpu::*;
+
+use crate::binding::{self, BindGroupLayoutBuilder, BindingLayout};
+use crate::buffer::GpuBuffer;
+use crate::scene::{InstanceComponents, InstanceRaw, Scene};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct TransformParams {
+    len: u32,
+    _padding: [u32; 3],
+}
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// builds the instance affines on the gpu from the uploaded instance components,
+/// so per-frame cpu work is a plain copy of the components.
+/// the frame before's are kept alongside, which the light pass tells how instances moved by
+pub struct TransformPass {
+    pipeline: ComputePipeline,
+    binding: BindingLayout,
+    bind_group: BindGroup,
+    params: GpuBuffer<TransformParams>,
+    components: GpuBuffer<InstanceComponents>,
+    /// written by the pass, read as the instance vertex buffer
+    pub instances: GpuBuffer<InstanceRaw>,
+    /// `instances` of the frame before, at the same indices
+    pub previous_instances: GpuBuffer<InstanceRaw>,
+    /// the instances moved around in the buffer, so they start out as if they didn't move
+    previous_stale: bool,
+    /// set by `invalidate_previous` for the next upload
+    previous_invalidated: bool,
+}
+
+impl TransformPass {
+    pub fn new(device: &Device, capacity: usize) -> Self {
+        let source = include_str!("transform.wgsl");
+        let layout = BindGroupLayoutBuilder::new("transform bind group layout")
+            .storage(ShaderStages::COMPUTE, true)
+            .storage(ShaderStages::COMPUTE, false)
+            .uniform(ShaderStages::COMPUTE);
+        layout.validate(0, &binding::reflect("transform.wgsl", source)).unwrap();
+        let binding = layout.build(device);
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Instance transform shader"),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Instance transform pipeline layout"),
+            bind_group_layouts: &[&binding.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Instance transform pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        let params = GpuBuffer::new_uniform(device, "Instance transform params");
+        let components = GpuBuffer::new_storage(device, "Instance components buffer", capacity);
+        let instances = GpuBuffer::new(
+            device,
+            "Instance buffer",
+            BufferUsages::VERTEX | BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            capacity,
+        );
+        let previous_instances = GpuBuffer::new(device, "Previous instance buffer", BufferUsages::VERTEX, capacity);
+        let bind_group = create_bind_group(device, &binding, &params, &components, &instances);
+
+        Self {
+            pipeline,
+            binding,
+            bind_group,
+            params,
+            components,
+            instances,
+            previous_instances,
+            previous_stale: true,
+            previous_invalidated: false,
+        }
+    }
+
+    /// uploads the scene's instance components, growing the buffers and rebinding them if needed
+    #[profiling::function]
+    pub fn upload(&mut self, device: &Device, queue: &Queue, scene: &mut Scene) {
+        let mut recreated = scene.upload(device, queue, &mut self.components);
+        recreated |= self.instances.reserve(device, scene.len());
+        self.instances.set_len(scene.len());
+        let previous_recreated = self.previous_instances.reserve(device, scene.len());
+        self.previous_instances.set_len(scene.len());
+        self.previous_stale = recreated
+            || previous_recreated
+            || scene.layout_changed()
+            || std::mem::take(&mut self.previous_invalidated);
+        self.params.write(queue, &[TransformParams {
+            len: scene.len() as u32,
+            _padding: Default::default(),
+        }]);
+
+        if recreated {
+            self.bind_group = create_bind_group(
+                device,
+                &self.binding,
+                &self.params,
+                &self.components,
+                &self.instances,
+            );
+        }
+    }
+
+    /// the next frame starts out as if no instance moved, e.g. after all of them were moved at once
+    pub fn invalidate_previous(&mut self) {
+        self.previous_invalidated = true;
+    }
+
+    /// keeps the frame before's instances, then builds this frame's
+    pub fn dispatch(&self, encoder: &mut CommandEncoder) {
+        let len = self.instances.len() as u32;
+        if len == 0 {
+            return;
+        }
+
+        let size = (len as usize * std::mem::size_of::<InstanceRaw>()) as BufferAddress;
+        if !self.previous_stale {
+            encoder.copy_buffer_to_buffer(self.instances.buffer(), 0, self.previous_instances.buffer(), 0, size);
+        }
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("instance transform pass"),
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(len.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        if self.previous_stale {
+            encoder.copy_buffer_to_buffer(self.instances.buffer(), 0, self.previous_instances.buffer(), 0, size);
+        }
+    }
+}
+
+fn create_bind_group(
+    device: &Device,
+    binding: &BindingLayout,
+    params: &GpuBuffer<TransformParams>,
+    components: &GpuBuffer<InstanceComponents>,
+    instances: &GpuBuffer<InstanceRaw>,
+) -> BindGroup {
+    binding.create_bind_group(
+        device,
+        "transform bind group",
+        &[
+            components.as_entire_binding(),
+            instances.as_entire_binding(),
+            params.as_entire_binding(),
+        ],
+    )
+}
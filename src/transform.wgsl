@@ -0,0 +1,75 @@
+struct Components {
+    // (_1, xy, yz, zx)
+    rotation: vec4<f32>,
+    translation: vec3<f32>,
+    flags: u32,
+    scale: vec3<f32>,
+    lightmap: vec3<f32>,
+}
+
+// InstanceRaw
+struct Affine {
+    _0: vec4<f32>,
+    _1: vec4<f32>,
+    _2: vec4<f32>,
+    flags: u32,
+    // offset and size of the lightmap tile, an array to pack it right after the flags
+    lightmap: array<f32, 3>,
+}
+
+struct Params {
+    len: u32,
+}
+
+@group(0) @binding(0) var<storage, read> components: array<Components>;
+@group(0) @binding(1) var<storage, read_write> affines: array<Affine>;
+@group(0) @binding(2) var<uniform> params: Params;
+
+// mirrors math::Affine3::from
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.len {
+        return;
+    }
+
+    let c = components[i];
+    let r = c.rotation;
+    let s = c.scale;
+    let t = c.translation;
+
+    let _1zx = r.x * r.w;
+    let _1xy = r.x * r.y;
+    let _1yz = r.x * r.z;
+
+    let zxzx = r.w * r.w;
+    let zxxy = r.w * r.y;
+    let xyxy = r.y * r.y;
+
+    let zxyz = r.z * r.w;
+    let yzxy = r.z * r.y;
+    let yzyz = r.z * r.z;
+
+    var out: Affine;
+    out._0 = vec4<f32>(
+        (1.0 - 2.0 * (zxzx + xyxy)) * s.x,
+        (2.0 * (zxyz - _1xy)) * s.y,
+        (2.0 * (yzxy + _1zx)) * s.z,
+        t.x,
+    );
+    out._1 = vec4<f32>(
+        (2.0 * (zxyz + _1xy)) * s.x,
+        (1.0 - 2.0 * (yzyz + xyxy)) * s.y,
+        (2.0 * (zxxy - _1yz)) * s.z,
+        t.y,
+    );
+    out._2 = vec4<f32>(
+        (2.0 * (yzxy - _1zx)) * s.x,
+        (2.0 * (zxxy + _1yz)) * s.y,
+        (1.0 - 2.0 * (yzyz + zxzx)) * s.z,
+        t.z,
+    );
+    out.flags = c.flags;
+    out.lightmap = array<f32, 3>(c.lightmap.x, c.lightmap.y, c.lightmap.z);
+    affines[i] = out;
+}
@@ -0,0 +1,378 @@
+use wgpu::*;
+
+use crate::binding::{self, BindGroupLayoutBuilder, BindingLayout};
+use crate::buffer::GpuBuffer;
+use crate::layout;
+use crate::math::{noise, Color, Vector2, Vector3};
+use crate::mipmap::MipmapGenerator;
+use crate::texture::{FormatSupport, TexelFormat, TextureAsset, TextureData};
+
+/// a grass billboard, drawn as an instance of a camera facing quad
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BladeRaw {
+    /// of its root
+    pub position: [f32; 3],
+    pub height: f32,
+    pub width: f32,
+    /// offsets its sway, so neighbours don't move in lockstep
+    pub phase: f32,
+    /// brightness multiplied into its texture
+    pub tint: f32,
+}
+
+const BLADE_LAYOUT: VertexBufferLayout = layout::vertex_layout!(BladeRaw, Instance, 0, [
+    position => Float32x3,
+    height => Float32,
+    width => Float32,
+    phase => Float32,
+    tint => Float32,
+]);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct VegetationRaw {
+    wind: [f32; 2],
+    wind_strength: f32,
+    time: f32,
+    light_direction: [f32; 3],
+    alpha_cutoff: f32,
+    light_color: [f32; 3],
+    _padding: u32,
+    ambient: [f32; 3],
+    _padding2: u32,
+}
+layout::assert_uniform_size!(VegetationRaw, 64);
+
+/// uniform in 0..1, hashed from `seed` and `i`
+fn random(seed: u32, i: u32) -> f32 {
+    noise::hash(seed.wrapping_add(noise::hash(i))) as f32 / u32::MAX as f32
+}
+
+/// blades on a jittered grid of `spacing` over the xz rect from `min` to `max`, standing on `ground`
+/// of x and z. they grow in patches, thinning out where low frequency noise is low
+pub fn scatter(min: Vector2, max: Vector2, spacing: f32, seed: u32, ground: impl Fn(f32, f32) -> f32) -> Vec<BladeRaw> {
+    // patches about this far apart
+    const PATCH_SIZE: f32 = 6.0;
+    let cells_x = ((max.x - min.x) / spacing) as u32;
+    let cells_z = ((max.y - min.y) / spacing) as u32;
+    let mut blades = vec![];
+    for z in 0..cells_z {
+        for x in 0..cells_x {
+            let i = 4 * (x + z * cells_x);
+            let p = min + Vector2::new(x as f32 + random(seed, i), z as f32 + random(seed, i + 1)) * spacing;
+            let density = noise::noise2(p * (1.0 / PATCH_SIZE)) + 0.3;
+            if random(seed, i + 2) > density {
+                continue;
+            }
+            let size = 0.6 + 0.8 * random(seed, i + 3);
+            blades.push(BladeRaw {
+                position: [p.x, ground(p.x, p.y), p.y],
+                height: 0.5 * size,
+                width: 0.5 * size,
+                phase: std::f32::consts::TAU * random(seed + 1, i),
+                tint: 0.75 + 0.5 * random(seed + 1, i + 1),
+            });
+        }
+    }
+    blades
+}
+
+/// a tuft of tapered blades on a transparent background, alpha 0 or 1, roots at the bottom
+pub fn grass_texture(size: u32) -> TextureData {
+    const BLADES: u32 = 7;
+    let mut texels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        // 0 at the roots, 1 at the top
+        let height = 1.0 - (y as f32 + 0.5) / size as f32;
+        for x in 0..size {
+            let u = (x as f32 + 0.5) / size as f32;
+            let blade = (0..BLADES).find(|&b| {
+                let top = 0.55 + 0.45 * random(7, b);
+                let root = (b as f32 + 0.5) / BLADES as f32;
+                // leaning out from the middle, narrowing towards the top
+                let center = root + (random(8, b) - 0.5) * 0.5 * height;
+                let half_width = 0.5 / BLADES as f32 * (1.0 - height / top);
+                height < top && (u - center).abs() < half_width
+            });
+            let texel = match blade {
+                Some(b) => {
+                    let shade = 0.7 + 0.3 * random(9, b);
+                    let green = |c: f32| ((c * shade * (0.6 + 0.4 * height)) * 255.0) as u8;
+                    [green(0.25), green(0.55), green(0.12), 255]
+                }
+                // the color bleeds into the edges when filtered, so it's kept dark green
+                None => [20, 40, 10, 0],
+            };
+            texels.extend_from_slice(&texel);
+        }
+    }
+
+    TextureData {
+        width: size,
+        height: size,
+        format: TexelFormat::Rgba8,
+        srgb: true,
+        levels: vec![texels],
+    }
+}
+
+/// the wind bending the blades, in world units of the xz plane
+#[derive(Clone, Copy, Debug)]
+pub struct Wind {
+    /// unit
+    pub direction: Vector2,
+    /// how far blade tips bend per unit of blade height
+    pub strength: f32,
+}
+
+/// how a material's shadows are cast
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShadowCasting {
+    /// depth only, as solid as its geometry
+    Opaque,
+    /// through a fragment shader discarding what its texture's alpha cuts out
+    AlphaTested,
+}
+
+impl ShadowCasting {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "opaque" => Some(ShadowCasting::Opaque),
+            "alpha-tested" => Some(ShadowCasting::AlphaTested),
+            _ => None,
+        }
+    }
+}
+
+/// `vegetation.wgsl` after the noise its gusts are made of
+pub fn source() -> String {
+    noise::NOISE_WGSL.to_string() + include_str!("vegetation.wgsl")
+}
+
+/// alpha tested grass billboards, swaying in the wind in the vertex shader.
+/// drawn in the light pass of the camera's own view, not receiving shadows and not pickable.
+/// there's no msaa to turn the alpha test into alpha to coverage
+pub struct Vegetation {
+    pub enabled: bool,
+    pub wind: Wind,
+    /// texture alpha below which fragments are discarded
+    pub alpha_cutoff: f32,
+    pub shadow_casting: ShadowCasting,
+    blades: GpuBuffer<BladeRaw>,
+    uniform: GpuBuffer<VegetationRaw>,
+    texture: TextureAsset,
+    binding: BindingLayout,
+    bind_group: BindGroup,
+    camera_bind_group: BindGroup,
+    light_bind_group: BindGroup,
+    pipeline: RenderPipeline,
+    opaque_shadow_pipeline: RenderPipeline,
+    alpha_tested_shadow_pipeline: RenderPipeline,
+}
+
+impl Vegetation {
+    /// `camera` is the camera uniform buffer of the light pass and `light` the light's uniform buffer,
+    /// `sampler` filters the grass texture. `shadow_format` is the shadow map's
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        support: &FormatSupport,
+        mipmaps: &mut MipmapGenerator,
+        (camera, light): (BindingResource, BindingResource),
+        sampler: &Sampler,
+        targets: [TextureFormat; 3],
+        depth_format: TextureFormat,
+        shadow_format: TextureFormat,
+        blades: &[BladeRaw],
+    ) -> Self {
+        let source = source();
+        let shader = binding::reflect("vegetation.wgsl", &source);
+        let layout = BindGroupLayoutBuilder::new("vegetation bind group layout")
+            .uniform(ShaderStages::VERTEX | ShaderStages::FRAGMENT) // vegetation
+            .texture(ShaderStages::FRAGMENT, TextureSampleType::Float { filterable: true }) // grass
+            .sampler(ShaderStages::FRAGMENT, SamplerBindingType::Filtering); // grass sampler
+        layout.validate(0, &shader).unwrap();
+        for entry_point in ["vs_main", "vs_shadow"] {
+            crate::layout::validate_vertex_input(&shader, entry_point, &[BLADE_LAYOUT]).unwrap();
+        }
+        let view_layout = BindGroupLayoutBuilder::new("vegetation view bind group layout")
+            .uniform(ShaderStages::VERTEX); // camera or light
+        view_layout.validate(1, &shader).unwrap();
+        let binding = layout.build(device);
+        let view_binding = view_layout.build(device);
+
+        let uniform = GpuBuffer::new_uniform(device, "Vegetation Uniform Buffer");
+        let texture = grass_texture(128).upload(device, queue, support, mipmaps, "grass texture");
+        let bind_group = binding.create_bind_group(device, "vegetation bind group", &[
+            uniform.as_entire_binding(),
+            BindingResource::TextureView(&texture.view),
+            BindingResource::Sampler(sampler),
+        ]);
+        let camera_bind_group = view_binding.create_bind_group(device, "vegetation camera bind group", &[camera]);
+        let light_bind_group = view_binding.create_bind_group(device, "vegetation light bind group", &[light]);
+
+        let mut blade_buffer = GpuBuffer::new_vertex(device, "Blade Buffer", blades.len());
+        blade_buffer.write(queue, blades);
+
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("vegetation.wgsl"),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Vegetation Pipeline Layout"),
+            bind_group_layouts: &[&binding.layout, &view_binding.layout],
+            push_constant_ranges: &[],
+        });
+        let color_targets = targets.map(|format| Some(ColorTargetState {
+            format,
+            blend: None,
+            write_mask: ColorWrites::ALL,
+        }));
+        let create_pipeline = |label, vertex_entry_point, fragment: Option<FragmentState>, depth_format| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: vertex_entry_point,
+                    buffers: &[BLADE_LAYOUT],
+                },
+                fragment,
+                // billboards are seen from both sides as they turn
+                primitive: PrimitiveState::default(),
+                depth_stencil: Some(DepthStencilState {
+                    format: depth_format,
+                    depth_write_enabled: true,
+                    depth_compare: CompareFunction::Greater,
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let pipeline = create_pipeline(
+            "Vegetation Pipeline",
+            "vs_main",
+            Some(FragmentState { module: &module, entry_point: "fs_main", targets: &color_targets }),
+            depth_format,
+        );
+        let opaque_shadow_pipeline = create_pipeline("Vegetation Shadow Pipeline", "vs_shadow", None, shadow_format);
+        let alpha_tested_shadow_pipeline = create_pipeline(
+            "Alpha Tested Vegetation Shadow Pipeline",
+            "vs_shadow",
+            Some(FragmentState { module: &module, entry_point: "fs_shadow", targets: &[] }),
+            shadow_format,
+        );
+
+        Self {
+            enabled: true,
+            wind: Wind {
+                direction: Vector2::new(0.8, 0.6),
+                strength: 0.3,
+            },
+            alpha_cutoff: 0.5,
+            shadow_casting: ShadowCasting::AlphaTested,
+            blades: blade_buffer,
+            uniform,
+            texture,
+            binding,
+            bind_group,
+            camera_bind_group,
+            light_bind_group,
+            pipeline,
+            opaque_shadow_pipeline,
+            alpha_tested_shadow_pipeline,
+        }
+    }
+
+    /// rebinds the grass texture with another sampler
+    pub fn set_sampler(&mut self, device: &Device, sampler: &Sampler) {
+        self.bind_group = self.binding.create_bind_group(device, "vegetation bind group", &[
+            self.uniform.as_entire_binding(),
+            BindingResource::TextureView(&self.texture.view),
+            BindingResource::Sampler(sampler),
+        ]);
+    }
+
+    /// replaces the blades, e.g. moved with the floating origin
+    pub fn set_blades(&mut self, device: &Device, queue: &Queue, blades: &[BladeRaw]) {
+        self.blades.reserve(device, blades.len());
+        self.blades.write(queue, blades);
+    }
+
+    /// `time` in seconds drives the wind, `light_direction` is the one the light shines along
+    pub fn update(&mut self, queue: &Queue, time: f32, light_direction: Vector3, light_color: Color, ambient: Color) {
+        self.uniform.write(queue, &[VegetationRaw {
+            wind: [self.wind.direction.x, self.wind.direction.y],
+            wind_strength: self.wind.strength,
+            time,
+            light_direction: [light_direction.x, light_direction.y, light_direction.z],
+            alpha_cutoff: self.alpha_cutoff,
+            light_color: light_color.to_rgb_array(),
+            _padding: 0,
+            ambient: ambient.to_rgb_array(),
+            _padding2: 0,
+        }]);
+    }
+
+    fn draw_with<'a>(&'a self, pass: &mut RenderPass<'a>, pipeline: &'a RenderPipeline, view: &'a BindGroup) {
+        if !self.enabled || self.blades.is_empty() {
+            return;
+        }
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_bind_group(1, view, &[]);
+        pass.set_vertex_buffer(0, self.blades.slice());
+        pass.draw(0..6, 0..self.blades.len() as u32);
+    }
+
+    /// in the light pass
+    pub fn draw<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        self.draw_with(pass, &self.pipeline, &self.camera_bind_group);
+    }
+
+    /// in a shadow pass, with the pipeline of `shadow_casting`
+    pub fn draw_shadow<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        let pipeline = match self.shadow_casting {
+            ShadowCasting::Opaque => &self.opaque_shadow_pipeline,
+            ShadowCasting::AlphaTested => &self.alpha_tested_shadow_pipeline,
+        };
+        self.draw_with(pass, pipeline, &self.light_bind_group);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::offset_of;
+
+    #[test]
+    fn vegetation_matches_vegetation_wgsl() {
+        let shader = binding::reflect("vegetation.wgsl", &source());
+        layout::validate_struct(&shader, "Vegetation", size_of::<VegetationRaw>(), &[
+            ("wind", offset_of!(VegetationRaw, wind)),
+            ("time", offset_of!(VegetationRaw, time)),
+            ("light_direction", offset_of!(VegetationRaw, light_direction)),
+            ("alpha_cutoff", offset_of!(VegetationRaw, alpha_cutoff)),
+            ("light_color", offset_of!(VegetationRaw, light_color)),
+            ("ambient", offset_of!(VegetationRaw, ambient)),
+        ])
+        .unwrap();
+    }
+
+    #[test]
+    fn scattered_blades_stand_on_the_ground_in_patches() {
+        let (min, max) = (Vector2::new(-10.0, -10.0), Vector2::new(10.0, 10.0));
+        let blades = scatter(min, max, 0.5, 3, |x, z| 0.1 * x + z);
+        let cells = 40 * 40;
+        assert!(blades.len() > cells / 10 && blades.len() < cells, "{}", blades.len());
+        for blade in &blades {
+            let [x, y, z] = blade.position;
+            assert!(min.x <= x && x <= max.x && min.y <= z && z <= max.y);
+            assert!((y - (0.1 * x + z)).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+// vegetation::VegetationRaw
+struct Vegetation {
+    wind: vec2<f32>,
+    wind_strength: f32,
+    time: f32,
+    // the light shines along it
+    light_direction: vec3<f32>,
+    alpha_cutoff: f32,
+    light_color: vec3<f32>,
+    ambient: vec3<f32>,
+};
+@group(0) @binding(0)
+var<uniform> vegetation: Vegetation;
+@group(0) @binding(1)
+var grass_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var grass_sampler: sampler;
+
+// the camera in the light pass and the light in the shadow pass, which start alike
+struct View {
+    view_0: vec4<f32>,
+    view_1: vec4<f32>,
+    view_2: vec4<f32>,
+    near_z: f32,
+};
+@group(1) @binding(0)
+var<uniform> view: View;
+
+// vegetation::BladeRaw, one per billboard
+struct BladeIn {
+    @location(0) position: vec3<f32>,
+    @location(1) height: f32,
+    @location(2) width: f32,
+    @location(3) phase: f32,
+    @location(4) tint: f32,
+}
+
+struct VertexOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) tint: f32,
+    @location(2) height: f32,
+}
+
+// infinite reversed z, with the blade turned about y to face the view
+fn vertex(vertex_index: u32, blade: BladeIn) -> VertexOut {
+    // two triangles of a quad standing on the blade's position
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+
+    // the view's x row is its right in world space, horizontal as neither the camera nor the light roll
+    let right = normalize(vec3<f32>(view.view_0.x, 0.0, view.view_0.z));
+    var position = blade.position
+        + right * (corner.x - 0.5) * blade.width
+        + vec3<f32>(0.0, corner.y * blade.height, 0.0);
+
+    // gusts are patches of turbulence drifting along the wind, changing as they go.
+    // the tips bend the most and the roots stay put
+    let drift = blade.position.xz * 0.15 - vegetation.wind * vegetation.time * 0.6;
+    let gust = 0.6 + 0.4 * noise3(vec3<f32>(drift, vegetation.time * 0.3));
+    let flutter = 0.15 * sin(vegetation.time * 6.0 + blade.phase * 3.0);
+    let bend = vegetation.wind_strength * (gust + flutter) * corner.y * corner.y * blade.height;
+    position += vec3<f32>(vegetation.wind.x, 0.0, vegetation.wind.y) * bend;
+    // keeps the blade's length, so strong wind flattens rather than stretches it
+    position.y -= bend * bend * 0.5 / max(blade.height, 1e-3);
+
+    let view_position = vec3<f32>(
+        dot(view.view_0.xyz, position) + view.view_0.w,
+        dot(view.view_1.xyz, position) + view.view_1.w,
+        dot(view.view_2.xyz, position) + view.view_2.w,
+    );
+
+    var out: VertexOut;
+    out.clip_position = vec4<f32>(view_position.xy, view.near_z, view_position.z);
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    out.tint = blade.tint;
+    out.height = corner.y;
+    return out;
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, blade: BladeIn) -> VertexOut {
+    return vertex(vertex_index, blade);
+}
+
+// faces the light, so the shadow has the blade's whole outline
+@vertex
+fn vs_shadow(@builtin(vertex_index) vertex_index: u32, blade: BladeIn) -> VertexOut {
+    var out = vertex(vertex_index, blade);
+    // blades between the light and its near plane are flattened onto it, with or without unclipped depth
+    out.clip_position.z = min(view.near_z, out.clip_position.w);
+    return out;
+}
+
+struct FragmentOut {
+    @location(0) color: vec4<f32>,
+    // not pickable
+    @location(1) id: u32,
+    // the sway isn't tracked, only the camera's motion blurs it
+    @location(2) motion: vec2<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> FragmentOut {
+    let albedo = textureSample(grass_texture, grass_sampler, in.uv);
+    if albedo.a < vegetation.alpha_cutoff {
+        discard;
+    }
+
+    // lit as if facing up, darker towards the roots where the blades shade each other
+    let lighting = max(-vegetation.light_direction.y, 0.0);
+    let occlusion = mix(0.4, 1.0, in.height);
+    let color = albedo.rgb * in.tint * occlusion * (vegetation.light_color * lighting + vegetation.ambient);
+
+    var out: FragmentOut;
+    out.color = vec4<f32>(color, 1.0);
+    out.id = 0u;
+    return out;
+}
+
+// the alpha test alone, for the depth only shadow pass
+@fragment
+fn fs_shadow(in: VertexOut) {
+    if textureSample(grass_texture, grass_sampler, in.uv).a < vegetation.alpha_cutoff {
+        discard;
+    }
+}
@@ -0,0 +1,111 @@
+use wgpu::*;
+
+use crate::buffer::GpuBuffer;
+use crate::math::{Affine3, Color, Vector3};
+use crate::post::{self, FullscreenPass, PostInput, PostNode, HDR_FORMAT};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct VolumetricParams {
+    inverse_view: Affine3,
+    camera_position: [f32; 3],
+    near_z: f32,
+    light_view: Affine3,
+    light_near_z: f32,
+    steps: u32,
+    density: f32,
+    max_distance: f32,
+    light_color: [f32; 3],
+    intensity: f32,
+}
+
+/// light shafts, marching view rays through the shadow map and adding the light scattered
+/// by the lit samples on top of the scene
+pub struct Volumetric {
+    /// samples per ray
+    pub steps: u32,
+    /// scattering per unit of distance
+    pub density: f32,
+    /// rays stop there when nothing is hit before
+    pub max_distance: f32,
+    pub light_color: Color,
+    pub intensity: f32,
+    params: VolumetricParams,
+    params_buffer: GpuBuffer<VolumetricParams>,
+    pass: FullscreenPass,
+    shadow_map: TextureView,
+    bind_group: Option<BindGroup>,
+}
+
+impl Volumetric {
+    /// `shadow_map` is the depth texture the light's shadow pass renders into
+    pub fn new(device: &Device, shadow_map: &Texture) -> Self {
+        let pass = FullscreenPass::new(
+            device,
+            "volumetric.wgsl",
+            include_str!("volumetric.wgsl"),
+            "fs_main",
+            HDR_FORMAT,
+            post::input_layout("volumetric bind group layout")
+                .uniform(ShaderStages::FRAGMENT)
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Depth)
+                .texture(ShaderStages::FRAGMENT, TextureSampleType::Depth),
+        );
+
+        Self {
+            steps: 32,
+            density: 0.02,
+            max_distance: 100.0,
+            light_color: Color::rgb(1.0, 0.8, 0.5),
+            intensity: 1.0,
+            params: bytemuck::Zeroable::zeroed(),
+            params_buffer: GpuBuffer::new_uniform(device, "volumetric params buffer"),
+            pass,
+            shadow_map: shadow_map.create_view(&TextureViewDescriptor::default()),
+            bind_group: None,
+        }
+    }
+
+    /// `view` as uploaded in the camera buffer
+    pub fn set_view(&mut self, view: &Affine3, camera_position: Vector3, near_z: f32) {
+        self.params.inverse_view = view.inverse();
+        self.params.camera_position = [camera_position.x, camera_position.y, camera_position.z];
+        self.params.near_z = near_z;
+    }
+
+    /// `view` as uploaded in the light buffer
+    pub fn set_light(&mut self, view: &Affine3, near_z: f32) {
+        self.params.light_view = *view;
+        self.params.light_near_z = near_z;
+    }
+}
+
+impl PostNode for Volumetric {
+    fn name(&self) -> &'static str {
+        "volumetric"
+    }
+
+    fn inputs(&self) -> &'static [PostInput] {
+        &[PostInput::Previous, PostInput::SceneDepth]
+    }
+
+    fn bind(&mut self, device: &Device, sampler: &Sampler, inputs: &[&TextureView]) {
+        self.bind_group = Some(self.pass.binding.create_bind_group(device, "volumetric bind group", &[
+            BindingResource::TextureView(inputs[0]),
+            BindingResource::Sampler(sampler),
+            self.params_buffer.as_entire_binding(),
+            BindingResource::TextureView(inputs[1]),
+            BindingResource::TextureView(&self.shadow_map),
+        ]));
+    }
+
+    fn run(&mut self, queue: &Queue, encoder: &mut CommandEncoder, output: &TextureView) {
+        self.params.steps = self.steps.max(1);
+        self.params.density = self.density;
+        self.params.max_distance = self.max_distance;
+        self.params.light_color = self.light_color.to_rgb_array();
+        self.params.intensity = self.intensity;
+        self.params_buffer.write(queue, &[self.params]);
+        self.pass.draw(encoder, "volumetric pass", output, self.bind_group.as_ref().unwrap());
+    }
+}
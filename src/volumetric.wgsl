@@ -0,0 +1,91 @@
+struct VolumetricParams {
+    // camera view to world
+    inverse_view_0: vec4<f32>,
+    inverse_view_1: vec4<f32>,
+    inverse_view_2: vec4<f32>,
+    camera_position: vec3<f32>,
+    near_z: f32,
+    // world to light view, as in the light uniform
+    light_view_0: vec4<f32>,
+    light_view_1: vec4<f32>,
+    light_view_2: vec4<f32>,
+    light_near_z: f32,
+    steps: u32,
+    // scattering per unit of distance
+    density: f32,
+    // rays stop there when nothing is hit before
+    max_distance: f32,
+    light_color: vec3<f32>,
+    intensity: f32,
+}
+
+@group(0) @binding(2) var<uniform> params: VolumetricParams;
+@group(0) @binding(3) var depth: texture_depth_2d;
+@group(0) @binding(4) var shadow_map: texture_depth_2d;
+
+fn to_world(v: vec3<f32>, w: f32) -> vec3<f32> {
+    return vec3<f32>(
+        dot(params.inverse_view_0.xyz, v) + params.inverse_view_0.w * w,
+        dot(params.inverse_view_1.xyz, v) + params.inverse_view_1.w * w,
+        dot(params.inverse_view_2.xyz, v) + params.inverse_view_2.w * w,
+    );
+}
+
+// same test as the light pass
+fn is_lit(position: vec3<f32>) -> bool {
+    var from_light = vec3<f32>(
+        dot(params.light_view_0.xyz, position) + params.light_view_0.w,
+        dot(params.light_view_1.xyz, position) + params.light_view_1.w,
+        dot(params.light_view_2.xyz, position) + params.light_view_2.w,
+    );
+    from_light.x = ( from_light.x + from_light.z) * 0.5;
+    from_light.y = (-from_light.y + from_light.z) * 0.5;
+
+    if from_light.z <= params.light_near_z
+    || from_light.x <= 0.0 || from_light.x >= from_light.z
+    || from_light.y <= 0.0 || from_light.y >= from_light.z {
+        return false;
+    }
+
+    let size = vec2<i32>(textureDimensions(shadow_map));
+    let coords = clamp(vec2<i32>(from_light.xy / from_light.z * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    let occluder = textureLoad(shadow_map, coords, 0);
+    return params.light_near_z + 0.001 > occluder * from_light.z;
+}
+
+// interleaved gradient noise, offsets the samples per pixel to trade banding for noise
+fn jitter(pixel: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+@fragment
+fn fs_main(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(depth));
+    let coords = clamp(vec2<i32>(in.uv * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    let d = textureLoad(depth, coords, 0);
+
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let ray = normalize(to_world(vec3<f32>(ndc, 1.0), 0.0));
+    var distance = params.max_distance;
+    if d > 0.0 {
+        let view_z = params.near_z / d;
+        let hit = to_world(vec3<f32>(ndc * view_z, view_z), 1.0);
+        distance = min(distance, length(hit - params.camera_position));
+    }
+
+    let step = distance / f32(params.steps);
+    let step_transmittance = exp(-params.density * step);
+    var transmittance = 1.0;
+    var scattered = 0.0;
+    var t = step * jitter(in.position.xy);
+    for (var i = 0u; i < params.steps; i++) {
+        if is_lit(params.camera_position + ray * t) {
+            scattered += transmittance * (1.0 - step_transmittance);
+        }
+        transmittance *= step_transmittance;
+        t += step;
+    }
+
+    let color = textureSampleLevel(input, input_sampler, in.uv, 0.0);
+    return vec4<f32>(color.rgb + params.light_color * params.intensity * scattered, color.a);
+}
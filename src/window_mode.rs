@@ -0,0 +1,92 @@
+use winit::dpi::LogicalSize;
+use winit::monitor::{MonitorHandle, VideoMode};
+use winit::window::{Fullscreen, Window};
+
+/// how the window covers the screen
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WindowMode {
+    Windowed,
+    /// a borderless window covering the current monitor
+    Borderless,
+    /// the current monitor switched to its best video mode
+    Exclusive,
+}
+
+/// switches the window between the modes, remembering its size while fullscreen.
+/// the surface and the textures sized like it follow through the resize event
+pub struct WindowModes {
+    mode: WindowMode,
+    /// restored when switching back to windowed, logical so it stays right across scale factor changes
+    windowed_size: LogicalSize<f64>,
+}
+
+impl WindowModes {
+    pub fn new(window: &Window) -> Self {
+        Self {
+            mode: WindowMode::Windowed,
+            windowed_size: window.inner_size().to_logical(window.scale_factor()),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn mode(&self) -> WindowMode {
+        self.mode
+    }
+
+    /// switches to the mode after the current one, skipping exclusive fullscreen
+    /// when the monitor has no video modes
+    pub fn next(&mut self, window: &Window) -> WindowMode {
+        let mode = match self.mode {
+            WindowMode::Windowed => WindowMode::Borderless,
+            WindowMode::Borderless => WindowMode::Exclusive,
+            WindowMode::Exclusive => WindowMode::Windowed,
+        };
+        self.set(window, mode);
+        self.mode
+    }
+
+    pub fn set(&mut self, window: &Window, mode: WindowMode) {
+        if self.mode == WindowMode::Windowed {
+            self.windowed_size = window.inner_size().to_logical(window.scale_factor());
+        }
+
+        let monitor = window.current_monitor();
+        self.mode = match mode {
+            WindowMode::Exclusive => match monitor.as_ref().and_then(best_video_mode) {
+                Some(video_mode) => {
+                    println!(
+                        "video mode: {}x{} at {}hz",
+                        video_mode.size().width,
+                        video_mode.size().height,
+                        video_mode.refresh_rate_millihertz() as f32 / 1000.0,
+                    );
+                    window.set_fullscreen(Some(Fullscreen::Exclusive(video_mode)));
+                    WindowMode::Exclusive
+                }
+                None => {
+                    window.set_fullscreen(None);
+                    window.set_inner_size(self.windowed_size);
+                    WindowMode::Windowed
+                }
+            },
+            WindowMode::Borderless => {
+                window.set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+                WindowMode::Borderless
+            }
+            WindowMode::Windowed => {
+                window.set_fullscreen(None);
+                window.set_inner_size(self.windowed_size);
+                WindowMode::Windowed
+            }
+        };
+    }
+}
+
+/// the video mode with the most pixels, then the highest refresh rate, then the most bits per pixel
+pub fn best_video_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    monitor.video_modes().max_by_key(|mode| (
+        mode.size().width * mode.size().height,
+        mode.refresh_rate_millihertz(),
+        mode.bit_depth(),
+    ))
+}